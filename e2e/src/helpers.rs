@@ -29,7 +29,7 @@ where
 {
     let init_spec = chain_spec.clone();
     let preflight_result = tokio::task::spawn_blocking(move || {
-        N::preflight_with_external_data(&init_spec, rpc_cache, None, block_no)
+        N::preflight_with_external_data(&init_spec, rpc_cache.map(Into::into), None, block_no)
     })
     .await?;
     let preflight_data = preflight_result.context("preflight failed")?;
@@ -49,7 +49,7 @@ where
             hash, head, state, ..
         } => {
             debug!("Verifying final state using provider data ...");
-            preflight_data.verify_block(head, state)?;
+            preflight_data.verify_block(head, state)?.ensure_valid()?;
 
             debug!("Final block hash derived successfully. {}", hash);
         }
@@ -1,2 +1,6 @@
 mod helpers;
 mod runtime;
+mod simulate;
+mod stf_runner;
+mod synthetic;
+mod trie_bench;
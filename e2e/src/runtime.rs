@@ -5,18 +5,29 @@ use anyhow::Context;
 use ethers_core::types::{Block, Transaction};
 use zeth_lib::{
     builder::{BlockBuildInput, BlockBuilderStrategy, EthereumStrategy},
-    consts::ETH_MAINNET_CHAIN_SPEC,
+    consts::{ETH_MAINNET_CHAIN_SPEC, GWEI_TO_WEI},
     host::{
         cache_file_path,
+        input_file::{replay_input_file, write_input_file},
         preflight::new_preflight_input,
         provider::{new_provider, BlockQuery},
         provider_db::ProviderDb,
     },
     output::BlockBuildOutput,
 };
-use zeth_primitives::{block::Header, transactions::ethereum::EthereumTxEssence};
+use zeth_primitives::{
+    block::Header,
+    transactions::{
+        builder::{signer_address, TxBuilder},
+        ethereum::EthereumTxEssence,
+    },
+    Address,
+};
 
-use crate::helpers::{build_block, build_block_result, init, prepare_block_build_input};
+use crate::{
+    helpers::{build_block, build_block_result, init, prepare_block_build_input},
+    synthetic::{eoa, ether, key, pre_state, transaction},
+};
 
 #[tokio::test]
 async fn proof_child_block_test() {
@@ -57,11 +68,12 @@ async fn build_block_with_preflight_test() {
         network, result_block_no, cache_ext
     );
     let cache_file_path = Path::new(cache_file_path_string.as_str());
-    let mut provider = new_provider(Some(cache_file_path.to_path_buf()), None).unwrap();
+    let mut provider = new_provider(Some(cache_file_path.to_path_buf().into()), None).unwrap();
 
     let parent_block = provider
         .get_partial_block(&BlockQuery {
             block_no: parent_block_no,
+            block_hash: None,
         })
         .unwrap();
     let parent_block_header: Header = parent_block.try_into().context("invalid block").unwrap();
@@ -69,6 +81,7 @@ async fn build_block_with_preflight_test() {
     let result_block = provider
         .get_full_block(&BlockQuery {
             block_no: parent_block_no + 1,
+            block_hash: None,
         })
         .unwrap();
     let input = new_preflight_input(parent_block_header.clone(), result_block.clone()).unwrap();
@@ -120,11 +133,12 @@ async fn build_block_with_fully_initialized_block_build_input_test() {
         network, result_block_no, cache_ext
     );
     let cache_file_path = Path::new(cache_file_path_string.as_str());
-    let mut provider = new_provider(Some(cache_file_path.to_path_buf()), None).unwrap();
+    let mut provider = new_provider(Some(cache_file_path.to_path_buf().into()), None).unwrap();
 
     let parent_block = provider
         .get_partial_block(&BlockQuery {
             block_no: parent_block_no,
+            block_hash: None,
         })
         .unwrap();
     let parent_block_header: Header = parent_block.try_into().context("invalid block").unwrap();
@@ -132,6 +146,7 @@ async fn build_block_with_fully_initialized_block_build_input_test() {
     let result_block = provider
         .get_full_block(&BlockQuery {
             block_no: parent_block_no + 1,
+            block_hash: None,
         })
         .unwrap();
     let result_block_header: Header = result_block
@@ -161,3 +176,32 @@ async fn build_block_with_fully_initialized_block_build_input_test() {
 
     assert_eq!(original_expected_header_hash.0, hash.0);
 }
+
+#[test]
+fn replay_input_file_test() {
+    init();
+
+    let key = key(1);
+    let block = pre_state()
+        .account(signer_address(&key), eoa(ether(1)))
+        .transaction(transaction(
+            &key,
+            TxBuilder::new()
+                .to(Address::repeat_byte(0x10))
+                .value(GWEI_TO_WEI),
+        ));
+    let input = block.input().unwrap();
+    let (expected_header, _) = block.build().unwrap();
+
+    // the block is built again from the file alone
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(format!("{}.bin", expected_header.number));
+    write_input_file(&path, &ETH_MAINNET_CHAIN_SPEC, &input).unwrap();
+    let (header, output) = replay_input_file::<EthereumStrategy>(&path).unwrap();
+
+    assert_eq!(header.block_number, expected_header.number);
+    let BlockBuildOutput::SUCCESS { hash, .. } = output else {
+        panic!("block build output error")
+    };
+    assert_eq!(hash, expected_header.hash());
+}
@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+
+use zeth_lib::{
+    builder::{BlockBuilderStrategy, EthereumStrategy},
+    consts::{
+        ForkCondition, ETH_MAINNET_CHAIN_SPEC, FLUENT_DEVNET_CHAIN_SPEC, GWEI_TO_WEI,
+        HISTORY_SERVE_WINDOW, HISTORY_STORAGE_ADDRESS,
+    },
+    host::{
+        provider::CachePath,
+        simulate::{BlockOverrides, Simulate},
+    },
+    output::BlockBuildOutput,
+};
+use zeth_primitives::{
+    transactions::builder::{signer_address, TxBuilder},
+    Address, U256,
+};
+
+use crate::synthetic::{
+    chain_transaction, contract, eoa, ether, key, pre_state, transaction, SyntheticBlock,
+};
+
+#[test]
+fn simulate_transfer() {
+    let key = key(1);
+    let recipient = Address::repeat_byte(0x10);
+    let fee_recipient = Address::repeat_byte(0xfe);
+    let transactions = vec![transaction(
+        &key,
+        TxBuilder::new().to(recipient).value(GWEI_TO_WEI),
+    )];
+    let block = pre_state()
+        .account(signer_address(&key), eoa(ether(10)))
+        .account(fee_recipient, eoa(U256::from(1)))
+        .transaction(transactions[0].clone());
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cache.json.gz");
+    let parent_header = block.write_cache(path.clone()).unwrap();
+
+    let simulation = EthereumStrategy::simulate(
+        &ETH_MAINNET_CHAIN_SPEC,
+        Some(CachePath::File(path.clone())),
+        None,
+        parent_header.number,
+        &BlockOverrides::default(),
+        transactions.clone(),
+    )
+    .unwrap();
+    assert_eq!(simulation.header.parent_hash, parent_header.hash());
+    assert_eq!(simulation.header.number, parent_header.number + 1);
+    assert_eq!(simulation.header.gas_used, U256::from(21_000));
+    assert_eq!(simulation.header.base_fee_per_gas, Some(GWEI_TO_WEI));
+    assert_eq!(simulation.receipts.len(), 1);
+    assert!(simulation.receipts[0].payload.success);
+
+    // without overrides, the simulated block is built exactly as it would be proven
+    let BlockBuildOutput::SUCCESS { head, .. } =
+        EthereumStrategy::build_from(&ETH_MAINNET_CHAIN_SPEC, simulation.input).unwrap()
+    else {
+        panic!("block building failed");
+    };
+    assert_eq!(head, simulation.header);
+
+    let time = parent_header.timestamp + U256::from(5);
+    let overrides = BlockOverrides {
+        fee_recipient: Some(fee_recipient),
+        time: Some(time.to()),
+        base_fee_per_gas: Some(GWEI_TO_WEI * U256::from(3) / U256::from(2)),
+    };
+    let simulation = EthereumStrategy::simulate(
+        &ETH_MAINNET_CHAIN_SPEC,
+        Some(CachePath::File(path)),
+        None,
+        parent_header.number,
+        &overrides,
+        transactions,
+    )
+    .unwrap();
+    assert_eq!(simulation.header.beneficiary, fee_recipient);
+    assert_eq!(simulation.header.timestamp, time);
+    assert_eq!(
+        simulation.header.base_fee_per_gas,
+        overrides.base_fee_per_gas
+    );
+
+    // the proven build path ignores the override
+    let BlockBuildOutput::SUCCESS { head, .. } =
+        EthereumStrategy::build_from(&ETH_MAINNET_CHAIN_SPEC, simulation.input).unwrap()
+    else {
+        panic!("block building failed");
+    };
+    assert_eq!(head.base_fee_per_gas, Some(GWEI_TO_WEI));
+}
+
+#[test]
+fn simulate_restructuring_deletion() {
+    let key = key(2);
+    let target = Address::repeat_byte(0xc0);
+    // SSTORE(0, 0)
+    let code: [u8; 6] = [0x60, 0x00, 0x60, 0x00, 0x55, 0x00];
+    let storage = [
+        (U256::from(0), U256::from(1)),
+        (U256::from(1), U256::from(2)),
+    ];
+    let transactions = vec![transaction(
+        &key,
+        TxBuilder::new().to(target).gas_limit(100_000),
+    )];
+    let block = SyntheticBlock::new()
+        .account(signer_address(&key), eoa(ether(10)))
+        .account(target, contract(code, storage.into()))
+        .transaction(transactions[0].clone());
+
+    // only slot 0 is read, so the cache contains no proof of slot 1
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cache.json.gz");
+    let parent_header = block.write_cache(path.clone()).unwrap();
+
+    // deleting slot 0 merges the branch with the unknown leaf of slot 1
+    let err = EthereumStrategy::simulate(
+        &ETH_MAINNET_CHAIN_SPEC,
+        Some(CachePath::File(path)),
+        None,
+        parent_header.number,
+        &BlockOverrides::default(),
+        transactions,
+    )
+    .unwrap_err();
+    let message = format!("{:#}", err);
+    assert!(message.contains("slot 0 of"), "{}", message);
+    assert!(message.contains("is not supported"), "{}", message);
+}
+
+#[test]
+fn simulate_block_hash_history() {
+    let chain_spec = &*FLUENT_DEVNET_CHAIN_SPEC;
+    let key = key(3);
+    let caller = Address::repeat_byte(0xbb);
+    let block = pre_state().chain_spec(chain_spec.clone()).ancestors(3);
+    let number = 17034870 - 3;
+    let block_hash = block.ancestor_hash(number).unwrap();
+
+    // SSTORE(0, BLOCKHASH(number))
+    let mut code = vec![0x63];
+    code.extend((number as u32).to_be_bytes());
+    code.extend([0x40, 0x60, 0x00, 0x55, 0x00]);
+    let transactions = vec![chain_transaction(
+        chain_spec,
+        &key,
+        TxBuilder::new().to(caller).gas_limit(100_000),
+    )];
+    let block = block
+        .account(signer_address(&key), eoa(ether(10)))
+        .account(caller, contract(code, BTreeMap::new()))
+        .account(
+            HISTORY_STORAGE_ADDRESS,
+            contract(
+                vec![0x00],
+                BTreeMap::from([(
+                    U256::from(number % HISTORY_SERVE_WINDOW),
+                    U256::from_be_bytes(block_hash.0),
+                )]),
+            ),
+        )
+        .transaction(transactions[0].clone());
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cache.json.gz");
+    let parent_header = block.write_cache(path.clone()).unwrap();
+
+    // the hash is proven by the storage of the history contract instead of the headers
+    let simulation = EthereumStrategy::simulate(
+        chain_spec,
+        Some(CachePath::File(path.clone())),
+        None,
+        parent_header.number,
+        &BlockOverrides::default(),
+        transactions.clone(),
+    )
+    .unwrap();
+    assert!(simulation.input.ancestor_headers.is_empty());
+    assert!(simulation.receipts[0].payload.success);
+
+    // without the history storage, every header back to the accessed block is required
+    let chain_spec = chain_spec.clone().with_history_storage(ForkCondition::TBD);
+    let simulation_without_history = EthereumStrategy::simulate(
+        &chain_spec,
+        Some(CachePath::File(path)),
+        None,
+        parent_header.number,
+        &BlockOverrides::default(),
+        transactions,
+    )
+    .unwrap();
+    let numbers: Vec<_> = simulation_without_history
+        .input
+        .ancestor_headers
+        .iter()
+        .map(|header| header.number)
+        .collect();
+    assert_eq!(numbers, [number + 2, number + 1, number]);
+}
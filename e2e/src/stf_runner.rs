@@ -0,0 +1,353 @@
+use std::path::Path;
+
+use alloy_primitives::keccak256;
+use anyhow::{anyhow, bail, Context};
+use log::info;
+use serde::Serialize;
+use wasmi::{core::Trap, Caller, Config, Engine, Extern, Linker, Module, Store};
+use zeth_lib::{
+    builder::BlockBuilderStrategy,
+    consts::ChainSpec,
+    host::input_file::stf_input,
+    input::BlockBuildInput,
+    stf::{StfFailure, StfInputHeader, StfOutput, StfStrategy},
+};
+
+/// Path of the guest built by `make build_stf`, relative to the `e2e` crate.
+pub const STF_WASM_PATH: &str = "../build/fluent-stf.wasm";
+
+/// Path of the guest built with the `mpt-trie` feature by `make build_stf_mpt`.
+pub const STF_MPT_WASM_PATH: &str = "../build/fluent-stf-mpt.wasm";
+
+/// Fuel available to a single run, far more than building any block requires.
+pub const DEFAULT_FUEL_LIMIT: u64 = 1 << 40;
+
+/// Runs the `fluent-stf` guest in a local wasm interpreter, emulating the `LowLevelSDK`
+/// of a Fluent node.
+pub struct StfRunner {
+    engine: Engine,
+    module: Module,
+    fuel_limit: u64,
+}
+
+/// The result of a run of the guest.
+#[derive(Debug, Clone)]
+pub struct StfRun {
+    /// The bytes written by the guest.
+    pub output: Vec<u8>,
+    /// The fuel consumed by the guest, roughly the number of executed instructions.
+    pub fuel: u64,
+}
+
+impl StfRun {
+    /// Decodes the written bytes as an [StfOutput].
+    pub fn stf_output(&self) -> anyhow::Result<StfOutput> {
+        StfOutput::decode(&self.output)
+    }
+}
+
+/// The state of the emulated `LowLevelSDK`.
+struct HostState {
+    input: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl StfRunner {
+    /// Compiles the guest from its wasm bytes.
+    pub fn new(wasm: &[u8]) -> anyhow::Result<Self> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, wasm).context("invalid wasm module")?;
+        Ok(StfRunner {
+            engine,
+            module,
+            fuel_limit: DEFAULT_FUEL_LIMIT,
+        })
+    }
+
+    /// Loads the guest from a file, e.g. [STF_WASM_PATH].
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let wasm = std::fs::read(path).with_context(|| format!("failed to read {:?}", path))?;
+        Self::new(&wasm)
+    }
+
+    /// Sets the fuel available to a single run, [DEFAULT_FUEL_LIMIT] by default.
+    pub fn with_fuel_limit(mut self, fuel_limit: u64) -> Self {
+        self.fuel_limit = fuel_limit;
+        self
+    }
+
+    /// Runs the `main` export of the guest on the given raw input.
+    pub fn run(&self, input: Vec<u8>) -> anyhow::Result<StfRun> {
+        let mut store = Store::new(
+            &self.engine,
+            HostState {
+                input,
+                output: Vec::new(),
+            },
+        );
+        store
+            .add_fuel(self.fuel_limit)
+            .map_err(|err| anyhow!("failed to add fuel: {err}"))?;
+        let linker = self.linker()?;
+        let instance = linker
+            .instantiate(&mut store, &self.module)
+            .and_then(|instance| instance.start(&mut store))
+            .context("failed to instantiate the guest")?;
+        let main = instance
+            .get_typed_func::<(), ()>(&store, "main")
+            .context("the guest does not export `main`")?;
+        if let Err(trap) = main.call(&mut store, ()) {
+            // halting with exit code 0 is a regular return
+            if trap.i32_exit_status() != Some(0) {
+                bail!("the guest trapped: {trap}");
+            }
+        }
+
+        let fuel = store.fuel_consumed().context("fuel metering is disabled")?;
+        Ok(StfRun {
+            output: std::mem::take(&mut store.data_mut().output),
+            fuel,
+        })
+    }
+
+    /// Runs the guest on the input for a block built with strategy `N`.
+    pub fn run_block<N: BlockBuilderStrategy>(
+        &self,
+        strategy: StfStrategy,
+        chain_spec: &ChainSpec,
+        input: BlockBuildInput<N::TxEssence>,
+    ) -> anyhow::Result<StfRun>
+    where
+        N::TxEssence: Serialize,
+    {
+        let header = StfInputHeader::new(strategy, chain_spec.clone());
+        let run = self.run(stf_input(&header, input)?)?;
+        info!("Guest consumed {} fuel", run.fuel);
+        Ok(run)
+    }
+
+    /// Binds the imports of the guest to the emulated `LowLevelSDK` functions, matching
+    /// them by name so that the runner does not depend on the version of the import
+    /// module.
+    fn linker(&self) -> anyhow::Result<Linker<HostState>> {
+        let mut linker = Linker::new(&self.engine);
+        for import in self.module.imports() {
+            let (module, name) = (import.module(), import.name());
+            match name {
+                "_sys_read" | "_read" => linker.func_wrap(
+                    module,
+                    name,
+                    |mut caller: Caller<'_, HostState>,
+                     target: u32,
+                     offset: u32,
+                     length: u32|
+                     -> Result<(), Trap> {
+                        let input = &caller.data().input;
+                        let bytes = offset
+                            .checked_add(length)
+                            .and_then(|end| input.get(offset as usize..end as usize))
+                            .ok_or_else(|| Trap::new("input read out of bounds"))?
+                            .to_vec();
+                        memory(&caller)?
+                            .write(&mut caller, target as usize, &bytes)
+                            .map_err(|err| Trap::new(err.to_string()))
+                    },
+                ),
+                "_sys_input_size" | "_input_size" => {
+                    linker.func_wrap(module, name, |caller: Caller<'_, HostState>| {
+                        caller.data().input.len() as u32
+                    })
+                }
+                "_sys_write" | "_write" => linker.func_wrap(
+                    module,
+                    name,
+                    |mut caller: Caller<'_, HostState>,
+                     offset: u32,
+                     length: u32|
+                     -> Result<(), Trap> {
+                        let mut bytes = vec![0; length as usize];
+                        memory(&caller)?
+                            .read(&caller, offset as usize, &mut bytes)
+                            .map_err(|err| Trap::new(err.to_string()))?;
+                        caller.data_mut().output.extend(bytes);
+                        Ok(())
+                    },
+                ),
+                "_sys_halt" | "_exit" => linker.func_wrap(
+                    module,
+                    name,
+                    |_: Caller<'_, HostState>, code: i32| -> Result<(), Trap> {
+                        Err(Trap::i32_exit(code))
+                    },
+                ),
+                "_crypto_keccak256" | "_keccak256" => linker.func_wrap(
+                    module,
+                    name,
+                    |mut caller: Caller<'_, HostState>,
+                     offset: u32,
+                     length: u32,
+                     target: u32|
+                     -> Result<(), Trap> {
+                        let memory = memory(&caller)?;
+                        let mut data = vec![0; length as usize];
+                        memory
+                            .read(&caller, offset as usize, &mut data)
+                            .map_err(|err| Trap::new(err.to_string()))?;
+                        memory
+                            .write(&mut caller, target as usize, keccak256(data).as_slice())
+                            .map_err(|err| Trap::new(err.to_string()))
+                    },
+                ),
+                _ => bail!("the guest imports unsupported function {module}::{name}"),
+            }?;
+        }
+        Ok(linker)
+    }
+}
+
+/// Returns the memory exported by the guest.
+fn memory(caller: &Caller<'_, HostState>) -> Result<wasmi::Memory, Trap> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| Trap::new("the guest does not export `memory`"))
+}
+
+/// Returns the output the guest is expected to write for the given input, by building the
+/// block natively with strategy `N`.
+pub fn native_stf_output<N: BlockBuilderStrategy>(
+    chain_spec: &ChainSpec,
+    input: BlockBuildInput<N::TxEssence>,
+) -> StfOutput {
+    let parent_header = input.state_input.parent_header.clone();
+    let output = match N::build_from(chain_spec, input) {
+        Ok(output) => StfOutput::new(&parent_header, &output),
+        Err(_) => StfOutput::failed(StfFailure::InvalidWitness, Some(&parent_header)),
+    };
+    output.with_chain_spec(chain_spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use k256::ecdsa::SigningKey;
+    use zeth_lib::{
+        builder::EthereumStrategy,
+        consts::{ETH_MAINNET_CHAIN_SPEC, FLUENT_DEVNET_CHAIN_SPEC},
+    };
+    use zeth_primitives::{
+        transactions::builder::{sign, signer_address, TxBuilder},
+        Address, U256,
+    };
+
+    use super::*;
+    use crate::synthetic::{GenesisAccount, SyntheticBlock};
+
+    /// A guest that echoes its input, hashed if it is longer than 32 bytes.
+    const ECHO_WAT: &str = r#"
+        (module
+            (import "fluentbase_v1alpha" "_sys_input_size" (func $input_size (result i32)))
+            (import "fluentbase_v1alpha" "_sys_read" (func $read (param i32 i32 i32)))
+            (import "fluentbase_v1alpha" "_sys_write" (func $write (param i32 i32)))
+            (import "fluentbase_v1alpha" "_crypto_keccak256" (func $keccak256 (param i32 i32 i32)))
+            (import "fluentbase_v1alpha" "_sys_halt" (func $halt (param i32)))
+            (memory (export "memory") 1)
+            (func (export "main") (local $size i32)
+                (local.set $size (call $input_size))
+                (call $read (i32.const 0) (i32.const 0) (local.get $size))
+                (if (i32.gt_u (local.get $size) (i32.const 32))
+                    (then
+                        (call $keccak256 (i32.const 0) (local.get $size) (i32.const 1024))
+                        (call $write (i32.const 1024) (i32.const 32))
+                        (call $halt (i32.const 0))))
+                (call $write (i32.const 0) (local.get $size))))
+    "#;
+
+    fn echo_runner() -> StfRunner {
+        StfRunner::new(&wat::parse_str(ECHO_WAT).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn emulated_sdk() {
+        let run = echo_runner().run(b"zeth".to_vec()).unwrap();
+        assert_eq!(run.output, b"zeth");
+        assert!(run.fuel > 0);
+
+        // the guest halts after writing the hash
+        let input = vec![0xab; 100];
+        let run = echo_runner().run(input.clone()).unwrap();
+        assert_eq!(run.output, keccak256(input).as_slice());
+
+        assert!(echo_runner()
+            .with_fuel_limit(1)
+            .run(b"zeth".to_vec())
+            .is_err());
+    }
+
+    #[test]
+    fn overflowing_read() {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (import "fluentbase_v1alpha" "_sys_read" (func $read (param i32 i32 i32)))
+                (memory (export "memory") 1)
+                (func (export "main")
+                    (call $read (i32.const 0) (i32.const 1) (i32.const -1))))
+            "#,
+        )
+        .unwrap();
+        let runner = StfRunner::new(&wasm).unwrap();
+        assert!(runner.run(b"zeth".to_vec()).is_err());
+    }
+
+    #[test]
+    fn unsupported_import() {
+        let wasm = wat::parse_str(r#"(module (import "env" "abort" (func)))"#).unwrap();
+        let err = StfRunner::new(&wasm).unwrap().run(Vec::new()).unwrap_err();
+        assert!(err.to_string().contains("env::abort"));
+    }
+
+    /// Compares the guest built by `make build_stf` against the native block builder.
+    #[test]
+    #[ignore = "requires the guest built with `make build_stf`"]
+    fn guest_matches_native() {
+        let runner = StfRunner::from_file(STF_WASM_PATH).unwrap();
+
+        let key = SigningKey::from_slice(&[1; 32]).unwrap();
+        let sender = signer_address(&key);
+        let essence = TxBuilder::new()
+            .to(Address::repeat_byte(0x42))
+            .value(U256::from(1_000))
+            .gas_limit(21_000)
+            .max_fee_per_gas(U256::from(2_000_000_000))
+            .max_priority_fee_per_gas(U256::from(1_000_000_000))
+            .build_eip1559();
+        for chain_spec in [&*ETH_MAINNET_CHAIN_SPEC, &*FLUENT_DEVNET_CHAIN_SPEC] {
+            let block = SyntheticBlock::new()
+                .chain_spec(chain_spec.clone())
+                .account(
+                    sender,
+                    GenesisAccount {
+                        balance: U256::from(10).pow(U256::from(18)),
+                        ..Default::default()
+                    },
+                )
+                .transaction(sign(essence.clone(), &key, chain_spec.chain_id()));
+            let input = block.input().unwrap();
+
+            let expected = native_stf_output::<EthereumStrategy>(chain_spec, input.clone());
+            let run = runner
+                .run_block::<EthereumStrategy>(StfStrategy::Ethereum, chain_spec, input)
+                .unwrap();
+            assert_eq!(run.stf_output().unwrap(), expected);
+        }
+
+        let run = runner.run(vec![0xff; 64]).unwrap();
+        assert_eq!(
+            run.stf_output().unwrap().failure,
+            Some(StfFailure::InvalidInput)
+        );
+    }
+}
@@ -0,0 +1,871 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+
+use anyhow::{bail, ensure, Context};
+use ethers_core::types::{Block, Bytes as EthersBytes, EIP1186ProofResponse, StorageProof, H256};
+use k256::ecdsa::SigningKey;
+use revm::{
+    primitives::{Account, AccountInfo, Bytecode, HashMap},
+    Database, DatabaseCommit,
+};
+use zeth_lib::{
+    builder::{
+        BlockBuildInput, BlockBuilder, BlockBuilderStrategy, EthHeaderPrepStrategy,
+        EthTxExecStrategy, EthereumStrategy,
+    },
+    consts::{
+        ChainSpec, ETH_MAINNET_CHAIN_SPEC, GWEI_TO_WEI, HISTORY_SERVE_WINDOW,
+        HISTORY_STORAGE_ADDRESS,
+    },
+    host::provider::{
+        file_provider::FileProvider, AccountQuery, BlockQuery, MutProvider, ProofQuery, Provider,
+        StorageQuery,
+    },
+    input::StateInput,
+    mem_db::{DbError, MemDb},
+    output::BlockBuildOutput,
+};
+use zeth_primitives::{
+    block::Header,
+    ethers::{to_ethers_h160, to_ethers_h256, to_ethers_u256},
+    keccak::{keccak, KECCAK_EMPTY},
+    transactions::{
+        builder::{sign, TxBuilder},
+        ethereum::EthereumTxEssence,
+        EthereumTransaction,
+    },
+    trie::{to_nibs, MptNode, MptNodeData, MptNodeReference, StateAccount},
+    withdrawal::Withdrawal,
+    Address, BlockNumber, Bytes, B256, U256,
+};
+
+/// An account of the synthetic pre-state.
+#[derive(Debug, Clone, Default)]
+pub struct GenesisAccount {
+    pub nonce: u64,
+    pub balance: U256,
+    pub code: Bytes,
+    pub storage: BTreeMap<U256, U256>,
+}
+
+/// Builds a block on top of an in-memory pre-state without requiring any provider.
+///
+/// Similar to the preflight, the block is first executed against the complete pre-state
+/// to record all accessed accounts and storage slots. Only the paths to these keys are
+/// resolved in the tries of the resulting [BlockBuildInput], all other subtries are
+/// replaced by their digests.
+#[derive(Debug, Clone)]
+pub struct SyntheticBlock {
+    chain_spec: ChainSpec,
+    accounts: BTreeMap<Address, GenesisAccount>,
+    parent_header: Header,
+    /// The headers preceding the parent, oldest first.
+    ancestors: Vec<Header>,
+    beneficiary: Address,
+    transactions: Vec<EthereumTransaction>,
+    withdrawals: Vec<Withdrawal>,
+}
+
+impl Default for SyntheticBlock {
+    fn default() -> Self {
+        SyntheticBlock {
+            chain_spec: ETH_MAINNET_CHAIN_SPEC.clone(),
+            accounts: BTreeMap::new(),
+            // a Shanghai block with a base fee of 1 gwei that stays constant
+            parent_header: Header {
+                number: 17034870,
+                gas_limit: U256::from(30_000_000),
+                gas_used: U256::from(15_000_000),
+                timestamp: U256::from(1681338455),
+                beneficiary: Address::repeat_byte(0xbe),
+                base_fee_per_gas: Some(GWEI_TO_WEI),
+                withdrawals_root: Some(MptNode::default().hash()),
+                ..Default::default()
+            },
+            ancestors: vec![],
+            beneficiary: Address::repeat_byte(0xbe),
+            transactions: vec![],
+            withdrawals: vec![],
+        }
+    }
+}
+
+impl SyntheticBlock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the chain spec to build the block with.
+    pub fn chain_spec(mut self, chain_spec: ChainSpec) -> Self {
+        self.chain_spec = chain_spec;
+        self
+    }
+
+    /// Adds an account to the pre-state.
+    pub fn account(mut self, address: Address, account: GenesisAccount) -> Self {
+        self.accounts.insert(address, account);
+        self
+    }
+
+    /// Appends a signed transaction to the block.
+    pub fn transaction(mut self, transaction: EthereumTransaction) -> Self {
+        self.transactions.push(transaction);
+        self
+    }
+
+    /// Appends a withdrawal of the given amount in gwei to the block.
+    pub fn withdrawal(mut self, address: Address, amount: u64) -> Self {
+        let index = self.withdrawals.len() as u64;
+        self.withdrawals.push(Withdrawal {
+            index,
+            validator_index: index,
+            address,
+            amount,
+        });
+        self
+    }
+
+    /// Precedes the parent block by a chain of the given number of ancestors.
+    ///
+    /// The ancestors are only written to the cache by [SyntheticBlock::write_cache], the
+    /// input reads the hashes of older blocks from the history storage contract.
+    pub fn ancestors(mut self, count: u64) -> Self {
+        let parent_number = self.parent_header.number;
+        let mut parent_hash = B256::ZERO;
+        self.ancestors = (parent_number - count..parent_number)
+            .map(|number| {
+                let age = U256::from(12 * (parent_number - number));
+                let header = Header {
+                    number,
+                    parent_hash,
+                    timestamp: self.parent_header.timestamp - age,
+                    ..self.parent_header.clone()
+                };
+                parent_hash = header.hash();
+                header
+            })
+            .collect();
+        self.parent_header.parent_hash = parent_hash;
+        self
+    }
+
+    /// Returns the hash of the ancestor with the given number.
+    pub fn ancestor_hash(&self, number: BlockNumber) -> Option<B256> {
+        self.ancestors
+            .iter()
+            .find(|header| header.number == number)
+            .map(Header::hash)
+    }
+
+    pub fn beneficiary(&self) -> Address {
+        self.beneficiary
+    }
+
+    pub fn base_fee_per_gas(&self) -> U256 {
+        self.parent_header.base_fee_per_gas.unwrap_or_default()
+    }
+
+    /// Returns the complete state trie of the pre-state and the storage trie of each
+    /// account.
+    fn tries(&self) -> anyhow::Result<(MptNode, BTreeMap<Address, MptNode>)> {
+        let storage_tries: BTreeMap<_, _> = self
+            .accounts
+            .iter()
+            .map(|(address, account)| (*address, storage_trie(&account.storage)))
+            .collect();
+        let mut state_trie = MptNode::default();
+        for (address, account) in &self.accounts {
+            let state_account = StateAccount {
+                nonce: account.nonce,
+                balance: account.balance,
+                storage_root: storage_tries[address].hash(),
+                code_hash: keccak(&account.code).into(),
+            };
+            state_trie.insert_rlp(&keccak(address), state_account)?;
+        }
+        Ok((state_trie, storage_tries))
+    }
+
+    /// Returns the complete input to build the block.
+    pub fn input(&self) -> anyhow::Result<BlockBuildInput<EthereumTxEssence>> {
+        let (state_trie, storage_tries) = self.tries()?;
+        let parent_header = Header {
+            state_root: state_trie.hash(),
+            ..self.parent_header.clone()
+        };
+
+        // execute the block against the complete pre-state to find all accessed keys
+        let timestamp = self.parent_header.timestamp + U256::from(12);
+        let mut db = PreStateDb {
+            accounts: &self.accounts,
+            history_storage: self
+                .chain_spec
+                .history_storage_active(parent_header.number + 1, &timestamp),
+            initial_db: MemDb::default(),
+            latest_db: MemDb::default(),
+        };
+        db.initial_db
+            .insert_block_hash(parent_header.number, parent_header.hash());
+        let mut input = BlockBuildInput {
+            state_input: StateInput {
+                parent_header,
+                beneficiary: self.beneficiary,
+                gas_limit: self.parent_header.gas_limit,
+                timestamp,
+                extra_data: Bytes::new(),
+                mix_hash: B256::repeat_byte(0xaa),
+                transactions: self.transactions.clone(),
+                withdrawals: self.withdrawals.clone(),
+            },
+            parent_state_trie: MptNode::default(),
+            parent_storage: Default::default(),
+            contracts: vec![],
+            ancestor_headers: vec![],
+        };
+        let db = BlockBuilder::new(&self.chain_spec, input.clone())
+            .with_db(db)
+            .prepare_header::<EthHeaderPrepStrategy>()?
+            .execute_transactions::<EthTxExecStrategy>()?
+            .take_db()
+            .unwrap();
+
+        // collect the read slots and all the slots that are modified by the block
+        let mut slots: BTreeMap<Address, (Vec<U256>, Vec<U256>)> = BTreeMap::new();
+        for (address, account) in &db.initial_db.accounts {
+            slots.entry(*address).or_default().0 = account.storage.keys().copied().collect();
+        }
+        for (address, account) in &db.latest_db.accounts {
+            slots.entry(*address).or_default().1 = account.storage.keys().copied().collect();
+        }
+
+        let mut state_keys = Vec::with_capacity(slots.len());
+        for (address, (read, written)) in slots {
+            state_keys.push(to_nibs(&keccak(address)));
+
+            let storage_keys: Vec<_> = read
+                .iter()
+                .chain(&written)
+                .map(|slot| to_nibs(&keccak(slot.to_be_bytes::<32>())))
+                .collect();
+            let storage_trie = storage_tries.get(&address).cloned().unwrap_or_default();
+            let sparse_trie = sparse(&storage_trie, &storage_keys);
+            assert_eq!(sparse_trie.hash(), storage_trie.hash());
+            input.parent_storage.insert(address, (sparse_trie, read));
+
+            if let Some(account) = self.accounts.get(&address) {
+                if !account.code.is_empty() {
+                    input.contracts.push(account.code.clone());
+                }
+            }
+        }
+        input.parent_state_trie = sparse(&state_trie, &state_keys);
+        assert_eq!(input.parent_state_trie.hash(), state_trie.hash());
+
+        Ok(input)
+    }
+
+    /// Writes a [FileProvider] cache of the parent block and its ancestors, containing
+    /// every account of the pre-state or accessed by the block with a proof of the
+    /// storage slots read by the block, and returns the parent header.
+    ///
+    /// This allows the block to be simulated from the cache alone.
+    pub fn write_cache(&self, path: PathBuf) -> anyhow::Result<Header> {
+        let input = self.input()?;
+        let (state_trie, storage_tries) = self.tries()?;
+        let parent_header = input.state_input.parent_header;
+        let block_hash = to_ethers_h256(parent_header.hash());
+
+        let mut cache = FileProvider::new(path)?;
+        for header in self.ancestors.iter().chain([&parent_header]) {
+            cache.insert_partial_block(
+                BlockQuery {
+                    block_no: header.number,
+                    block_hash: None,
+                },
+                partial_block(header)?,
+            );
+        }
+        let addresses: BTreeSet<_> = self
+            .accounts
+            .keys()
+            .chain(input.parent_storage.keys())
+            .copied()
+            .collect();
+        for address in addresses {
+            let account = self.accounts.get(&address).cloned().unwrap_or_default();
+            let storage_trie = storage_tries.get(&address).cloned().unwrap_or_default();
+            let slots = match input.parent_storage.get(&address) {
+                Some((_, slots)) => slots.clone(),
+                None => vec![],
+            };
+
+            let account_query = AccountQuery {
+                block_no: parent_header.number,
+                block_hash: Some(block_hash),
+                address: to_ethers_h160(address),
+            };
+            cache.insert_transaction_count(account_query.clone(), account.nonce.into());
+            cache.insert_balance(account_query.clone(), to_ethers_u256(account.balance));
+            cache.insert_code(account_query, account.code.to_vec().into());
+
+            let mut storage_proof = Vec::with_capacity(slots.len());
+            for slot in &slots {
+                let value = account.storage.get(slot).copied().unwrap_or_default();
+                cache.insert_storage(
+                    StorageQuery {
+                        block_no: parent_header.number,
+                        block_hash: Some(block_hash),
+                        address: to_ethers_h160(address),
+                        index: to_h256(*slot),
+                    },
+                    to_h256(value),
+                );
+                storage_proof.push(StorageProof {
+                    key: to_ethers_u256(*slot),
+                    value: to_ethers_u256(value),
+                    proof: proof(&storage_trie, &slot.to_be_bytes::<32>())?,
+                });
+            }
+            cache.insert_proof(
+                ProofQuery {
+                    block_no: parent_header.number,
+                    block_hash: Some(block_hash),
+                    address: to_ethers_h160(address),
+                    indices: slots.iter().copied().map(to_h256).collect(),
+                },
+                EIP1186ProofResponse {
+                    address: to_ethers_h160(address),
+                    balance: to_ethers_u256(account.balance),
+                    code_hash: H256::from(keccak(&account.code)),
+                    nonce: account.nonce.into(),
+                    storage_hash: to_ethers_h256(storage_trie.hash()),
+                    account_proof: proof(&state_trie, address.as_slice())?,
+                    storage_proof,
+                },
+            );
+        }
+        cache.save()?;
+
+        Ok(parent_header)
+    }
+
+    /// Builds the block using the [EthereumStrategy] and returns its header together with
+    /// the resulting state trie.
+    pub fn build(&self) -> anyhow::Result<(Header, MptNode)> {
+        let input = self.input().context("failed to create the input")?;
+        match EthereumStrategy::build_from(&self.chain_spec, input)? {
+            BlockBuildOutput::SUCCESS { head, state, .. } => Ok((head, state)),
+            BlockBuildOutput::FAILURE { .. } => bail!("block building failed"),
+        }
+    }
+}
+
+/// Returns the storage trie for the given storage slots.
+pub fn storage_trie(storage: &BTreeMap<U256, U256>) -> MptNode {
+    let mut trie = MptNode::default();
+    for (slot, value) in storage {
+        if *value != U256::ZERO {
+            trie.insert_rlp(&keccak(slot.to_be_bytes::<32>()), value)
+                .unwrap();
+        }
+    }
+    trie
+}
+
+fn to_h256(value: U256) -> H256 {
+    H256::from(value.to_be_bytes::<32>())
+}
+
+/// Returns the block without transactions as returned by the RPC for the header.
+fn partial_block(header: &Header) -> anyhow::Result<Block<H256>> {
+    let block = Block::<H256> {
+        hash: Some(to_ethers_h256(header.hash())),
+        parent_hash: to_ethers_h256(header.parent_hash),
+        uncles_hash: to_ethers_h256(header.ommers_hash),
+        author: Some(to_ethers_h160(header.beneficiary)),
+        state_root: to_ethers_h256(header.state_root),
+        transactions_root: to_ethers_h256(header.transactions_root),
+        receipts_root: to_ethers_h256(header.receipts_root),
+        logs_bloom: Some(header.logs_bloom.0 .0.into()),
+        difficulty: to_ethers_u256(header.difficulty),
+        number: Some(header.number.into()),
+        gas_limit: to_ethers_u256(header.gas_limit),
+        gas_used: to_ethers_u256(header.gas_used),
+        timestamp: to_ethers_u256(header.timestamp),
+        extra_data: header.extra_data.to_vec().into(),
+        mix_hash: Some(to_ethers_h256(header.mix_hash)),
+        nonce: Some(header.nonce.0.into()),
+        base_fee_per_gas: header.base_fee_per_gas.map(to_ethers_u256),
+        withdrawals_root: header.withdrawals_root.map(to_ethers_h256),
+        ..Default::default()
+    };
+    ensure!(
+        &Header::try_from(block.clone())? == header,
+        "the header {} cannot be cached",
+        header.number
+    );
+    Ok(block)
+}
+
+/// Returns the encoded nodes proving the value of the hashed key in the trie.
+fn proof(trie: &MptNode, key: &[u8]) -> anyhow::Result<Vec<EthersBytes>> {
+    let nodes = trie.prove(&keccak(key))?;
+    Ok(nodes.into_iter().map(EthersBytes::from).collect())
+}
+
+/// Returns the given amount of ether in wei.
+pub fn ether(value: u64) -> U256 {
+    U256::from(value) * GWEI_TO_WEI * GWEI_TO_WEI
+}
+
+/// Returns a deterministic signing key.
+pub fn key(i: u8) -> SigningKey {
+    SigningKey::from_slice(&[i; 32]).unwrap()
+}
+
+/// Returns an externally owned account with the given balance.
+pub fn eoa(balance: U256) -> GenesisAccount {
+    GenesisAccount {
+        balance,
+        ..Default::default()
+    }
+}
+
+/// Returns a contract account with the given code and storage.
+pub fn contract(code: impl Into<Bytes>, storage: BTreeMap<U256, U256>) -> GenesisAccount {
+    GenesisAccount {
+        nonce: 1,
+        code: code.into(),
+        storage,
+        ..Default::default()
+    }
+}
+
+/// Returns a pre-state with unrelated accounts, so that the state trie is not trivial.
+pub fn pre_state() -> SyntheticBlock {
+    (0x10..0x50).fold(SyntheticBlock::new(), |block, i| {
+        block.account(Address::repeat_byte(i), eoa(U256::from(i)))
+    })
+}
+
+/// Returns a signed EIP-1559 transaction with a max fee of 2 gwei, paying a priority fee
+/// of 1 gwei.
+pub fn transaction(key: &SigningKey, builder: TxBuilder) -> EthereumTransaction {
+    chain_transaction(&ETH_MAINNET_CHAIN_SPEC, key, builder)
+}
+
+/// Returns a signed transaction like [transaction] for the chain of the given spec.
+pub fn chain_transaction(
+    chain_spec: &ChainSpec,
+    key: &SigningKey,
+    builder: TxBuilder,
+) -> EthereumTransaction {
+    let essence = builder
+        .max_fee_per_gas(U256::from(2) * GWEI_TO_WEI)
+        .max_priority_fee_per_gas(GWEI_TO_WEI)
+        .build_eip1559();
+    sign(essence, key, chain_spec.chain_id())
+}
+
+/// Returns init code that runs `constructor` and deploys `runtime`.
+pub fn init_code(constructor: &[u8], runtime: &[u8]) -> Vec<u8> {
+    let offset = constructor.len() as u8 + 12;
+    let len = runtime.len() as u8;
+    let mut code = constructor.to_vec();
+    // CODECOPY(0, offset, len), RETURN(0, len)
+    code.extend([0x60, len, 0x60, offset, 0x60, 0x00, 0x39]);
+    code.extend([0x60, len, 0x60, 0x00, 0xf3]);
+    code.extend(runtime);
+    code
+}
+
+/// Returns a copy of the trie, in which only the nodes required to access or modify the
+/// given keys are resolved.
+///
+/// The siblings of the nodes on each path are resolved up to their children, as they
+/// might be merged with their parent when a key gets deleted.
+fn sparse(node: &MptNode, key_nibs: &[Vec<u8>]) -> MptNode {
+    if key_nibs.is_empty() {
+        return digest(node);
+    }
+    match node.as_data() {
+        MptNodeData::Branch(children) => {
+            let children = core::array::from_fn(|i| {
+                let child = children[i].as_ref()?;
+                let tails: Vec<_> = key_nibs
+                    .iter()
+                    .filter(|nibs| nibs.first() == Some(&(i as u8)))
+                    .map(|nibs| nibs[1..].to_vec())
+                    .collect();
+                Some(Box::new(match tails.is_empty() {
+                    true => shallow(child),
+                    false => sparse(child, &tails),
+                }))
+            });
+            MptNodeData::Branch(children).into()
+        }
+        MptNodeData::Extension(prefix, child) => {
+            let nibs = node.nibs();
+            let tails: Vec<_> = key_nibs
+                .iter()
+                .filter_map(|key_nibs| key_nibs.strip_prefix(nibs.as_slice()))
+                .map(<[u8]>::to_vec)
+                .collect();
+            MptNodeData::Extension(prefix.clone(), Box::new(sparse(child, &tails))).into()
+        }
+        MptNodeData::Null | MptNodeData::Leaf(_, _) | MptNodeData::Digest(_) => node.clone(),
+    }
+}
+
+/// Returns the node with only its children replaced by their digests.
+fn shallow(node: &MptNode) -> MptNode {
+    match node.as_data() {
+        MptNodeData::Branch(children) => MptNodeData::Branch(
+            children
+                .clone()
+                .map(|child| child.map(|child| Box::new(digest(&child)))),
+        )
+        .into(),
+        MptNodeData::Extension(prefix, child) => {
+            MptNodeData::Extension(prefix.clone(), Box::new(digest(child))).into()
+        }
+        _ => node.clone(),
+    }
+}
+
+/// Replaces the node by its digest, unless it is embedded into its parent.
+fn digest(node: &MptNode) -> MptNode {
+    match node.reference() {
+        MptNodeReference::Digest(digest) => digest.into(),
+        MptNodeReference::Bytes(_) => node.clone(),
+    }
+}
+
+/// Database of the complete pre-state that records the accessed accounts and slots in the
+/// same way as the `ProviderDb`.
+struct PreStateDb<'a> {
+    accounts: &'a BTreeMap<Address, GenesisAccount>,
+    /// Whether the hashes of the ancestors are read from the history storage contract.
+    history_storage: bool,
+    initial_db: MemDb,
+    latest_db: MemDb,
+}
+
+impl Database for PreStateDb<'_> {
+    type Error = DbError;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        match self.latest_db.basic(address) {
+            Ok(db_result) => return Ok(db_result),
+            Err(DbError::AccountNotFound(_)) => {}
+            Err(err) => return Err(err),
+        }
+        match self.initial_db.basic(address) {
+            Ok(db_result) => return Ok(db_result),
+            Err(DbError::AccountNotFound(_)) => {}
+            Err(err) => return Err(err),
+        }
+
+        // accounts that are not part of the pre-state do not exist
+        let account = self.accounts.get(&address).cloned().unwrap_or_default();
+        let bytecode = Bytecode::new_raw(account.code);
+        let account_info = AccountInfo::new(
+            account.balance,
+            account.nonce,
+            bytecode.hash_slow(),
+            bytecode,
+        );
+
+        self.initial_db
+            .insert_account_info(address, account_info.clone());
+        Ok(Some(account_info))
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        if code_hash == KECCAK_EMPTY {
+            return Ok(Bytecode::new());
+        }
+        self.accounts
+            .values()
+            .map(|account| Bytecode::new_raw(account.code.clone()))
+            .find(|bytecode| bytecode.hash_slow() == code_hash)
+            .ok_or(DbError::HashNotFound(code_hash))
+    }
+
+    fn storage(&mut self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        match self.latest_db.storage(address, index) {
+            Ok(db_result) => return Ok(db_result),
+            Err(DbError::AccountNotFound(_)) | Err(DbError::SlotNotFound(_, _)) => {}
+            Err(err) => return Err(err),
+        }
+        match self.initial_db.storage(address, index) {
+            Ok(db_result) => return Ok(db_result),
+            Err(DbError::AccountNotFound(_)) | Err(DbError::SlotNotFound(_, _)) => {}
+            Err(err) => return Err(err),
+        }
+
+        // ensure that the corresponding account is loaded
+        self.initial_db.basic(address)?;
+
+        let storage = self
+            .accounts
+            .get(&address)
+            .and_then(|account| account.storage.get(&index))
+            .copied()
+            .unwrap_or_default();
+
+        self.initial_db
+            .insert_account_storage(&address, index, storage);
+        Ok(storage)
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
+        match self.initial_db.block_hash(number) {
+            Err(DbError::BlockNotFound(_)) if self.history_storage => {
+                self.basic(HISTORY_STORAGE_ADDRESS)?;
+                let slot = number % U256::from(HISTORY_SERVE_WINDOW);
+                let block_hash = self.storage(HISTORY_STORAGE_ADDRESS, slot)?;
+                Ok(block_hash.to_be_bytes::<32>().into())
+            }
+            result => result,
+        }
+    }
+}
+
+impl DatabaseCommit for PreStateDb<'_> {
+    fn commit(&mut self, changes: HashMap<Address, Account>) {
+        self.latest_db.commit(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zeth_lib::consts::ForkCondition;
+    use zeth_primitives::{
+        alloy_rlp,
+        transactions::builder::{signer_address, TxBuilder},
+    };
+
+    use super::*;
+
+    fn account(state: &MptNode, address: Address) -> Option<StateAccount> {
+        state.get_rlp(&keccak(address)).unwrap()
+    }
+
+    fn slots(slots: impl IntoIterator<Item = (u64, u64)>) -> BTreeMap<U256, U256> {
+        slots
+            .into_iter()
+            .map(|(slot, value)| (U256::from(slot), U256::from(value)))
+            .collect()
+    }
+
+    #[test]
+    fn contract_deploy() {
+        let key = key(1);
+        let sender = signer_address(&key);
+        let deployed = sender.create(0);
+
+        // SSTORE(0, CALLDATALOAD(0))
+        let runtime = [0x60, 0x00, 0x35, 0x60, 0x00, 0x55, 0x00];
+        // SSTORE(1, 42)
+        let constructor = [0x60, 0x2a, 0x60, 0x01, 0x55];
+        let block = pre_state()
+            .account(sender, eoa(ether(10)))
+            .transaction(transaction(
+                &key,
+                TxBuilder::new()
+                    .create()
+                    .gas_limit(200_000)
+                    .data(init_code(&constructor, &runtime)),
+            ))
+            .transaction(transaction(
+                &key,
+                TxBuilder::new()
+                    .nonce(1)
+                    .to(deployed)
+                    .gas_limit(100_000)
+                    .data(U256::from(7).to_be_bytes_vec()),
+            ));
+        let (header, state) = block.build().unwrap();
+        assert_eq!(header.state_root, state.hash());
+
+        let contract = account(&state, deployed).unwrap();
+        assert_eq!(contract.nonce, 1);
+        assert_eq!(contract.code_hash, B256::from(keccak(runtime)));
+        assert_eq!(
+            contract.storage_root,
+            storage_trie(&slots([(0, 7), (1, 42)])).hash()
+        );
+
+        // the sender pays the base fee and the priority fee for all the gas
+        let fees = header.gas_used * (block.base_fee_per_gas() + GWEI_TO_WEI);
+        let sender = account(&state, sender).unwrap();
+        assert_eq!(sender.nonce, 2);
+        assert_eq!(sender.balance, ether(10) - fees);
+        let beneficiary = account(&state, block.beneficiary()).unwrap();
+        assert_eq!(beneficiary.balance, header.gas_used * GWEI_TO_WEI);
+    }
+
+    #[test]
+    fn selfdestruct() {
+        let key = key(2);
+        let sender = signer_address(&key);
+        let destructed = Address::repeat_byte(0xde);
+
+        // SELFDESTRUCT(CALLER)
+        let code = [0x33, 0xff];
+        let block = pre_state()
+            .account(sender, eoa(ether(10)))
+            .account(
+                destructed,
+                GenesisAccount {
+                    balance: ether(1),
+                    ..contract(code, slots([(0, 1), (1, 2)]))
+                },
+            )
+            .transaction(transaction(
+                &key,
+                TxBuilder::new().to(destructed).gas_limit(100_000),
+            ));
+        let (header, state) = block.build().unwrap();
+        assert_eq!(header.state_root, state.hash());
+
+        assert_eq!(account(&state, destructed), None);
+        let fees = header.gas_used * (block.base_fee_per_gas() + GWEI_TO_WEI);
+        assert_eq!(account(&state, sender).unwrap().balance, ether(11) - fees);
+    }
+
+    #[test]
+    fn storage_clear() {
+        let key = key(3);
+        let sender = signer_address(&key);
+        let partial = Address::repeat_byte(0xc1);
+        let complete = Address::repeat_byte(0xc2);
+
+        // SSTORE(0, 0), SSTORE(1, 0)
+        let code = [
+            0x60, 0x00, 0x60, 0x00, 0x55, 0x60, 0x00, 0x60, 0x01, 0x55, 0x00,
+        ];
+        let block = pre_state()
+            .account(sender, eoa(ether(10)))
+            .account(partial, contract(code, slots((0..32).map(|i| (i, i + 1)))))
+            .account(complete, contract(code, slots([(0, 1), (1, 2)])))
+            .transaction(transaction(
+                &key,
+                TxBuilder::new().to(partial).gas_limit(100_000),
+            ))
+            .transaction(transaction(
+                &key,
+                TxBuilder::new().nonce(1).to(complete).gas_limit(100_000),
+            ));
+        let (header, state) = block.build().unwrap();
+        assert_eq!(header.state_root, state.hash());
+
+        assert_eq!(
+            account(&state, partial).unwrap().storage_root,
+            storage_trie(&slots((2..32).map(|i| (i, i + 1)))).hash()
+        );
+        assert_eq!(
+            account(&state, complete).unwrap().storage_root,
+            MptNode::default().hash()
+        );
+    }
+
+    #[test]
+    fn block_hash_history() {
+        let key = key(5);
+        let sender = signer_address(&key);
+        let caller = Address::repeat_byte(0xbb);
+        let number = 17034870 - 100;
+        let block_hash = U256::from_be_bytes([0x11; 32]);
+
+        // SSTORE(0, BLOCKHASH(number))
+        let mut code = vec![0x63];
+        code.extend((number as u32).to_be_bytes());
+        code.extend([0x40, 0x60, 0x00, 0x55, 0x00]);
+        let block = pre_state()
+            .chain_spec(
+                ETH_MAINNET_CHAIN_SPEC
+                    .clone()
+                    .with_history_storage(ForkCondition::Block(0)),
+            )
+            .account(sender, eoa(ether(10)))
+            .account(caller, contract(code, BTreeMap::new()))
+            // the code of the contract is not executed when storing the parent hash
+            .account(
+                HISTORY_STORAGE_ADDRESS,
+                contract(
+                    vec![0x00],
+                    BTreeMap::from([(U256::from(number % HISTORY_SERVE_WINDOW), block_hash)]),
+                ),
+            )
+            .transaction(transaction(
+                &key,
+                TxBuilder::new().to(caller).gas_limit(100_000),
+            ));
+
+        // the hash is proven by the storage of the contract instead of 100 headers, the slot
+        // of the parent hash is also read as it is written by the block
+        let input = block.input().unwrap();
+        assert!(input.ancestor_headers.is_empty());
+        let parent_slot = U256::from(input.state_input.parent_header.number % HISTORY_SERVE_WINDOW);
+        let mut read = input.parent_storage[&HISTORY_STORAGE_ADDRESS].1.clone();
+        read.sort();
+        let mut expected = vec![U256::from(number % HISTORY_SERVE_WINDOW), parent_slot];
+        expected.sort();
+        assert_eq!(read, expected);
+        let (header, state) = block.build().unwrap();
+        assert_eq!(header.state_root, state.hash());
+        assert_eq!(
+            account(&state, caller).unwrap().storage_root,
+            storage_trie(&BTreeMap::from([(U256::ZERO, block_hash)])).hash()
+        );
+        assert_eq!(
+            account(&state, HISTORY_STORAGE_ADDRESS)
+                .unwrap()
+                .storage_root,
+            storage_trie(&BTreeMap::from([
+                (U256::from(number % HISTORY_SERVE_WINDOW), block_hash),
+                (parent_slot, U256::from_be_bytes(header.parent_hash.0)),
+            ]))
+            .hash()
+        );
+
+        // without the history storage, the hash is not available
+        let block = block.chain_spec(ETH_MAINNET_CHAIN_SPEC.clone());
+        assert!(block.input().is_err());
+    }
+
+    #[test]
+    fn withdrawals() {
+        let existing = Address::repeat_byte(0x10);
+        let new = Address::repeat_byte(0xee);
+        // the amounts are given in gwei: 1 ether, 2 ether and 3 gwei
+        let block = pre_state()
+            .withdrawal(existing, GWEI_TO_WEI.to())
+            .withdrawal(new, 2 * GWEI_TO_WEI.to::<u64>())
+            .withdrawal(new, 3);
+        let (header, state) = block.build().unwrap();
+        assert_eq!(header.state_root, state.hash());
+        assert_eq!(header.gas_used, U256::ZERO);
+
+        let mut withdrawals_trie = MptNode::default();
+        for (i, withdrawal) in block.withdrawals.iter().enumerate() {
+            withdrawals_trie
+                .insert_rlp(&alloy_rlp::encode(i), withdrawal)
+                .unwrap();
+        }
+        assert_eq!(header.withdrawals_root, Some(withdrawals_trie.hash()));
+
+        assert_eq!(
+            account(&state, existing).unwrap().balance,
+            U256::from(0x10) + ether(1)
+        );
+        assert_eq!(
+            account(&state, new).unwrap().balance,
+            ether(2) + U256::from(3) * GWEI_TO_WEI
+        );
+    }
+}
@@ -0,0 +1,318 @@
+use std::time::Instant;
+
+use anyhow::Context;
+use log::info;
+use zeth_lib::{
+    builder::{
+        BlockBuildInput, BlockBuilder, BlockBuilderStrategy, EthHeaderPrepStrategy,
+        EthTxExecStrategy, EthereumStrategy, MemDbInitStrategy,
+    },
+    compact::CompactBlockBuildInput,
+    consts::ETH_MAINNET_CHAIN_SPEC,
+    mem_db::{AccountState, MemDb},
+    stf::StfStrategy,
+};
+use zeth_primitives::{
+    alloy_rlp,
+    block::Header,
+    keccak::keccak,
+    transactions::{
+        builder::{signer_address, TxBuilder},
+        ethereum::EthereumTxEssence,
+    },
+    trie::{ArenaTrie, MptNode, StateAccount, Trie},
+    Address, B256, U256,
+};
+
+use crate::{
+    helpers::init,
+    stf_runner::{StfRunner, STF_MPT_WASM_PATH, STF_WASM_PATH},
+    synthetic::{contract, eoa, ether, key, pre_state, transaction, SyntheticBlock},
+};
+
+const RUNS: u32 = 10;
+
+/// All the trie updates of a block, keyed by the trie index.
+struct Updates {
+    storage: Vec<(Address, bool, Vec<([u8; 32], Option<Vec<u8>>)>)>,
+    state: Vec<([u8; 32], Option<StateAccount>)>,
+}
+
+/// Collects the sorted trie updates resulting from the execution of the block.
+fn collect_updates(db: &MemDb) -> Updates {
+    let mut storage = Vec::new();
+    let mut state = Vec::new();
+    for (address, account) in &db.accounts {
+        if account.state == AccountState::None {
+            continue;
+        }
+        let state_trie_index = keccak(address);
+        if account.state == AccountState::Deleted {
+            state.push((state_trie_index, None));
+            continue;
+        }
+
+        let mut storage_updates: Vec<_> = account
+            .storage
+            .iter()
+            .map(|(key, value)| {
+                let value = (*value != U256::ZERO).then(|| alloy_rlp::encode(value));
+                (keccak(key.to_be_bytes::<32>()), value)
+            })
+            .collect();
+        storage_updates.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        let cleared = account.state == AccountState::StorageCleared;
+        storage.push((*address, cleared, storage_updates));
+
+        state.push((
+            state_trie_index,
+            Some(StateAccount {
+                nonce: account.info.nonce,
+                balance: account.info.balance,
+                storage_root: B256::ZERO,
+                code_hash: account.info.code_hash,
+            }),
+        ));
+    }
+    state.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    Updates { storage, state }
+}
+
+/// Applies the updates to copies of the parent tries and returns the new state root.
+fn apply_updates(
+    input: &BlockBuildInput<EthereumTxEssence>,
+    updates: &Updates,
+    batched: bool,
+) -> anyhow::Result<B256> {
+    let mut storage_roots = Vec::with_capacity(updates.storage.len());
+    for (address, cleared, storage_updates) in &updates.storage {
+        let (mut storage_trie, _) = input
+            .parent_storage
+            .get(address)
+            .cloned()
+            .context("storage trie missing")?;
+        if *cleared {
+            storage_trie.clear();
+        }
+        apply(&mut storage_trie, storage_updates.clone(), batched)?;
+        storage_roots.push((keccak(address), storage_trie.hash()));
+    }
+
+    let state_updates = updates.state.iter().map(|(index, account)| {
+        let value = account.as_ref().map(|account| {
+            let (_, storage_root) = storage_roots.iter().find(|(i, _)| i == index).unwrap();
+            alloy_rlp::encode(StateAccount {
+                storage_root: *storage_root,
+                ..account.clone()
+            })
+        });
+        (*index, value)
+    });
+    let mut state_trie = input.parent_state_trie.clone();
+    apply(&mut state_trie, state_updates.collect(), batched)?;
+
+    Ok(state_trie.hash())
+}
+
+fn apply(
+    trie: &mut MptNode,
+    updates: Vec<([u8; 32], Option<Vec<u8>>)>,
+    batched: bool,
+) -> anyhow::Result<()> {
+    if batched {
+        trie.batch_update(updates)?;
+    } else {
+        for (key, value) in updates {
+            match value {
+                Some(value) => trie.insert(&key, value)?,
+                None => trie.delete(&key)?,
+            };
+        }
+    }
+    Ok(())
+}
+
+/// Loads the input of a block for the given trie representation and builds it.
+fn load_and_build<M: Trie>(
+    compact: &CompactBlockBuildInput<EthereumTxEssence>,
+) -> anyhow::Result<B256>
+where
+    BlockBuildInput<EthereumTxEssence, M>:
+        TryFrom<CompactBlockBuildInput<EthereumTxEssence>, Error = anyhow::Error>,
+{
+    let input = BlockBuildInput::<_, M>::try_from(compact.clone())?;
+    let output = EthereumStrategy::build_from(&ETH_MAINNET_CHAIN_SPEC, input)?;
+    output.hash().copied().context("block building failed")
+}
+
+/// Address of the contract whose storage is rewritten by the synthetic block.
+const POOL: Address = Address::repeat_byte(0xd0);
+
+/// Number of storage slots of the pool contract, all of which are rewritten.
+const POOL_SLOTS: u64 = 1024;
+
+/// Runtime code of the pool contract: for each slot `i` in `[CALLDATALOAD(0),
+/// CALLDATALOAD(32))`, `SSTORE(i, NUMBER + i)`.
+const POOL_CODE: [u8; 26] = [
+    0x60, 0x00, 0x35, 0x5b, 0x60, 0x20, 0x35, 0x81, 0x10, 0x15, 0x60, 0x18, 0x57, 0x43, 0x81, 0x01,
+    0x81, 0x55, 0x60, 0x01, 0x01, 0x60, 0x03, 0x56, 0x5b, 0x00,
+];
+
+/// Returns a synthetic block resembling a busy mainnet block: many senders transfer
+/// ether to new accounts and a contract rewrites a large part of its storage, so that
+/// many updates share the same sub-tries.
+fn synthetic_block() -> SyntheticBlock {
+    let storage = (0..POOL_SLOTS)
+        .map(|i| (U256::from(i), U256::from(i + 1)))
+        .collect();
+    let mut block = pre_state().account(POOL, contract(POOL_CODE, storage));
+
+    for i in 1..=64 {
+        let key = key(i);
+        block = block
+            .account(signer_address(&key), eoa(ether(1)))
+            .transaction(transaction(
+                &key,
+                TxBuilder::new()
+                    .to(Address::repeat_byte(0x50 + i))
+                    .value(U256::from(i)),
+            ));
+    }
+
+    let key = key(0xff);
+    block = block.account(signer_address(&key), eoa(ether(1)));
+    for (nonce, start) in (0..POOL_SLOTS).step_by(256).enumerate() {
+        let mut data = U256::from(start).to_be_bytes_vec();
+        data.extend(U256::from(start + 256).to_be_bytes::<32>());
+        block = block.transaction(transaction(
+            &key,
+            TxBuilder::new()
+                .nonce(nonce as u64)
+                .to(POOL)
+                .gas_limit(2_000_000)
+                .data(data),
+        ));
+    }
+
+    block
+}
+
+/// Returns the input of the synthetic block and its header.
+fn synthetic_input() -> (BlockBuildInput<EthereumTxEssence>, Header) {
+    let block = synthetic_block();
+    let (header, _) = block.build().unwrap();
+    (block.input().unwrap(), header)
+}
+
+/// Compares applying the state updates of the synthetic block key by key and in one
+/// batch.
+///
+/// Run with `cargo test --release -p zeth-e2e trie_update_bench -- --nocapture`.
+#[test]
+fn trie_update_bench() {
+    init();
+
+    let (input, _) = synthetic_input();
+
+    // execute the block to obtain the state updates
+    let db = BlockBuilder::<MemDb, _>::new(&ETH_MAINNET_CHAIN_SPEC, input.clone())
+        .initialize_database::<MemDbInitStrategy>()
+        .unwrap()
+        .prepare_header::<EthHeaderPrepStrategy>()
+        .unwrap()
+        .execute_transactions::<EthTxExecStrategy>()
+        .unwrap()
+        .take_db()
+        .unwrap();
+    let updates = collect_updates(&db);
+
+    // the parent tries are always hashed during the database initialization
+    input.parent_state_trie.hash();
+    for (storage_trie, _) in input.parent_storage.values() {
+        storage_trie.hash();
+    }
+
+    let expected = apply_updates(&input, &updates, false).unwrap();
+    assert_eq!(apply_updates(&input, &updates, true).unwrap(), expected);
+
+    for batched in [false, true] {
+        let start = Instant::now();
+        for _ in 0..RUNS {
+            apply_updates(&input, &updates, batched).unwrap();
+        }
+        info!(
+            "{} updates: {:?} per block ({} accounts, {} storage slots)",
+            if batched { "batched" } else { "per-key" },
+            start.elapsed() / RUNS,
+            updates.state.len(),
+            updates
+                .storage
+                .iter()
+                .map(|(_, _, s)| s.len())
+                .sum::<usize>(),
+        );
+    }
+}
+
+/// Compares loading and building the synthetic block with the nested [MptNode] and the
+/// arena-backed [ArenaTrie], as done by fluent-stf with and without the `mpt-trie`
+/// feature.
+///
+/// Run with `cargo test --release -p zeth-e2e trie_representation_bench -- --nocapture`.
+#[test]
+fn trie_representation_bench() {
+    init();
+
+    let (input, result_block_header) = synthetic_input();
+    let compact = CompactBlockBuildInput::from(input);
+
+    assert_eq!(
+        load_and_build::<MptNode>(&compact).unwrap(),
+        result_block_header.hash()
+    );
+    assert_eq!(
+        load_and_build::<ArenaTrie>(&compact).unwrap(),
+        result_block_header.hash()
+    );
+
+    for (name, build) in [
+        ("MptNode", load_and_build::<MptNode> as fn(&_) -> _),
+        ("ArenaTrie", load_and_build::<ArenaTrie>),
+    ] {
+        let start = Instant::now();
+        for _ in 0..RUNS {
+            build(&compact).unwrap();
+        }
+        info!("{}: {:?} per block", name, start.elapsed() / RUNS);
+    }
+}
+
+/// Compares the fuel consumed by fluent-stf built with the arena-backed trie and with the
+/// `mpt-trie` feature on the synthetic block.
+///
+/// Requires `make build_stf build_stf_mpt`, run with `cargo test --release -p zeth-e2e
+/// trie_representation_fuel -- --ignored --nocapture`.
+#[test]
+#[ignore = "requires the guests built with `make build_stf build_stf_mpt`"]
+fn trie_representation_fuel() {
+    init();
+
+    let (input, result_block_header) = synthetic_input();
+
+    for (name, path) in [("ArenaTrie", STF_WASM_PATH), ("MptNode", STF_MPT_WASM_PATH)] {
+        let run = StfRunner::from_file(path)
+            .unwrap()
+            .run_block::<EthereumStrategy>(
+                StfStrategy::Ethereum,
+                &ETH_MAINNET_CHAIN_SPEC,
+                input.clone(),
+            )
+            .unwrap();
+        assert_eq!(
+            run.stf_output().unwrap().block_hash,
+            result_block_header.hash()
+        );
+        info!("{}: {} fuel", name, run.fuel);
+    }
+}
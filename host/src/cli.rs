@@ -16,12 +16,34 @@ use core::fmt;
 use std::path::PathBuf;
 
 use clap::ValueEnum;
+use zeth_lib::host::provider::{rpc_provider::DEFAULT_INITIAL_BACKOFF, RpcConfig};
 
 #[derive(clap::Parser, Debug, Clone)]
 #[command(name = "zeth")]
 #[command(bin_name = "zeth")]
 #[command(author, version, about, long_about = None)]
 pub enum Cli {
+    #[command(flatten)]
+    Block(BlockCommand),
+    /// Copy the cache files of a network into its content-addressed store
+    MigrateCache(MigrateCacheArgs),
+    /// Serve cache files as a local JSON-RPC endpoint
+    ServeCache(ServeCacheArgs),
+    /// Print statistics of a cache file, and optionally validate or prune it
+    InspectCache(InspectCacheArgs),
+    /// Preflight consecutive blocks and write the inputs to build them
+    Preflight(PreflightArgs),
+    /// Build blocks from input files without any RPC provider
+    Replay(ReplayArgs),
+    /// Print the size of each part of the input files
+    InputStats(InputStatsArgs),
+    /// Convert input files into inputs of the `fluent-stf` guest
+    ExportStf(ExportStfArgs),
+}
+
+/// The commands that build blocks from RPC data.
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum BlockCommand {
     /// Build blocks only on the host
     Build(BuildArgs),
     /// Run the block building inside the executor
@@ -32,13 +54,13 @@ pub enum Cli {
     Verify(VerifyArgs),
 }
 
-impl Cli {
+impl BlockCommand {
     pub fn build_args(&self) -> &BuildArgs {
         match &self {
-            Cli::Build(build_args) => build_args,
-            Cli::Run(run_args) => &run_args.build_args,
-            Cli::Prove(prove_args) => &prove_args.run_args.build_args,
-            Cli::Verify(..) => unimplemented!(),
+            BlockCommand::Build(build_args) => build_args,
+            BlockCommand::Run(run_args) => &run_args.build_args,
+            BlockCommand::Prove(prove_args) => &prove_args.run_args.build_args,
+            BlockCommand::Verify(verify_args) => &verify_args.build_args,
         }
     }
 
@@ -48,15 +70,15 @@ impl Cli {
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap();
         match &self {
-            Cli::Build(args) => format!("{}_build_{}", time.as_secs(), args.tag()),
-            Cli::Run(args) => format!("{}_run_{}", time.as_secs(), args.tag()),
-            Cli::Prove(args) => format!("{}_prove_{}", time.as_secs(), args.tag()),
-            Cli::Verify(..) => unimplemented!(),
+            BlockCommand::Build(args) => format!("{}_build_{}", time.as_secs(), args.tag()),
+            BlockCommand::Run(args) => format!("{}_run_{}", time.as_secs(), args.tag()),
+            BlockCommand::Prove(args) => format!("{}_prove_{}", time.as_secs(), args.tag()),
+            BlockCommand::Verify(args) => format!("{}_verify_{}", time.as_secs(), args.tag()),
         }
     }
 
     pub fn submit_to_bonsai(&self) -> bool {
-        if let Cli::Prove(prove_args) = self {
+        if let BlockCommand::Prove(prove_args) = self {
             prove_args.submit_to_bonsai
         } else {
             false
@@ -64,7 +86,7 @@ impl Cli {
     }
 
     pub fn snark(&self) -> bool {
-        if let Cli::Prove(prove_args) = self {
+        if let BlockCommand::Prove(prove_args) = self {
             prove_args.snark_args.snark
         } else {
             false
@@ -72,16 +94,16 @@ impl Cli {
     }
 
     pub fn verifier_or_eth_rpc_url(&self) -> Option<String> {
-        let verifier_rpc_url = if let Cli::Prove(prove_args) = self {
+        let verifier_rpc_url = if let BlockCommand::Prove(prove_args) = self {
             prove_args.snark_args.verifier_rpc_url.clone()
         } else {
             None
         };
-        verifier_rpc_url.or(self.build_args().eth_rpc_url.clone())
+        verifier_rpc_url.or(self.build_args().eth_rpc_url.first().cloned())
     }
 
     pub fn verifier_contract(&self) -> Option<String> {
-        if let Cli::Prove(prove_args) = self {
+        if let BlockCommand::Prove(prove_args) = self {
             prove_args.snark_args.verifier_contract.clone()
         } else {
             None
@@ -123,13 +145,28 @@ pub struct BuildArgs {
     /// Network name
     pub network: Network,
 
-    #[clap(short, long, require_equals = true)]
-    /// URL of the Ethereum RPC node
-    pub eth_rpc_url: Option<String>,
+    #[clap(short, long, require_equals = true, value_delimiter = ',')]
+    /// URLs of the Ethereum RPC nodes, separated by commas; the first one is preferred
+    /// and the others are used as fallbacks
+    pub eth_rpc_url: Vec<String>,
 
-    #[clap(short, long, require_equals = true)]
-    /// URL of the Optimism RPC node
-    pub op_rpc_url: Option<String>,
+    #[clap(short, long, require_equals = true, value_delimiter = ',')]
+    /// URLs of the Optimism RPC nodes, separated by commas; the first one is preferred
+    /// and the others are used as fallbacks
+    pub op_rpc_url: Vec<String>,
+
+    #[clap(long, require_equals = true, default_value_t = 3)]
+    /// Number of times a failed RPC request is retried before failing over to the next
+    /// RPC node
+    pub rpc_retries: u32,
+
+    #[clap(long, default_value_t = false)]
+    /// Fetch every proof from two different RPC nodes and reject mismatching responses
+    pub rpc_cross_check: bool,
+
+    #[clap(long, default_value_t = false)]
+    /// Decode the RPC responses into the alloy types (requires the `alloy` feature)
+    pub rpc_alloy: bool,
 
     #[clap(short, long, require_equals = true, num_args = 0..=1, default_missing_value = "cache_rpc")]
     /// Cache RPC calls locally; the value specifies the cache directory
@@ -137,12 +174,17 @@ pub struct BuildArgs {
     /// [default when the flag is present: cache_rpc]
     pub cache: Option<PathBuf>,
 
+    #[clap(long, default_value_t = false)]
+    /// Use the content-addressed store in the cache directory instead of one cache file
+    /// per block
+    pub store: bool,
+
     #[clap(short, long, require_equals = true)]
     /// Start block number
     pub block_number: u64,
 
     #[clap(short = 'n', long, require_equals = true, default_value_t = 1)]
-    /// Number of blocks to derive (optimism-derived network only)
+    /// Number of blocks to derive (optimism-derived network only) or to preflight
     pub block_count: u32,
 
     #[clap(short='m', long, require_equals = true, num_args = 0..=1, default_missing_value = "1")]
@@ -151,6 +193,34 @@ pub struct BuildArgs {
     ///
     /// [default when the flag is present: 1]
     pub composition: Option<u32>,
+
+    #[clap(long, require_equals = true)]
+    /// Write the report of verifying the built block against the RPC data as JSON to
+    /// this file
+    pub verification_report: Option<PathBuf>,
+}
+
+impl BuildArgs {
+    /// Returns the configuration of the Ethereum RPC nodes, if any are given.
+    pub fn eth_rpc_config(&self) -> Option<RpcConfig> {
+        self.rpc_config(&self.eth_rpc_url)
+    }
+
+    /// Returns the configuration of the Optimism RPC nodes, if any are given.
+    pub fn op_rpc_config(&self) -> Option<RpcConfig> {
+        self.rpc_config(&self.op_rpc_url)
+    }
+
+    fn rpc_config(&self, urls: &[String]) -> Option<RpcConfig> {
+        if urls.is_empty() {
+            return None;
+        }
+        let config = RpcConfig::new(urls.to_vec())
+            .with_retries(self.rpc_retries, DEFAULT_INITIAL_BACKOFF)
+            .with_cross_check(self.rpc_cross_check)
+            .with_alloy(self.rpc_alloy);
+        Some(config)
+    }
 }
 
 impl Tag for BuildArgs {
@@ -222,7 +292,131 @@ impl Tag for ProveArgs {
 
 #[derive(clap::Args, Debug, Clone)]
 pub struct VerifyArgs {
-    #[clap(short, long, require_equals = true)]
+    #[clap(flatten)]
+    pub build_args: BuildArgs,
+
+    #[clap(long, require_equals = true)]
     /// Verify the receipt from the provided Bonsai Session UUID
     pub bonsai_receipt_uuid: String,
 }
+
+impl Tag for VerifyArgs {
+    fn tag(&self) -> String {
+        self.build_args.tag()
+    }
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct MigrateCacheArgs {
+    #[clap(
+        short = 'w',
+        long,
+        require_equals = true,
+        value_enum,
+        default_value_t = Network::Ethereum
+    )]
+    /// Network name
+    pub network: Network,
+
+    #[clap(short, long, require_equals = true, default_value = "cache_rpc")]
+    /// Cache directory containing the cache files
+    pub cache: PathBuf,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ServeCacheArgs {
+    #[clap(required = true)]
+    /// Cache files to serve; directories are searched for `.json.gz` files
+    pub files: Vec<PathBuf>,
+
+    #[clap(short, long, require_equals = true, default_value = "127.0.0.1:8545")]
+    /// Address to listen on
+    pub address: String,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct InspectCacheArgs {
+    /// Cache file to inspect
+    pub file: PathBuf,
+
+    #[clap(short, long, require_equals = true)]
+    /// Block the cache is used for; defaults to the number in the file name
+    pub block_number: Option<u64>,
+
+    #[clap(long, default_value_t = false)]
+    /// Check all proofs against the state roots and the account data against the proofs
+    pub validate: bool,
+
+    #[clap(long, default_value_t = false)]
+    /// Remove all entries not required to build the block and save the file
+    pub prune: bool,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct PreflightArgs {
+    #[clap(flatten)]
+    pub build_args: BuildArgs,
+
+    #[clap(long, require_equals = true, default_value = "inputs")]
+    /// Directory to write the block inputs to, as `<network>/<block number>.bin`
+    pub output: PathBuf,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ReplayArgs {
+    #[clap(
+        short = 'w',
+        long,
+        require_equals = true,
+        value_enum,
+        default_value_t = Network::Ethereum
+    )]
+    /// Network name
+    pub network: Network,
+
+    #[clap(required = true)]
+    /// Input files written by the `preflight` command
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct InputStatsArgs {
+    #[clap(
+        short = 'w',
+        long,
+        require_equals = true,
+        value_enum,
+        default_value_t = Network::Ethereum
+    )]
+    /// Network name
+    pub network: Network,
+
+    #[clap(short, long, require_equals = true, default_value_t = 10)]
+    /// Number of the largest storage tries and contracts to list
+    pub top: usize,
+
+    #[clap(long, default_value_t = false)]
+    /// Print the statistics as JSON
+    pub json: bool,
+
+    #[clap(required = true)]
+    /// Input files written by the `preflight` command
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ExportStfArgs {
+    #[clap(
+        short = 'w',
+        long,
+        require_equals = true,
+        value_enum,
+        default_value_t = Network::Ethereum
+    )]
+    /// Network name
+    pub network: Network,
+
+    #[clap(required = true)]
+    /// Input files written by the `preflight` command
+    pub files: Vec<PathBuf>,
+}
@@ -14,25 +14,67 @@
 
 extern crate core;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::Parser;
 use log::info;
 use risc0_zkvm::sha::Digest;
 use zeth::{
-    cli::{Cli, Network},
-    operations::{build, rollups, snarks::verify_groth16_snark, stark2snark},
+    cli::{BlockCommand, Cli, Network, PreflightArgs},
+    operations::{build, cache, rollups, snarks::verify_groth16_snark, stark2snark},
 };
 use zeth_guests::*;
 use zeth_lib::{
     builder::{EthereumStrategy, OptimismStrategy},
     consts::{ETH_MAINNET_CHAIN_SPEC, OP_MAINNET_CHAIN_SPEC},
+    stf::StfStrategy,
 };
 
 #[tokio::main]
 async fn main() -> Result<()> {
     env_logger::init();
-    let cli = Cli::parse();
+    match Cli::parse() {
+        Cli::Block(cli) => build_blocks(cli).await,
+        Cli::MigrateCache(args) => cache::migrate_cache(&args),
+        Cli::ServeCache(args) => cache::serve_cache(&args).await,
+        Cli::InspectCache(args) => cache::inspect_cache(&args),
+        Cli::Preflight(args) => preflight_blocks(&args).await,
+        Cli::Replay(args) => match args.network {
+            Network::Ethereum => build::replay_blocks::<EthereumStrategy>(&args),
+            Network::Optimism => build::replay_blocks::<OptimismStrategy>(&args),
+            Network::OptimismDerived => bail!("derived blocks cannot be replayed"),
+        },
+        Cli::InputStats(args) => match args.network {
+            Network::Ethereum => build::print_input_stats::<EthereumStrategy>(&args),
+            Network::Optimism => build::print_input_stats::<OptimismStrategy>(&args),
+            Network::OptimismDerived => bail!("derived blocks have no input files"),
+        },
+        Cli::ExportStf(args) => match args.network {
+            Network::Ethereum => {
+                build::export_stf_inputs::<EthereumStrategy>(&args, StfStrategy::Ethereum)
+            }
+            _ => bail!("the fluent-stf guest only builds Ethereum blocks"),
+        },
+    }
+}
+
+async fn preflight_blocks(args: &PreflightArgs) -> Result<()> {
+    let build_args = &args.build_args;
+    match build_args.network {
+        Network::Ethereum => {
+            let rpc_config = build_args.eth_rpc_config();
+            build::preflight_blocks::<EthereumStrategy>(args, rpc_config, &ETH_MAINNET_CHAIN_SPEC)
+                .await
+        }
+        Network::Optimism => {
+            let rpc_config = build_args.op_rpc_config();
+            build::preflight_blocks::<OptimismStrategy>(args, rpc_config, &OP_MAINNET_CHAIN_SPEC)
+                .await
+        }
+        Network::OptimismDerived => bail!("derived blocks cannot be preflighted"),
+    }
+}
 
+async fn build_blocks(cli: BlockCommand) -> Result<()> {
     info!("Using the following image ids:");
     info!("  eth-block: {}", Digest::from(ETH_BLOCK_ID));
     info!("  op-block: {}", Digest::from(OP_BLOCK_ID));
@@ -43,12 +85,12 @@ async fn main() -> Result<()> {
     let build_args = cli.build_args();
     let (image_id, stark) = match build_args.network {
         Network::Ethereum => {
-            let rpc_url = build_args.eth_rpc_url.clone();
+            let rpc_config = build_args.eth_rpc_config();
             (
                 ETH_BLOCK_ID,
                 build::build_block::<EthereumStrategy>(
                     &cli,
-                    rpc_url,
+                    rpc_config,
                     &ETH_MAINNET_CHAIN_SPEC,
                     ETH_BLOCK_ELF,
                 )
@@ -56,12 +98,12 @@ async fn main() -> Result<()> {
             )
         }
         Network::Optimism => {
-            let rpc_url = build_args.op_rpc_url.clone();
+            let rpc_config = build_args.op_rpc_config();
             (
                 OP_BLOCK_ID,
                 build::build_block::<OptimismStrategy>(
                     &cli,
-                    rpc_url,
+                    rpc_config,
                     &OP_MAINNET_CHAIN_SPEC,
                     OP_BLOCK_ELF,
                 )
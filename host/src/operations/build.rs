@@ -12,30 +12,43 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::fmt::Debug;
+use std::{
+    fmt::Debug,
+    fs::{self, File},
+};
 
-use anyhow::Context;
+use anyhow::{bail, ensure, Context};
 use ethers_core::types::Transaction as EthersTransaction;
 use log::{info, warn};
 use risc0_zkvm::{compute_image_id, Receipt};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use zeth_lib::{
     builder::BlockBuilderStrategy,
     consts::ChainSpec,
-    host::{cache_file_path, preflight::Preflight, verify::Verifier},
+    host::{
+        cache_file_path, cache_store_path,
+        input_file::{export_stf_input_file, read_input_file, replay_input_file, write_input_file},
+        input_stats::InputStats,
+        preflight::Preflight,
+        provider::{CachePath, RpcConfig},
+        range::RangePreflight,
+        verify::Verifier,
+        ProviderFactory,
+    },
     input::BlockBuildInput,
     output::BlockBuildOutput,
+    stf::StfStrategy,
 };
 
 use crate::{
-    cli::Cli,
+    cli::{BlockCommand, ExportStfArgs, InputStatsArgs, PreflightArgs, ReplayArgs},
     operations::{execute, maybe_prove, verify_bonsai_receipt},
 };
 
 /// Build a single block using the specified strategy.
 pub async fn build_block<N: BlockBuilderStrategy>(
-    cli: &Cli,
-    rpc_url: Option<String>,
+    cli: &BlockCommand,
+    rpc_config: Option<RpcConfig>,
     chain_spec: &ChainSpec,
     guest_elf: &[u8],
 ) -> anyhow::Result<Option<(String, Receipt)>>
@@ -49,18 +62,15 @@ where
     }
 
     // Fetch all of the initial data
-    let rpc_cache = build_args.cache.as_ref().map(|dir| {
-        cache_file_path(
-            dir,
-            &build_args.network.to_string(),
-            build_args.block_number,
-            "json.gz",
-        )
+    let network = build_args.network.to_string();
+    let rpc_cache = build_args.cache.as_ref().map(|dir| match build_args.store {
+        true => CachePath::Store(cache_store_path(dir, &network), build_args.block_number),
+        false => cache_file_path(dir, &network, build_args.block_number, "json.gz").into(),
     });
 
     let init_spec = chain_spec.clone();
     let preflight_result = tokio::task::spawn_blocking(move || {
-        N::preflight_with_external_data(&init_spec, rpc_cache, rpc_url, build_args.block_number)
+        N::preflight_with_external_data(&init_spec, rpc_cache, rpc_config, build_args.block_number)
     })
     .await?;
     let preflight_data = preflight_result.context("preflight failed")?;
@@ -80,7 +90,15 @@ where
             hash, head, state, ..
         } => {
             info!("Verifying final state using provider data ...");
-            preflight_data.verify_block(head, state)?;
+            let report = preflight_data.verify_block(head, state)?;
+            if let Some(path) = &build_args.verification_report {
+                let file = File::create(path)
+                    .with_context(|| format!("failed to create {}", path.display()))?;
+                serde_json::to_writer_pretty(file, &report)
+                    .context("failed to write verification report")?;
+                info!("Verification report written to {}", path.display());
+            }
+            report.ensure_valid()?;
 
             info!("Final block hash derived successfully. {}", hash);
         }
@@ -91,8 +109,8 @@ where
 
     let compressed_output = output.with_state_hashed();
     let result = match cli {
-        Cli::Build(..) => None,
-        Cli::Run(run_args) => {
+        BlockCommand::Build(..) => None,
+        BlockCommand::Run(run_args) => {
             execute(
                 &input,
                 run_args.execution_po2,
@@ -103,7 +121,7 @@ where
             );
             None
         }
-        Cli::Prove(..) => {
+        BlockCommand::Prove(..) => {
             maybe_prove(
                 cli,
                 &input,
@@ -113,7 +131,7 @@ where
             )
             .await
         }
-        Cli::Verify(verify_args) => Some(
+        BlockCommand::Verify(verify_args) => Some(
             verify_bonsai_receipt(
                 compute_image_id(guest_elf)?,
                 &compressed_output,
@@ -126,3 +144,136 @@ where
 
     Ok(result)
 }
+
+/// Preflight consecutive blocks using the specified strategy and write the input of each
+/// block to the output directory.
+pub async fn preflight_blocks<N: BlockBuilderStrategy>(
+    args: &PreflightArgs,
+    rpc_config: Option<RpcConfig>,
+    chain_spec: &ChainSpec,
+) -> anyhow::Result<()>
+where
+    N::TxEssence: 'static + Send + TryFrom<EthersTransaction> + Serialize + Deserialize<'static>,
+    <N::TxEssence as TryFrom<EthersTransaction>>::Error: Debug,
+{
+    let build_args = args.build_args.clone();
+    let network = build_args.network.to_string();
+    let providers = ProviderFactory::new(build_args.cache.clone(), network.clone(), rpc_config)
+        .with_store(build_args.store);
+    let output_dir = args.output.join(&network);
+    fs::create_dir_all(&output_dir)
+        .with_context(|| format!("failed to create {}", output_dir.display()))?;
+
+    let chain_spec = chain_spec.clone();
+    tokio::task::spawn_blocking(move || {
+        N::preflight_range(
+            &chain_spec,
+            &providers,
+            build_args.block_number,
+            build_args.block_count.into(),
+            |data| {
+                let header = data.header.clone().context("missing block header")?;
+                let input: BlockBuildInput<N::TxEssence> =
+                    data.try_into().context("invalid preflight data")?;
+
+                // Make sure that the input builds the expected block
+                let output = N::build_from(&chain_spec, input.clone())
+                    .context("Error while building block")?;
+                let BlockBuildOutput::SUCCESS { hash, .. } = output else {
+                    bail!("block {} could not be built", header.number);
+                };
+                ensure!(
+                    hash == header.hash(),
+                    "block {} was built with the wrong hash {}",
+                    header.number,
+                    hash
+                );
+
+                let path = output_dir
+                    .join(header.number.to_string())
+                    .with_extension("bin");
+                write_input_file(&path, &chain_spec, &input)?;
+                info!(
+                    "Input of block {} written to {}",
+                    header.number,
+                    path.display()
+                );
+
+                Ok(())
+            },
+        )
+    })
+    .await?
+    .context("preflight failed")
+}
+
+/// Build the blocks of the input files using the specified strategy.
+pub fn replay_blocks<N: BlockBuilderStrategy>(args: &ReplayArgs) -> anyhow::Result<()>
+where
+    N::TxEssence: DeserializeOwned,
+{
+    for path in &args.files {
+        let (header, output) = replay_input_file::<N>(path)?;
+        match output {
+            BlockBuildOutput::SUCCESS { hash, .. } => {
+                info!(
+                    "Block {} built from {}: {}",
+                    header.block_number,
+                    path.display(),
+                    hash
+                )
+            }
+            BlockBuildOutput::FAILURE { .. } => {
+                bail!(
+                    "block {} could not be built from {}",
+                    header.block_number,
+                    path.display()
+                )
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the input of the `fluent-stf` guest next to each input file, with the extension
+/// `stf.bin`, so that the blocks can be built by the guest as is.
+pub fn export_stf_inputs<N: BlockBuilderStrategy>(
+    args: &ExportStfArgs,
+    strategy: StfStrategy,
+) -> anyhow::Result<()>
+where
+    N::TxEssence: Serialize + DeserializeOwned,
+{
+    for path in &args.files {
+        let stf_path = path.with_extension("stf.bin");
+        let header = export_stf_input_file::<N::TxEssence>(path, &stf_path, strategy)?;
+        info!(
+            "Guest input of block {} written to {}",
+            header.block_number,
+            stf_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Print the statistics of the input files using the specified strategy.
+pub fn print_input_stats<N: BlockBuilderStrategy>(args: &InputStatsArgs) -> anyhow::Result<()>
+where
+    N::TxEssence: Serialize + DeserializeOwned,
+{
+    for path in &args.files {
+        let (header, input) = read_input_file::<N::TxEssence>(path)?;
+        let mut stats = InputStats::new(&input)?;
+        stats.truncate(args.top);
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+        } else {
+            println!("block {} ({}):", header.block_number, path.display());
+            println!("{stats}");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+
+use anyhow::{bail, Context};
+use log::{info, warn};
+use tokio::net::TcpListener;
+use zeth_lib::host::{
+    cache_store_path,
+    provider::{
+        cache_server::CacheServer, file_provider::FileProvider, store_provider::import_file,
+        Provider,
+    },
+};
+
+use crate::cli::{InspectCacheArgs, MigrateCacheArgs, ServeCacheArgs};
+
+/// Copy every `<block>.json.gz` cache file of the network into its content-addressed store.
+pub fn migrate_cache(args: &MigrateCacheArgs) -> anyhow::Result<()> {
+    let network = args.network.to_string();
+    let dir = args.cache.join(&network);
+    let store_path = cache_store_path(&args.cache, &network);
+
+    let mut migrated = 0;
+    for entry in fs::read_dir(&dir).with_context(|| format!("could not read {:?}", dir))? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some(Ok(block_no)) = name.strip_suffix(".json.gz").map(str::parse::<u64>) else {
+            continue;
+        };
+        import_file(store_path.clone(), path.clone(), block_no)
+            .with_context(|| format!("could not migrate {:?}", path))?;
+        migrated += 1;
+    }
+    info!("Migrated {} cache files into {:?}", migrated, store_path);
+
+    Ok(())
+}
+
+/// Serve the given cache files as a JSON-RPC endpoint until the process is stopped.
+pub async fn serve_cache(args: &ServeCacheArgs) -> anyhow::Result<()> {
+    let mut files = Vec::new();
+    for path in &args.files {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+        for entry in fs::read_dir(path).with_context(|| format!("could not read {:?}", path))? {
+            let path = entry?.path();
+            if path.to_string_lossy().ends_with(".json.gz") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+
+    let server = CacheServer::new(&files)?;
+    let listener = TcpListener::bind(&args.address)
+        .await
+        .with_context(|| format!("could not listen on {}", args.address))?;
+    info!(
+        "Serving {} cache files on http://{}",
+        files.len(),
+        listener.local_addr()?
+    );
+
+    server.run(listener).await
+}
+
+/// Print the statistics and missing entries of a cache file, validate and prune it.
+pub fn inspect_cache(args: &InspectCacheArgs) -> anyhow::Result<()> {
+    if !args.file.exists() {
+        bail!("cache file {:?} does not exist", args.file);
+    }
+    let block_no = match args.block_number {
+        Some(block_no) => block_no,
+        None => args
+            .file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.split('.').next())
+            .and_then(|stem| stem.parse().ok())
+            .context("block number missing and not contained in the file name")?,
+    };
+    let mut cache = FileProvider::new(args.file.clone())?;
+
+    println!("{}", cache.stats());
+    let missing = cache.missing(block_no);
+    for entry in &missing {
+        warn!("Missing for block {}: {}", block_no, entry);
+    }
+    println!("missing entries for block {}: {}", block_no, missing.len());
+
+    if args.validate {
+        let problems = cache.validate();
+        for problem in &problems {
+            warn!("{}", problem);
+        }
+        if !problems.is_empty() {
+            bail!("cache contains {} inconsistencies", problems.len());
+        }
+        info!("All proofs are valid");
+    }
+    if args.prune {
+        let removed = cache.prune(block_no);
+        cache.save()?;
+        info!("Removed {} entries", removed);
+    }
+
+    Ok(())
+}
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 pub mod build;
+pub mod cache;
 pub mod rollups;
 pub mod snarks;
 
@@ -29,7 +30,7 @@ use risc0_zkvm::{
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use zeth_primitives::keccak::keccak;
 
-use crate::{cli::Cli, load_receipt, save_receipt};
+use crate::{cli::BlockCommand, load_receipt, save_receipt};
 
 pub async fn stark2snark(
     image_id: Digest,
@@ -174,13 +175,13 @@ pub async fn verify_bonsai_receipt<O: Eq + Debug + DeserializeOwned>(
 }
 
 pub async fn maybe_prove<I: Serialize, O: Eq + Debug + Serialize + DeserializeOwned>(
-    cli: &Cli,
+    cli: &BlockCommand,
     input: &I,
     elf: &[u8],
     expected_output: &O,
     assumptions: (Vec<Assumption>, Vec<String>),
 ) -> Option<(String, Receipt)> {
-    let Cli::Prove(prove_args) = cli else {
+    let BlockCommand::Prove(prove_args) = cli else {
         return None;
     };
 
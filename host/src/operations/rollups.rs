@@ -38,26 +38,27 @@ use zeth_primitives::{
 };
 
 use crate::{
-    cli::{Cli, Network},
+    cli::{BlockCommand, Network},
     operations::{maybe_prove, verify_bonsai_receipt},
 };
 
-pub async fn derive_rollup_blocks(cli: &Cli) -> anyhow::Result<Option<(String, Receipt)>> {
+pub async fn derive_rollup_blocks(cli: &BlockCommand) -> anyhow::Result<Option<(String, Receipt)>> {
     info!("Fetching data ...");
     let build_args = cli.build_args();
     let op_builder_provider_factory = ProviderFactory::new(
         build_args.cache.clone(),
         Network::Optimism.to_string(),
-        build_args.op_rpc_url.clone(),
-    );
+        build_args.op_rpc_config(),
+    )
+    .with_store(build_args.store);
 
     info!("Running preflight");
     let config = ChainConfig::optimism();
     let derive_input = DeriveInput {
         db: RpcDb::new(
             &config,
-            build_args.eth_rpc_url.clone(),
-            build_args.op_rpc_url.clone(),
+            build_args.eth_rpc_config(),
+            build_args.op_rpc_config(),
             build_args.cache.clone(),
         ),
         op_head_block_no: build_args.block_number,
@@ -119,7 +120,7 @@ pub async fn derive_rollup_blocks(cli: &Cli) -> anyhow::Result<Option<(String, R
     }
 
     let final_result = match cli {
-        Cli::Prove(..) => {
+        BlockCommand::Prove(..) => {
             maybe_prove(
                 cli,
                 &derive_input_mem,
@@ -129,7 +130,7 @@ pub async fn derive_rollup_blocks(cli: &Cli) -> anyhow::Result<Option<(String, R
             )
             .await
         }
-        Cli::Verify(verify_args) => Some(
+        BlockCommand::Verify(verify_args) => Some(
             verify_bonsai_receipt(
                 OP_DERIVE_ID.into(),
                 &derive_output,
@@ -145,7 +146,7 @@ pub async fn derive_rollup_blocks(cli: &Cli) -> anyhow::Result<Option<(String, R
 }
 
 pub async fn compose_derived_rollup_blocks(
-    cli: &Cli,
+    cli: &BlockCommand,
     composition_size: u32,
 ) -> anyhow::Result<Option<(String, Receipt)>> {
     let build_args = cli.build_args();
@@ -157,15 +158,16 @@ pub async fn compose_derived_rollup_blocks(
         let config = ChainConfig::optimism();
         let db = RpcDb::new(
             &config,
-            build_args.eth_rpc_url.clone(),
-            build_args.op_rpc_url.clone(),
+            build_args.eth_rpc_config(),
+            build_args.op_rpc_config(),
             build_args.cache.clone(),
         );
         let op_builder_provider_factory = ProviderFactory::new(
             build_args.cache.clone(),
             Network::Optimism.to_string(),
-            build_args.op_rpc_url.clone(),
-        );
+            build_args.op_rpc_config(),
+        )
+        .with_store(build_args.store);
 
         let derive_input = DeriveInput {
             db,
@@ -449,7 +451,7 @@ pub async fn compose_derived_rollup_blocks(
             ),
         )
         .await
-    } else if let Cli::Verify(verify_args) = cli {
+    } else if let BlockCommand::Verify(verify_args) = cli {
         Some(
             verify_bonsai_receipt(
                 OP_COMPOSE_ID.into(),
@@ -470,7 +472,7 @@ pub async fn compose_derived_rollup_blocks(
 }
 
 async fn build_op_blocks(
-    cli: &Cli,
+    cli: &BlockCommand,
     op_block_inputs: Vec<BlockBuildInput<OptimismTxEssence>>,
 ) -> (Vec<Assumption>, Vec<String>, Vec<BlockBuildOutput>) {
     let mut assumptions: Vec<Assumption> = vec![];
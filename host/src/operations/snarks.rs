@@ -22,7 +22,7 @@ use ethers_contract::abigen;
 use ethers_providers::{Http, Provider, RetryClient};
 use risc0_zkvm::sha::{Digest, Digestible};
 
-use crate::cli::Cli;
+use crate::cli::BlockCommand;
 
 sol!(
     /// A Groth16 seal over the claimed receipt claim.
@@ -81,7 +81,7 @@ impl From<Groth16Seal> for Seal {
 }
 
 pub async fn verify_groth16_snark(
-    cli: &Cli,
+    cli: &BlockCommand,
     image_id: Digest,
     snark_receipt: SnarkReceipt,
 ) -> anyhow::Result<()> {
@@ -19,24 +19,35 @@ use anyhow::{anyhow, bail, Context};
 use log::{debug, trace};
 use revm::{
     interpreter::Host,
-    primitives::{Account, Address, ResultAndState, SpecId, TransactTo, TxEnv},
+    primitives::{Account, Address, EvmStorageSlot, ResultAndState, SpecId, TransactTo, TxEnv},
     Database, DatabaseCommit, Evm,
 };
 use ruint::aliases::U256;
-use zeth_primitives::{alloy_rlp, receipt::Receipt, transactions::{
-    ethereum::{EthereumTxEssence, TransactionKind},
-    TxEssence,
-}, trie::MptNode, Bloom, TxHash, hex};
+use zeth_primitives::{
+    alloy_rlp, hex,
+    keccak::KECCAK_EMPTY,
+    receipt::Receipt,
+    transactions::{
+        ethereum::{EthereumTxEssence, TransactionKind},
+        TxEssence,
+    },
+    trie::MptNode,
+    BlockNumber, Bloom, TxHash, B256,
+};
 
 use super::TxExecStrategy;
-use crate::{builder::BlockBuilder, consts, guest_mem_forget};
+use crate::{
+    builder::BlockBuilder,
+    consts::{self, HISTORY_SERVE_WINDOW, HISTORY_STORAGE_ADDRESS},
+    guest_mem_forget,
+};
 
 pub struct EthTxExecStrategy {}
 
 impl TxExecStrategy<EthereumTxEssence> for EthTxExecStrategy {
-    fn execute_transactions<D>(
-        mut block_builder: BlockBuilder<D, EthereumTxEssence>,
-    ) -> anyhow::Result<BlockBuilder<D, EthereumTxEssence>>
+    fn execute_transactions<D, M>(
+        mut block_builder: BlockBuilder<D, EthereumTxEssence, M>,
+    ) -> anyhow::Result<BlockBuilder<D, EthereumTxEssence, M>>
     where
         D: Database + DatabaseCommit,
         <D as Database>::Error: Debug,
@@ -78,7 +89,7 @@ impl TxExecStrategy<EthereumTxEssence> for EthTxExecStrategy {
                 block_builder.input.state_input.beneficiary
             );
             trace!("  Gas limit: {}", block_builder.input.state_input.gas_limit);
-            trace!("  Base fee per gas: {}", header.base_fee_per_gas);
+            trace!("  Base fee per gas: {:?}", header.base_fee_per_gas);
             trace!(
                 "  Extra data: {:?}",
                 block_builder.input.state_input.extra_data
@@ -96,7 +107,7 @@ impl TxExecStrategy<EthereumTxEssence> for EthTxExecStrategy {
                 blk_env.timestamp = header.timestamp;
                 blk_env.difficulty = U256::ZERO;
                 blk_env.prevrandao = Some(header.mix_hash);
-                blk_env.basefee = header.base_fee_per_gas;
+                blk_env.basefee = header.base_fee_per_gas.unwrap_or_default();
                 blk_env.gas_limit = block_builder.input.state_input.gas_limit;
             })
             .modify_cfg_env(|cfg_env| {
@@ -105,6 +116,20 @@ impl TxExecStrategy<EthereumTxEssence> for EthTxExecStrategy {
             })
             .build();
 
+        // store the hash of the parent block before any transaction, as the EIP-2935 system
+        // call does
+        if block_builder
+            .chain_spec
+            .history_storage_active(header.number, &header.timestamp)
+        {
+            store_parent_hash(
+                &mut evm.context.evm.db,
+                header.number - 1,
+                header.parent_hash,
+            )
+            .context("Failed to store the parent hash")?;
+        }
+
         // bloom filter over all transaction logs
         let mut logs_bloom = Bloom::default();
         // keep track of the gas used over all transactions
@@ -157,11 +182,18 @@ impl TxExecStrategy<EthereumTxEssence> for EthTxExecStrategy {
                 tx.essence.tx_type(),
                 result.is_success(),
                 cumulative_gas_used,
-                result.logs().into_iter().map(|log| log.clone().into()).collect(),
+                result
+                    .logs()
+                    .into_iter()
+                    .map(|log| log.clone().into())
+                    .collect(),
             );
 
             // accumulate logs to the block bloom filter
             logs_bloom.accrue_bloom(&receipt.payload.logs_bloom);
+            if let Some(receipts) = block_builder.receipts.as_mut() {
+                receipts.push(receipt.clone());
+            }
 
             // Add receipt and tx to tries
             let trie_key = alloy_rlp::encode(tx_no);
@@ -337,3 +369,40 @@ where
 
     Ok(())
 }
+
+/// Stores the hash of the parent block in the
+/// [EIP-2935](https://eips.ethereum.org/EIPS/eip-2935) history storage contract. This has
+/// the same effect on the state as the system call, which fails silently when the
+/// contract has not been deployed.
+pub fn store_parent_hash<D>(
+    db: &mut D,
+    parent_number: BlockNumber,
+    parent_hash: B256,
+) -> anyhow::Result<()>
+where
+    D: Database + DatabaseCommit,
+    <D as Database>::Error: Debug,
+{
+    let db_err =
+        |db_err: <D as Database>::Error| anyhow!("Error reading the history storage: {:?}", db_err);
+    // Read the contract from database
+    let Some(info) = db.basic(HISTORY_STORAGE_ADDRESS).map_err(db_err)? else {
+        return Ok(());
+    };
+    if info.code_hash.0 == KECCAK_EMPTY.0 {
+        return Ok(());
+    }
+    let slot = U256::from(parent_number % HISTORY_SERVE_WINDOW);
+    let original_value = db.storage(HISTORY_STORAGE_ADDRESS, slot).map_err(db_err)?;
+    // Store the hash in its slot
+    let mut account: Account = info.into();
+    account.storage.insert(
+        slot,
+        EvmStorageSlot::new_changed(original_value, U256::from_be_bytes(parent_hash.0)),
+    );
+    account.mark_touch();
+    // Commit changes to database
+    db.commit([(HISTORY_STORAGE_ADDRESS, account)].into());
+
+    Ok(())
+}
@@ -24,7 +24,9 @@ pub(super) mod ethereum;
 // pub(super) mod optimism;
 
 pub trait TxExecStrategy<E: TxEssence> {
-    fn execute_transactions<D>(block_builder: BlockBuilder<D, E>) -> Result<BlockBuilder<D, E>>
+    fn execute_transactions<D, M>(
+        block_builder: BlockBuilder<D, E, M>,
+    ) -> Result<BlockBuilder<D, E, M>>
     where
         D: Database + DatabaseCommit,
         <D as Database>::Error: Debug;
@@ -81,7 +81,7 @@ impl TxExecStrategy<OptimismTxEssence> for OpTxExecStrategy {
                 block_builder.input.state_input.beneficiary
             );
             trace!("  Gas limit: {}", block_builder.input.state_input.gas_limit);
-            trace!("  Base fee per gas: {}", header.base_fee_per_gas);
+            trace!("  Base fee per gas: {:?}", header.base_fee_per_gas);
             trace!(
                 "  Extra data: {:?}",
                 block_builder.input.state_input.extra_data
@@ -100,7 +100,7 @@ impl TxExecStrategy<OptimismTxEssence> for OpTxExecStrategy {
                 blk_env.timestamp = header.timestamp;
                 blk_env.difficulty = U256::ZERO;
                 blk_env.prevrandao = Some(header.mix_hash);
-                blk_env.basefee = header.base_fee_per_gas;
+                blk_env.basefee = header.base_fee_per_gas.unwrap_or_default();
                 blk_env.gas_limit = block_builder.input.state_input.gas_limit;
             })
             .modify_cfg_env(|cfg_env| {
@@ -231,6 +231,9 @@ impl TxExecStrategy<OptimismTxEssence> for OpTxExecStrategy {
 
             // accumulate logs to the block bloom filter
             logs_bloom.accrue_bloom(&receipt.payload.logs_bloom);
+            if let Some(receipts) = block_builder.receipts.as_mut() {
+                receipts.push(receipt.clone());
+            }
 
             // Add receipt and tx to tries
             let trie_key = alloy_rlp::encode(tx_no);
@@ -21,7 +21,7 @@ use zeth_primitives::{
     hex::FromHex,
     keccak::keccak,
     transactions::TxEssence,
-    trie::{MptNode, StateAccount},
+    trie::{StateAccount, Trie},
     U256,
 };
 
@@ -36,22 +36,25 @@ where
     D: Database + DatabaseCommit,
     <D as Database>::Error: core::fmt::Debug,
 {
-    fn finalize<E>(block_builder: BlockBuilder<D, E>) -> Result<(Header, MptNode)>
+    fn finalize<E, M>(block_builder: BlockBuilder<D, E, M>) -> Result<(Header, M)>
     where
-        E: TxEssence;
+        E: TxEssence,
+        M: Trie;
 }
 
 pub struct MemDbBlockFinalizeStrategy {}
 
 impl BlockFinalizeStrategy<MemDb> for MemDbBlockFinalizeStrategy {
-    fn finalize<E: TxEssence>(
-        mut block_builder: BlockBuilder<MemDb, E>,
-    ) -> Result<(Header, MptNode)> {
+    fn finalize<E: TxEssence, M: Trie>(
+        mut block_builder: BlockBuilder<MemDb, E, M>,
+    ) -> Result<(Header, M)> {
         let db = block_builder.db.take().expect("DB not initialized");
 
         // apply state updates
         let mut state_trie = mem::take(&mut block_builder.input.parent_state_trie);
 
+        // collect all state updates, so that they can be applied to the trie in one batch
+        let mut state_updates = Vec::with_capacity(db.accounts.len());
         for (address, account) in &db.accounts {
             // if the account has not been touched, it can be ignored
             if account.state == AccountState::None {
@@ -63,13 +66,12 @@ impl BlockFinalizeStrategy<MemDb> for MemDbBlockFinalizeStrategy {
 
             // remove deleted accounts from the state trie
             if account.state == AccountState::Deleted {
-                state_trie.delete(&state_trie_index)?;
+                state_updates.push((state_trie_index, None));
                 continue;
             }
 
             // otherwise, compute the updated storage root for that account
-            let mut state_storage = account.storage.clone();
-            let mut storage_root = {
+            let storage_root = {
                 // getting a mutable reference is more efficient than calling remove
                 // every account must have an entry, even newly created accounts
                 let (storage_trie, _) =
@@ -80,14 +82,17 @@ impl BlockFinalizeStrategy<MemDb> for MemDbBlockFinalizeStrategy {
                 }
 
                 // apply all new storage entries for the current account (address)
-                for (key, value) in state_storage {
-                    let storage_trie_index = keccak(key.to_be_bytes::<32>());
-                    if value == U256::ZERO {
-                        storage_trie.delete(&storage_trie_index)?;
-                    } else {
-                        storage_trie.insert_rlp(&storage_trie_index, value)?;
-                    }
-                }
+                let mut storage_updates: Vec<_> = account
+                    .storage
+                    .iter()
+                    .map(|(key, value)| {
+                        let storage_trie_index = keccak(key.to_be_bytes::<32>());
+                        let value = (*value != U256::ZERO).then(|| alloy_rlp::encode(value));
+                        (storage_trie_index, value)
+                    })
+                    .collect();
+                storage_updates.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+                storage_trie.batch_update(storage_updates)?;
 
                 storage_trie.hash()
             };
@@ -98,8 +103,10 @@ impl BlockFinalizeStrategy<MemDb> for MemDbBlockFinalizeStrategy {
                 storage_root,
                 code_hash: account.info.code_hash,
             };
-            state_trie.insert_rlp(&state_trie_index, state_account)?;
+            state_updates.push((state_trie_index, Some(alloy_rlp::encode(state_account))));
         }
+        state_updates.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        state_trie.batch_update(state_updates)?;
 
         // update result header with the new state root
         let mut header = block_builder.header.take().expect("Header not initialized");
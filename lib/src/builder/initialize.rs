@@ -17,40 +17,42 @@ use core::mem;
 use anyhow::{bail, Result};
 use fluentbase_types::POSEIDON_EMPTY;
 use revm::{
-    Database,
-    DatabaseCommit, primitives::{AccountInfo, B256, Bytecode},
+    primitives::{AccountInfo, Bytecode, B256},
+    Database, DatabaseCommit,
 };
-
 use zeth_primitives::{
-    Bytes,
     keccak::{keccak, KECCAK_EMPTY},
     transactions::TxEssence,
-    trie::StateAccount,
+    trie::{StateAccount, Trie},
+    Bytes, U256,
 };
 
 use crate::{
     builder::BlockBuilder,
-    consts::MAX_BLOCK_HASH_AGE,
+    consts::{HISTORY_SERVE_WINDOW, HISTORY_STORAGE_ADDRESS, MAX_BLOCK_HASH_AGE},
     mem_db::{AccountState, DbAccount, MemDb},
+    HashMap,
 };
-use crate::HashMap;
 
 pub trait DbInitStrategy<D>
 where
     D: Database + DatabaseCommit,
     <D as Database>::Error: core::fmt::Debug,
 {
-    fn initialize_database<E>(block_builder: BlockBuilder<D, E>) -> Result<BlockBuilder<D, E>>
+    fn initialize_database<E, M>(
+        block_builder: BlockBuilder<D, E, M>,
+    ) -> Result<BlockBuilder<D, E, M>>
     where
-        E: TxEssence;
+        E: TxEssence,
+        M: Trie;
 }
 
 pub struct MemDbInitStrategy {}
 
 impl DbInitStrategy<MemDb> for MemDbInitStrategy {
-    fn initialize_database<E: TxEssence>(
-        mut block_builder: BlockBuilder<MemDb, E>,
-    ) -> Result<BlockBuilder<MemDb, E>> {
+    fn initialize_database<E: TxEssence, M: Trie>(
+        mut block_builder: BlockBuilder<MemDb, E, M>,
+    ) -> Result<BlockBuilder<MemDb, E, M>> {
         // Verify state trie root
         if block_builder.input.parent_state_trie.hash()
             != block_builder.input.state_input.parent_header.state_root
@@ -158,7 +160,37 @@ impl DbInitStrategy<MemDb> for MemDbInitStrategy {
             prev = current;
         }
 
-        let mut contracts: HashMap<B256, Bytecode> = contracts.into_iter()
+        // the hashes of older blocks can also be read from the history storage contract
+        let parent_number = block_builder.input.state_input.parent_header.number;
+        let history = match block_builder.chain_spec.history_storage_active(
+            parent_number + 1,
+            &block_builder.input.state_input.timestamp,
+        ) {
+            true => accounts.get(&HISTORY_STORAGE_ADDRESS),
+            false => None,
+        };
+        if let (Some(history), Some(latest)) = (history, parent_number.checked_sub(1)) {
+            // when the parent was built, the hash of its own parent was stored last
+            for (slot, value) in &history.storage {
+                if value.is_zero() || *slot >= U256::from(HISTORY_SERVE_WINDOW) {
+                    continue;
+                }
+                let slot: u64 = slot.saturating_to();
+                let age = (latest % HISTORY_SERVE_WINDOW + HISTORY_SERVE_WINDOW - slot)
+                    % HISTORY_SERVE_WINDOW;
+                match latest.checked_sub(age) {
+                    Some(number) if parent_number - number < MAX_BLOCK_HASH_AGE => {
+                        block_hashes
+                            .entry(number)
+                            .or_insert_with(|| B256::from(value.to_be_bytes::<32>()));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut contracts: HashMap<B256, Bytecode> = contracts
+            .into_iter()
             .map(|v| (v.0, Bytecode::LegacyRaw(v.1.clone())))
             .collect();
         contracts.insert(KECCAK_EMPTY, Bytecode::new());
@@ -17,8 +17,9 @@ use revm::{primitives::SpecId, Database, DatabaseCommit};
 use serde::Serialize;
 use zeth_primitives::{
     block::Header,
+    receipt::Receipt,
     transactions::{ethereum::EthereumTxEssence, TxEssence},
-    trie::MptNode,
+    trie::{MptNode, Trie},
 };
 
 pub use crate::{
@@ -44,28 +45,33 @@ mod initialize;
 mod prepare;
 
 /// A generic builder for building a block.
+///
+/// The tries of the input are represented by `M`, which defaults to [MptNode].
 #[derive(Clone, Debug)]
-pub struct BlockBuilder<'a, D, E: TxEssence> {
+pub struct BlockBuilder<'a, D, E: TxEssence, M = MptNode> {
     pub(crate) chain_spec: &'a ChainSpec,
-    pub(crate) input: BlockBuildInput<E>,
+    pub(crate) input: BlockBuildInput<E, M>,
     pub(crate) db: Option<D>,
     pub(crate) spec_id: Option<SpecId>,
     pub(crate) header: Option<Header>,
+    pub(crate) receipts: Option<Vec<Receipt>>,
 }
 
-impl<D, E> BlockBuilder<'_, D, E>
+impl<D, E, M> BlockBuilder<'_, D, E, M>
 where
     D: Database + DatabaseCommit,
     <D as Database>::Error: core::fmt::Debug,
     E: TxEssence,
+    M: Trie,
 {
     /// Creates a new block builder.
-    pub fn new(chain_spec: &ChainSpec, input: BlockBuildInput<E>) -> BlockBuilder<'_, D, E> {
+    pub fn new(chain_spec: &ChainSpec, input: BlockBuildInput<E, M>) -> BlockBuilder<'_, D, E, M> {
         BlockBuilder {
             chain_spec,
             db: None,
             spec_id: None,
             header: None,
+            receipts: None,
             input,
         }
     }
@@ -76,6 +82,13 @@ where
         self
     }
 
+    /// Records the receipts of the executed transactions, so that they can be inspected
+    /// after the execution.
+    pub fn with_receipts(mut self) -> Self {
+        self.receipts = Some(Vec::new());
+        self
+    }
+
     /// Initializes the database from the input.
     pub fn initialize_database<T: DbInitStrategy<D>>(self) -> Result<Self> {
         T::initialize_database(self)
@@ -92,7 +105,7 @@ where
     }
 
     /// Finalizes the block building and returns the header and the state trie.
-    pub fn finalize<T: BlockFinalizeStrategy<D>>(self) -> Result<(Header, MptNode)> {
+    pub fn finalize<T: BlockFinalizeStrategy<D>>(self) -> Result<(Header, M)> {
         T::finalize(self)
     }
 
@@ -106,6 +119,11 @@ where
         self.db.as_mut()
     }
 
+    /// Returns the recorded receipts, if enabled with [BlockBuilder::with_receipts].
+    pub fn take_receipts(&mut self) -> Option<Vec<Receipt>> {
+        self.receipts.take()
+    }
+
     /// Destroys the builder and returns the database
     pub fn take_db(mut self) -> Option<D> {
         self.db.take()
@@ -121,14 +139,14 @@ pub trait BlockBuilderStrategy {
     type TxExecStrategy: TxExecStrategy<Self::TxEssence>;
     type BlockFinalizeStrategy: BlockFinalizeStrategy<MemDb>;
 
-    /// Builds a block from the given input.
-    fn build_from(
+    /// Builds a block from the given input, using the trie representation of the input.
+    fn build_from<M: Trie>(
         chain_spec: &ChainSpec,
-        input: BlockBuildInput<Self::TxEssence>,
-    ) -> Result<BlockBuildOutput> {
+        input: BlockBuildInput<Self::TxEssence, M>,
+    ) -> Result<BlockBuildOutput<M>> {
         let input_hash = input.state_input.hash();
 
-        let builder = BlockBuilder::<MemDb, Self::TxEssence>::new(chain_spec, input);
+        let builder = BlockBuilder::<MemDb, Self::TxEssence, M>::new(chain_spec, input);
 
         // Database initialization errors do not indicate a faulty block
         let initialized = builder.initialize_database::<Self::DbInitStrategy>()?;
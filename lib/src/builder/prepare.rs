@@ -24,7 +24,9 @@ use crate::{
 };
 
 pub trait HeaderPrepStrategy {
-    fn prepare_header<D, E>(block_builder: BlockBuilder<D, E>) -> Result<BlockBuilder<D, E>>
+    fn prepare_header<D, E, M>(
+        block_builder: BlockBuilder<D, E, M>,
+    ) -> Result<BlockBuilder<D, E, M>>
     where
         D: Database + DatabaseCommit,
         <D as Database>::Error: core::fmt::Debug,
@@ -34,7 +36,9 @@ pub trait HeaderPrepStrategy {
 pub struct EthHeaderPrepStrategy {}
 
 impl HeaderPrepStrategy for EthHeaderPrepStrategy {
-    fn prepare_header<D, E>(mut block_builder: BlockBuilder<D, E>) -> Result<BlockBuilder<D, E>>
+    fn prepare_header<D, E, M>(
+        mut block_builder: BlockBuilder<D, E, M>,
+    ) -> Result<BlockBuilder<D, E, M>>
     where
         D: Database + DatabaseCommit,
         <D as Database>::Error: Debug,
@@ -94,15 +98,22 @@ impl HeaderPrepStrategy for EthHeaderPrepStrategy {
             .active_fork(number, &timestamp)
             .unwrap_or_else(|err| panic!("Invalid version: {:#}", err));
         block_builder.spec_id = Some(spec_id);
+        let parent_base_fee = block_builder
+            .input
+            .state_input
+            .parent_header
+            .base_fee_per_gas
+            .context("Invalid parent header: base fee missing")?;
         // Derive header
         block_builder.header = Some(Header {
             // Initialize fields that we can compute from the parent
             parent_hash: block_builder.input.state_input.parent_header.hash(),
             number,
-            base_fee_per_gas: derive_base_fee(
+            base_fee_per_gas: Some(derive_base_fee(
                 &block_builder.input.state_input.parent_header,
+                parent_base_fee,
                 block_builder.chain_spec.gas_constants(spec_id).unwrap(),
-            ),
+            )),
             // Initialize metadata from input
             beneficiary: block_builder.input.state_input.beneficiary,
             gas_limit: block_builder.input.state_input.gas_limit,
@@ -116,35 +127,35 @@ impl HeaderPrepStrategy for EthHeaderPrepStrategy {
 }
 
 /// Base fee for next block. [EIP-1559](https://github.com/ethereum/EIPs/blob/master/EIPS/eip-1559.md) spec
-fn derive_base_fee(parent: &Header, eip_1559_constants: &Eip1559Constants) -> U256 {
+fn derive_base_fee(
+    parent: &Header,
+    parent_base_fee: U256,
+    eip_1559_constants: &Eip1559Constants,
+) -> U256 {
     let parent_gas_target = parent.gas_limit / eip_1559_constants.elasticity_multiplier;
 
     match parent.gas_used.cmp(&parent_gas_target) {
-        std::cmp::Ordering::Equal => parent.base_fee_per_gas,
+        std::cmp::Ordering::Equal => parent_base_fee,
 
         std::cmp::Ordering::Greater => {
             let gas_used_delta = parent.gas_used - parent_gas_target;
             let base_fee_delta = ONE
                 .max(
-                    parent.base_fee_per_gas * gas_used_delta
+                    parent_base_fee * gas_used_delta
                         / parent_gas_target
                         / eip_1559_constants.base_fee_change_denominator,
                 )
-                .min(
-                    parent.base_fee_per_gas / eip_1559_constants.base_fee_max_increase_denominator,
-                );
-            parent.base_fee_per_gas + base_fee_delta
+                .min(parent_base_fee / eip_1559_constants.base_fee_max_increase_denominator);
+            parent_base_fee + base_fee_delta
         }
 
         std::cmp::Ordering::Less => {
             let gas_used_delta = parent_gas_target - parent.gas_used;
-            let base_fee_delta = (parent.base_fee_per_gas * gas_used_delta
+            let base_fee_delta = (parent_base_fee * gas_used_delta
                 / parent_gas_target
                 / eip_1559_constants.base_fee_change_denominator)
-                .min(
-                    parent.base_fee_per_gas / eip_1559_constants.base_fee_max_decrease_denominator,
-                );
-            parent.base_fee_per_gas - base_fee_delta
+                .min(parent_base_fee / eip_1559_constants.base_fee_max_decrease_denominator);
+            parent_base_fee - base_fee_delta
         }
     }
 }
@@ -0,0 +1,535 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use zeth_primitives::{
+    block::Header,
+    keccak::keccak,
+    transactions::TxEssence,
+    trie::{ArenaNodeData, ArenaTrie, MptNode, MptNodeData, StateAccount, Trie},
+    Address, Bytes, B256, U256,
+};
+
+use crate::{
+    input::{BlockBuildInput, StateInput},
+    HashMap, HashSet,
+};
+
+/// A node in the node table of a [CompactBlockBuildInput].
+///
+/// Child nodes are referenced by their index in the node table instead of being nested.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub enum CompactNode {
+    /// Represents an empty trie node.
+    Null,
+    /// The indices of the children incremented by one, where `0` indicates no child.
+    Branch([u32; 16]),
+    /// The encoded path and the value of a leaf.
+    Leaf(Bytes, Bytes),
+    /// The encoded path and the index of the child of an extension.
+    Extension(Bytes, u32),
+    /// An unresolved sub-trie.
+    Digest(B256),
+}
+
+impl CompactNode {
+    /// Returns the indices of all the children of the node.
+    fn children(&self) -> impl Iterator<Item = u32> + '_ {
+        let (branch, extension) = match self {
+            CompactNode::Branch(children) => (&children[..], None),
+            CompactNode::Extension(_, child) => (&[][..], Some(*child)),
+            _ => (&[][..], None),
+        };
+        branch
+            .iter()
+            .filter_map(|child| child.checked_sub(1))
+            .chain(extension)
+    }
+}
+
+/// Flat and deduplicated representation of a [BlockBuildInput].
+///
+/// Instead of nesting the nodes of each trie, all unique nodes of the state trie and the
+/// storage tries are stored once in a common node table, where a node is always stored
+/// after all of its children. Tries are then given by the index of their root node.
+/// Similarly, the ancestor headers only store the parent hashes that do not follow from
+/// the chain itself.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct CompactBlockBuildInput<E: TxEssence> {
+    /// Block and transaction data to execute
+    pub state_input: StateInput<E>,
+    /// Table of all unique trie nodes.
+    pub nodes: Vec<CompactNode>,
+    /// Index of the root node of the state trie of the parent block.
+    pub parent_state_trie: u32,
+    /// Each address with the index of the root node of its storage trie and the used
+    /// storage slots, sorted by address.
+    pub parent_storage: Vec<(Address, u32, Vec<U256>)>,
+    /// The code of all unique contracts.
+    pub contracts: Vec<Bytes>,
+    /// List of at most 256 previous block headers, oldest first. The parent hash of each
+    /// header that is the hash of the header before it is left zero.
+    pub ancestor_headers: Vec<Header>,
+}
+
+impl<E: TxEssence> From<BlockBuildInput<E>> for CompactBlockBuildInput<E> {
+    fn from(input: BlockBuildInput<E>) -> Self {
+        let mut table = NodeTable::default();
+        let parent_state_trie = table.insert(&input.parent_state_trie);
+
+        let mut parent_storage: Vec<_> = input
+            .parent_storage
+            .into_iter()
+            .map(|(address, (storage_trie, slots))| (address, table.insert(&storage_trie), slots))
+            .collect();
+        parent_storage.sort_unstable_by_key(|(address, _, _)| *address);
+
+        // deduplicate the contracts by their code hash
+        let mut code_hashes = HashSet::new();
+        let contracts = input
+            .contracts
+            .into_iter()
+            .filter(|code| code_hashes.insert(keccak(code)))
+            .collect();
+
+        // omit the parent hashes that are restored from the previous header when loading
+        let mut ancestor_headers = input.ancestor_headers;
+        ancestor_headers.reverse();
+        let mut parent_hash = None;
+        for header in &mut ancestor_headers {
+            let hash = header.hash();
+            if parent_hash == Some(header.parent_hash) {
+                header.parent_hash = B256::ZERO;
+            }
+            parent_hash = Some(hash);
+        }
+
+        CompactBlockBuildInput {
+            state_input: input.state_input,
+            nodes: table.nodes,
+            parent_state_trie,
+            parent_storage,
+            contracts,
+            ancestor_headers,
+        }
+    }
+}
+
+/// Rebuilds the [BlockBuildInput] and verifies the roots of all the tries.
+impl<E: TxEssence> TryFrom<CompactBlockBuildInput<E>> for BlockBuildInput<E> {
+    type Error = anyhow::Error;
+
+    fn try_from(mut input: CompactBlockBuildInput<E>) -> Result<Self> {
+        let mut loader = NodeLoader::new(core::mem::take(&mut input.nodes), input.roots())?;
+        input.load(|root| Ok(loader.take(root)))
+    }
+}
+
+/// Rebuilds the [BlockBuildInput] with [ArenaTrie]s and verifies the roots of all the
+/// tries.
+impl<E: TxEssence> TryFrom<CompactBlockBuildInput<E>> for BlockBuildInput<E, ArenaTrie> {
+    type Error = anyhow::Error;
+
+    fn try_from(mut input: CompactBlockBuildInput<E>) -> Result<Self> {
+        let table = core::mem::take(&mut input.nodes);
+        count_refs(&table, input.roots())?;
+        input.load(|root| arena_trie(&table, root))
+    }
+}
+
+impl<E: TxEssence> CompactBlockBuildInput<E> {
+    /// Returns the indices of the root nodes of all the tries.
+    fn roots(&self) -> impl Iterator<Item = u32> + '_ {
+        core::iter::once(self.parent_state_trie)
+            .chain(self.parent_storage.iter().map(|(_, root, _)| *root))
+    }
+
+    /// Assembles the [BlockBuildInput] from the tries returned by `take` for each root.
+    fn load<M: Trie>(
+        self,
+        mut take: impl FnMut(u32) -> Result<M>,
+    ) -> Result<BlockBuildInput<E, M>> {
+        let parent_state_trie = take(self.parent_state_trie)?;
+        let state_root = self.state_input.parent_header.state_root;
+        if parent_state_trie.hash() != state_root {
+            bail!(
+                "Invalid state trie: expected {}, got {}",
+                state_root,
+                parent_state_trie.hash()
+            );
+        }
+
+        let mut parent_storage = HashMap::with_capacity(self.parent_storage.len());
+        for (address, root, slots) in self.parent_storage {
+            let storage_trie = take(root)?;
+            let state_account = parent_state_trie
+                .get_rlp::<StateAccount>(&keccak(address))?
+                .unwrap_or_default();
+            if storage_trie.hash() != state_account.storage_root {
+                bail!(
+                    "Invalid storage trie for {:?}: expected {}, got {}",
+                    address,
+                    state_account.storage_root,
+                    storage_trie.hash()
+                );
+            }
+            if parent_storage
+                .insert(address, (storage_trie, slots))
+                .is_some()
+            {
+                bail!("Duplicate storage trie for {:?}", address);
+            }
+        }
+
+        // restore the omitted parent hashes, the chain itself is verified when building
+        let mut ancestor_headers = self.ancestor_headers;
+        let mut parent_hash = None;
+        for header in &mut ancestor_headers {
+            if let Some(hash) = parent_hash.filter(|_| header.parent_hash.is_zero()) {
+                header.parent_hash = hash;
+            }
+            parent_hash = Some(header.hash());
+        }
+        ancestor_headers.reverse();
+
+        Ok(BlockBuildInput {
+            state_input: self.state_input,
+            parent_state_trie,
+            parent_storage,
+            contracts: self.contracts,
+            ancestor_headers,
+        })
+    }
+}
+
+/// Builds the node table by inserting each node only once.
+#[derive(Default)]
+struct NodeTable {
+    nodes: Vec<CompactNode>,
+    /// Maps each node to its index. Nodes with the same reference are not necessarily
+    /// identical, as they might be resolved to a different extent. Keying by the compact
+    /// node, i.e. by the node with the indices of its children, avoids that ambiguity.
+    indices: HashMap<CompactNode, u32>,
+}
+
+impl NodeTable {
+    fn insert(&mut self, node: &MptNode) -> u32 {
+        let compact = match node.as_data() {
+            MptNodeData::Null => CompactNode::Null,
+            MptNodeData::Branch(children) => {
+                let mut indices = [0; 16];
+                for (index, child) in indices.iter_mut().zip(children) {
+                    if let Some(child) = child {
+                        *index = self.insert(child) + 1;
+                    }
+                }
+                CompactNode::Branch(indices)
+            }
+            MptNodeData::Leaf(prefix, value) => {
+                CompactNode::Leaf(prefix.clone().into(), value.clone().into())
+            }
+            MptNodeData::Extension(prefix, child) => {
+                CompactNode::Extension(prefix.clone().into(), self.insert(child))
+            }
+            MptNodeData::Digest(digest) => CompactNode::Digest(*digest),
+        };
+
+        if let Some(index) = self.indices.get(&compact) {
+            return *index;
+        }
+        let index = self.nodes.len() as u32;
+        self.nodes.push(compact.clone());
+        self.indices.insert(compact, index);
+        index
+    }
+}
+
+/// Rebuilds the nested tries from the node table.
+struct NodeLoader {
+    nodes: Vec<Option<MptNode>>,
+    /// The number of remaining references to each node.
+    refs: Vec<u32>,
+}
+
+impl NodeLoader {
+    fn new(table: Vec<CompactNode>, roots: impl IntoIterator<Item = u32>) -> Result<Self> {
+        // count the references, so that shared nodes are only cloned when necessary
+        let refs = count_refs(&table, roots)?;
+
+        let mut loader = NodeLoader {
+            nodes: Vec::with_capacity(table.len()),
+            refs,
+        };
+        for node in table {
+            let data = match node {
+                CompactNode::Null => MptNodeData::Null,
+                CompactNode::Branch(indices) => {
+                    let mut children: [Option<Box<MptNode>>; 16] = Default::default();
+                    for (child, index) in children.iter_mut().zip(indices) {
+                        if index > 0 {
+                            *child = Some(Box::new(loader.take(index - 1)));
+                        }
+                    }
+                    MptNodeData::Branch(children)
+                }
+                CompactNode::Leaf(prefix, value) => {
+                    MptNodeData::Leaf(prefix.to_vec(), value.to_vec())
+                }
+                CompactNode::Extension(prefix, index) => {
+                    MptNodeData::Extension(prefix.to_vec(), Box::new(loader.take(index)))
+                }
+                CompactNode::Digest(digest) => MptNodeData::Digest(digest),
+            };
+            loader.nodes.push(Some(data.into()));
+        }
+
+        Ok(loader)
+    }
+
+    /// Returns the node at the given index, moving it out on its last reference.
+    fn take(&mut self, index: u32) -> MptNode {
+        let index = index as usize;
+        self.refs[index] -= 1;
+        if self.refs[index] == 0 {
+            self.nodes[index].take().unwrap()
+        } else {
+            self.nodes[index].clone().unwrap()
+        }
+    }
+}
+
+/// Validates the node table and returns the number of references to each node.
+fn count_refs(table: &[CompactNode], roots: impl IntoIterator<Item = u32>) -> Result<Vec<u32>> {
+    let mut refs = vec![0u32; table.len()];
+    for (index, node) in table.iter().enumerate() {
+        for child in node.children() {
+            ensure!(
+                (child as usize) < index,
+                "node {} references node {} which is not stored before it",
+                index,
+                child
+            );
+            refs[child as usize] += 1;
+        }
+    }
+    for root in roots {
+        *refs
+            .get_mut(root as usize)
+            .with_context(|| format!("root node {} does not exist", root))? += 1;
+    }
+    Ok(refs)
+}
+
+/// Copies the trie with the given root from the validated node table into a new
+/// [ArenaTrie].
+fn arena_trie(table: &[CompactNode], root: u32) -> Result<ArenaTrie> {
+    fn push(table: &[CompactNode], index: u32, nodes: &mut Vec<ArenaNodeData>) -> u32 {
+        let data = match &table[index as usize] {
+            CompactNode::Null => ArenaNodeData::Null,
+            CompactNode::Branch(indices) => {
+                let mut children = [None; 16];
+                for (child, index) in children.iter_mut().zip(indices) {
+                    *child = index.checked_sub(1).map(|index| push(table, index, nodes));
+                }
+                ArenaNodeData::Branch(children)
+            }
+            CompactNode::Leaf(prefix, value) => {
+                ArenaNodeData::Leaf(prefix.to_vec(), value.to_vec())
+            }
+            CompactNode::Extension(prefix, index) => {
+                ArenaNodeData::Extension(prefix.to_vec(), push(table, *index, nodes))
+            }
+            CompactNode::Digest(digest) => ArenaNodeData::Digest(*digest),
+        };
+        nodes.push(data);
+        (nodes.len() - 1) as u32
+    }
+
+    let mut nodes = Vec::new();
+    let root = push(table, root, &mut nodes);
+    Ok(ArenaTrie::from_parts(nodes, root)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::{keccak::KECCAK_EMPTY, transactions::ethereum::EthereumTxEssence};
+
+    use super::*;
+
+    fn storage_trie(n: u64) -> MptNode {
+        let mut trie = MptNode::default();
+        for i in 0..n {
+            trie.insert_rlp(&keccak(U256::from(i).to_be_bytes::<32>()), i + 1)
+                .unwrap();
+        }
+        trie
+    }
+
+    /// Replaces the first child of the root branch with its digest.
+    fn prune(trie: &MptNode) -> MptNode {
+        let MptNodeData::Branch(mut children) = trie.as_data().clone() else {
+            panic!("not a branch")
+        };
+        let child = children.iter_mut().flatten().next().unwrap();
+        **child = child.hash().into();
+        MptNodeData::Branch(children).into()
+    }
+
+    fn test_input() -> BlockBuildInput<EthereumTxEssence> {
+        // accounts with identical storage share all of their storage nodes, unless they
+        // are resolved differently
+        let storage: Vec<_> = [8, 8, 8, 0, 3]
+            .into_iter()
+            .enumerate()
+            .map(|(i, n)| {
+                let slots = (0..n).map(U256::from).collect();
+                let trie = match i {
+                    2 => prune(&storage_trie(n)),
+                    _ => storage_trie(n),
+                };
+                (Address::with_last_byte(i as u8), (trie, slots))
+            })
+            .collect();
+
+        let mut state_trie = MptNode::default();
+        for (address, (storage_trie, _)) in &storage {
+            let account = StateAccount {
+                nonce: 1,
+                balance: U256::from(100),
+                storage_root: storage_trie.hash(),
+                code_hash: KECCAK_EMPTY,
+            };
+            state_trie.insert_rlp(&keccak(address), account).unwrap();
+        }
+        // a chain of ancestors, newest first
+        let mut ancestor_headers: Vec<Header> = Vec::new();
+        for number in 1..4 {
+            let parent_hash = match ancestor_headers.first() {
+                Some(parent) => parent.hash(),
+                None => B256::repeat_byte(0x01),
+            };
+            let header = Header {
+                parent_hash,
+                number,
+                ..Default::default()
+            };
+            ancestor_headers.insert(0, header);
+        }
+        let parent_header = Header {
+            parent_hash: ancestor_headers[0].hash(),
+            number: 4,
+            state_root: state_trie.hash(),
+            ..Default::default()
+        };
+
+        BlockBuildInput {
+            state_input: StateInput {
+                parent_header,
+                beneficiary: Default::default(),
+                gas_limit: Default::default(),
+                timestamp: Default::default(),
+                extra_data: Default::default(),
+                mix_hash: Default::default(),
+                transactions: vec![],
+                withdrawals: vec![],
+            },
+            parent_state_trie: state_trie,
+            parent_storage: storage.into_iter().collect(),
+            contracts: vec![Bytes::from(vec![0x60, 0x00]), Bytes::from(vec![0x60, 0x00])],
+            ancestor_headers,
+        }
+    }
+
+    #[test]
+    fn compact_roundtrip() {
+        let input = test_input();
+        let compact = CompactBlockBuildInput::from(input.clone());
+
+        // nodes shared between the storage tries are only stored once
+        let total_nodes = input.parent_state_trie.size()
+            + input
+                .parent_storage
+                .values()
+                .map(|(trie, _)| trie.size())
+                .sum::<usize>();
+        assert!(compact.nodes.len() < total_nodes);
+        assert_eq!(compact.contracts.len(), 1);
+        // only the parent hash of the oldest header is stored
+        assert_eq!(
+            compact.ancestor_headers[0].parent_hash,
+            B256::repeat_byte(0x01)
+        );
+        assert!(compact.ancestor_headers[1..]
+            .iter()
+            .all(|header| header.parent_hash.is_zero()));
+
+        let compact: CompactBlockBuildInput<EthereumTxEssence> =
+            bincode::deserialize(&bincode::serialize(&compact).unwrap()).unwrap();
+        let arena = BlockBuildInput::<_, ArenaTrie>::try_from(compact.clone()).unwrap();
+        assert_loaded(&input, &arena);
+        let loaded = BlockBuildInput::<_, MptNode>::try_from(compact).unwrap();
+        assert_loaded(&input, &loaded);
+    }
+
+    fn assert_loaded<M: Trie>(
+        input: &BlockBuildInput<EthereumTxEssence>,
+        loaded: &BlockBuildInput<EthereumTxEssence, M>,
+    ) {
+        assert_eq!(
+            loaded.parent_state_trie.hash(),
+            input.parent_state_trie.hash()
+        );
+        assert_eq!(
+            loaded.parent_state_trie.size(),
+            input.parent_state_trie.size()
+        );
+        for (address, (trie, slots)) in &input.parent_storage {
+            let (loaded_trie, loaded_slots) = loaded.parent_storage.get(address).unwrap();
+            assert_eq!(loaded_trie.hash(), trie.hash());
+            assert_eq!(loaded_trie.size(), trie.size());
+            assert_eq!(loaded_slots, slots);
+        }
+        assert_eq!(loaded.ancestor_headers, input.ancestor_headers);
+    }
+
+    /// Returns whether loading the input fails for both trie representations.
+    fn load_fails(compact: CompactBlockBuildInput<EthereumTxEssence>) -> bool {
+        BlockBuildInput::<_, ArenaTrie>::try_from(compact.clone()).is_err()
+            && BlockBuildInput::<_, MptNode>::try_from(compact).is_err()
+    }
+
+    #[test]
+    fn compact_invalid_root() {
+        let mut input = test_input();
+        input.state_input.parent_header.state_root = B256::ZERO;
+        let compact = CompactBlockBuildInput::from(input);
+        assert!(load_fails(compact));
+
+        // swapping the storage tries must be detected
+        let mut compact = CompactBlockBuildInput::from(test_input());
+        let (_, root, _) = compact.parent_storage[4];
+        compact.parent_storage[0].1 = root;
+        assert!(load_fails(compact));
+    }
+
+    #[test]
+    fn compact_invalid_reference() {
+        let mut compact = CompactBlockBuildInput::from(test_input());
+        compact
+            .nodes
+            .insert(0, CompactNode::Extension(Bytes::from(vec![0x00]), 1));
+        assert!(load_fails(compact));
+    }
+}
@@ -21,7 +21,7 @@ use anyhow::{bail, Result};
 use once_cell::sync::Lazy;
 use revm::primitives::SpecId;
 use serde::{Deserialize, Serialize};
-use zeth_primitives::{uint, BlockNumber, ChainId, U256};
+use zeth_primitives::{address, keccak::keccak, uint, Address, BlockNumber, ChainId, B256, U256};
 
 /// U256 representation of 0.
 pub const ZERO: U256 = U256::ZERO;
@@ -39,6 +39,14 @@ pub const MAX_EXTRA_DATA_BYTES: usize = 32;
 /// Maximum allowed block number difference for the `block_hash` call.
 pub const MAX_BLOCK_HASH_AGE: u64 = 256;
 
+/// Address of the [EIP-2935](https://eips.ethereum.org/EIPS/eip-2935) contract storing the
+/// hashes of the previous blocks.
+pub const HISTORY_STORAGE_ADDRESS: Address = address!("0000F90827F1C53a10cb7A02335B175320002935");
+
+/// Number of block hashes kept by the history storage contract, the hash of a block is
+/// stored in the slot given by its number modulo this value.
+pub const HISTORY_SERVE_WINDOW: u64 = 8191;
+
 /// Multiplier for converting gwei to wei.
 pub const GWEI_TO_WEI: U256 = uint!(1_000_000_000_U256);
 
@@ -52,8 +60,13 @@ pub static ETH_MAINNET_CHAIN_SPEC: Lazy<ChainSpec> = Lazy::new(|| ChainSpec {
         (SpecId::CANCUN, ForkCondition::Timestamp(1710338135)),
     ]),
     gas_constants: BTreeMap::from([(SpecId::LONDON, ETH_MAINNET_EIP1559_CONSTANTS)]),
+    history_storage: ForkCondition::TBD,
 });
 
+/// The Fluent devnet specification.
+///
+/// The devnet deploys the history storage contract at genesis, so the block hashes are
+/// stored from the first block on.
 pub static FLUENT_DEVNET_CHAIN_SPEC: Lazy<ChainSpec> = Lazy::new(|| ChainSpec {
     chain_id: 1337,
     max_spec_id: SpecId::CANCUN,
@@ -63,6 +76,7 @@ pub static FLUENT_DEVNET_CHAIN_SPEC: Lazy<ChainSpec> = Lazy::new(|| ChainSpec {
         (SpecId::CANCUN, ForkCondition::Timestamp(1710338135)),
     ]),
     gas_constants: BTreeMap::from([(SpecId::LONDON, ETH_MAINNET_EIP1559_CONSTANTS)]),
+    history_storage: ForkCondition::Block(0),
 });
 
 /// The Ethereum mainnet EIP-1559 gas constants.
@@ -109,6 +123,7 @@ pub static OP_MAINNET_CHAIN_SPEC: Lazy<ChainSpec> = Lazy::new(|| ChainSpec {
             },
         ),
     ]),
+    history_storage: ForkCondition::TBD,
 });
 
 /// The condition at which a fork is activated.
@@ -132,6 +147,17 @@ impl ForkCondition {
             ForkCondition::TBD => false,
         }
     }
+
+    /// Appends a fixed-size encoding of the condition.
+    fn encode(&self, out: &mut Vec<u8>) {
+        let (tag, value) = match self {
+            ForkCondition::Block(block) => (0, *block),
+            ForkCondition::Timestamp(ts) => (1, *ts),
+            ForkCondition::TBD => (2, 0),
+        };
+        out.push(tag);
+        out.extend(value.to_be_bytes());
+    }
 }
 
 /// [EIP-1559](https://eips.ethereum.org/EIPS/eip-1559) parameters.
@@ -150,6 +176,10 @@ pub struct ChainSpec {
     max_spec_id: SpecId,
     hard_forks: BTreeMap<SpecId, ForkCondition>,
     gas_constants: BTreeMap<SpecId, Eip1559Constants>,
+    /// The condition at which the block hashes are stored in the history storage
+    /// contract.
+    #[serde(default)]
+    history_storage: ForkCondition,
 }
 
 impl ChainSpec {
@@ -164,8 +194,21 @@ impl ChainSpec {
             max_spec_id: spec_id,
             hard_forks: BTreeMap::from([(spec_id, ForkCondition::Block(0))]),
             gas_constants: BTreeMap::from([(spec_id, eip_1559_constants)]),
+            history_storage: ForkCondition::TBD,
         }
     }
+    /// Sets the condition at which the block hashes are stored in the
+    /// [EIP-2935](https://eips.ethereum.org/EIPS/eip-2935) history storage contract.
+    pub fn with_history_storage(mut self, condition: ForkCondition) -> Self {
+        self.history_storage = condition;
+        self
+    }
+    /// Returns whether the hashes of the previous blocks can be read from the history
+    /// storage contract when building the given block.
+    pub fn history_storage_active(&self, block_number: BlockNumber, timestamp: &U256) -> bool {
+        self.history_storage
+            .active(block_number, timestamp.saturating_to())
+    }
     /// Returns the network chain ID.
     pub fn chain_id(&self) -> ChainId {
         self.chain_id
@@ -184,6 +227,33 @@ impl ChainSpec {
             None => bail!("no supported fork for block {}", block_number),
         }
     }
+    /// Returns a commitment to all the rules of the chain.
+    pub fn hash(&self) -> B256 {
+        let mut bytes = Vec::new();
+        bytes.extend(self.chain_id.to_be_bytes());
+        bytes.push(self.max_spec_id as u8);
+        for (spec_id, condition) in &self.hard_forks {
+            bytes.push(*spec_id as u8);
+            condition.encode(&mut bytes);
+        }
+        for (spec_id, constants) in &self.gas_constants {
+            bytes.push(*spec_id as u8);
+            bytes.extend(constants.base_fee_change_denominator.to_be_bytes::<32>());
+            bytes.extend(
+                constants
+                    .base_fee_max_increase_denominator
+                    .to_be_bytes::<32>(),
+            );
+            bytes.extend(
+                constants
+                    .base_fee_max_decrease_denominator
+                    .to_be_bytes::<32>(),
+            );
+            bytes.extend(constants.elasticity_multiplier.to_be_bytes::<32>());
+        }
+        self.history_storage.encode(&mut bytes);
+        keccak(bytes).into()
+    }
     /// Returns the Eip1559 constants for a given [SpecId].
     pub fn gas_constants(&self, spec_id: SpecId) -> Option<&Eip1559Constants> {
         self.gas_constants
@@ -223,6 +293,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn history_storage() {
+        let timestamp = U256::from(1710338135);
+        assert!(!ETH_MAINNET_CHAIN_SPEC.history_storage_active(19426587, &timestamp));
+        assert!(FLUENT_DEVNET_CHAIN_SPEC.history_storage_active(1, &U256::ZERO));
+        let chain_spec = ETH_MAINNET_CHAIN_SPEC
+            .clone()
+            .with_history_storage(ForkCondition::Timestamp(1710338135));
+        assert!(chain_spec.history_storage_active(19426587, &timestamp));
+        assert!(!chain_spec.history_storage_active(19426586, &(timestamp - U256::from(12))));
+    }
+
+    #[test]
+    fn chain_spec_hash() {
+        assert_eq!(
+            ETH_MAINNET_CHAIN_SPEC.hash(),
+            ETH_MAINNET_CHAIN_SPEC.clone().hash()
+        );
+        assert_ne!(
+            ETH_MAINNET_CHAIN_SPEC.hash(),
+            FLUENT_DEVNET_CHAIN_SPEC.hash()
+        );
+        let chain_spec = ETH_MAINNET_CHAIN_SPEC
+            .clone()
+            .with_history_storage(ForkCondition::Block(0));
+        assert_ne!(ETH_MAINNET_CHAIN_SPEC.hash(), chain_spec.hash());
+    }
+
     #[test]
     fn gas_constants() {
         assert_eq!(ETH_MAINNET_CHAIN_SPEC.gas_constants(SpecId::BERLIN), None);
@@ -0,0 +1,264 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Versioned files containing a [BlockBuildInput], so that a block can be built again
+//! without any provider.
+//!
+//! A file starts with [INPUT_FILE_MAGIC] and the little-endian [INPUT_FILE_VERSION],
+//! followed by the length-prefixed [InputFileHeader] and the input, both encoded with
+//! bincode.
+//!
+//! An input file can be exported with [export_stf_input_file] into the input of the
+//! `fluent-stf` guest, so that the block can also be built in the guest.
+
+use std::{fs, path::Path};
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use zeth_primitives::{keccak::keccak, transactions::TxEssence, B256};
+
+use crate::{
+    builder::BlockBuilderStrategy,
+    compact::CompactBlockBuildInput,
+    consts::ChainSpec,
+    input::BlockBuildInput,
+    output::BlockBuildOutput,
+    stf::{StfInputHeader, StfStrategy},
+};
+
+/// The bytes every input file starts with.
+pub const INPUT_FILE_MAGIC: [u8; 8] = *b"ZETHINPT";
+/// The version of the file format.
+pub const INPUT_FILE_VERSION: u32 = 2;
+
+/// The metadata of the input contained in an input file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputFileHeader {
+    /// The chain spec to build the block with.
+    pub chain_spec: ChainSpec,
+    /// The number of the block built from the input.
+    pub block_number: u64,
+    /// The Keccak hash of the encoded input.
+    pub input_hash: B256,
+}
+
+/// Encodes the input to build a block of the given chain into the input file format.
+pub fn encode_input<E: TxEssence + Serialize>(
+    chain_spec: &ChainSpec,
+    input: &BlockBuildInput<E>,
+) -> Result<Vec<u8>> {
+    let body = bincode::serialize(input).context("failed to encode input")?;
+    let header = InputFileHeader {
+        chain_spec: chain_spec.clone(),
+        block_number: input.state_input.parent_header.number + 1,
+        input_hash: keccak(&body).into(),
+    };
+    let header = bincode::serialize(&header).context("failed to encode header")?;
+
+    let mut bytes = Vec::with_capacity(INPUT_FILE_MAGIC.len() + 8 + header.len() + body.len());
+    bytes.extend_from_slice(&INPUT_FILE_MAGIC);
+    bytes.extend_from_slice(&INPUT_FILE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&u32::try_from(header.len())?.to_le_bytes());
+    bytes.extend_from_slice(&header);
+    bytes.extend_from_slice(&body);
+    Ok(bytes)
+}
+
+/// Decodes an input file, checking its version and the hash of the input.
+pub fn decode_input<E: TxEssence + DeserializeOwned>(
+    bytes: &[u8],
+) -> Result<(InputFileHeader, BlockBuildInput<E>)> {
+    let (magic, bytes) = split(bytes, INPUT_FILE_MAGIC.len())?;
+    ensure!(magic == INPUT_FILE_MAGIC, "not an input file");
+    let (version, bytes) = split(bytes, 4)?;
+    let version = u32::from_le_bytes(version.try_into().unwrap());
+    if version != INPUT_FILE_VERSION {
+        bail!("unsupported input file version {version}, expected {INPUT_FILE_VERSION}");
+    }
+    let (header_len, bytes) = split(bytes, 4)?;
+    let header_len = u32::from_le_bytes(header_len.try_into().unwrap());
+    let (header, body) = split(bytes, header_len as usize)?;
+
+    let header: InputFileHeader =
+        bincode::deserialize(header).context("failed to decode header")?;
+    ensure!(
+        header.input_hash == B256::from(keccak(body)),
+        "input hash does not match, the file is corrupted"
+    );
+    let input: BlockBuildInput<E> = bincode::deserialize(body).context("failed to decode input")?;
+    ensure!(
+        input.state_input.parent_header.number + 1 == header.block_number,
+        "input is not for block {}",
+        header.block_number
+    );
+
+    Ok((header, input))
+}
+
+/// Writes the input to build a block of the given chain to an input file.
+pub fn write_input_file<E: TxEssence + Serialize>(
+    path: &Path,
+    chain_spec: &ChainSpec,
+    input: &BlockBuildInput<E>,
+) -> Result<()> {
+    let bytes = encode_input(chain_spec, input)?;
+    fs::write(path, bytes).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Reads an input file.
+pub fn read_input_file<E: TxEssence + DeserializeOwned>(
+    path: &Path,
+) -> Result<(InputFileHeader, BlockBuildInput<E>)> {
+    let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    decode_input(&bytes).with_context(|| format!("invalid input file {}", path.display()))
+}
+
+/// Builds the block from an input file with the chain spec recorded in the file.
+pub fn replay_input_file<N: BlockBuilderStrategy>(
+    path: &Path,
+) -> Result<(InputFileHeader, BlockBuildOutput)>
+where
+    N::TxEssence: DeserializeOwned,
+{
+    let (header, input) = read_input_file::<N::TxEssence>(path)?;
+    let output = N::build_from(&header.chain_spec, input)
+        .with_context(|| format!("failed to build block {}", header.block_number))?;
+    Ok((header, output))
+}
+
+/// Serializes the input of the `fluent-stf` guest, i.e. the header followed by the
+/// compacted block input, in the word format read by its `FluentWordReader`.
+pub fn stf_input<E: TxEssence + Serialize>(
+    header: &StfInputHeader,
+    input: BlockBuildInput<E>,
+) -> Result<Vec<u8>> {
+    let mut words = risc0_zkvm::serde::to_vec(header).context("failed to serialize header")?;
+    words.extend(
+        risc0_zkvm::serde::to_vec(&CompactBlockBuildInput::from(input))
+            .context("failed to serialize input")?,
+    );
+    Ok(words.into_iter().flat_map(u32::to_le_bytes).collect())
+}
+
+/// Converts an input file into the input of the `fluent-stf` guest, which builds the block
+/// with the given strategy and the chain spec recorded in the file.
+pub fn export_stf_input_file<E: TxEssence + Serialize + DeserializeOwned>(
+    path: &Path,
+    stf_path: &Path,
+    strategy: StfStrategy,
+) -> Result<InputFileHeader> {
+    let (header, input) = read_input_file::<E>(path)?;
+    let bytes = stf_input(
+        &StfInputHeader::new(strategy, header.chain_spec.clone()),
+        input,
+    )?;
+    fs::write(stf_path, bytes)
+        .with_context(|| format!("failed to write {}", stf_path.display()))?;
+    Ok(header)
+}
+
+fn split(bytes: &[u8], mid: usize) -> Result<(&[u8], &[u8])> {
+    ensure!(bytes.len() >= mid, "input file is truncated");
+    Ok(bytes.split_at(mid))
+}
+
+#[cfg(test)]
+mod tests {
+    use risc0_zkvm::serde::Deserializer;
+    use zeth_primitives::{block::Header, transactions::ethereum::EthereumTxEssence};
+
+    use super::*;
+    use crate::{consts::ETH_MAINNET_CHAIN_SPEC, input::StateInput};
+
+    fn input() -> BlockBuildInput<EthereumTxEssence> {
+        BlockBuildInput {
+            state_input: StateInput {
+                parent_header: Header {
+                    number: 41,
+                    ..Default::default()
+                },
+                beneficiary: Default::default(),
+                gas_limit: Default::default(),
+                timestamp: Default::default(),
+                extra_data: Default::default(),
+                mix_hash: Default::default(),
+                transactions: vec![],
+                withdrawals: vec![],
+            },
+            parent_state_trie: Default::default(),
+            parent_storage: Default::default(),
+            contracts: vec![vec![0x60, 0x00].into()],
+            ancestor_headers: vec![],
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let bytes = encode_input(&ETH_MAINNET_CHAIN_SPEC, &input()).unwrap();
+        let (header, decoded) = decode_input::<EthereumTxEssence>(&bytes).unwrap();
+        assert_eq!(header.block_number, 42);
+        assert_eq!(
+            header.chain_spec.chain_id(),
+            ETH_MAINNET_CHAIN_SPEC.chain_id()
+        );
+        assert_eq!(decoded, input());
+    }
+
+    #[test]
+    fn invalid_files() {
+        let bytes = encode_input(&ETH_MAINNET_CHAIN_SPEC, &input()).unwrap();
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        let err = decode_input::<EthereumTxEssence>(&corrupted).unwrap_err();
+        assert!(err.to_string().contains("input hash"));
+
+        let mut version = bytes.clone();
+        version[INPUT_FILE_MAGIC.len()] += 1;
+        let err = decode_input::<EthereumTxEssence>(&version).unwrap_err();
+        assert!(err.to_string().contains("unsupported input file version"));
+
+        assert!(decode_input::<EthereumTxEssence>(&bytes[..bytes.len() / 2]).is_err());
+        assert!(decode_input::<EthereumTxEssence>(&bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn stf_input_words() {
+        let header = StfInputHeader::new(StfStrategy::Ethereum, ETH_MAINNET_CHAIN_SPEC.clone());
+        let bytes = stf_input(&header, input()).unwrap();
+        assert_eq!(bytes.len() % 4, 0);
+
+        let words: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        let mut words = words.as_slice();
+        let decoded = StfInputHeader::deserialize(&mut Deserializer::new(&mut words)).unwrap();
+        assert_eq!(decoded.version, header.version);
+        assert_eq!(decoded.strategy, StfStrategy::Ethereum);
+        assert_eq!(decoded.chain_spec.hash(), ETH_MAINNET_CHAIN_SPEC.hash());
+        let decoded = CompactBlockBuildInput::<EthereumTxEssence>::deserialize(
+            &mut Deserializer::new(&mut words),
+        )
+        .unwrap();
+        assert!(words.is_empty());
+        let decoded: BlockBuildInput<EthereumTxEssence> = decoded.try_into().unwrap();
+        assert_eq!(decoded.state_input, input().state_input);
+        assert_eq!(
+            decoded.parent_state_trie.hash(),
+            input().parent_state_trie.hash()
+        );
+        assert_eq!(decoded.contracts, input().contracts);
+    }
+}
@@ -0,0 +1,350 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Statistics about the size of a [BlockBuildInput], to find out which parts of the
+//! witness make a block expensive to prove.
+
+use std::fmt;
+
+use alloy_rlp::Encodable;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use zeth_primitives::{
+    keccak::keccak,
+    transactions::TxEssence,
+    trie::{MptNode, MptNodeData, MptNodeReference, StateAccount},
+    Address, B256,
+};
+
+use crate::{input::BlockBuildInput, HashMap};
+
+/// Size of a sparse trie.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TrieStats {
+    /// Number of resolved nodes.
+    pub nodes: usize,
+    /// Total size of the RLP encoding of the resolved nodes, counting embedded nodes once.
+    pub bytes: usize,
+    /// Number of unresolved sub-tries.
+    pub digests: usize,
+}
+
+impl TrieStats {
+    pub fn new(trie: &MptNode) -> Self {
+        let mut stats = TrieStats {
+            nodes: trie.size(),
+            ..Default::default()
+        };
+        stats.add_node(trie);
+        // the root is stored on its own even if its encoding is shorter than a digest
+        if !trie.is_empty() && matches!(trie.reference(), MptNodeReference::Bytes(_)) {
+            stats.bytes += trie.length();
+        }
+        stats
+    }
+
+    /// Counts the sub-trie of the node. Only nodes referenced by their digest are stored
+    /// on their own, smaller nodes are already part of the encoding of their parent.
+    fn add_node(&mut self, node: &MptNode) {
+        match node.as_data() {
+            MptNodeData::Null => return,
+            MptNodeData::Digest(_) => {
+                self.digests += 1;
+                return;
+            }
+            MptNodeData::Branch(children) => {
+                for child in children.iter().flatten() {
+                    self.add_node(child);
+                }
+            }
+            MptNodeData::Extension(_, child) => self.add_node(child),
+            MptNodeData::Leaf(_, _) => {}
+        }
+        if matches!(node.reference(), MptNodeReference::Digest(_)) {
+            self.bytes += node.length();
+        }
+    }
+
+    fn add(&mut self, other: &TrieStats) {
+        self.nodes += other.nodes;
+        self.bytes += other.bytes;
+        self.digests += other.digests;
+    }
+}
+
+/// Size of the storage trie of an account.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct StorageStats {
+    pub address: Address,
+    /// Number of storage slots accessed by the block.
+    pub slots: usize,
+    pub trie: TrieStats,
+}
+
+/// Size of a contract code.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CodeStats {
+    pub code_hash: B256,
+    pub bytes: usize,
+    /// Accounts of the input with this code.
+    pub addresses: Vec<Address>,
+}
+
+/// Size of each field of the input when serialized with bincode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SerializedSizes {
+    pub state_input: u64,
+    pub parent_state_trie: u64,
+    pub parent_storage: u64,
+    pub contracts: u64,
+    pub ancestor_headers: u64,
+    pub total: u64,
+}
+
+/// Summary of the contents of a [BlockBuildInput].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct InputStats {
+    pub state_trie: TrieStats,
+    /// Total size of all storage tries.
+    pub storage_trie: TrieStats,
+    /// Storage tries, largest first.
+    pub storage: Vec<StorageStats>,
+    /// Total size of all contracts.
+    pub code_bytes: usize,
+    /// Contracts, largest first.
+    pub contracts: Vec<CodeStats>,
+    pub ancestor_headers: usize,
+    pub transactions: usize,
+    /// Total size of the RLP encoding of the transactions.
+    pub transaction_bytes: usize,
+    pub serialized: SerializedSizes,
+}
+
+impl InputStats {
+    pub fn new<E: TxEssence + Serialize>(input: &BlockBuildInput<E>) -> Result<Self> {
+        let mut storage_trie = TrieStats::default();
+        let mut storage: Vec<_> = input
+            .parent_storage
+            .iter()
+            .map(|(address, (trie, slots))| {
+                let trie = TrieStats::new(trie);
+                storage_trie.add(&trie);
+                StorageStats {
+                    address: *address,
+                    slots: slots.len(),
+                    trie,
+                }
+            })
+            .collect();
+        storage.sort_by(|a, b| (b.trie.bytes, a.address).cmp(&(a.trie.bytes, b.address)));
+
+        // the addresses of the code can only be found for the accounts in the storage
+        let mut addresses: HashMap<B256, Vec<Address>> = HashMap::new();
+        for address in input.parent_storage.keys() {
+            let account: Option<StateAccount> = input
+                .parent_state_trie
+                .get_rlp(&keccak(address))
+                .context("invalid state trie")?;
+            if let Some(account) = account {
+                addresses
+                    .entry(account.code_hash)
+                    .or_default()
+                    .push(*address);
+            }
+        }
+        let mut contracts: Vec<_> = input
+            .contracts
+            .iter()
+            .map(|code| {
+                let code_hash = B256::from(keccak(code));
+                let mut addresses = addresses.remove(&code_hash).unwrap_or_default();
+                addresses.sort();
+                CodeStats {
+                    code_hash,
+                    bytes: code.len(),
+                    addresses,
+                }
+            })
+            .collect();
+        contracts.sort_by(|a, b| (b.bytes, a.code_hash).cmp(&(a.bytes, b.code_hash)));
+
+        let serialized = SerializedSizes {
+            state_input: serialized_size(&input.state_input)?,
+            parent_state_trie: serialized_size(&input.parent_state_trie)?,
+            parent_storage: serialized_size(&input.parent_storage)?,
+            contracts: serialized_size(&input.contracts)?,
+            ancestor_headers: serialized_size(&input.ancestor_headers)?,
+            total: serialized_size(input)?,
+        };
+
+        Ok(InputStats {
+            state_trie: TrieStats::new(&input.parent_state_trie),
+            storage_trie,
+            storage,
+            code_bytes: input.contracts.iter().map(|code| code.len()).sum(),
+            contracts,
+            ancestor_headers: input.ancestor_headers.len(),
+            transactions: input.state_input.transactions.len(),
+            transaction_bytes: input
+                .state_input
+                .transactions
+                .iter()
+                .map(|tx| tx.length())
+                .sum(),
+            serialized,
+        })
+    }
+
+    /// Keeps only the `n` largest storage tries and contracts.
+    pub fn truncate(&mut self, n: usize) {
+        self.storage.truncate(n);
+        self.contracts.truncate(n);
+    }
+}
+
+impl fmt::Display for InputStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let trie = |trie: &TrieStats| {
+            format!(
+                "{} nodes ({} bytes), {} digests",
+                trie.nodes, trie.bytes, trie.digests
+            )
+        };
+        writeln!(f, "state trie:       {}", trie(&self.state_trie))?;
+        writeln!(f, "storage tries:    {}", trie(&self.storage_trie))?;
+        for storage in &self.storage {
+            writeln!(
+                f,
+                "  {}: {} slots, {}",
+                storage.address,
+                storage.slots,
+                trie(&storage.trie)
+            )?;
+        }
+        writeln!(f, "code bytes:       {}", self.code_bytes)?;
+        for code in &self.contracts {
+            write!(f, "  {}: {} bytes", code.code_hash, code.bytes)?;
+            for address in &code.addresses {
+                write!(f, " {address}")?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "ancestor headers: {}", self.ancestor_headers)?;
+        writeln!(
+            f,
+            "transactions:     {} ({} bytes)",
+            self.transactions, self.transaction_bytes
+        )?;
+        let serialized = &self.serialized;
+        writeln!(f, "serialized bytes: {}", serialized.total)?;
+        writeln!(f, "  state input:       {}", serialized.state_input)?;
+        writeln!(f, "  parent state trie: {}", serialized.parent_state_trie)?;
+        writeln!(f, "  parent storage:    {}", serialized.parent_storage)?;
+        writeln!(f, "  contracts:         {}", serialized.contracts)?;
+        write!(f, "  ancestor headers:  {}", serialized.ancestor_headers)
+    }
+}
+
+fn serialized_size(value: &impl Serialize) -> Result<u64> {
+    bincode::serialized_size(value).context("failed to serialize input")
+}
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::{transactions::ethereum::EthereumTxEssence, U256};
+
+    use super::*;
+    use crate::input::StateInput;
+
+    #[test]
+    fn input_stats() {
+        let code = vec![0x60, 0x00, 0x60, 0x00];
+        let address = Address::repeat_byte(1);
+        let mut storage_trie = MptNode::default();
+        for slot in 0..3u8 {
+            storage_trie
+                .insert_rlp(&keccak([slot]), U256::from(slot + 1))
+                .unwrap();
+        }
+        let mut state_trie = MptNode::default();
+        let account = StateAccount {
+            code_hash: keccak(&code).into(),
+            storage_root: storage_trie.hash(),
+            ..Default::default()
+        };
+        state_trie.insert_rlp(&keccak(address), account).unwrap();
+        state_trie
+            .insert_rlp(&keccak(Address::repeat_byte(2)), StateAccount::default())
+            .unwrap();
+
+        let input = BlockBuildInput {
+            state_input: StateInput::<EthereumTxEssence> {
+                parent_header: Default::default(),
+                beneficiary: Default::default(),
+                gas_limit: Default::default(),
+                timestamp: Default::default(),
+                extra_data: Default::default(),
+                mix_hash: Default::default(),
+                transactions: vec![],
+                withdrawals: vec![],
+            },
+            parent_state_trie: state_trie.clone(),
+            parent_storage: [(address, (storage_trie.clone(), vec![U256::from(1)]))].into(),
+            contracts: vec![code.clone().into(), vec![0x00].into()],
+            ancestor_headers: vec![Default::default()],
+        };
+        let stats = InputStats::new(&input).unwrap();
+
+        assert_eq!(stats.state_trie.nodes, state_trie.size());
+        assert_eq!(stats.state_trie.digests, 0);
+        assert_eq!(stats.storage.len(), 1);
+        assert_eq!(stats.storage[0].slots, 1);
+        assert_eq!(stats.storage_trie.nodes, 4);
+        assert_eq!(stats.storage_trie, stats.storage[0].trie);
+        assert_eq!(stats.code_bytes, 5);
+        assert_eq!(stats.contracts[0].bytes, 4);
+        assert_eq!(stats.contracts[0].addresses, [address]);
+        assert!(stats.contracts[1].addresses.is_empty());
+        assert_eq!(stats.ancestor_headers, 1);
+        assert_eq!(
+            stats.serialized.total,
+            bincode::serialized_size(&input).unwrap()
+        );
+
+        // nodes embedded in their parent are not counted twice
+        let mut inline_trie = MptNode::default();
+        for key in 1..3u8 {
+            inline_trie.insert(&[key], vec![key]).unwrap();
+        }
+        assert!(matches!(
+            inline_trie.reference(),
+            MptNodeReference::Bytes(_)
+        ));
+        assert_eq!(
+            TrieStats::new(&inline_trie).bytes,
+            alloy_rlp::encode(&inline_trie).len()
+        );
+
+        // only the resolved nodes are counted
+        let digest: MptNode = state_trie.hash().into();
+        assert_eq!(
+            TrieStats::new(&digest),
+            TrieStats {
+                nodes: 0,
+                bytes: 0,
+                digests: 1
+            }
+        );
+    }
+}
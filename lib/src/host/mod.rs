@@ -14,12 +14,16 @@
 
 use std::path::{Path, PathBuf};
 
-use crate::host::provider::{new_provider, Provider};
+use crate::host::provider::{new_provider, CachePath, Provider, RpcConfig};
 
+pub mod input_file;
+pub mod input_stats;
 pub mod mpt;
 pub mod preflight;
 pub mod provider;
 pub mod provider_db;
+pub mod range;
+pub mod simulate;
 // pub mod rpc_db;
 pub mod verify;
 
@@ -29,27 +33,41 @@ pub fn cache_file_path(cache_path: &Path, network: &str, block_no: u64, ext: &st
     dir.join(block_no.to_string()).with_extension(ext)
 }
 
+/// Returns the directory of the content-addressed store of the given network.
+pub fn cache_store_path(cache_path: &Path, network: &str) -> PathBuf {
+    cache_path.join(network).join("store")
+}
+
 #[derive(Clone)]
 pub struct ProviderFactory {
     pub dir: Option<PathBuf>,
     pub network: String,
-    pub rpc_url: Option<String>,
+    pub rpc_config: Option<RpcConfig>,
+    pub store: bool,
 }
 
 impl ProviderFactory {
-    pub fn new(dir: Option<PathBuf>, network: String, rpc_url: Option<String>) -> Self {
+    pub fn new(dir: Option<PathBuf>, network: String, rpc_config: Option<RpcConfig>) -> Self {
         Self {
             dir,
             network,
-            rpc_url,
+            rpc_config,
+            store: false,
         }
     }
 
+    /// Sets whether the content-addressed store is used instead of one cache file per
+    /// block.
+    pub fn with_store(mut self, store: bool) -> Self {
+        self.store = store;
+        self
+    }
+
     pub fn create_provider(&self, block_number: u64) -> anyhow::Result<Box<dyn Provider>> {
-        let rpc_cache = self
-            .dir
-            .as_ref()
-            .map(|dir| cache_file_path(dir, &self.network, block_number, "json.gz"));
-        new_provider(rpc_cache, self.rpc_url.clone())
+        let rpc_cache = self.dir.as_ref().map(|dir| match self.store {
+            true => CachePath::Store(cache_store_path(dir, &self.network), block_number),
+            false => cache_file_path(dir, &self.network, block_number, "json.gz").into(),
+        });
+        new_provider(rpc_cache, self.rpc_config.clone())
     }
 }
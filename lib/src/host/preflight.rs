@@ -14,11 +14,11 @@
 
 use std::{
     fmt::Debug,
-    path::PathBuf,
+    mem,
     sync::{Arc, Mutex},
 };
 
-use anyhow::{anyhow, Context, Error, Result};
+use anyhow::{anyhow, ensure, Context, Error, Result};
 use ethers_core::types::{
     Block as EthersBlock, Block, EIP1186ProofResponse, Transaction as EthersTransaction,
 };
@@ -30,6 +30,7 @@ use zeth_primitives::{
     block::Header,
     ethers::{from_ethers_h160, from_ethers_h256, from_ethers_u256},
     keccak::keccak,
+    receipt::Receipt,
     transactions::{Transaction, TxEssence},
     trie::{MptNode, MptNodeReference},
     withdrawal::Withdrawal,
@@ -41,11 +42,11 @@ use crate::{
     consts::ChainSpec,
     host::{
         mpt::{is_not_included, mpt_from_proof, parse_proof, resolve_nodes, shorten_node_path},
-        provider::{new_provider, BlockQuery},
+        provider::{new_provider, BlockQuery, CachePath, Provider, RpcConfig},
         provider_db::ProviderDb,
     },
     input::{BlockBuildInput, StateInput, StorageEntry},
-    mem_db::MemDb,
+    mem_db::{DbAccount, MemDb},
     HashMap, HashSet,
 };
 
@@ -53,6 +54,8 @@ use crate::{
 #[derive(Debug, Clone)]
 pub struct Data<E: TxEssence> {
     pub db: MemDb,
+    /// The accounts changed by executing the block.
+    pub changed_accounts: HashMap<Address, DbAccount>,
     pub parent_header: Header,
     pub parent_proofs: HashMap<Address, EIP1186ProofResponse>,
     pub proofs: HashMap<Address, EIP1186ProofResponse>,
@@ -60,6 +63,10 @@ pub struct Data<E: TxEssence> {
     pub transactions: Vec<Transaction<E>>,
     pub withdrawals: Vec<Withdrawal>,
     pub ancestor_headers: Vec<Header>,
+    /// The receipts of the locally executed transactions.
+    pub receipts: Vec<Receipt>,
+    /// The receipts of the block according to the RPC provider, if available.
+    pub rpc_receipts: Option<Vec<Receipt>>,
 }
 
 pub trait Preflight<E: TxEssence> {
@@ -67,8 +74,15 @@ pub trait Preflight<E: TxEssence> {
     /// It returns all the data required to build and validate the block.
     fn preflight_with_external_data(
         chain_spec: &ChainSpec,
-        cache_path: Option<PathBuf>,
-        rpc_url: Option<String>,
+        cache_path: Option<CachePath>,
+        rpc_config: Option<RpcConfig>,
+        block_no: u64,
+    ) -> Result<Data<E>>;
+
+    /// Executes the complete block using the input and state from the given provider.
+    fn preflight_with_provider(
+        chain_spec: &ChainSpec,
+        provider: Box<dyn Provider>,
         block_no: u64,
     ) -> Result<Data<E>>;
 
@@ -87,15 +101,23 @@ where
 {
     fn preflight_with_external_data(
         chain_spec: &ChainSpec,
-        cache_path: Option<PathBuf>,
-        rpc_url: Option<String>,
+        cache_path: Option<CachePath>,
+        rpc_config: Option<RpcConfig>,
         block_no: u64,
     ) -> Result<Data<N::TxEssence>> {
-        let mut provider = new_provider(cache_path, rpc_url)?;
+        let provider = new_provider(cache_path, rpc_config)?;
+        Self::preflight_with_provider(chain_spec, provider, block_no)
+    }
 
+    fn preflight_with_provider(
+        chain_spec: &ChainSpec,
+        mut provider: Box<dyn Provider>,
+        block_no: u64,
+    ) -> Result<Data<N::TxEssence>> {
         // Fetch the parent block
         let parent_block = provider.get_partial_block(&BlockQuery {
             block_no: block_no - 1,
+            block_hash: None,
         })?;
 
         debug!(
@@ -103,10 +125,14 @@ where
             parent_block.number.unwrap(),
             parent_block.hash.unwrap()
         );
+        let parent_hash = parent_block.hash.context("parent block has no hash")?;
         let parent_header: Header = parent_block.try_into().context("invalid parent block")?;
 
         // Fetch the target block
-        let block = provider.get_full_block(&BlockQuery { block_no })?;
+        let block = provider.get_full_block(&BlockQuery {
+            block_no,
+            block_hash: None,
+        })?;
 
         debug!(
             "Final block number: {:?} ({:?})",
@@ -115,8 +141,32 @@ where
         );
         debug!("Transaction count: {:?}", block.transactions.len());
 
+        // Make sure that both blocks belong to the same chain before any state is queried
+        ensure!(
+            block.parent_hash == parent_hash,
+            "parent hash {:?} of block {} does not match the hash {:?} of the fetched parent, \
+             the chain might have been reorganized",
+            block.parent_hash,
+            block_no,
+            parent_hash
+        );
+        let block_hash = block.hash.context("block has no hash")?;
+
+        // Fetch the receipts to locate diverging transactions when verifying the block
+        let rpc_receipts = match provider.get_receipts(&BlockQuery {
+            block_no,
+            block_hash: Some(block_hash),
+        }) {
+            Ok(receipts) => Some(receipts),
+            Err(err) => {
+                info!("Block receipts are not available and will not be verified: {err:#}");
+                None
+            }
+        };
+
         // Create the provider DB
-        let provider_db = ProviderDb::new(provider, parent_header.number);
+        let provider_db = ProviderDb::new(provider, parent_header.number)
+            .with_block_hashes(parent_hash, block_hash);
 
         // Create the input data
         let block_header: Header = block.clone().try_into().expect("invalid block");
@@ -126,6 +176,7 @@ where
         Self::preflight_with_local_data(chain_spec, provider_db, input).map(
             move |mut headerless_preflight_data| {
                 headerless_preflight_data.header = Some(block_header);
+                headerless_preflight_data.rpc_receipts = rpc_receipts;
                 headerless_preflight_data
             },
         )
@@ -139,24 +190,32 @@ where
         let parent_header = input.state_input.parent_header.clone();
         let transactions = input.state_input.transactions.clone();
         let withdrawals = input.state_input.withdrawals.clone();
-        let builder = BlockBuilder::new(chain_spec, input).with_db(provider_db);
-        let mut provider_db = match builder.prepare_header::<N::HeaderPrepStrategy>() {
+        let mut provider_db = provider_db;
+        provider_db.history_storage = chain_spec
+            .history_storage_active(parent_header.number + 1, &input.state_input.timestamp);
+        let builder = BlockBuilder::new(chain_spec, input)
+            .with_db(provider_db)
+            .with_receipts();
+        let (receipts, mut provider_db) = match builder.prepare_header::<N::HeaderPrepStrategy>() {
             Ok(builder) => match builder.execute_transactions::<N::TxExecStrategy>() {
-                Ok(builder) => builder.take_db().unwrap(),
+                Ok(mut builder) => (
+                    builder.take_receipts().unwrap_or_default(),
+                    builder.take_db().unwrap(),
+                ),
                 Err(_) => return Err(anyhow!("builder execute transactions failed")),
             },
             Err(_) => return Err(anyhow!("builder prepare failed")),
         };
 
+        // Gather proofs for block history, this may access the history storage
+        let ancestor_headers = provider_db.get_ancestor_headers()?;
+
         info!("Gathering inclusion proofs ...");
 
         // Gather inclusion proofs for the initial and final state
         let parent_proofs = provider_db.get_initial_proofs()?;
         let proofs = provider_db.get_latest_proofs()?;
 
-        // Gather proofs for block history
-        let ancestor_headers = provider_db.get_ancestor_headers()?;
-
         info!("Saving provider cache ...");
 
         // Save the provider cache
@@ -167,6 +226,8 @@ where
         // Fetch the target block
         Ok(Data {
             db: provider_db.get_initial_db().clone(),
+            // the provider DB is dropped, so the changes are moved instead of cloned
+            changed_accounts: mem::take(&mut provider_db.latest_db.accounts),
             parent_header,
             parent_proofs,
             header: None,
@@ -174,6 +235,8 @@ where
             withdrawals,
             proofs,
             ancestor_headers,
+            receipts,
+            rpc_receipts: None,
         })
     }
 }
@@ -280,7 +343,7 @@ impl<E: TxEssence> TryFrom<Data<E>> for BlockBuildInput<E> {
     }
 }
 
-fn proofs_to_tries(
+pub(crate) fn proofs_to_tries(
     state_root: B256,
     parent_proofs: HashMap<Address, EIP1186ProofResponse>,
     proofs: HashMap<Address, EIP1186ProofResponse>,
@@ -0,0 +1,492 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::hash_map::Iter, mem};
+
+use alloy_rpc_types::{
+    serde_helpers::WithOtherFields, AccessList as AlloyAccessList, AnyTransactionReceipt,
+    Block as AlloyBlock, BlockTransactions, EIP1186AccountProofResponse, Log as AlloyLog,
+    Transaction as AlloyTransaction,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use ethers_core::types::{
+    transaction::eip2930::{AccessList, AccessListItem},
+    Block, Bytes, EIP1186ProofResponse, Log, OtherFields, StorageProof, Transaction,
+    TransactionReceipt, Withdrawal, H256, U256, U64,
+};
+use log::debug;
+use serde_json::{json, Value};
+use zeth_primitives::{
+    ethers::{to_ethers_bytes, to_ethers_h160, to_ethers_h256, to_ethers_u256},
+    receipt::Receipt,
+    B256,
+};
+
+use super::{
+    rpc_provider::{block_id, Call, RpcProvider},
+    AccountQuery, BlockQuery, ProofQuery, Provider, RpcConfig, StorageQuery,
+};
+
+/// Block as returned by the RPC, keeping the fields of the transactions that are unknown
+/// to Ethereum, e.g. those of Optimism deposits.
+type RpcBlock = AlloyBlock<WithOtherFields<AlloyTransaction>>;
+
+/// RPC provider that decodes the responses into the `alloy_rpc_types`.
+///
+/// The responses are converted field by field into the types of the [Provider]. Unlike
+/// the [RpcProvider], this keeps the deposit nonces of Optimism receipts.
+pub struct AlloyRpcProvider {
+    rpc: RpcProvider,
+    cross_check: bool,
+}
+
+impl AlloyRpcProvider {
+    pub fn new(config: impl Into<RpcConfig>) -> Result<Self> {
+        let config = config.into();
+        let cross_check = config.cross_check;
+        let rpc = RpcProvider::new(config)?;
+
+        Ok(AlloyRpcProvider { rpc, cross_check })
+    }
+
+    fn get_block(&self, query: &BlockQuery, full: bool) -> Result<RpcBlock> {
+        let response: Option<RpcBlock> = self.rpc.call(Call::block(query, full))?;
+
+        match response {
+            Some(out) if out.header.number == Some(query.block_no) => Ok(out),
+            Some(out) => Err(anyhow!(
+                "{:?} returned block {:?}",
+                query,
+                out.header.number
+            )),
+            None => Err(anyhow!("No data for {:?}", query)),
+        }
+    }
+
+    fn get_receipts_response(&self, query: &BlockQuery) -> Result<Vec<AnyTransactionReceipt>> {
+        let response: Option<Vec<AnyTransactionReceipt>> = self
+            .rpc
+            .call(Call::new("eth_getBlockReceipts", json!([block_id(query)])))?;
+
+        Ok(response.unwrap_or_default())
+    }
+}
+
+impl Provider for AlloyRpcProvider {
+    fn save(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_full_block(&mut self, query: &BlockQuery) -> Result<Block<Transaction>> {
+        debug!("Querying RPC for full block: {:?}", query);
+
+        let mut block = self.get_block(query, true)?;
+        let transactions = match mem::take(&mut block.transactions) {
+            BlockTransactions::Full(transactions) => transactions
+                .into_iter()
+                .map(ethers_transaction)
+                .collect::<Result<_>>()?,
+            BlockTransactions::Hashes(hashes) if hashes.is_empty() => vec![],
+            _ => bail!("{:?} returned no full transactions", query),
+        };
+
+        Ok(ethers_block(block, transactions))
+    }
+
+    fn get_partial_block(&mut self, query: &BlockQuery) -> Result<Block<H256>> {
+        debug!("Querying RPC for partial block: {:?}", query);
+
+        let mut block = self.get_block(query, false)?;
+        let transactions = match mem::take(&mut block.transactions) {
+            BlockTransactions::Hashes(hashes) => hashes.into_iter().map(to_ethers_h256).collect(),
+            BlockTransactions::Full(transactions) => transactions
+                .into_iter()
+                .map(|tx| to_ethers_h256(tx.inner.hash))
+                .collect(),
+            BlockTransactions::Uncle => vec![],
+        };
+
+        Ok(ethers_block(block, transactions))
+    }
+
+    fn get_block_receipts(&mut self, query: &BlockQuery) -> Result<Vec<TransactionReceipt>> {
+        debug!("Querying RPC for block receipts: {:?}", query);
+
+        self.get_receipts_response(query)?
+            .into_iter()
+            .map(ethers_receipt)
+            .collect()
+    }
+
+    fn get_receipts(&mut self, query: &BlockQuery) -> Result<Vec<Receipt>> {
+        debug!("Querying RPC for block receipts: {:?}", query);
+
+        self.get_receipts_response(query)?
+            .into_iter()
+            .map(Receipt::try_from)
+            .collect()
+    }
+
+    fn get_proof(&mut self, query: &ProofQuery) -> Result<EIP1186ProofResponse> {
+        debug!("Querying RPC for inclusion proof: {:?}", query);
+
+        let mut proofs = self.get_proofs(std::slice::from_ref(query))?;
+        Ok(proofs.remove(0))
+    }
+
+    fn get_proofs(&mut self, queries: &[ProofQuery]) -> Result<Vec<EIP1186ProofResponse>> {
+        debug!("Querying RPC for {} inclusion proofs", queries.len());
+
+        let proofs: Vec<EIP1186AccountProofResponse> = self
+            .rpc
+            .batch_call(queries.iter().map(Call::proof).collect(), self.cross_check)?;
+        Ok(proofs.into_iter().map(ethers_proof).collect())
+    }
+
+    fn get_transaction_count(&mut self, query: &AccountQuery) -> Result<U256> {
+        self.rpc.get_transaction_count(query)
+    }
+
+    fn get_balance(&mut self, query: &AccountQuery) -> Result<U256> {
+        self.rpc.get_balance(query)
+    }
+
+    fn get_code(&mut self, query: &AccountQuery) -> Result<Bytes> {
+        self.rpc.get_code(query)
+    }
+
+    fn get_account(&mut self, query: &AccountQuery) -> Result<(U256, U256, Bytes)> {
+        self.rpc.get_account(query)
+    }
+
+    fn get_all_codes(&self) -> Iter<AccountQuery, Bytes> {
+        self.rpc.get_all_codes()
+    }
+
+    fn get_storage(&mut self, query: &StorageQuery) -> Result<H256> {
+        self.rpc.get_storage(query)
+    }
+}
+
+/// Encodes a number like the RPC does for quantities.
+fn quantity(value: impl Into<u128>) -> Value {
+    json!(format!("{:#x}", value.into()))
+}
+
+/// Converts an alloy block header and its withdrawals into an ethers block.
+///
+/// Header fields unknown to ethers, like the `requestsHash` of Prague headers, are kept
+/// in [Block::other].
+fn ethers_block<T>(block: RpcBlock, transactions: Vec<T>) -> Block<T> {
+    let header = block.header;
+    let mut other = OtherFields::default();
+    other.extend(block.other);
+
+    Block {
+        hash: header.hash.map(to_ethers_h256),
+        parent_hash: to_ethers_h256(header.parent_hash),
+        uncles_hash: to_ethers_h256(header.uncles_hash),
+        author: Some(to_ethers_h160(header.miner)),
+        state_root: to_ethers_h256(header.state_root),
+        transactions_root: to_ethers_h256(header.transactions_root),
+        receipts_root: to_ethers_h256(header.receipts_root),
+        number: header.number.map(U64::from),
+        gas_used: header.gas_used.into(),
+        gas_limit: header.gas_limit.into(),
+        extra_data: to_ethers_bytes(header.extra_data),
+        logs_bloom: Some(header.logs_bloom.0 .0.into()),
+        timestamp: header.timestamp.into(),
+        difficulty: to_ethers_u256(header.difficulty),
+        total_difficulty: header.total_difficulty.map(to_ethers_u256),
+        seal_fields: vec![],
+        uncles: block.uncles.into_iter().map(to_ethers_h256).collect(),
+        transactions,
+        size: block.size.map(to_ethers_u256),
+        mix_hash: header.mix_hash.map(to_ethers_h256),
+        nonce: header.nonce.map(|nonce| nonce.0.into()),
+        base_fee_per_gas: header.base_fee_per_gas.map(U256::from),
+        blob_gas_used: header.blob_gas_used.map(U256::from),
+        excess_blob_gas: header.excess_blob_gas.map(U256::from),
+        withdrawals_root: header.withdrawals_root.map(to_ethers_h256),
+        withdrawals: block.withdrawals.map(|withdrawals| {
+            withdrawals
+                .into_iter()
+                .map(|withdrawal| Withdrawal {
+                    index: withdrawal.index.into(),
+                    validator_index: withdrawal.validator_index.into(),
+                    address: to_ethers_h160(withdrawal.address),
+                    amount: withdrawal.amount.into(),
+                })
+                .collect()
+        }),
+        parent_beacon_block_root: header.parent_beacon_block_root.map(to_ethers_h256),
+        other,
+    }
+}
+
+/// Converts an alloy transaction into an ethers transaction.
+///
+/// The Optimism deposit fields are read from the unknown fields of the transaction, all
+/// other fields unknown to ethers are kept in [Transaction::other].
+fn ethers_transaction(tx: WithOtherFields<AlloyTransaction>) -> Result<Transaction> {
+    let WithOtherFields { inner: tx, other } = tx;
+    let mut fields = OtherFields::default();
+    fields.extend(tx.other);
+    fields.extend(other);
+
+    let source_hash = fields
+        .remove_deserialized::<B256>("sourceHash")
+        .transpose()
+        .context("invalid sourceHash")?
+        .map(to_ethers_h256)
+        .unwrap_or_default();
+    let mint = fields
+        .remove_deserialized::<zeth_primitives::U256>("mint")
+        .transpose()
+        .context("invalid mint")?
+        .map(to_ethers_u256);
+    let is_system_tx = fields
+        .remove_deserialized::<bool>("isSystemTx")
+        .transpose()
+        .context("invalid isSystemTx")?
+        .unwrap_or_default();
+    if let Some(max_fee_per_blob_gas) = tx.max_fee_per_blob_gas {
+        fields.insert(
+            "maxFeePerBlobGas".to_string(),
+            quantity(max_fee_per_blob_gas),
+        );
+    }
+    if let Some(blob_versioned_hashes) = tx.blob_versioned_hashes {
+        fields.insert(
+            "blobVersionedHashes".to_string(),
+            json!(blob_versioned_hashes),
+        );
+    }
+
+    let (v, r, s) = match tx.signature {
+        Some(signature) => {
+            if let Some(y_parity) = signature.y_parity {
+                fields.insert("yParity".to_string(), quantity(y_parity.0));
+            }
+            let v: u64 = signature.v.try_into().ok().context("invalid v")?;
+            (U64::from(v), signature.r, signature.s)
+        }
+        None => Default::default(),
+    };
+
+    Ok(Transaction {
+        hash: to_ethers_h256(tx.hash),
+        nonce: tx.nonce.into(),
+        block_hash: tx.block_hash.map(to_ethers_h256),
+        block_number: tx.block_number.map(U64::from),
+        transaction_index: tx.transaction_index.map(U64::from),
+        from: to_ethers_h160(tx.from),
+        to: tx.to.map(to_ethers_h160),
+        value: to_ethers_u256(tx.value),
+        gas_price: tx.gas_price.map(U256::from),
+        gas: tx.gas.into(),
+        input: to_ethers_bytes(tx.input),
+        v,
+        r: to_ethers_u256(r),
+        s: to_ethers_u256(s),
+        source_hash,
+        mint,
+        is_system_tx,
+        transaction_type: tx.transaction_type.map(U64::from),
+        access_list: tx.access_list.map(ethers_access_list),
+        max_priority_fee_per_gas: tx.max_priority_fee_per_gas.map(U256::from),
+        max_fee_per_gas: tx.max_fee_per_gas.map(U256::from),
+        chain_id: tx.chain_id.map(U256::from),
+        other: fields,
+    })
+}
+
+fn ethers_access_list(access_list: AlloyAccessList) -> AccessList {
+    AccessList(
+        access_list
+            .0
+            .into_iter()
+            .map(|item| AccessListItem {
+                address: to_ethers_h160(item.address),
+                storage_keys: item.storage_keys.into_iter().map(to_ethers_h256).collect(),
+            })
+            .collect(),
+    )
+}
+
+/// Converts an alloy receipt into an ethers receipt.
+///
+/// The ethers receipt does not decode the deposit nonce of the RPC response, so it is
+/// read from the unknown fields of the receipt instead.
+fn ethers_receipt(receipt: AnyTransactionReceipt) -> Result<TransactionReceipt> {
+    let WithOtherFields {
+        inner: receipt,
+        other,
+    } = receipt;
+    let deposit_nonce = other
+        .get_deserialized::<U64>("depositNonce")
+        .transpose()
+        .context("invalid depositNonce")?
+        .map(|nonce| nonce.as_u64());
+
+    let mut fields = OtherFields::default();
+    fields.extend(other);
+    if let Some(blob_gas_used) = receipt.blob_gas_used {
+        fields.insert("blobGasUsed".to_string(), quantity(blob_gas_used));
+    }
+    if let Some(blob_gas_price) = receipt.blob_gas_price {
+        fields.insert("blobGasPrice".to_string(), quantity(blob_gas_price));
+    }
+
+    let envelope = receipt.inner;
+    let status = envelope.inner.receipt.status;
+    Ok(TransactionReceipt {
+        transaction_hash: to_ethers_h256(receipt.transaction_hash),
+        transaction_index: receipt.transaction_index.unwrap_or_default().into(),
+        block_hash: receipt.block_hash.map(to_ethers_h256),
+        block_number: receipt.block_number.map(U64::from),
+        from: to_ethers_h160(receipt.from),
+        to: receipt.to.map(to_ethers_h160),
+        cumulative_gas_used: envelope.cumulative_gas_used().into(),
+        gas_used: Some(receipt.gas_used.into()),
+        contract_address: receipt.contract_address.map(to_ethers_h160),
+        logs: envelope
+            .inner
+            .receipt
+            .logs
+            .into_iter()
+            .map(ethers_log)
+            .collect(),
+        status: status.as_eip658().map(|success| U64::from(success as u64)),
+        root: status
+            .as_post_state()
+            .or(receipt.state_root)
+            .map(to_ethers_h256),
+        logs_bloom: envelope.inner.logs_bloom.0 .0.into(),
+        transaction_type: Some(envelope.r#type.into()),
+        effective_gas_price: Some(receipt.effective_gas_price.into()),
+        deposit_nonce,
+        other: fields,
+        ..Default::default()
+    })
+}
+
+fn ethers_log(log: AlloyLog) -> Log {
+    Log {
+        address: to_ethers_h160(log.inner.address),
+        topics: log
+            .inner
+            .data
+            .topics()
+            .iter()
+            .copied()
+            .map(to_ethers_h256)
+            .collect(),
+        data: to_ethers_bytes(log.inner.data.data),
+        block_hash: log.block_hash.map(to_ethers_h256),
+        block_number: log.block_number.map(U64::from),
+        transaction_hash: log.transaction_hash.map(to_ethers_h256),
+        transaction_index: log.transaction_index.map(U64::from),
+        log_index: log.log_index.map(U256::from),
+        transaction_log_index: None,
+        log_type: None,
+        removed: Some(log.removed),
+    }
+}
+
+fn ethers_proof(proof: EIP1186AccountProofResponse) -> EIP1186ProofResponse {
+    EIP1186ProofResponse {
+        address: to_ethers_h160(proof.address),
+        balance: to_ethers_u256(proof.balance),
+        code_hash: to_ethers_h256(proof.code_hash),
+        nonce: proof.nonce.to::<u64>().into(),
+        storage_hash: to_ethers_h256(proof.storage_hash),
+        account_proof: proof
+            .account_proof
+            .into_iter()
+            .map(to_ethers_bytes)
+            .collect(),
+        storage_proof: proof
+            .storage_proof
+            .into_iter()
+            .map(|storage| StorageProof {
+                key: U256::from_big_endian(storage.key.0.as_slice()),
+                proof: storage.proof.into_iter().map(to_ethers_bytes).collect(),
+                value: to_ethers_u256(storage.value),
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::{block::Header, trie::EMPTY_ROOT, Address, B64, U256};
+
+    use super::*;
+
+    #[test]
+    fn prague_header() {
+        let header = Header {
+            parent_hash: B256::repeat_byte(0x01),
+            beneficiary: Address::repeat_byte(0x02),
+            state_root: B256::repeat_byte(0x03),
+            number: 1,
+            gas_limit: U256::from(30_000_000),
+            gas_used: U256::from(21_000),
+            timestamp: U256::from(1_700_000_000),
+            mix_hash: B256::repeat_byte(0x04),
+            nonce: B64::ZERO,
+            base_fee_per_gas: Some(U256::from(7)),
+            withdrawals_root: Some(EMPTY_ROOT),
+            blob_gas_used: Some(0),
+            excess_blob_gas: Some(0),
+            parent_beacon_block_root: Some(B256::repeat_byte(0x05)),
+            requests_hash: Some(B256::repeat_byte(0x06)),
+            ..Default::default()
+        };
+        let block: RpcBlock = serde_json::from_value(json!({
+            "hash": header.hash(),
+            "parentHash": header.parent_hash,
+            "sha3Uncles": header.ommers_hash,
+            "miner": header.beneficiary,
+            "stateRoot": header.state_root,
+            "transactionsRoot": header.transactions_root,
+            "receiptsRoot": header.receipts_root,
+            "logsBloom": header.logs_bloom,
+            "difficulty": header.difficulty,
+            "number": quantity(header.number),
+            "gasLimit": header.gas_limit,
+            "gasUsed": header.gas_used,
+            "timestamp": header.timestamp,
+            "extraData": header.extra_data,
+            "mixHash": header.mix_hash,
+            "nonce": header.nonce,
+            "baseFeePerGas": header.base_fee_per_gas,
+            "withdrawalsRoot": header.withdrawals_root,
+            "blobGasUsed": quantity(header.blob_gas_used.unwrap()),
+            "excessBlobGas": quantity(header.excess_blob_gas.unwrap()),
+            "parentBeaconBlockRoot": header.parent_beacon_block_root,
+            "requestsHash": header.requests_hash,
+            "uncles": [],
+            "transactions": [],
+            "withdrawals": []
+        }))
+        .unwrap();
+
+        // the requests hash must be kept by the alloy and the ethers conversion
+        assert_eq!(Header::try_from(block.clone()).unwrap(), header);
+        let block = ethers_block::<H256>(block, vec![]);
+        assert_eq!(Header::try_from(block).unwrap(), header);
+    }
+}
@@ -0,0 +1,518 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A local JSON-RPC server answering from recorded [FileProvider] caches.
+
+use std::{
+    net::TcpListener as StdTcpListener,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{bail, Context, Result};
+use ethers_core::types::{H160, H256, U64};
+use log::{debug, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+use super::{
+    file_provider::FileProvider, AccountQuery, BlockQuery, ProofQuery, Provider, StorageQuery,
+};
+
+/// Maximum size of the body of an HTTP request.
+const MAX_BODY_SIZE: usize = 4 << 20;
+
+/// JSON-RPC error code of a request that is not valid.
+const INVALID_REQUEST: i64 = -32600;
+/// JSON-RPC error code of a method that is not supported.
+const METHOD_NOT_FOUND: i64 = -32601;
+/// JSON-RPC error code of malformed parameters.
+const INVALID_PARAMS: i64 = -32602;
+/// JSON-RPC error code of a query that is not contained in the caches.
+const NOT_CACHED: i64 = -32001;
+
+/// Serves the data of one or more cache files over HTTP JSON-RPC.
+///
+/// Only the methods used by the providers are supported, blocks must be given by number
+/// or hash and proofs are only returned for exactly the cached set of storage keys.
+pub struct CacheServer {
+    cache: Mutex<FileProvider>,
+}
+
+impl CacheServer {
+    /// Creates a new [CacheServer] from the union of the given cache files.
+    pub fn new(cache_paths: &[PathBuf]) -> Result<Self> {
+        let mut cache = FileProvider::default();
+        for path in cache_paths {
+            if !path.exists() {
+                bail!("cache file {:?} does not exist", path);
+            }
+            FileProvider::new(path.clone())
+                .with_context(|| format!("could not read {:?}", path))?
+                .copy_into(&mut cache);
+        }
+
+        Ok(CacheServer {
+            cache: Mutex::new(cache),
+        })
+    }
+
+    /// Starts the server on a free local port of the current Tokio runtime and returns
+    /// its URL.
+    pub fn start(self) -> Result<String> {
+        let listener = StdTcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let listener = TcpListener::from_std(listener)?;
+        tokio::spawn(self.run(listener));
+
+        Ok(url)
+    }
+
+    /// Accepts and serves connections until the listener fails.
+    pub async fn run(self, listener: TcpListener) -> Result<()> {
+        let server = Arc::new(self);
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let server = server.clone();
+            tokio::spawn(async move {
+                if let Err(err) = server.serve(stream).await {
+                    warn!("Connection failed: {:#}", err);
+                }
+            });
+        }
+    }
+
+    async fn serve(&self, stream: TcpStream) -> Result<()> {
+        let mut stream = BufReader::new(stream);
+        while let Some(body) = read_request(&mut stream).await? {
+            let response = match serde_json::from_slice::<Value>(&body) {
+                Ok(Value::Array(requests)) => Value::Array(
+                    requests
+                        .iter()
+                        .map(|request| self.respond(request))
+                        .collect(),
+                ),
+                Ok(request) => self.respond(&request),
+                Err(err) => error(&Value::Null, -32700, &format!("parse error: {}", err)),
+            };
+            write_response(&mut stream, 200, &serde_json::to_vec(&response)?).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the JSON-RPC response to a single request.
+    pub fn respond(&self, request: &Value) -> Value {
+        let Some(method) = request["method"].as_str() else {
+            return error(request, INVALID_PARAMS, "missing method");
+        };
+        debug!("Serving {} {}", method, request["params"]);
+
+        let mut cache = self.cache.lock().unwrap();
+        let result = Params::new(request).and_then(|params| match method {
+            "eth_getBlockByNumber" | "eth_getBlockByHash" => {
+                let (block_no, block_hash) = params.block(0, &cache)?;
+                let query = BlockQuery {
+                    block_no,
+                    block_hash,
+                };
+                match params.get::<bool>(1)? {
+                    true => cached(cache.get_full_block(&query)),
+                    false => cached(cache.get_partial_block(&query)),
+                }
+            }
+            "eth_getBlockReceipts" => {
+                let (block_no, block_hash) = params.block(0, &cache)?;
+                let query = BlockQuery {
+                    block_no,
+                    block_hash,
+                };
+                cached(cache.get_block_receipts(&query))
+            }
+            "eth_getProof" => {
+                let (block_no, block_hash) = params.block(2, &cache)?;
+                let query = ProofQuery {
+                    block_no,
+                    block_hash,
+                    address: params.get(0)?,
+                    indices: params.get::<Vec<H256>>(1)?.into_iter().collect(),
+                };
+                cached(cache.get_proof(&query))
+            }
+            "eth_getStorageAt" => {
+                let (block_no, block_hash) = params.block(2, &cache)?;
+                let query = StorageQuery {
+                    block_no,
+                    block_hash,
+                    address: params.get(0)?,
+                    index: params.get(1)?,
+                };
+                cached(cache.get_storage(&query))
+            }
+            "eth_getBalance" => {
+                let query = params.account(&cache)?;
+                cached(cache.get_balance(&query))
+            }
+            "eth_getTransactionCount" => {
+                let query = params.account(&cache)?;
+                cached(cache.get_transaction_count(&query))
+            }
+            "eth_getCode" => {
+                let query = params.account(&cache)?;
+                cached(cache.get_code(&query))
+            }
+            method => Err((METHOD_NOT_FOUND, format!("{} is not supported", method))),
+        });
+
+        match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }),
+            Err((code, message)) => error(request, code, &format!("{}: {}", method, message)),
+        }
+    }
+}
+
+type RpcResult<T> = std::result::Result<T, (i64, String)>;
+
+/// Converts the result of a cache lookup into the JSON result of a request.
+fn cached<T: Serialize>(result: Result<T>) -> RpcResult<Value> {
+    match result {
+        Ok(val) => Ok(serde_json::to_value(val).unwrap()),
+        Err(err) => Err((NOT_CACHED, format!("not in cache ({})", err))),
+    }
+}
+
+/// The positional parameters of a request.
+struct Params<'a>(&'a [Value]);
+
+impl<'a> Params<'a> {
+    fn new(request: &'a Value) -> RpcResult<Self> {
+        match request["params"].as_array() {
+            Some(params) => Ok(Params(params)),
+            None => Err((INVALID_PARAMS, "params must be an array".to_string())),
+        }
+    }
+
+    fn get<T: DeserializeOwned>(&self, i: usize) -> RpcResult<T> {
+        let param = self.0.get(i).cloned().unwrap_or_default();
+        serde_json::from_value(param)
+            .map_err(|err| (INVALID_PARAMS, format!("invalid parameter {}: {}", i, err)))
+    }
+
+    /// Returns the number and hash of the block at the given position, which is either a
+    /// block number or a block hash, optionally as an EIP-1898 object. Block tags cannot
+    /// be answered from the caches.
+    fn block(&self, i: usize, cache: &FileProvider) -> RpcResult<(u64, Option<H256>)> {
+        if let Ok(block_no) = self.get::<U64>(i) {
+            return Ok((block_no.as_u64(), None));
+        }
+        let block_hash = match self.get::<BlockHash>(i) {
+            Ok(BlockHash::Hash(block_hash)) | Ok(BlockHash::Object { block_hash }) => block_hash,
+            Err(_) => {
+                return Err((
+                    INVALID_PARAMS,
+                    format!("parameter {} must be a block number or hash", i),
+                ))
+            }
+        };
+        // the number of a block is only known if the block itself is cached; the data of a
+        // block recorded without its hash is also looked up without the hash
+        let block = cache
+            .full_blocks
+            .iter()
+            .map(|(query, block)| (query, block.hash, block.number))
+            .chain(
+                cache
+                    .partial_blocks
+                    .iter()
+                    .map(|(query, block)| (query, block.hash, block.number)),
+            )
+            .find_map(|(query, hash, number)| {
+                number
+                    .filter(|_| hash == Some(block_hash))
+                    .map(|number| (number.as_u64(), query.block_hash))
+            });
+        block.ok_or_else(|| (NOT_CACHED, format!("block {:?} not in cache", block_hash)))
+    }
+
+    fn account(&self, cache: &FileProvider) -> RpcResult<AccountQuery> {
+        let (block_no, block_hash) = self.block(1, cache)?;
+        Ok(AccountQuery {
+            block_no,
+            block_hash,
+            address: self.get::<H160>(0)?,
+        })
+    }
+}
+
+/// A block hash parameter.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BlockHash {
+    Hash(H256),
+    #[serde(rename_all = "camelCase")]
+    Object {
+        block_hash: H256,
+    },
+}
+
+fn error(request: &Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "error": { "code": code, "message": message },
+    })
+}
+
+/// Reads the body of the next HTTP request on the connection, or `None` if it was closed.
+///
+/// Bodies larger than [MAX_BODY_SIZE] are answered with an error response and fail the
+/// connection.
+pub(crate) async fn read_request(stream: &mut BufReader<TcpStream>) -> Result<Option<Vec<u8>>> {
+    let mut line = String::new();
+    if stream.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        stream.read_line(&mut line).await?;
+        if line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().context("invalid content-length")?;
+            }
+        }
+    }
+    if content_length > MAX_BODY_SIZE {
+        let message = format!(
+            "request body of {} bytes exceeds the limit of {} bytes",
+            content_length, MAX_BODY_SIZE
+        );
+        let response = error(&Value::Null, INVALID_REQUEST, &message);
+        write_response(stream, 413, &serde_json::to_vec(&response)?).await?;
+        bail!(message);
+    }
+    let mut body = vec![0; content_length];
+    stream.read_exact(&mut body).await?;
+
+    Ok(Some(body))
+}
+
+/// Writes an HTTP response with the given status and JSON body.
+pub(crate) async fn write_response(
+    stream: &mut BufReader<TcpStream>,
+    status: u16,
+    body: &[u8],
+) -> Result<()> {
+    let header = format!(
+        "HTTP/1.1 {} {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n",
+        status,
+        if status == 200 { "OK" } else { "Error" },
+        body.len()
+    );
+    stream.get_mut().write_all(header.as_bytes()).await?;
+    stream.get_mut().write_all(body).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::types::{Block, Bytes, EIP1186ProofResponse, Transaction, U256};
+
+    use super::*;
+    use crate::host::provider::{
+        cached_rpc_provider::CachedRpcProvider,
+        rpc_provider::RpcProvider,
+        test_queries::{account_query, address, proof_query},
+        MutProvider,
+    };
+
+    /// Writes one cache file for each of the blocks 1 and 2.
+    fn write_caches(dir: &std::path::Path) -> Vec<PathBuf> {
+        (1..=2)
+            .map(|block_no| {
+                let path = dir.join(format!("{}.json.gz", block_no));
+                let mut cache = FileProvider::new(path.clone()).unwrap();
+                cache.insert_full_block(
+                    BlockQuery {
+                        block_no,
+                        block_hash: None,
+                    },
+                    Block::<Transaction> {
+                        number: Some(block_no.into()),
+                        hash: Some(H256::from_low_u64_be(block_no)),
+                        ..Default::default()
+                    },
+                );
+                cache.insert_transaction_count(account_query(block_no, 1), block_no.into());
+                cache.insert_balance(account_query(block_no, 1), U256::from(42));
+                cache.insert_code(account_query(block_no, 1), Bytes::from(vec![0x42]));
+                if block_no == 1 {
+                    cache.insert_proof(
+                        proof_query(1, 1, &[2]),
+                        EIP1186ProofResponse {
+                            address: address(1),
+                            ..Default::default()
+                        },
+                    );
+                }
+                cache.save().unwrap();
+                path
+            })
+            .collect()
+    }
+
+    #[test]
+    fn serve_caches() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let dir = tempfile::tempdir().unwrap();
+        let url = CacheServer::new(&write_caches(dir.path()))
+            .unwrap()
+            .start()
+            .unwrap();
+        let mut provider = RpcProvider::new(url).unwrap();
+
+        for block_no in 1..=2 {
+            let block = provider
+                .get_full_block(&BlockQuery {
+                    block_no,
+                    block_hash: None,
+                })
+                .unwrap();
+            assert_eq!(block.number, Some(block_no.into()));
+            let account = provider.get_account(&account_query(block_no, 1)).unwrap();
+            assert_eq!(
+                account,
+                (block_no.into(), U256::from(42), Bytes::from(vec![0x42]))
+            );
+        }
+        let proof = provider.get_proof(&proof_query(1, 1, &[2])).unwrap();
+        assert_eq!(proof.address, address(1));
+
+        let err = provider
+            .get_full_block(&BlockQuery {
+                block_no: 3,
+                block_hash: None,
+            })
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("not in cache"));
+        let err = provider.get_balance(&account_query(3, 1)).unwrap_err();
+        assert!(format!("{:#}", err).contains("eth_getBalance"));
+    }
+
+    #[test]
+    fn block_hashes() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let dir = tempfile::tempdir().unwrap();
+        let url = CacheServer::new(&write_caches(dir.path()))
+            .unwrap()
+            .start()
+            .unwrap();
+        let mut provider = RpcProvider::new(url).unwrap();
+
+        let block_query = |block_hash: u64| BlockQuery {
+            block_no: 2,
+            block_hash: Some(H256::from_low_u64_be(block_hash)),
+        };
+        let block = provider.get_full_block(&block_query(2)).unwrap();
+        assert_eq!(block.hash, Some(H256::from_low_u64_be(2)));
+        let query = AccountQuery {
+            block_hash: Some(H256::from_low_u64_be(2)),
+            ..account_query(2, 1)
+        };
+        assert_eq!(provider.get_balance(&query).unwrap(), U256::from(42));
+
+        // the hash of block 1 does not belong to block 2
+        assert!(provider.get_full_block(&block_query(1)).is_err());
+        // unknown hashes cannot be answered
+        assert!(provider.get_full_block(&block_query(3)).is_err());
+        let query = AccountQuery {
+            block_hash: Some(H256::from_low_u64_be(3)),
+            ..account_query(2, 1)
+        };
+        let err = provider.get_balance(&query).unwrap_err();
+        assert!(format!("{:#}", err).contains("not in cache"));
+    }
+
+    #[test]
+    fn cached_rpc_provider() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let dir = tempfile::tempdir().unwrap();
+        let url = CacheServer::new(&write_caches(dir.path()))
+            .unwrap()
+            .start()
+            .unwrap();
+
+        // an empty cache is filled from the server
+        let cache_path = dir.path().join("cached").join("1.json.gz");
+        let mut provider = CachedRpcProvider::new(cache_path.clone(), url).unwrap();
+        let proof = provider.get_proof(&proof_query(1, 1, &[2])).unwrap();
+        provider.get_account(&account_query(1, 1)).unwrap();
+        provider.save().unwrap();
+
+        let mut cache = FileProvider::new(cache_path).unwrap();
+        assert_eq!(cache.get_proof(&proof_query(1, 1, &[2])).unwrap(), proof);
+        assert_eq!(
+            cache.get_balance(&account_query(1, 1)).unwrap(),
+            U256::from(42)
+        );
+    }
+
+    #[test]
+    fn invalid_requests() {
+        let server = CacheServer::new(&[]).unwrap();
+        let response = server.respond(&json!({
+            "jsonrpc": "2.0", "id": 1, "method": "eth_getBalance", "params": ["0x01", "latest"]
+        }));
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        let response = server.respond(&json!({
+            "jsonrpc": "2.0", "id": 2, "method": "eth_call", "params": []
+        }));
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(response["id"], 2);
+    }
+
+    #[test]
+    fn oversized_request() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let url = CacheServer::new(&[]).unwrap().start().unwrap();
+            let mut stream = TcpStream::connect(url.trim_start_matches("http://"))
+                .await
+                .unwrap();
+
+            // the body is rejected before it is sent
+            let request = format!(
+                "POST / HTTP/1.1\r\ncontent-length: {}\r\n\r\n",
+                MAX_BODY_SIZE + 1
+            );
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            assert!(response.starts_with("HTTP/1.1 413"));
+            assert!(response.contains(&INVALID_REQUEST.to_string()));
+        });
+    }
+}
@@ -21,25 +21,32 @@ use ethers_core::types::{
 };
 
 use super::{
-    file_provider::FileProvider, rpc_provider::RpcProvider, AccountQuery, BlockQuery, MutProvider,
-    ProofQuery, Provider, StorageQuery,
+    file_provider::FileProvider, new_rpc_provider, AccountQuery, BlockQuery, MutProvider,
+    ProofQuery, Provider, RpcConfig, StorageQuery,
 };
 
-pub struct CachedRpcProvider {
-    cache: FileProvider,
-    rpc: RpcProvider,
+pub struct CachedRpcProvider<C = FileProvider> {
+    cache: C,
+    rpc: Box<dyn Provider>,
 }
 
 impl CachedRpcProvider {
-    pub fn new(cache_path: PathBuf, rpc_url: String) -> Result<Self> {
+    pub fn new(cache_path: PathBuf, rpc_config: impl Into<RpcConfig>) -> Result<Self> {
         let cache = FileProvider::new(cache_path).context("failed to init cache")?;
-        let rpc = RpcProvider::new(rpc_url).context("failed to init RPC")?;
+        CachedRpcProvider::with_cache(cache, rpc_config)
+    }
+}
+
+impl<C: MutProvider> CachedRpcProvider<C> {
+    /// Creates a new provider that caches the RPC responses in the given provider.
+    pub fn with_cache(cache: C, rpc_config: impl Into<RpcConfig>) -> Result<Self> {
+        let rpc = new_rpc_provider(rpc_config.into()).context("failed to init RPC")?;
 
         Ok(CachedRpcProvider { cache, rpc })
     }
 }
 
-impl Provider for CachedRpcProvider {
+impl<C: MutProvider> Provider for CachedRpcProvider<C> {
     fn save(&self) -> Result<()> {
         self.cache.save()
     }
@@ -92,6 +99,40 @@ impl Provider for CachedRpcProvider {
         Ok(out)
     }
 
+    fn get_proofs(&mut self, queries: &[ProofQuery]) -> Result<Vec<EIP1186ProofResponse>> {
+        // only fetch the proofs that are not cached yet
+        let missing: Vec<_> = queries
+            .iter()
+            .filter(|query| self.cache.get_proof(query).is_err())
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            let proofs = self.rpc.get_proofs(&missing)?;
+            for (query, proof) in missing.into_iter().zip(proofs) {
+                self.cache.insert_proof(query, proof);
+            }
+        }
+
+        queries
+            .iter()
+            .map(|query| self.cache.get_proof(query))
+            .collect()
+    }
+
+    fn get_account(&mut self, query: &AccountQuery) -> Result<(U256, U256, Bytes)> {
+        let cache_out = self.cache.get_account(query);
+        if cache_out.is_ok() {
+            return cache_out;
+        }
+
+        let (nonce, balance, code) = self.rpc.get_account(query)?;
+        self.cache.insert_transaction_count(query.clone(), nonce);
+        self.cache.insert_balance(query.clone(), balance);
+        self.cache.insert_code(query.clone(), code.clone());
+
+        Ok((nonce, balance, code))
+    }
+
     fn get_transaction_count(&mut self, query: &AccountQuery) -> Result<U256> {
         let cache_out = self.cache.get_transaction_count(query);
         if cache_out.is_ok() {
@@ -129,7 +170,7 @@ impl Provider for CachedRpcProvider {
     }
 
     fn get_all_codes(&self) -> Iter<AccountQuery, Bytes> {
-        unimplemented!()
+        self.cache.get_all_codes()
     }
 
     fn get_storage(&mut self, query: &StorageQuery) -> Result<H256> {
@@ -144,3 +185,53 @@ impl Provider for CachedRpcProvider {
         Ok(out)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use ethers_core::types::H160;
+
+    use super::*;
+    use crate::host::provider::mock_server::MockServer;
+
+    #[test]
+    fn cached_batches() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let server = MockServer::start();
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join("cache.json.gz");
+        let mut provider = CachedRpcProvider::new(cache_path, server.url()).unwrap();
+
+        let queries: Vec<_> = (0..5)
+            .map(|i| ProofQuery {
+                block_no: 1,
+                block_hash: None,
+                address: H160::from_low_u64_be(i),
+                indices: BTreeSet::from([H256::from_low_u64_be(i)]),
+            })
+            .collect();
+        provider.get_proofs(&queries[..3]).unwrap();
+        assert_eq!(server.batch_sizes(), vec![3]);
+
+        // only the missing proofs are fetched
+        let proofs = provider.get_proofs(&queries).unwrap();
+        assert_eq!(server.batch_sizes(), vec![3, 2]);
+        for (query, proof) in queries.iter().zip(&proofs) {
+            assert_eq!(proof.address, query.address);
+            assert_eq!(&provider.get_proof(query).unwrap(), proof);
+        }
+        assert_eq!(server.batch_sizes(), vec![3, 2]);
+
+        let query = AccountQuery {
+            block_no: 1,
+            block_hash: None,
+            address: H160::from_low_u64_be(42),
+        };
+        let account = provider.get_account(&query).unwrap();
+        assert_eq!(provider.get_account(&query).unwrap(), account);
+        assert_eq!(provider.get_balance(&query).unwrap(), account.1);
+        assert_eq!(server.batch_sizes(), vec![3, 2, 3]);
+    }
+}
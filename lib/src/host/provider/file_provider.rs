@@ -13,13 +13,12 @@
 // limitations under the License.
 
 use std::{
-    collections::HashMap,
+    collections::{hash_map::Iter, HashMap},
     fs::{self, File},
     io,
     io::Write,
     path::{Path, PathBuf},
 };
-use std::collections::hash_map::Iter;
 
 use anyhow::{anyhow, Context, Result};
 use ethers_core::types::{
@@ -28,31 +27,31 @@ use ethers_core::types::{
 use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use serde::{Deserialize, Serialize};
 
-use super::{AccountQuery, BlockQuery, MutProvider, ProofQuery, Provider, StorageQuery};
+use super::{AccountQuery, BlockQuery, MutProvider, ProofQuery, Provider, Query, StorageQuery};
 
 #[derive(Clone, Default, Deserialize, Serialize)]
 pub struct FileProvider {
     #[serde(skip)]
     file_path: PathBuf,
     #[serde(skip)]
-    dirty: bool,
+    pub(super) dirty: bool,
     #[serde(with = "ordered_map")]
-    full_blocks: HashMap<BlockQuery, Block<Transaction>>,
+    pub(super) full_blocks: HashMap<BlockQuery, Block<Transaction>>,
     #[serde(with = "ordered_map")]
-    partial_blocks: HashMap<BlockQuery, Block<H256>>,
+    pub(super) partial_blocks: HashMap<BlockQuery, Block<H256>>,
     #[serde(default)]
     #[serde(with = "ordered_map")]
-    receipts: HashMap<BlockQuery, Vec<TransactionReceipt>>,
+    pub(super) receipts: HashMap<BlockQuery, Vec<TransactionReceipt>>,
     #[serde(with = "ordered_map")]
-    proofs: HashMap<ProofQuery, EIP1186ProofResponse>,
+    pub(super) proofs: HashMap<ProofQuery, EIP1186ProofResponse>,
     #[serde(with = "ordered_map")]
-    transaction_count: HashMap<AccountQuery, U256>,
+    pub(super) transaction_count: HashMap<AccountQuery, U256>,
     #[serde(with = "ordered_map")]
-    balance: HashMap<AccountQuery, U256>,
+    pub(super) balance: HashMap<AccountQuery, U256>,
     #[serde(with = "ordered_map")]
-    code: HashMap<AccountQuery, Bytes>,
+    pub(super) code: HashMap<AccountQuery, Bytes>,
     #[serde(with = "ordered_map")]
-    storage: HashMap<StorageQuery, H256>,
+    pub(super) storage: HashMap<StorageQuery, H256>,
 }
 
 /// A serde helper to serialize a HashMap into a vector sorted by key
@@ -127,6 +126,82 @@ impl FileProvider {
 
         Ok(())
     }
+
+    /// Inserts all the cached data into another provider.
+    pub fn copy_into(&self, target: &mut impl MutProvider) {
+        for (query, val) in &self.full_blocks {
+            target.insert_full_block(query.clone(), val.clone());
+        }
+        for (query, val) in &self.partial_blocks {
+            target.insert_partial_block(query.clone(), val.clone());
+        }
+        for (query, val) in &self.receipts {
+            target.insert_block_receipts(query.clone(), val.clone());
+        }
+        for (query, val) in &self.proofs {
+            target.insert_proof(query.clone(), val.clone());
+        }
+        for (query, val) in &self.transaction_count {
+            target.insert_transaction_count(query.clone(), *val);
+        }
+        for (query, val) in &self.balance {
+            target.insert_balance(query.clone(), *val);
+        }
+        for (query, val) in &self.code {
+            target.insert_code(query.clone(), val.clone());
+        }
+        for (query, val) in &self.storage {
+            target.insert_storage(query.clone(), *val);
+        }
+    }
+}
+
+/// Returns the block of the query. A block recorded without a block hash only matches
+/// queries of its own hash.
+pub(super) fn lookup_block<'a, T>(
+    map: &'a HashMap<BlockQuery, Block<T>>,
+    query: &BlockQuery,
+) -> Option<&'a Block<T>> {
+    match map.get(query) {
+        Some(block) => Some(block),
+        None if query.block_hash.is_some() => map
+            .get(&query.without_block_hash())
+            .filter(|block| block.hash == query.block_hash),
+        None => None,
+    }
+}
+
+impl FileProvider {
+    /// Returns the entry of the query. An entry recorded without a block hash only
+    /// matches the query if the block cached for its number has the queried hash.
+    fn lookup<'a, Q: Query, V>(&self, map: &'a HashMap<Q, V>, query: &Q) -> Option<&'a V> {
+        match map.get(query) {
+            Some(val) => Some(val),
+            None if query.block_hash().is_some() && self.is_cached_block(query) => {
+                map.get(&query.without_block_hash())
+            }
+            None => None,
+        }
+    }
+
+    /// Returns whether the block recorded without a block hash for the number of the
+    /// query has the queried hash.
+    fn is_cached_block(&self, query: &impl Query) -> bool {
+        let block_query = BlockQuery {
+            block_no: query.block_no(),
+            block_hash: None,
+        };
+        let block_hash = self
+            .full_blocks
+            .get(&block_query)
+            .map(|block| block.hash)
+            .or_else(|| {
+                self.partial_blocks
+                    .get(&block_query)
+                    .map(|block| block.hash)
+            });
+        block_hash.is_some_and(|hash| hash == query.block_hash())
+    }
 }
 
 impl Provider for FileProvider {
@@ -143,49 +218,49 @@ impl Provider for FileProvider {
     }
 
     fn get_full_block(&mut self, query: &BlockQuery) -> Result<Block<Transaction>> {
-        match self.full_blocks.get(query) {
+        match lookup_block(&self.full_blocks, query) {
             Some(val) => Ok(val.clone()),
             None => Err(anyhow!("No data for {:?}", query)),
         }
     }
 
     fn get_partial_block(&mut self, query: &BlockQuery) -> Result<Block<H256>> {
-        match self.partial_blocks.get(query) {
+        match lookup_block(&self.partial_blocks, query) {
             Some(val) => Ok(val.clone()),
             None => Err(anyhow!("No data for {:?}", query)),
         }
     }
 
     fn get_block_receipts(&mut self, query: &BlockQuery) -> Result<Vec<TransactionReceipt>> {
-        match self.receipts.get(query) {
+        match self.lookup(&self.receipts, query) {
             Some(val) => Ok(val.clone()),
             None => Err(anyhow!("No data for {:?}", query)),
         }
     }
 
     fn get_proof(&mut self, query: &ProofQuery) -> Result<EIP1186ProofResponse> {
-        match self.proofs.get(query) {
+        match self.lookup(&self.proofs, query) {
             Some(val) => Ok(val.clone()),
             None => Err(anyhow!("No data for {:?}", query)),
         }
     }
 
     fn get_transaction_count(&mut self, query: &AccountQuery) -> Result<U256> {
-        match self.transaction_count.get(query) {
+        match self.lookup(&self.transaction_count, query) {
             Some(val) => Ok(*val),
             None => Err(anyhow!("No data for {:?}", query)),
         }
     }
 
     fn get_balance(&mut self, query: &AccountQuery) -> Result<U256> {
-        match self.balance.get(query) {
+        match self.lookup(&self.balance, query) {
             Some(val) => Ok(*val),
             None => Err(anyhow!("No data for {:?}", query)),
         }
     }
 
     fn get_code(&mut self, query: &AccountQuery) -> Result<Bytes> {
-        match self.code.get(query) {
+        match self.lookup(&self.code, query) {
             Some(val) => Ok(val.clone()),
             None => Err(anyhow!("No data for {:?}", query)),
         }
@@ -196,7 +271,7 @@ impl Provider for FileProvider {
     }
 
     fn get_storage(&mut self, query: &StorageQuery) -> Result<H256> {
-        match self.storage.get(query) {
+        match self.lookup(&self.storage, query) {
             Some(val) => Ok(*val),
             None => Err(anyhow!("No data for {:?}", query)),
         }
@@ -244,3 +319,58 @@ impl MutProvider for FileProvider {
         self.dirty = true;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::provider::test_queries;
+
+    fn account_query(block_hash: Option<H256>) -> AccountQuery {
+        AccountQuery {
+            block_hash,
+            ..test_queries::account_query(1, 1)
+        }
+    }
+
+    #[test]
+    fn read_hashless_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("1.json.gz");
+        let block_hash = H256::from_low_u64_be(1);
+
+        // record the data without block hashes, like the caches of older versions
+        let mut cache = FileProvider::new(path.clone()).unwrap();
+        cache.insert_partial_block(
+            BlockQuery {
+                block_no: 1,
+                block_hash: None,
+            },
+            Block::<H256> {
+                number: Some(1.into()),
+                hash: Some(block_hash),
+                ..Default::default()
+            },
+        );
+        cache.insert_balance(account_query(None), U256::from(42));
+        cache.save().unwrap();
+
+        let mut cache = FileProvider::new(path).unwrap();
+        assert_eq!(
+            cache.get_balance(&account_query(Some(block_hash))).unwrap(),
+            U256::from(42)
+        );
+        assert_eq!(
+            cache.get_balance(&account_query(None)).unwrap(),
+            U256::from(42)
+        );
+        // the data must not be used for a different block of the same number
+        let other_hash = H256::from_low_u64_be(2);
+        assert!(cache.get_balance(&account_query(Some(other_hash))).is_err());
+        assert!(cache
+            .get_partial_block(&BlockQuery {
+                block_no: 1,
+                block_hash: Some(other_hash),
+            })
+            .is_err());
+    }
+}
@@ -0,0 +1,599 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Inspection, validation and pruning of [FileProvider] caches.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+use anyhow::{ensure, Context, Result};
+use ethers_core::types::{EIP1186ProofResponse, H160, H256};
+use zeth_primitives::{
+    ethers::{from_ethers_h256, from_ethers_u256},
+    keccak::keccak,
+    trie::StateAccount,
+    U256,
+};
+
+use super::{
+    file_provider::{lookup_block, FileProvider},
+    AccountQuery, BlockQuery, ProofQuery,
+};
+use crate::host::mpt::{mpt_from_proof, parse_proof};
+
+/// Number of ancestors whose hash can be accessed by the `BLOCKHASH` opcode.
+pub(super) const BLOCK_HASH_HISTORY: u64 = 256;
+
+/// Summary of the contents of a cache.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub full_blocks: usize,
+    pub partial_blocks: usize,
+    pub receipts: usize,
+    /// Number of distinct accounts over all blocks.
+    pub accounts: usize,
+    /// Number of distinct storage slots over all blocks.
+    pub slots: usize,
+    /// Total size of the distinct contract codes.
+    pub code_bytes: usize,
+    /// Number of distinct nodes in all account and storage proofs.
+    pub proof_nodes: usize,
+    /// Total size of the distinct proof nodes.
+    pub proof_bytes: usize,
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "full blocks:    {}", self.full_blocks)?;
+        writeln!(f, "partial blocks: {}", self.partial_blocks)?;
+        writeln!(f, "receipts:       {}", self.receipts)?;
+        writeln!(f, "accounts:       {}", self.accounts)?;
+        writeln!(f, "slots:          {}", self.slots)?;
+        writeln!(f, "code bytes:     {}", self.code_bytes)?;
+        write!(
+            f,
+            "proof nodes:    {} ({} bytes)",
+            self.proof_nodes, self.proof_bytes
+        )
+    }
+}
+
+impl FileProvider {
+    /// Returns statistics about the cached data.
+    pub fn stats(&self) -> CacheStats {
+        let mut accounts = HashSet::new();
+        let mut slots = HashSet::new();
+        for query in self.proofs.keys() {
+            accounts.insert((query.block_no, query.address));
+            for index in &query.indices {
+                slots.insert((query.block_no, query.address, *index));
+            }
+        }
+        for query in self
+            .transaction_count
+            .keys()
+            .chain(self.balance.keys())
+            .chain(self.code.keys())
+        {
+            accounts.insert((query.block_no, query.address));
+        }
+        for query in self.storage.keys() {
+            slots.insert((query.block_no, query.address, query.index));
+        }
+
+        let codes: HashMap<_, _> = self
+            .code
+            .values()
+            .map(|code| (keccak(code), code.len()))
+            .collect();
+        let nodes: HashSet<_> = self
+            .proofs
+            .values()
+            .flat_map(|proof| {
+                let storage_nodes = proof.storage_proof.iter().flat_map(|p| &p.proof);
+                proof.account_proof.iter().chain(storage_nodes)
+            })
+            .collect();
+
+        CacheStats {
+            full_blocks: self.full_blocks.len(),
+            partial_blocks: self.partial_blocks.len(),
+            receipts: self.receipts.len(),
+            accounts: accounts.len(),
+            slots: slots.len(),
+            code_bytes: codes.values().sum(),
+            proof_nodes: nodes.len(),
+            proof_bytes: nodes.iter().map(|node| node.len()).sum(),
+        }
+    }
+
+    /// Checks every proof against the state root of its block, and the cached account
+    /// data against these proofs. Returns a description of each inconsistency.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        let mut proofs: Vec<_> = self.proofs.iter().collect();
+        proofs.sort_by_key(|(query, _)| *query);
+        for (query, proof) in &proofs {
+            let block_query = BlockQuery {
+                block_no: query.block_no,
+                block_hash: query.block_hash,
+            };
+            let state_root = match lookup_block(&self.full_blocks, &block_query) {
+                Some(block) => block.state_root,
+                None => match lookup_block(&self.partial_blocks, &block_query) {
+                    Some(block) => block.state_root,
+                    None => {
+                        problems.push(format!(
+                            "no block {} to validate the proof of {:?}",
+                            query.block_no, query.address
+                        ));
+                        continue;
+                    }
+                },
+            };
+            if let Err(err) = verify_proof(query, proof, state_root) {
+                problems.push(format!(
+                    "invalid proof of {:?} at block {}: {:#}",
+                    query.address, query.block_no, err
+                ));
+            }
+        }
+
+        // every proof of the same account and block contains the same account data
+        let accounts: BTreeMap<_, _> = proofs
+            .iter()
+            .map(|(query, proof)| ((query.block_no, query.block_hash, query.address), *proof))
+            .collect();
+        let account_proof =
+            |query: &AccountQuery| accounts.get(&(query.block_no, query.block_hash, query.address));
+        let mismatch = |what: &str, query: &AccountQuery| {
+            format!(
+                "{} of {:?} at block {} does not match its proof",
+                what, query.address, query.block_no
+            )
+        };
+        for (query, code) in sorted(&self.code) {
+            if let Some(proof) = account_proof(query) {
+                if H256::from(keccak(code)) != proof.code_hash {
+                    problems.push(mismatch("code", query));
+                }
+            }
+        }
+        for (query, nonce) in sorted(&self.transaction_count) {
+            if let Some(proof) = account_proof(query) {
+                if *nonce != proof.nonce.as_u64().into() {
+                    problems.push(mismatch("transaction count", query));
+                }
+            }
+        }
+        for (query, balance) in sorted(&self.balance) {
+            if let Some(proof) = account_proof(query) {
+                if *balance != proof.balance {
+                    problems.push(mismatch("balance", query));
+                }
+            }
+        }
+        for (query, value) in sorted(&self.storage) {
+            let slot_proof = proofs
+                .iter()
+                .filter(|(proof_query, _)| {
+                    proof_query.block_no == query.block_no && proof_query.address == query.address
+                })
+                .flat_map(|(_, proof)| &proof.storage_proof)
+                .find(|slot| from_ethers_u256(slot.key) == U256::from_be_bytes(query.index.0));
+            if let Some(slot) = slot_proof {
+                if from_ethers_u256(slot.value) != U256::from_be_bytes(value.0) {
+                    problems.push(format!(
+                        "storage slot {:?} of {:?} at block {} does not match its proof",
+                        query.index, query.address, query.block_no
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// Returns a description of each entry that is required to build the given block,
+    /// but missing from the cache.
+    pub fn missing(&self, block_no: u64) -> Vec<String> {
+        let mut missing = Vec::new();
+        let parent_no = block_no.saturating_sub(1);
+        if !has_block(&self.partial_blocks, parent_no) {
+            missing.push(format!("parent block {}", parent_no));
+        }
+        if !has_block(&self.full_blocks, block_no) {
+            missing.push(format!("block {}", block_no));
+        }
+
+        // collect the accounts and slots accessed when executing the block
+        let mut accessed: BTreeMap<H160, BTreeSet<H256>> = BTreeMap::new();
+        for query in self
+            .transaction_count
+            .keys()
+            .chain(self.balance.keys())
+            .chain(self.code.keys())
+            .filter(|query| query.block_no == parent_no)
+        {
+            accessed.entry(query.address).or_default();
+        }
+        for query in self
+            .storage
+            .keys()
+            .filter(|query| query.block_no == parent_no)
+        {
+            accessed
+                .entry(query.address)
+                .or_default()
+                .insert(query.index);
+        }
+
+        for (address, indices) in accessed {
+            if !has_account(&self.transaction_count, parent_no, address) {
+                missing.push(format!("transaction count of {:?}", address));
+            }
+            if !has_account(&self.balance, parent_no, address) {
+                missing.push(format!("balance of {:?}", address));
+            }
+            if !has_account(&self.code, parent_no, address) {
+                missing.push(format!("code of {:?}", address));
+            }
+            for proof_block_no in [parent_no, block_no] {
+                let covered = self.proofs.keys().any(|query| {
+                    query.block_no == proof_block_no
+                        && query.address == address
+                        && query.indices.is_superset(&indices)
+                });
+                if !covered {
+                    missing.push(format!(
+                        "proof of {:?} with {} slots at block {}",
+                        address,
+                        indices.len(),
+                        proof_block_no
+                    ));
+                }
+            }
+        }
+
+        missing
+    }
+
+    /// Removes all entries that are not required to build the given block, as well as
+    /// proofs whose slots are all contained in another proof of the same account.
+    /// Returns the number of removed entries.
+    pub fn prune(&mut self, block_no: u64) -> usize {
+        let parent_no = block_no.saturating_sub(1);
+        let ancestors = block_no.saturating_sub(BLOCK_HASH_HISTORY)..block_no;
+        let state = |n: u64| n == parent_no || n == block_no;
+
+        let superseded: HashSet<ProofQuery> = self
+            .proofs
+            .keys()
+            .filter(|query| {
+                self.proofs.keys().any(|other| {
+                    other.block_no == query.block_no
+                        && other.address == query.address
+                        && other.indices.len() > query.indices.len()
+                        && other.indices.is_superset(&query.indices)
+                })
+            })
+            .cloned()
+            .collect();
+
+        let before = self.len();
+        self.full_blocks
+            .retain(|query, _| query.block_no == block_no);
+        self.partial_blocks
+            .retain(|query, _| ancestors.contains(&query.block_no));
+        self.receipts.retain(|query, _| query.block_no == block_no);
+        self.proofs
+            .retain(|query, _| state(query.block_no) && !superseded.contains(query));
+        self.transaction_count
+            .retain(|query, _| state(query.block_no));
+        self.balance.retain(|query, _| state(query.block_no));
+        self.code.retain(|query, _| state(query.block_no));
+        self.storage.retain(|query, _| state(query.block_no));
+
+        let removed = before - self.len();
+        if removed > 0 {
+            self.dirty = true;
+        }
+        removed
+    }
+
+    /// Returns the total number of cached entries.
+    fn len(&self) -> usize {
+        self.full_blocks.len()
+            + self.partial_blocks.len()
+            + self.receipts.len()
+            + self.proofs.len()
+            + self.transaction_count.len()
+            + self.balance.len()
+            + self.code.len()
+            + self.storage.len()
+    }
+}
+
+/// Returns whether the map contains the block with the given number for any hash.
+fn has_block<V>(blocks: &HashMap<BlockQuery, V>, block_no: u64) -> bool {
+    blocks.keys().any(|query| query.block_no == block_no)
+}
+
+/// Returns whether the map contains the account at the given block for any hash.
+fn has_account<V>(accounts: &HashMap<AccountQuery, V>, block_no: u64, address: H160) -> bool {
+    accounts
+        .keys()
+        .any(|query| query.block_no == block_no && query.address == address)
+}
+
+/// Verifies the account and storage proofs of the response against the state root.
+fn verify_proof(query: &ProofQuery, proof: &EIP1186ProofResponse, state_root: H256) -> Result<()> {
+    ensure!(
+        proof.address == query.address,
+        "proof of {:?}",
+        proof.address
+    );
+
+    let nodes = parse_proof(&proof.account_proof).context("invalid account proof encoding")?;
+    let state_trie = mpt_from_proof(&nodes).context("invalid account proof")?;
+    ensure!(
+        state_trie.hash() == from_ethers_h256(state_root),
+        "account proof does not match the state root"
+    );
+    match state_trie
+        .get_rlp::<StateAccount>(&keccak(query.address))
+        .context("incomplete account proof")?
+    {
+        Some(account) => ensure!(
+            account == StateAccount::from(proof.clone()),
+            "account does not match the proof response"
+        ),
+        None => ensure!(
+            proof.nonce.is_zero() && proof.balance.is_zero(),
+            "proof response of a non-existing account is not empty"
+        ),
+    }
+
+    let keys: BTreeSet<H256> = proof
+        .storage_proof
+        .iter()
+        .map(|slot| H256::from(from_ethers_u256(slot.key).to_be_bytes::<32>()))
+        .collect();
+    ensure!(keys == query.indices, "storage keys do not match the query");
+    for slot in &proof.storage_proof {
+        let key = from_ethers_u256(slot.key).to_be_bytes::<32>();
+        let nodes = parse_proof(&slot.proof).context("invalid storage proof encoding")?;
+        let storage_trie = mpt_from_proof(&nodes).context("invalid storage proof")?;
+        ensure!(
+            storage_trie.hash() == from_ethers_h256(proof.storage_hash),
+            "storage proof of {:?} does not match the storage root",
+            H256::from(key)
+        );
+        let value = storage_trie
+            .get_rlp::<U256>(&keccak(key))
+            .context("incomplete storage proof")?
+            .unwrap_or_default();
+        ensure!(
+            value == from_ethers_u256(slot.value),
+            "value of slot {:?} does not match the storage proof",
+            H256::from(key)
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the entries of the map sorted by key.
+fn sorted<K: Ord, V>(map: &HashMap<K, V>) -> Vec<(&K, &V)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by_key(|(key, _)| *key);
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::types::{Block, Bytes, StorageProof, Transaction, U64};
+    use zeth_primitives::trie::MptNode;
+
+    use super::*;
+    use crate::host::provider::{
+        test_queries::{account_query, address, proof_query, slot},
+        MutProvider, StorageQuery,
+    };
+
+    const BLOCK_NO: u64 = 1000;
+
+    /// Returns a cache containing everything needed to build `BLOCK_NO` with a single
+    /// account that has one storage slot.
+    fn cache() -> FileProvider {
+        let code = Bytes::from(vec![0x60, 0x00]);
+        let mut storage_trie = MptNode::default();
+        storage_trie
+            .insert_rlp(&keccak(slot(2)), U256::from(5))
+            .unwrap();
+        let account = StateAccount {
+            nonce: 1,
+            balance: U256::from(2),
+            storage_root: storage_trie.hash(),
+            code_hash: keccak(&code).into(),
+        };
+        let mut state_trie = MptNode::default();
+        state_trie.insert_rlp(&keccak(address(1)), account).unwrap();
+        for i in 2..10 {
+            let other = StateAccount {
+                nonce: i,
+                ..Default::default()
+            };
+            state_trie.insert_rlp(&keccak(address(i)), other).unwrap();
+        }
+        let state_root = H256::from(state_trie.hash().0);
+
+        let proof = EIP1186ProofResponse {
+            address: address(1),
+            balance: 2.into(),
+            code_hash: keccak(&code).into(),
+            nonce: U64::from(1),
+            storage_hash: H256::from(storage_trie.hash().0),
+            account_proof: state_trie
+                .prove(&keccak(address(1)))
+                .unwrap()
+                .into_iter()
+                .map(Bytes::from)
+                .collect(),
+            storage_proof: vec![StorageProof {
+                key: slot(2).as_bytes().into(),
+                value: 5.into(),
+                proof: storage_trie
+                    .prove(&keccak(slot(2)))
+                    .unwrap()
+                    .into_iter()
+                    .map(Bytes::from)
+                    .collect(),
+            }],
+        };
+
+        let mut cache = FileProvider::default();
+        cache.insert_partial_block(
+            BlockQuery {
+                block_no: BLOCK_NO - 1,
+                block_hash: None,
+            },
+            Block {
+                state_root,
+                ..Default::default()
+            },
+        );
+        cache.insert_full_block(
+            BlockQuery {
+                block_no: BLOCK_NO,
+                block_hash: None,
+            },
+            Block::<Transaction> {
+                state_root,
+                ..Default::default()
+            },
+        );
+        for block_no in [BLOCK_NO - 1, BLOCK_NO] {
+            cache.insert_proof(proof_query(block_no, 1, &[2]), proof.clone());
+        }
+        cache.insert_transaction_count(account_query(BLOCK_NO - 1, 1), 1.into());
+        cache.insert_balance(account_query(BLOCK_NO - 1, 1), 2.into());
+        cache.insert_code(account_query(BLOCK_NO - 1, 1), code);
+        cache.insert_storage(
+            StorageQuery {
+                block_no: BLOCK_NO - 1,
+                block_hash: None,
+                address: address(1),
+                index: slot(2),
+            },
+            H256::from_low_u64_be(5),
+        );
+
+        cache
+    }
+
+    #[test]
+    fn consistent_cache() {
+        let cache = cache();
+        assert_eq!(cache.validate(), Vec::<String>::new());
+        assert_eq!(cache.missing(BLOCK_NO), Vec::<String>::new());
+
+        let stats = cache.stats();
+        assert_eq!(stats.accounts, 2);
+        assert_eq!(stats.slots, 2);
+        assert_eq!(stats.code_bytes, 2);
+        assert!(stats.proof_nodes > 2);
+    }
+
+    #[test]
+    fn inconsistent_cache() {
+        let mut cache = cache();
+        cache.insert_balance(account_query(BLOCK_NO - 1, 1), 3.into());
+        let query = proof_query(BLOCK_NO, 1, &[2]);
+        let mut proof = cache.proofs[&query].clone();
+        proof.storage_proof[0].value = 6.into();
+        cache.insert_proof(query, proof);
+
+        let problems = cache.validate();
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(problems[0].contains("does not match the storage proof"));
+        assert!(problems[1].starts_with("balance"));
+
+        cache.proofs.clear();
+        assert_eq!(cache.missing(BLOCK_NO).len(), 2);
+        assert_eq!(
+            cache.missing(BLOCK_NO + 1),
+            vec![
+                format!("parent block {}", BLOCK_NO),
+                format!("block {}", BLOCK_NO + 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn prune() {
+        let mut cache = cache();
+        let pruned_len = cache.len();
+        cache.insert_partial_block(
+            BlockQuery {
+                block_no: BLOCK_NO - 1 - BLOCK_HASH_HISTORY,
+                block_hash: None,
+            },
+            Block::default(),
+        );
+        cache.insert_balance(
+            AccountQuery {
+                block_no: BLOCK_NO + 1,
+                block_hash: None,
+                address: address(1),
+            },
+            2.into(),
+        );
+        let superseded = proof_query(BLOCK_NO, 1, &[]);
+        cache.insert_proof(
+            superseded.clone(),
+            cache.proofs[&proof_query(BLOCK_NO, 1, &[2])].clone(),
+        );
+
+        assert_eq!(cache.prune(BLOCK_NO), 3);
+        assert_eq!(cache.len(), pruned_len);
+        assert!(!cache.proofs.contains_key(&superseded));
+        assert_eq!(cache.prune(BLOCK_NO), 0);
+    }
+
+    #[test]
+    fn block_hash_queries() {
+        let mut cache = cache();
+        let block_hash = H256::from_low_u64_be(1);
+        let mut query = proof_query(BLOCK_NO, 1, &[2]);
+        let proof = cache.proofs.remove(&query).unwrap();
+        query.block_hash = Some(block_hash);
+        cache.insert_proof(query, proof);
+
+        // the block was recorded without a hash, so it does not belong to the queried hash
+        let problems = cache.validate();
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].starts_with(&format!("no block {}", BLOCK_NO)));
+
+        let block_query = BlockQuery {
+            block_no: BLOCK_NO,
+            block_hash: None,
+        };
+        cache.full_blocks.get_mut(&block_query).unwrap().hash = Some(block_hash);
+        assert_eq!(cache.validate(), Vec::<String>::new());
+    }
+}
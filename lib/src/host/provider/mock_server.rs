@@ -0,0 +1,180 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A local JSON-RPC server for testing the RPC providers.
+
+use std::{
+    collections::VecDeque,
+    net::TcpListener as StdTcpListener,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::Result;
+use ethers_core::types::{EIP1186ProofResponse, StorageProof, H160, H256, U256, U64};
+use serde_json::{json, Value};
+use tokio::{
+    io::BufReader,
+    net::{TcpListener, TcpStream},
+};
+
+use super::cache_server::{read_request, write_response};
+
+/// A failure that is returned instead of the response to an HTTP request.
+#[derive(Clone, Copy, Debug)]
+pub enum Fault {
+    /// Respond with the given HTTP status code.
+    Status(u16),
+    /// Respond to every call of the request with the given JSON-RPC error code.
+    RpcError(i64),
+}
+
+#[derive(Default)]
+struct State {
+    batch_sizes: Mutex<Vec<usize>>,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+    faults: Mutex<VecDeque<Fault>>,
+    tampered: AtomicBool,
+}
+
+/// Serves canned responses for the methods used by the providers.
+///
+/// Batch responses are returned in reverse order and every request is delayed, so that
+/// concurrent requests overlap.
+pub struct MockServer {
+    url: String,
+    state: Arc<State>,
+}
+
+impl MockServer {
+    /// Starts the server on the current Tokio runtime.
+    pub fn start() -> Self {
+        let listener = StdTcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let listener = TcpListener::from_std(listener).unwrap();
+
+        let state = Arc::new(State::default());
+        let server_state = state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, server_state.clone()));
+            }
+        });
+
+        MockServer { url, state }
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// Returns the number of calls of each received HTTP request.
+    pub fn batch_sizes(&self) -> Vec<usize> {
+        self.state.batch_sizes.lock().unwrap().clone()
+    }
+
+    /// Returns the maximum number of HTTP requests that were handled concurrently.
+    pub fn max_in_flight(&self) -> usize {
+        self.state.max_in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Fails the next `count` HTTP requests with the given fault.
+    pub fn inject(&self, fault: Fault, count: usize) {
+        let mut faults = self.state.faults.lock().unwrap();
+        faults.extend(std::iter::repeat(fault).take(count));
+    }
+
+    /// Makes all further proofs differ from the ones of an untampered server.
+    pub fn tamper_proofs(&self) {
+        self.state.tampered.store(true, Ordering::SeqCst);
+    }
+}
+
+async fn serve(stream: TcpStream, state: Arc<State>) -> Result<()> {
+    let mut stream = BufReader::new(stream);
+    while let Some(body) = read_request(&mut stream).await? {
+        let in_flight = state.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        state.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let fault = state.faults.lock().unwrap().pop_front();
+        let tampered = state.tampered.load(Ordering::SeqCst);
+        let answer = |request: &Value| match fault {
+            Some(Fault::RpcError(code)) => error(request, code, "injected fault"),
+            _ => respond(request, tampered),
+        };
+        let response = match serde_json::from_slice::<Value>(&body)? {
+            Value::Array(requests) => {
+                state.batch_sizes.lock().unwrap().push(requests.len());
+                Value::Array(requests.iter().rev().map(answer).collect())
+            }
+            request => {
+                state.batch_sizes.lock().unwrap().push(1);
+                answer(&request)
+            }
+        };
+        state.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        let (status, body) = match fault {
+            Some(Fault::Status(status)) => (status, vec![]),
+            _ => (200, serde_json::to_vec(&response)?),
+        };
+        write_response(&mut stream, status, &body).await?;
+    }
+
+    Ok(())
+}
+
+fn respond(request: &Value, tampered: bool) -> Value {
+    let id = request["id"].clone();
+    let params = &request["params"];
+    let address = || serde_json::from_value::<H160>(params[0].clone()).unwrap();
+    let result = match request["method"].as_str().unwrap() {
+        "eth_getProof" => {
+            let keys: Vec<H256> = serde_json::from_value(params[1].clone()).unwrap();
+            let proof = EIP1186ProofResponse {
+                address: address(),
+                storage_proof: keys
+                    .iter()
+                    .map(|key| StorageProof {
+                        key: U256::from_big_endian(key.as_bytes()),
+                        ..Default::default()
+                    })
+                    .collect(),
+                nonce: U64::from(tampered as u64),
+                ..Default::default()
+            };
+            serde_json::to_value(proof).unwrap()
+        }
+        "eth_getTransactionCount" => json!(U256::one()),
+        "eth_getBalance" => json!(U256::from(address().to_low_u64_be())),
+        "eth_getCode" => json!("0x42"),
+        method => return error(request, -32601, &format!("{} not found", method)),
+    };
+
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error(request: &Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "error": { "code": code, "message": message },
+    })
+}
@@ -12,32 +12,71 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::BTreeSet, path::PathBuf};
+use std::{collections::BTreeSet, hash::Hash, path::PathBuf};
 
 use anyhow::{anyhow, Result};
 use ethers_core::types::{
     Block, Bytes, EIP1186ProofResponse, Transaction, TransactionReceipt, H160, H256, U256,
 };
 use serde::{Deserialize, Serialize};
+use zeth_primitives::receipt::Receipt;
 
+#[cfg(feature = "alloy")]
+pub mod alloy_rpc_provider;
+pub mod cache_server;
 pub mod cached_rpc_provider;
 pub mod file_provider;
+pub mod inspect;
+#[cfg(test)]
+mod mock_server;
+pub mod range_provider;
 pub mod rpc_provider;
+pub mod store_provider;
+#[cfg(test)]
+mod test_queries;
+
+pub use rpc_provider::RpcConfig;
+
+/// Location of the local cache of a provider.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CachePath {
+    /// Gzipped JSON file containing all the data of a single block.
+    File(PathBuf),
+    /// Directory of a content-addressed store shared by all blocks, opened for building
+    /// the given block.
+    Store(PathBuf, u64),
+}
+
+impl From<PathBuf> for CachePath {
+    fn from(file_path: PathBuf) -> Self {
+        CachePath::File(file_path)
+    }
+}
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
 pub struct AccountQuery {
     pub block_no: u64,
+    /// Optional hash of the block (EIP-1898), so that data of a block that was reorged
+    /// out is never mistaken for the canonical one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<H256>,
     pub address: H160,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Deserialize, Serialize)]
 pub struct BlockQuery {
     pub block_no: u64,
+    /// Optional hash of the block, see [AccountQuery::block_hash].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<H256>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Deserialize, Serialize)]
 pub struct ProofQuery {
     pub block_no: u64,
+    /// Optional hash of the block, see [AccountQuery::block_hash].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<H256>,
     pub address: H160,
     pub indices: BTreeSet<H256>,
 }
@@ -45,10 +84,81 @@ pub struct ProofQuery {
 #[derive(Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Deserialize, Serialize)]
 pub struct StorageQuery {
     pub block_no: u64,
+    /// Optional hash of the block, see [AccountQuery::block_hash].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<H256>,
     pub address: H160,
     pub index: H256,
 }
 
+/// Common accessors of the queries for data of a specific block.
+pub trait Query: Clone + Eq + Hash {
+    fn block_no(&self) -> u64;
+    fn block_hash(&self) -> Option<H256>;
+    /// Returns the same query without the block hash.
+    fn without_block_hash(&self) -> Self;
+}
+
+impl Query for AccountQuery {
+    fn block_no(&self) -> u64 {
+        self.block_no
+    }
+    fn block_hash(&self) -> Option<H256> {
+        self.block_hash
+    }
+    fn without_block_hash(&self) -> Self {
+        AccountQuery {
+            block_hash: None,
+            ..self.clone()
+        }
+    }
+}
+
+impl Query for BlockQuery {
+    fn block_no(&self) -> u64 {
+        self.block_no
+    }
+    fn block_hash(&self) -> Option<H256> {
+        self.block_hash
+    }
+    fn without_block_hash(&self) -> Self {
+        BlockQuery {
+            block_hash: None,
+            ..self.clone()
+        }
+    }
+}
+
+impl Query for ProofQuery {
+    fn block_no(&self) -> u64 {
+        self.block_no
+    }
+    fn block_hash(&self) -> Option<H256> {
+        self.block_hash
+    }
+    fn without_block_hash(&self) -> Self {
+        ProofQuery {
+            block_hash: None,
+            ..self.clone()
+        }
+    }
+}
+
+impl Query for StorageQuery {
+    fn block_no(&self) -> u64 {
+        self.block_no
+    }
+    fn block_hash(&self) -> Option<H256> {
+        self.block_hash
+    }
+    fn without_block_hash(&self) -> Self {
+        StorageQuery {
+            block_hash: None,
+            ..self.clone()
+        }
+    }
+}
+
 pub trait Provider: Send {
     fn save(&self) -> Result<()>;
 
@@ -59,6 +169,28 @@ pub trait Provider: Send {
     fn get_transaction_count(&mut self, query: &AccountQuery) -> Result<U256>;
     fn get_balance(&mut self, query: &AccountQuery) -> Result<U256>;
     fn get_code(&mut self, query: &AccountQuery) -> Result<Bytes>;
+
+    /// Returns the proofs for all the queries in the same order.
+    /// By default, the proofs are fetched one after another.
+    fn get_proofs(&mut self, queries: &[ProofQuery]) -> Result<Vec<EIP1186ProofResponse>> {
+        queries.iter().map(|query| self.get_proof(query)).collect()
+    }
+    /// Returns the transaction count, balance and code of an account.
+    fn get_account(&mut self, query: &AccountQuery) -> Result<(U256, U256, Bytes)> {
+        Ok((
+            self.get_transaction_count(query)?,
+            self.get_balance(query)?,
+            self.get_code(query)?,
+        ))
+    }
+    /// Returns the receipts of a block as the local [Receipt] type.
+    /// By default, the receipts are converted from the [TransactionReceipt] type.
+    fn get_receipts(&mut self, query: &BlockQuery) -> Result<Vec<Receipt>> {
+        self.get_block_receipts(query)?
+            .into_iter()
+            .map(Receipt::try_from)
+            .collect()
+    }
     fn get_all_codes(&self) -> std::collections::hash_map::Iter<AccountQuery, Bytes>;
     fn get_storage(&mut self, query: &StorageQuery) -> Result<H256>;
 }
@@ -80,26 +212,64 @@ pub fn new_file_provider(file_path: PathBuf) -> Result<Box<dyn Provider>> {
     Ok(Box::new(provider))
 }
 
-pub fn new_rpc_provider(rpc_url: String) -> Result<Box<dyn Provider>> {
-    let provider = rpc_provider::RpcProvider::new(rpc_url)?;
+pub fn new_store_provider(store_path: PathBuf, block_no: u64) -> Result<Box<dyn Provider>> {
+    let provider = store_provider::StoreProvider::new(store_path, block_no)?;
 
     Ok(Box::new(provider))
 }
 
-pub fn new_cached_rpc_provider(cache_path: PathBuf, rpc_url: String) -> Result<Box<dyn Provider>> {
-    let provider = cached_rpc_provider::CachedRpcProvider::new(cache_path, rpc_url)?;
+pub fn new_rpc_provider(rpc_config: RpcConfig) -> Result<Box<dyn Provider>> {
+    if rpc_config.alloy {
+        #[cfg(feature = "alloy")]
+        return Ok(Box::new(alloy_rpc_provider::AlloyRpcProvider::new(
+            rpc_config,
+        )?));
+        #[cfg(not(feature = "alloy"))]
+        return Err(anyhow!(
+            "the alloy RPC provider requires the `alloy` feature"
+        ));
+    }
+    let provider = rpc_provider::RpcProvider::new(rpc_config)?;
+
+    Ok(Box::new(provider))
+}
+
+pub fn new_cached_rpc_provider(
+    cache_path: PathBuf,
+    rpc_config: RpcConfig,
+) -> Result<Box<dyn Provider>> {
+    let provider = cached_rpc_provider::CachedRpcProvider::new(cache_path, rpc_config)?;
+
+    Ok(Box::new(provider))
+}
+
+pub fn new_cached_store_rpc_provider(
+    store_path: PathBuf,
+    block_no: u64,
+    rpc_config: RpcConfig,
+) -> Result<Box<dyn Provider>> {
+    let cache = store_provider::StoreProvider::new(store_path, block_no)?;
+    let provider = cached_rpc_provider::CachedRpcProvider::with_cache(cache, rpc_config)?;
 
     Ok(Box::new(provider))
 }
 
 pub fn new_provider(
-    cache_path: Option<PathBuf>,
-    rpc_url: Option<String>,
+    cache_path: Option<CachePath>,
+    rpc_config: Option<RpcConfig>,
 ) -> Result<Box<dyn Provider>> {
-    match (cache_path, rpc_url) {
-        (Some(cache_path), Some(rpc_url)) => new_cached_rpc_provider(cache_path, rpc_url),
-        (Some(cache_path), None) => new_file_provider(cache_path),
-        (None, Some(rpc_url)) => new_rpc_provider(rpc_url),
+    match (cache_path, rpc_config) {
+        (Some(CachePath::File(cache_path)), Some(rpc_config)) => {
+            new_cached_rpc_provider(cache_path, rpc_config)
+        }
+        (Some(CachePath::Store(store_path, block_no)), Some(rpc_config)) => {
+            new_cached_store_rpc_provider(store_path, block_no, rpc_config)
+        }
+        (Some(CachePath::File(cache_path)), None) => new_file_provider(cache_path),
+        (Some(CachePath::Store(store_path, block_no)), None) => {
+            new_store_provider(store_path, block_no)
+        }
+        (None, Some(rpc_config)) => new_rpc_provider(rpc_config),
         (None, None) => Err(anyhow!("No cache_path or rpc_url given")),
     }
 }
@@ -0,0 +1,417 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A provider for the preflight of consecutive blocks, which answers the queries for the
+//! state before a block from the data fetched for the previous block.
+
+use std::{
+    collections::{hash_map::Iter, BTreeMap, BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result};
+use ethers_core::types::{
+    Block, Bytes, EIP1186ProofResponse, Transaction, TransactionReceipt, H160, H256, U256,
+};
+use zeth_primitives::keccak::{keccak, KECCAK_EMPTY};
+
+use super::{
+    inspect::BLOCK_HASH_HISTORY, AccountQuery, BlockQuery, ProofQuery, Provider, Query,
+    StorageQuery,
+};
+
+/// The data known after the preflight of a block, which is shared by the [RangeProvider]s
+/// of the following blocks.
+#[derive(Debug, Default)]
+pub struct RollingState {
+    /// Number and hash of the last block.
+    block: Option<(u64, H256)>,
+    /// Proofs of all the accounts and slots accessed by the last block, after the block.
+    proofs: HashMap<H160, EIP1186ProofResponse>,
+    /// Code of all the accounts seen so far by its hash.
+    code: HashMap<H256, Bytes>,
+    /// Partial blocks whose hash can still be accessed.
+    blocks: BTreeMap<u64, Block<H256>>,
+    /// Number of queries answered from this state instead of the provider.
+    reused: usize,
+}
+
+impl RollingState {
+    /// Records the state after the block `block_no`, given by the proofs of the accessed
+    /// accounts after that block and their code.
+    pub fn advance(
+        &mut self,
+        block_no: u64,
+        block_hash: H256,
+        proofs: impl IntoIterator<Item = (H160, EIP1186ProofResponse)>,
+        code: impl IntoIterator<Item = Bytes>,
+    ) {
+        self.block = Some((block_no, block_hash));
+        self.proofs = proofs.into_iter().collect();
+        for code in code {
+            self.code.insert(keccak(&code).into(), code);
+        }
+        // only the blocks accessible by the next block are kept
+        self.blocks = self
+            .blocks
+            .split_off(&block_no.saturating_sub(BLOCK_HASH_HISTORY));
+    }
+
+    /// Returns the number of queries answered from the state and resets it.
+    pub fn take_reused(&mut self) -> usize {
+        std::mem::take(&mut self.reused)
+    }
+
+    /// Returns the proof of the account, if the query is for the state after the last
+    /// block.
+    fn proof(&self, query: &impl Query, address: &H160) -> Option<&EIP1186ProofResponse> {
+        let (block_no, block_hash) = self.block?;
+        if query.block_no() != block_no || query.block_hash().is_some_and(|h| h != block_hash) {
+            return None;
+        }
+        self.proofs.get(address)
+    }
+
+    fn code(&self, code_hash: H256) -> Option<Bytes> {
+        if code_hash.is_zero() || code_hash.0 == KECCAK_EMPTY.0 {
+            return Some(Bytes::new());
+        }
+        self.code.get(&code_hash).cloned()
+    }
+}
+
+/// A [Provider] that answers queries from the [RollingState] and forwards all others to
+/// the inner provider.
+pub struct RangeProvider {
+    inner: Box<dyn Provider>,
+    state: Arc<Mutex<RollingState>>,
+    /// The code of the inner provider together with the known code of the last block.
+    all_codes: HashMap<AccountQuery, Bytes>,
+}
+
+impl RangeProvider {
+    pub fn new(inner: Box<dyn Provider>, state: Arc<Mutex<RollingState>>) -> Self {
+        let mut all_codes: HashMap<_, _> = inner
+            .get_all_codes()
+            .map(|(query, code)| (query.clone(), code.clone()))
+            .collect();
+        {
+            let state = state.lock().unwrap();
+            if let Some((block_no, _)) = state.block {
+                for (address, proof) in &state.proofs {
+                    if let Some(code) = state.code(proof.code_hash) {
+                        let query = AccountQuery {
+                            block_no,
+                            block_hash: None,
+                            address: *address,
+                        };
+                        all_codes.entry(query).or_insert(code);
+                    }
+                }
+            }
+        }
+
+        RangeProvider {
+            inner,
+            state,
+            all_codes,
+        }
+    }
+
+    /// Returns the result of `f` for the known proof of the account, if any.
+    fn known<T>(
+        &self,
+        query: &AccountQuery,
+        f: impl FnOnce(&RollingState, &EIP1186ProofResponse) -> Option<T>,
+    ) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        let result = f(&state, state.proof(query, &query.address)?)?;
+        state.reused += 1;
+        Some(result)
+    }
+}
+
+fn slot_key(index: &H256) -> U256 {
+    U256::from_big_endian(index.as_bytes())
+}
+
+impl Provider for RangeProvider {
+    fn save(&self) -> Result<()> {
+        self.inner.save()
+    }
+
+    fn get_full_block(&mut self, query: &BlockQuery) -> Result<Block<Transaction>> {
+        self.inner.get_full_block(query)
+    }
+
+    fn get_partial_block(&mut self, query: &BlockQuery) -> Result<Block<H256>> {
+        let mut state = self.state.lock().unwrap();
+        let known = state
+            .blocks
+            .get(&query.block_no)
+            .filter(|block| query.block_hash.is_none() || block.hash == query.block_hash)
+            .cloned();
+        if let Some(block) = known {
+            state.reused += 1;
+            return Ok(block);
+        }
+
+        let block = self.inner.get_partial_block(query)?;
+        state.blocks.insert(query.block_no, block.clone());
+        Ok(block)
+    }
+
+    fn get_block_receipts(&mut self, query: &BlockQuery) -> Result<Vec<TransactionReceipt>> {
+        self.inner.get_block_receipts(query)
+    }
+
+    fn get_proof(&mut self, query: &ProofQuery) -> Result<EIP1186ProofResponse> {
+        let mut proofs = self.get_proofs(std::slice::from_ref(query))?;
+        proofs.pop().context("missing proof")
+    }
+
+    fn get_proofs(&mut self, queries: &[ProofQuery]) -> Result<Vec<EIP1186ProofResponse>> {
+        // use the known proofs and only query the slots that are not known
+        let mut proofs = Vec::with_capacity(queries.len());
+        let mut missing = Vec::new();
+        {
+            let mut state = self.state.lock().unwrap();
+            for (i, query) in queries.iter().enumerate() {
+                let Some(known) = state.proof(query, &query.address) else {
+                    proofs.push(None);
+                    missing.push((i, query.clone()));
+                    continue;
+                };
+
+                let keys: BTreeSet<_> = query.indices.iter().map(slot_key).collect();
+                let mut proof = known.clone();
+                proof.storage_proof.retain(|slot| keys.contains(&slot.key));
+                let unknown: BTreeSet<_> = query
+                    .indices
+                    .iter()
+                    .filter(|index| !proof.storage_proof.iter().any(|s| s.key == slot_key(index)))
+                    .copied()
+                    .collect();
+                if unknown.is_empty() {
+                    state.reused += 1;
+                } else {
+                    let query = ProofQuery {
+                        indices: unknown,
+                        ..query.clone()
+                    };
+                    missing.push((i, query));
+                }
+                proofs.push(Some(proof));
+            }
+        }
+
+        if !missing.is_empty() {
+            let missing_queries: Vec<_> = missing.iter().map(|(_, query)| query.clone()).collect();
+            let fetched = self.inner.get_proofs(&missing_queries)?;
+            for ((i, _), fetched) in missing.into_iter().zip(fetched) {
+                match &mut proofs[i] {
+                    Some(proof) => {
+                        // the account proof is the same, as both refer to the same block
+                        proof.storage_proof.extend(fetched.storage_proof);
+                        proof.storage_proof.sort_by_key(|slot| slot.key);
+                    }
+                    None => proofs[i] = Some(fetched),
+                }
+            }
+        }
+
+        proofs
+            .into_iter()
+            .map(|proof| proof.context("missing proof"))
+            .collect()
+    }
+
+    fn get_transaction_count(&mut self, query: &AccountQuery) -> Result<U256> {
+        match self.known(query, |_, proof| Some(U256::from(proof.nonce.as_u64()))) {
+            Some(nonce) => Ok(nonce),
+            None => self.inner.get_transaction_count(query),
+        }
+    }
+
+    fn get_balance(&mut self, query: &AccountQuery) -> Result<U256> {
+        match self.known(query, |_, proof| Some(proof.balance)) {
+            Some(balance) => Ok(balance),
+            None => self.inner.get_balance(query),
+        }
+    }
+
+    fn get_code(&mut self, query: &AccountQuery) -> Result<Bytes> {
+        match self.known(query, |state, proof| state.code(proof.code_hash)) {
+            Some(code) => Ok(code),
+            None => self.inner.get_code(query),
+        }
+    }
+
+    fn get_all_codes(&self) -> Iter<AccountQuery, Bytes> {
+        self.all_codes.iter()
+    }
+
+    fn get_storage(&mut self, query: &StorageQuery) -> Result<H256> {
+        let account = AccountQuery {
+            block_no: query.block_no,
+            block_hash: query.block_hash,
+            address: query.address,
+        };
+        let key = slot_key(&query.index);
+        let value = self.known(&account, |_, proof| {
+            let slot = proof.storage_proof.iter().find(|slot| slot.key == key)?;
+            let mut value = H256::zero();
+            slot.value.to_big_endian(value.as_bytes_mut());
+            Some(value)
+        });
+        match value {
+            Some(value) => Ok(value),
+            None => self.inner.get_storage(query),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::types::{StorageProof, U64};
+
+    use super::{super::file_provider::FileProvider, *};
+    use crate::host::provider::MutProvider;
+
+    const BLOCK_NO: u64 = 10;
+
+    fn proof(address: H160, keys: &[u64]) -> EIP1186ProofResponse {
+        EIP1186ProofResponse {
+            address,
+            balance: U256::from(1),
+            nonce: U64::from(2),
+            storage_hash: H256::repeat_byte(3),
+            code_hash: keccak([0x60, 0x00]).into(),
+            account_proof: vec![vec![0xaa; 40].into()],
+            storage_proof: keys
+                .iter()
+                .map(|&key| StorageProof {
+                    key: U256::from(key),
+                    proof: vec![vec![key as u8; 40].into()],
+                    value: U256::from(key + 1),
+                })
+                .collect(),
+        }
+    }
+
+    fn provider(inner: FileProvider) -> (RangeProvider, Arc<Mutex<RollingState>>) {
+        let state = Arc::new(Mutex::new(RollingState::default()));
+        state.lock().unwrap().advance(
+            BLOCK_NO,
+            H256::from_low_u64_be(BLOCK_NO),
+            [(
+                H160::from_low_u64_be(1),
+                proof(H160::from_low_u64_be(1), &[1, 2]),
+            )],
+            [Bytes::from(vec![0x60, 0x00])],
+        );
+        (RangeProvider::new(Box::new(inner), state.clone()), state)
+    }
+
+    #[test]
+    fn known_state() {
+        let (mut provider, state) = provider(FileProvider::default());
+        let address = H160::from_low_u64_be(1);
+        let query = AccountQuery {
+            block_no: BLOCK_NO,
+            block_hash: Some(H256::from_low_u64_be(BLOCK_NO)),
+            address,
+        };
+        let (nonce, balance, code) = provider.get_account(&query).unwrap();
+        assert_eq!((nonce, balance), (U256::from(2), U256::from(1)));
+        assert_eq!(code, Bytes::from(vec![0x60, 0x00]));
+        let storage = provider
+            .get_storage(&StorageQuery {
+                block_no: BLOCK_NO,
+                block_hash: None,
+                address,
+                index: H256::from_low_u64_be(2),
+            })
+            .unwrap();
+        assert_eq!(storage, H256::from_low_u64_be(3));
+        assert_eq!(state.lock().unwrap().take_reused(), 4);
+        assert_eq!(provider.get_all_codes().count(), 1);
+
+        // queries for other blocks, unknown accounts or unknown slots are forwarded
+        let other_block = AccountQuery {
+            block_hash: Some(H256::from_low_u64_be(BLOCK_NO + 1)),
+            ..query.clone()
+        };
+        assert!(provider.get_balance(&other_block).is_err());
+        let other_account = AccountQuery {
+            address: H160::from_low_u64_be(2),
+            ..query
+        };
+        assert!(provider.get_balance(&other_account).is_err());
+        let unknown_slot = StorageQuery {
+            block_no: BLOCK_NO,
+            block_hash: None,
+            address,
+            index: H256::from_low_u64_be(3),
+        };
+        assert!(provider.get_storage(&unknown_slot).is_err());
+        assert_eq!(state.lock().unwrap().take_reused(), 0);
+    }
+
+    #[test]
+    fn partial_proofs() {
+        let address = H160::from_low_u64_be(1);
+        let proof_query = |keys: &[u64]| ProofQuery {
+            block_no: BLOCK_NO,
+            block_hash: None,
+            address,
+            indices: keys.iter().map(|&key| H256::from_low_u64_be(key)).collect(),
+        };
+        let mut inner = FileProvider::default();
+        inner.insert_proof(proof_query(&[0, 3]), proof(address, &[0, 3]));
+        let (mut provider, _) = provider(inner);
+
+        // known slots are taken from the state, only the others are fetched
+        let proof = provider.get_proof(&proof_query(&[0, 2, 3])).unwrap();
+        let keys: Vec<_> = proof.storage_proof.iter().map(|slot| slot.key).collect();
+        assert_eq!(keys, [0, 2, 3].map(U256::from));
+
+        let proof = provider.get_proof(&proof_query(&[1])).unwrap();
+        assert_eq!(proof.storage_proof.len(), 1);
+        assert!(provider.get_proof(&proof_query(&[4])).is_err());
+    }
+
+    #[test]
+    fn block_window() {
+        let block_query = |block_no| BlockQuery {
+            block_no,
+            block_hash: None,
+        };
+        let mut inner = FileProvider::default();
+        for block_no in [1, BLOCK_NO] {
+            inner.insert_partial_block(block_query(block_no), Block::default());
+        }
+        let (mut provider, state) = provider(inner);
+        provider.get_partial_block(&block_query(1)).unwrap();
+        provider.get_partial_block(&block_query(BLOCK_NO)).unwrap();
+        assert_eq!(state.lock().unwrap().blocks.len(), 2);
+
+        // blocks are answered from the window, and old blocks are dropped
+        provider.get_partial_block(&block_query(BLOCK_NO)).unwrap();
+        assert_eq!(state.lock().unwrap().take_reused(), 1);
+        let next = BLOCK_HASH_HISTORY + 2;
+        state.lock().unwrap().advance(next, H256::zero(), [], []);
+        assert_eq!(state.lock().unwrap().blocks.len(), 1);
+    }
+}
@@ -12,32 +12,192 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::hash_map::Iter;
-use anyhow::{anyhow, Result};
+use std::{
+    collections::hash_map::Iter,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use ethers_core::types::{
-    Block, Bytes, EIP1186ProofResponse, Transaction, TransactionReceipt, H256, U256,
+    Block, BlockId, Bytes, EIP1186ProofResponse, Transaction, TransactionReceipt, H256, U256, U64,
 };
-use ethers_providers::{Http, Middleware, RetryClient};
-use log::debug;
+use log::{debug, warn};
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{sync::Semaphore, task::JoinSet};
+
+use super::{AccountQuery, BlockQuery, ProofQuery, Provider, Query, StorageQuery};
+
+/// Default maximum number of calls that are combined into one JSON-RPC batch request.
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+/// Default maximum number of batch requests that are in flight at the same time.
+pub const DEFAULT_MAX_CONCURRENCY: usize = 8;
+
+/// Default number of times a failed request is retried on the same endpoint.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+/// Default backoff before a failed request is retried for the first time.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// JSON-RPC error code returned by nodes when a request limit has been exceeded.
+const LIMIT_EXCEEDED: i64 = -32005;
+
+/// Configuration of the JSON-RPC endpoints queried by the [RpcProvider].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RpcConfig {
+    /// URLs of the endpoints in order of preference. A request that fails on one
+    /// endpoint is sent to the next one.
+    pub urls: Vec<String>,
+    /// Number of times a transient failure, i.e. a network error or a rate limit, is
+    /// retried on the same endpoint before failing over to the next one.
+    pub max_retries: u32,
+    /// Backoff before the first retry; it doubles with every further retry.
+    pub initial_backoff: Duration,
+    /// Whether proofs are fetched from two different endpoints and rejected if the
+    /// responses do not match.
+    pub cross_check: bool,
+    /// Whether the responses are decoded into the `alloy_rpc_types` by the
+    /// [AlloyRpcProvider](super::alloy_rpc_provider::AlloyRpcProvider), which requires
+    /// the `alloy` feature.
+    pub alloy: bool,
+}
+
+impl RpcConfig {
+    pub fn new(urls: Vec<String>) -> Self {
+        RpcConfig {
+            urls,
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            cross_check: false,
+            alloy: false,
+        }
+    }
 
-use super::{AccountQuery, BlockQuery, ProofQuery, Provider, StorageQuery};
+    /// Sets the number of retries per endpoint and the backoff before the first retry.
+    pub fn with_retries(mut self, max_retries: u32, initial_backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Sets whether proofs are cross-checked between two endpoints.
+    pub fn with_cross_check(mut self, cross_check: bool) -> Self {
+        self.cross_check = cross_check;
+        self
+    }
+
+    /// Sets whether the responses are decoded into the `alloy_rpc_types`.
+    pub fn with_alloy(mut self, alloy: bool) -> Self {
+        self.alloy = alloy;
+        self
+    }
+}
+
+impl From<String> for RpcConfig {
+    fn from(rpc_url: String) -> Self {
+        RpcConfig::new(vec![rpc_url])
+    }
+}
 
 pub struct RpcProvider {
-    http_client: ethers_providers::Provider<RetryClient<Http>>,
+    client: RpcClient,
+    batch_size: usize,
+    max_concurrency: usize,
+    cross_check: bool,
     tokio_handle: tokio::runtime::Handle,
 }
 
 impl RpcProvider {
-    pub fn new(rpc_url: String) -> Result<Self> {
-        let http_client =
-            ethers_providers::Provider::<RetryClient<Http>>::new_client(&rpc_url, 3, 500)?;
+    pub fn new(config: impl Into<RpcConfig>) -> Result<Self> {
+        let config = config.into();
+        if config.cross_check && config.urls.len() < 2 {
+            bail!("cross-checking requires at least two RPC URLs");
+        }
+        let client = RpcClient::new(&config)?;
         let tokio_handle = tokio::runtime::Handle::current();
 
         Ok(RpcProvider {
-            http_client,
+            client,
+            batch_size: DEFAULT_BATCH_SIZE,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            cross_check: config.cross_check,
             tokio_handle,
         })
     }
+
+    /// Sets the maximum number of calls per batch request and the maximum number of
+    /// batch requests that are sent concurrently.
+    pub fn with_batching(mut self, batch_size: usize, max_concurrency: usize) -> Self {
+        assert!(batch_size > 0 && max_concurrency > 0);
+        self.batch_size = batch_size;
+        self.max_concurrency = max_concurrency;
+        self
+    }
+
+    pub(super) fn call<R: DeserializeOwned>(&self, call: Call) -> Result<R> {
+        let result = self.tokio_handle.block_on(self.client.call(&call))?;
+        serde_json::from_value(result).with_context(|| format!("invalid {} response", call.method))
+    }
+
+    /// Executes all the calls and returns their results in the same order.
+    ///
+    /// The calls are split into batch requests of at most `batch_size` calls, of which at
+    /// most `max_concurrency` are sent concurrently. When cross-checking, each batch is
+    /// sent to a second endpoint as well, and the results of both endpoints must match.
+    pub(super) fn batch_call<R>(&self, calls: Vec<Call>, cross_check: bool) -> Result<Vec<R>>
+    where
+        R: DeserializeOwned + PartialEq + Send + 'static,
+    {
+        let batches: Vec<Vec<Call>> = calls
+            .chunks(self.batch_size)
+            .map(<[Call]>::to_vec)
+            .collect();
+        let batch_count = batches.len();
+
+        let results = self.tokio_handle.block_on(async {
+            let semaphore = Arc::new(Semaphore::new(self.max_concurrency));
+            let mut tasks = JoinSet::new();
+            for (i, batch) in batches.into_iter().enumerate() {
+                let client = self.client.clone();
+                let semaphore = semaphore.clone();
+                tasks.spawn(async move {
+                    let _permit = semaphore.acquire_owned().await?;
+                    let (endpoint, results) = client.batch(&batch, None).await?;
+                    let results: Vec<R> = from_values(&batch, results)?;
+                    if cross_check {
+                        let (other, other_results) = client.batch(&batch, Some(endpoint)).await?;
+                        let other_results: Vec<R> = from_values(&batch, other_results)?;
+                        for (call, (result, other_result)) in
+                            batch.iter().zip(results.iter().zip(&other_results))
+                        {
+                            ensure!(
+                                result == other_result,
+                                "{} responses of {} and {} do not match for {}",
+                                call.method,
+                                client.url(endpoint),
+                                client.url(other),
+                                call.params
+                            );
+                        }
+                    }
+                    Ok::<_, anyhow::Error>((i, results))
+                });
+            }
+
+            let mut results: Vec<Vec<R>> = (0..batch_count).map(|_| vec![]).collect();
+            while let Some(task) = tasks.join_next().await {
+                let (i, batch_results) = task??;
+                results[i] = batch_results;
+            }
+            Ok::<_, anyhow::Error>(results)
+        })?;
+
+        Ok(results.into_iter().flatten().collect())
+    }
 }
 
 impl Provider for RpcProvider {
@@ -48,12 +208,11 @@ impl Provider for RpcProvider {
     fn get_full_block(&mut self, query: &BlockQuery) -> Result<Block<Transaction>> {
         debug!("Querying RPC for full block: {:?}", query);
 
-        let response = self
-            .tokio_handle
-            .block_on(self.http_client.get_block_with_txs(query.block_no))?;
+        let response: Option<Block<_>> = self.call(Call::block(query, true))?;
 
         match response {
-            Some(out) => Ok(out),
+            Some(out) if out.number == Some(query.block_no.into()) => Ok(out),
+            Some(out) => Err(anyhow!("{:?} returned block {:?}", query, out.number)),
             None => Err(anyhow!("No data for {:?}", query)),
         }
     }
@@ -61,12 +220,11 @@ impl Provider for RpcProvider {
     fn get_partial_block(&mut self, query: &BlockQuery) -> Result<Block<H256>> {
         debug!("Querying RPC for partial block: {:?}", query);
 
-        let response = self
-            .tokio_handle
-            .block_on(self.http_client.get_block(query.block_no))?;
+        let response: Option<Block<_>> = self.call(Call::block(query, false))?;
 
         match response {
-            Some(out) => Ok(out),
+            Some(out) if out.number == Some(query.block_no.into()) => Ok(out),
+            Some(out) => Err(anyhow!("{:?} returned block {:?}", query, out.number)),
             None => Err(anyhow!("No data for {:?}", query)),
         }
     }
@@ -74,56 +232,61 @@ impl Provider for RpcProvider {
     fn get_block_receipts(&mut self, query: &BlockQuery) -> Result<Vec<TransactionReceipt>> {
         debug!("Querying RPC for block receipts: {:?}", query);
 
-        let response = self
-            .tokio_handle
-            .block_on(self.http_client.get_block_receipts(query.block_no))?;
+        let response: Option<_> =
+            self.call(Call::new("eth_getBlockReceipts", json!([block_id(query)])))?;
 
-        Ok(response)
+        Ok(response.unwrap_or_default())
     }
 
     fn get_proof(&mut self, query: &ProofQuery) -> Result<EIP1186ProofResponse> {
         debug!("Querying RPC for inclusion proof: {:?}", query);
 
-        let out = self.tokio_handle.block_on(self.http_client.get_proof(
-            query.address,
-            query.indices.iter().cloned().collect(),
-            Some(query.block_no.into()),
-        ))?;
+        let mut proofs = self.batch_call(vec![Call::proof(query)], self.cross_check)?;
+        Ok(proofs.remove(0))
+    }
 
-        Ok(out)
+    fn get_proofs(&mut self, queries: &[ProofQuery]) -> Result<Vec<EIP1186ProofResponse>> {
+        debug!("Querying RPC for {} inclusion proofs", queries.len());
+
+        self.batch_call(queries.iter().map(Call::proof).collect(), self.cross_check)
     }
 
     fn get_transaction_count(&mut self, query: &AccountQuery) -> Result<U256> {
         debug!("Querying RPC for transaction count: {:?}", query);
 
-        let out = self.tokio_handle.block_on(
-            self.http_client
-                .get_transaction_count(query.address, Some(query.block_no.into())),
-        )?;
-
-        Ok(out)
+        self.call(Call::account("eth_getTransactionCount", query))
     }
 
     fn get_balance(&mut self, query: &AccountQuery) -> Result<U256> {
         debug!("Querying RPC for balance: {:?}", query);
 
-        let out = self.tokio_handle.block_on(
-            self.http_client
-                .get_balance(query.address, Some(query.block_no.into())),
-        )?;
-
-        Ok(out)
+        self.call(Call::account("eth_getBalance", query))
     }
 
     fn get_code(&mut self, query: &AccountQuery) -> Result<Bytes> {
         debug!("Querying RPC for code: {:?}", query);
 
-        let out = self.tokio_handle.block_on(
-            self.http_client
-                .get_code(query.address, Some(query.block_no.into())),
-        )?;
+        self.call(Call::account("eth_getCode", query))
+    }
+
+    fn get_account(&mut self, query: &AccountQuery) -> Result<(U256, U256, Bytes)> {
+        debug!("Querying RPC for account: {:?}", query);
+
+        let calls = vec![
+            Call::account("eth_getTransactionCount", query),
+            Call::account("eth_getBalance", query),
+            Call::account("eth_getCode", query),
+        ];
+        let (_, results) = self
+            .tokio_handle
+            .block_on(self.client.batch(&calls, None))?;
+        let [nonce, balance, code]: [Value; 3] = results.try_into().unwrap();
 
-        Ok(out)
+        Ok((
+            serde_json::from_value(nonce).context("invalid nonce")?,
+            serde_json::from_value(balance).context("invalid balance")?,
+            serde_json::from_value(code).context("invalid code")?,
+        ))
     }
 
     fn get_all_codes(&self) -> Iter<AccountQuery, Bytes> {
@@ -133,12 +296,432 @@ impl Provider for RpcProvider {
     fn get_storage(&mut self, query: &StorageQuery) -> Result<H256> {
         debug!("Querying RPC for storage: {:?}", query);
 
-        let out = self.tokio_handle.block_on(self.http_client.get_storage_at(
-            query.address,
-            query.index,
-            Some(query.block_no.into()),
-        ))?;
+        self.call(Call::new(
+            "eth_getStorageAt",
+            json!([query.address, query.index, block_id(query)]),
+        ))
+    }
+}
+
+/// A single JSON-RPC method call.
+#[derive(Clone, Debug)]
+pub(super) struct Call {
+    method: &'static str,
+    params: Value,
+}
+
+impl Call {
+    pub(super) fn new(method: &'static str, params: Value) -> Self {
+        Call { method, params }
+    }
+
+    pub(super) fn block(query: &BlockQuery, full: bool) -> Self {
+        match query.block_hash {
+            Some(hash) => Call::new("eth_getBlockByHash", json!([hash, full])),
+            None => Call::new(
+                "eth_getBlockByNumber",
+                json!([U64::from(query.block_no), full]),
+            ),
+        }
+    }
+
+    fn account(method: &'static str, query: &AccountQuery) -> Self {
+        Call::new(method, json!([query.address, block_id(query)]))
+    }
+
+    pub(super) fn proof(query: &ProofQuery) -> Self {
+        Call::new(
+            "eth_getProof",
+            json!([query.address, query.indices, block_id(query)]),
+        )
+    }
+}
+
+/// Returns the block parameter of the query, which is serialized as an EIP-1898 block
+/// hash object if the hash is known.
+pub(super) fn block_id(query: &impl Query) -> BlockId {
+    match query.block_hash() {
+        Some(hash) => BlockId::Hash(hash),
+        None => BlockId::Number(query.block_no().into()),
+    }
+}
+
+#[derive(Serialize)]
+struct Request<'a> {
+    jsonrpc: &'static str,
+    id: usize,
+    method: &'a str,
+    params: &'a Value,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    id: usize,
+    #[serde(default)]
+    result: Value,
+    error: Option<ErrorObject>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorObject {
+    code: i64,
+    message: String,
+}
+
+/// Minimal JSON-RPC client over HTTP supporting batch requests and multiple endpoints.
+#[derive(Clone)]
+struct RpcClient {
+    http_client: reqwest::Client,
+    urls: Arc<[reqwest::Url]>,
+    /// Index of the endpoint that answered the last request; it is tried first.
+    active: Arc<AtomicUsize>,
+    max_retries: u32,
+    initial_backoff: Duration,
+}
+
+/// Failure of a single attempt to send a request to an endpoint.
+enum Failure {
+    /// The request may succeed when it is retried, e.g. after a network error or a rate
+    /// limit.
+    Transient(anyhow::Error),
+    /// The endpoint rejected the request.
+    Permanent(anyhow::Error),
+}
+
+impl RpcClient {
+    fn new(config: &RpcConfig) -> Result<Self> {
+        if config.urls.is_empty() {
+            bail!("no RPC URL given");
+        }
+        let urls = config
+            .urls
+            .iter()
+            .map(|url| {
+                url.parse()
+                    .with_context(|| format!("invalid RPC URL: {}", url))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(RpcClient {
+            http_client: reqwest::Client::new(),
+            urls,
+            active: Arc::new(AtomicUsize::new(0)),
+            max_retries: config.max_retries,
+            initial_backoff: config.initial_backoff,
+        })
+    }
+
+    fn url(&self, endpoint: usize) -> &reqwest::Url {
+        &self.urls[endpoint]
+    }
+
+    /// Sends a single call and returns its result.
+    async fn call(&self, call: &Call) -> Result<Value> {
+        let request = Request {
+            jsonrpc: "2.0",
+            id: 0,
+            method: call.method,
+            params: &call.params,
+        };
+        let (_, response) = self.send(&request, None).await?;
+        let response: Response =
+            serde_json::from_value(response).context("invalid JSON-RPC response")?;
+        into_result(call, response)
+    }
+
+    /// Sends all calls in one batch request and returns the index of the endpoint that
+    /// answered together with the results in the same order as the calls.
+    ///
+    /// If `skip` is set, the request is not sent to the endpoint with that index.
+    async fn batch(&self, calls: &[Call], skip: Option<usize>) -> Result<(usize, Vec<Value>)> {
+        let requests: Vec<_> = calls
+            .iter()
+            .enumerate()
+            .map(|(id, call)| Request {
+                jsonrpc: "2.0",
+                id,
+                method: call.method,
+                params: &call.params,
+            })
+            .collect();
+        let (endpoint, responses) = self.send(&requests, skip).await?;
+        let responses: Vec<Response> =
+            serde_json::from_value(responses).context("invalid JSON-RPC batch response")?;
+        if responses.len() != calls.len() {
+            bail!(
+                "invalid batch response: expected {} responses, got {}",
+                calls.len(),
+                responses.len()
+            );
+        }
+
+        // the responses of a batch can be returned in any order
+        let mut results = vec![None; calls.len()];
+        for response in responses {
+            let id = response.id;
+            let call = calls.get(id).context("invalid response id")?;
+            results[id] = Some(into_result(call, response)?);
+        }
+        let results = results
+            .into_iter()
+            .map(|result| result.context("duplicate response id"))
+            .collect::<Result<_>>()?;
+
+        Ok((endpoint, results))
+    }
+
+    /// Posts the request to the endpoints in turn, starting with the one that answered
+    /// last, until one of them succeeds. Returns the index of that endpoint and its
+    /// response.
+    async fn send<T: Serialize>(&self, request: &T, skip: Option<usize>) -> Result<(usize, Value)> {
+        let start = self.active.load(Ordering::Relaxed);
+        let mut last_err = None;
+        for endpoint in (0..self.urls.len())
+            .map(|i| (start + i) % self.urls.len())
+            .filter(|&endpoint| Some(endpoint) != skip)
+        {
+            match self.send_to(endpoint, request).await {
+                Ok(response) => {
+                    self.active.store(endpoint, Ordering::Relaxed);
+                    return Ok((endpoint, response));
+                }
+                Err(err) => {
+                    warn!("RPC request to {} failed: {:#}", self.url(endpoint), err);
+                    last_err = Some(err);
+                }
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| anyhow!("no other RPC endpoint available")))
+    }
+
+    /// Posts the request to one endpoint, retrying transient failures with exponential
+    /// backoff.
+    async fn send_to<T: Serialize>(&self, endpoint: usize, request: &T) -> Result<Value> {
+        let mut backoff = self.initial_backoff;
+        let mut retries = 0;
+        loop {
+            match self.try_send(endpoint, request).await {
+                Ok(response) => return Ok(response),
+                Err(Failure::Transient(err)) if retries < self.max_retries => {
+                    debug!("RPC request failed, retrying in {:?}: {:#}", backoff, err);
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    retries += 1;
+                }
+                Err(Failure::Transient(err) | Failure::Permanent(err)) => return Err(err),
+            }
+        }
+    }
+
+    async fn try_send<T: Serialize>(&self, endpoint: usize, request: &T) -> Result<Value, Failure> {
+        let response = self
+            .http_client
+            .post(self.url(endpoint).clone())
+            .json(request)
+            .send()
+            .await
+            .map_err(|err| Failure::Transient(err.into()))?;
+
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            return Err(Failure::Transient(anyhow!("HTTP status {}", status)));
+        }
+        if !status.is_success() {
+            return Err(Failure::Permanent(anyhow!("HTTP status {}", status)));
+        }
+
+        let response: Value = response
+            .json()
+            .await
+            .map_err(|err| Failure::Transient(err.into()))?;
+        if let Some(error) = rate_limit_error(&response) {
+            return Err(Failure::Transient(anyhow!("rate limited: {}", error)));
+        }
+
+        Ok(response)
+    }
+}
+
+/// Returns the error of the response, or of any response in a batch, that signals that
+/// a rate limit has been exceeded.
+fn rate_limit_error(response: &Value) -> Option<&Value> {
+    let is_rate_limit = |response: &Value| {
+        matches!(
+            response["error"]["code"].as_i64(),
+            Some(LIMIT_EXCEEDED | 429)
+        )
+    };
+    let limited = match response {
+        Value::Array(responses) => responses.iter().find(|response| is_rate_limit(response)),
+        response => Some(response).filter(|response| is_rate_limit(response)),
+    };
+    limited.map(|response| &response["error"])
+}
+
+fn into_result(call: &Call, response: Response) -> Result<Value> {
+    match response.error {
+        Some(err) => Err(anyhow!(
+            "{} failed with error {}: {}",
+            call.method,
+            err.code,
+            err.message
+        )),
+        None => Ok(response.result),
+    }
+}
+
+fn from_values<R: DeserializeOwned>(calls: &[Call], results: Vec<Value>) -> Result<Vec<R>> {
+    calls
+        .iter()
+        .zip(results)
+        .map(|(call, result)| {
+            serde_json::from_value(result)
+                .with_context(|| format!("invalid {} response", call.method))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::types::H160;
+
+    use super::*;
+    use crate::host::provider::{
+        mock_server::{Fault, MockServer},
+        test_queries::{account_query, proof_query},
+    };
+
+    fn config(servers: &[&MockServer]) -> RpcConfig {
+        RpcConfig::new(servers.iter().map(|server| server.url()).collect())
+            .with_retries(2, Duration::from_millis(1))
+    }
+
+    #[test]
+    fn batched_proofs() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let server = MockServer::start();
+        let mut provider = RpcProvider::new(server.url()).unwrap().with_batching(4, 2);
+
+        let queries: Vec<_> = (0..10).map(|i| proof_query(1, i, &[i])).collect();
+        let proofs = provider.get_proofs(&queries).unwrap();
+        for (query, proof) in queries.iter().zip(&proofs) {
+            assert_eq!(proof.address, query.address);
+            assert_eq!(
+                proof.storage_proof[0].key,
+                U256::from_big_endian(query.indices.first().unwrap().as_bytes())
+            );
+        }
+
+        let mut batch_sizes = server.batch_sizes();
+        batch_sizes.sort_unstable();
+        assert_eq!(batch_sizes, vec![2, 4, 4]);
+        assert!(server.max_in_flight() <= 2);
+    }
+
+    #[test]
+    fn batched_account() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let server = MockServer::start();
+        let mut provider = RpcProvider::new(server.url()).unwrap();
+
+        let query = account_query(1, 42);
+        let (nonce, balance, code) = provider.get_account(&query).unwrap();
+        assert_eq!(nonce, U256::from(1));
+        assert_eq!(balance, U256::from(42));
+        assert_eq!(code, Bytes::from(vec![0x42]));
+        assert_eq!(server.batch_sizes(), vec![3]);
+
+        assert_eq!(provider.get_balance(&query).unwrap(), U256::from(42));
+        assert_eq!(server.batch_sizes(), vec![3, 1]);
+    }
+
+    #[test]
+    fn rpc_error() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let server = MockServer::start();
+        let mut provider = RpcProvider::new(server.url()).unwrap();
+
+        let query = StorageQuery {
+            block_no: 1,
+            block_hash: None,
+            address: H160::zero(),
+            index: H256::zero(),
+        };
+        let err = provider.get_storage(&query).unwrap_err();
+        assert!(err.to_string().contains("eth_getStorageAt"));
+        // errors that are not rate limits must not be retried
+        assert_eq!(server.batch_sizes(), vec![1]);
+    }
+
+    #[test]
+    fn retry_rate_limits() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let server = MockServer::start();
+        let mut provider = RpcProvider::new(config(&[&server])).unwrap();
+
+        server.inject(Fault::Status(429), 1);
+        server.inject(Fault::RpcError(LIMIT_EXCEEDED), 1);
+        let (_, balance, _) = provider.get_account(&account_query(1, 42)).unwrap();
+        assert_eq!(balance, U256::from(42));
+        assert_eq!(server.batch_sizes(), vec![3, 3, 3]);
+
+        server.inject(Fault::Status(503), 3);
+        let err = provider.get_balance(&account_query(1, 42)).unwrap_err();
+        assert!(err.to_string().contains("503"));
+        assert_eq!(server.batch_sizes(), vec![3, 3, 3, 1, 1, 1]);
+    }
+
+    #[test]
+    fn failover() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let failing = MockServer::start();
+        let server = MockServer::start();
+        let mut provider = RpcProvider::new(config(&[&failing, &server])).unwrap();
+
+        failing.inject(Fault::Status(502), 3);
+        assert_eq!(
+            provider.get_balance(&account_query(1, 42)).unwrap(),
+            U256::from(42)
+        );
+        assert_eq!(failing.batch_sizes(), vec![1, 1, 1]);
+        assert_eq!(server.batch_sizes(), vec![1]);
+
+        // further requests go to the endpoint that answered last
+        provider.get_balance(&account_query(1, 42)).unwrap();
+        assert_eq!(failing.batch_sizes(), vec![1, 1, 1]);
+        assert_eq!(server.batch_sizes(), vec![1, 1]);
+
+        // permanent failures are not retried
+        server.inject(Fault::Status(401), 1);
+        provider.get_balance(&account_query(1, 42)).unwrap();
+        assert_eq!(server.batch_sizes(), vec![1, 1, 1]);
+        assert_eq!(failing.batch_sizes(), vec![1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn cross_check() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let first = MockServer::start();
+        let second = MockServer::start();
+        assert!(RpcProvider::new(config(&[&first]).with_cross_check(true)).is_err());
+        let mut provider =
+            RpcProvider::new(config(&[&first, &second]).with_cross_check(true)).unwrap();
+
+        let queries: Vec<_> = (0..3).map(|i| proof_query(1, i, &[i])).collect();
+        provider.get_proofs(&queries).unwrap();
+        provider.get_proof(&queries[0]).unwrap();
+        assert_eq!(first.batch_sizes(), vec![3, 1]);
+        assert_eq!(second.batch_sizes(), vec![3, 1]);
 
-        Ok(out)
+        second.tamper_proofs();
+        let err = provider.get_proofs(&queries).unwrap_err();
+        assert!(err.to_string().contains("do not match"));
     }
 }
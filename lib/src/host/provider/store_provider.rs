@@ -0,0 +1,787 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A content-addressed on-disk cache that is shared by all blocks of a network.
+//!
+//! The store has the following layout:
+//! - `code/<xx>/<hash>`: contract bytecode by its hash,
+//! - `nodes/<xx>/<hash>`: RLP-encoded trie nodes of the proofs by their hash,
+//! - `blocks/<block_no>.json.gz`: blocks and receipts by block number,
+//! - `accounts/<block_no>/<address>.json`: the index of an account at a block, which
+//!   references the code and nodes by hash and contains the index of each storage slot.
+//!
+//! Bytecode and trie nodes are written once and never modified, so they are shared
+//! between all blocks. On [Provider::save] only new content and the modified indexes are
+//! written.
+
+use std::{
+    collections::{hash_map::Iter, BTreeMap, HashMap, HashSet},
+    fmt::Debug,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use ethers_core::types::{
+    Block, Bytes, EIP1186ProofResponse, StorageProof, Transaction, TransactionReceipt, H160, H256,
+    U256, U64,
+};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use log::warn;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use zeth_primitives::keccak::keccak;
+
+use super::{
+    file_provider::FileProvider, AccountQuery, BlockQuery, MutProvider, ProofQuery, Provider,
+    Query, StorageQuery,
+};
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+struct BlockIndex {
+    /// Hash of the block the data was recorded for, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    block_hash: Option<H256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    full_block: Option<Block<Transaction>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    partial_block: Option<Block<H256>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    receipts: Option<Vec<TransactionReceipt>>,
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+struct AccountIndex {
+    /// Hash of the block the data was recorded for, see [BlockIndex::block_hash].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    block_hash: Option<H256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    transaction_count: Option<U256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    balance: Option<U256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code_hash: Option<H256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    proof: Option<AccountProof>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    storage: BTreeMap<H256, SlotIndex>,
+}
+
+/// The account fields of an EIP-1186 proof with the hashes of the proof nodes.
+#[derive(Clone, Deserialize, Serialize)]
+struct AccountProof {
+    nonce: U64,
+    balance: U256,
+    code_hash: H256,
+    storage_hash: H256,
+    nodes: Vec<H256>,
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+struct SlotIndex {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<H256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    proof: Option<SlotProof>,
+}
+
+/// The storage value of an EIP-1186 proof with the hashes of the proof nodes.
+#[derive(Clone, Deserialize, Serialize)]
+struct SlotProof {
+    value: U256,
+    nodes: Vec<H256>,
+}
+
+/// A [Provider] backed by a content-addressed store.
+///
+/// The provider is opened for a certain block. The indexes of that block and its parent
+/// are read when the provider is created, and the code of all their accounts is loaded,
+/// so that [Provider::get_all_codes] behaves like for a [FileProvider] of that block. The
+/// indexes of other blocks are read lazily when they are first queried.
+pub struct StoreProvider {
+    root: PathBuf,
+    blocks: HashMap<u64, BlockIndex>,
+    accounts: HashMap<(u64, H160), AccountIndex>,
+    code: HashMap<AccountQuery, Bytes>,
+    new_content: HashMap<PathBuf, Bytes>,
+    /// The indexes modified since the last [Provider::save].
+    dirty_blocks: Mutex<HashSet<u64>>,
+    dirty_accounts: Mutex<HashSet<(u64, H160)>>,
+}
+
+impl StoreProvider {
+    /// Opens the store at `root` for building the block `block_no`. The directory is
+    /// created when the store is saved.
+    pub fn new(root: PathBuf, block_no: u64) -> Result<Self> {
+        let mut provider = StoreProvider {
+            root,
+            blocks: HashMap::new(),
+            accounts: HashMap::new(),
+            code: HashMap::new(),
+            new_content: HashMap::new(),
+            dirty_blocks: Mutex::new(HashSet::new()),
+            dirty_accounts: Mutex::new(HashSet::new()),
+        };
+        for block_no in [block_no.saturating_sub(1), block_no] {
+            provider.block(block_no)?;
+            for address in provider.indexed_accounts(block_no)? {
+                provider.account(block_no, address)?;
+                let query = AccountQuery {
+                    block_no,
+                    block_hash: None,
+                    address,
+                };
+                if let Ok(code) = provider.get_code(&query) {
+                    provider.code.insert(query, code);
+                }
+            }
+        }
+
+        Ok(provider)
+    }
+
+    /// Returns the addresses of all the accounts that are indexed at the given block.
+    fn indexed_accounts(&self, block_no: u64) -> Result<Vec<H160>> {
+        let dir = self.root.join("accounts").join(block_no.to_string());
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err).with_context(|| format!("failed to read {:?}", dir)),
+        };
+
+        let mut addresses = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(address) = path.file_stem().and_then(|s| s.to_str()) {
+                    addresses.push(address.parse().context("invalid account index name")?);
+                }
+            }
+        }
+        Ok(addresses)
+    }
+
+    fn block_path(&self, block_no: u64) -> PathBuf {
+        self.root
+            .join("blocks")
+            .join(block_no.to_string())
+            .with_extension("json.gz")
+    }
+
+    fn account_path(&self, block_no: u64, address: &H160) -> PathBuf {
+        self.root
+            .join("accounts")
+            .join(block_no.to_string())
+            .join(format!("{:x}.json", address))
+    }
+
+    fn content_path(&self, kind: &str, hash: &H256) -> PathBuf {
+        let hash = format!("{:x}", hash);
+        self.root.join(kind).join(&hash[..2]).join(hash)
+    }
+
+    fn block(&mut self, block_no: u64) -> Result<&mut BlockIndex> {
+        if !self.blocks.contains_key(&block_no) {
+            let path = self.block_path(block_no);
+            let index = read_json(&path, true)?.unwrap_or_default();
+            self.blocks.insert(block_no, index);
+        }
+        Ok(self.blocks.get_mut(&block_no).unwrap())
+    }
+
+    fn account(&mut self, block_no: u64, address: H160) -> Result<&mut AccountIndex> {
+        let key = (block_no, address);
+        if !self.accounts.contains_key(&key) {
+            let path = self.account_path(block_no, &address);
+            let index = read_json(&path, false)?.unwrap_or_default();
+            self.accounts.insert(key, index);
+        }
+        Ok(self.accounts.get_mut(&key).unwrap())
+    }
+
+    /// Returns the index of the queried block, unless it was recorded for a different
+    /// hash.
+    fn queried_block(&mut self, query: &BlockQuery) -> Result<&BlockIndex> {
+        let index = self.block(query.block_no)?;
+        if !same_block(index.block_hash, query.block_hash) {
+            bail!("No data for {:?}", query);
+        }
+        Ok(index)
+    }
+
+    /// Returns the index of the account at the queried block, unless it was recorded for
+    /// a different hash.
+    fn queried_account<Q: Query + Debug>(
+        &mut self,
+        query: &Q,
+        address: H160,
+    ) -> Result<&AccountIndex> {
+        let index = self.account(query.block_no(), address)?;
+        if !same_block(index.block_hash, query.block_hash()) {
+            bail!("No data for {:?}", query);
+        }
+        Ok(index)
+    }
+
+    /// Returns the index of the queried block for modification. An index recorded for a
+    /// different hash belongs to a block that was reorged out and is discarded, like an
+    /// index that cannot be read.
+    fn block_mut(&mut self, query: &BlockQuery) -> &mut BlockIndex {
+        self.dirty_blocks.get_mut().unwrap().insert(query.block_no);
+        if let Some(err) = self.block(query.block_no).err() {
+            warn!(
+                "Discarding the index of block {}: {:#}",
+                query.block_no, err
+            );
+            self.blocks.insert(query.block_no, BlockIndex::default());
+        }
+        let index = self.blocks.get_mut(&query.block_no).unwrap();
+        if !same_block(index.block_hash, query.block_hash) {
+            *index = BlockIndex::default();
+        }
+        index.block_hash = index.block_hash.or(query.block_hash);
+        index
+    }
+
+    /// Returns the index of the account at the queried block for modification, see
+    /// [StoreProvider::block_mut].
+    fn account_mut(&mut self, query: &impl Query, address: H160) -> &mut AccountIndex {
+        let key = (query.block_no(), address);
+        self.dirty_accounts.get_mut().unwrap().insert(key);
+        if let Some(err) = self.account(key.0, address).err() {
+            warn!(
+                "Discarding the index of {:?} at block {}: {:#}",
+                address, key.0, err
+            );
+            self.accounts.insert(key, AccountIndex::default());
+        }
+        let index = self.accounts.get_mut(&key).unwrap();
+        if !same_block(index.block_hash, query.block_hash()) {
+            *index = AccountIndex::default();
+        }
+        index.block_hash = index.block_hash.or(query.block_hash());
+        index
+    }
+
+    /// Adds the content to the store and returns its hash.
+    fn put(&mut self, kind: &str, content: Bytes) -> H256 {
+        let hash = H256(keccak(&content));
+        let path = self.content_path(kind, &hash);
+        self.new_content.entry(path).or_insert(content);
+        hash
+    }
+
+    /// Returns the content with the given hash.
+    fn get(&self, kind: &str, hash: &H256) -> Result<Bytes> {
+        let path = self.content_path(kind, hash);
+        if let Some(content) = self.new_content.get(&path) {
+            return Ok(content.clone());
+        }
+        let content = fs::read(&path).with_context(|| format!("missing {} {:?}", kind, hash))?;
+        Ok(content.into())
+    }
+
+    fn get_nodes(&self, hashes: &[H256]) -> Result<Vec<Bytes>> {
+        hashes.iter().map(|hash| self.get("nodes", hash)).collect()
+    }
+
+    fn put_nodes(&mut self, nodes: Vec<Bytes>) -> Vec<H256> {
+        nodes
+            .into_iter()
+            .map(|node| self.put("nodes", node))
+            .collect()
+    }
+}
+
+impl Provider for StoreProvider {
+    fn save(&self) -> Result<()> {
+        // write the content before the indexes referencing it
+        for (path, content) in &self.new_content {
+            if !path.exists() {
+                write_atomic(path, content)?;
+            }
+        }
+        let mut dirty_blocks = self.dirty_blocks.lock().unwrap();
+        for block_no in dirty_blocks.iter() {
+            let mut encoder = GzEncoder::new(vec![], Compression::best());
+            serde_json::to_writer(&mut encoder, &self.blocks[block_no])?;
+            write_atomic(&self.block_path(*block_no), &encoder.finish()?)?;
+        }
+        dirty_blocks.clear();
+        let mut dirty_accounts = self.dirty_accounts.lock().unwrap();
+        for key @ (block_no, address) in dirty_accounts.iter() {
+            let index = serde_json::to_vec(&self.accounts[key])?;
+            write_atomic(&self.account_path(*block_no, address), &index)?;
+        }
+        dirty_accounts.clear();
+
+        Ok(())
+    }
+
+    fn get_full_block(&mut self, query: &BlockQuery) -> Result<Block<Transaction>> {
+        let index = self.queried_block(query)?;
+        index
+            .full_block
+            .clone()
+            .filter(|block| same_block(block.hash, query.block_hash))
+            .ok_or_else(|| anyhow!("No data for {:?}", query))
+    }
+
+    fn get_partial_block(&mut self, query: &BlockQuery) -> Result<Block<H256>> {
+        let index = self.queried_block(query)?;
+        index
+            .partial_block
+            .clone()
+            .filter(|block| same_block(block.hash, query.block_hash))
+            .ok_or_else(|| anyhow!("No data for {:?}", query))
+    }
+
+    fn get_block_receipts(&mut self, query: &BlockQuery) -> Result<Vec<TransactionReceipt>> {
+        let index = self.queried_block(query)?;
+        index
+            .receipts
+            .clone()
+            .ok_or_else(|| anyhow!("No data for {:?}", query))
+    }
+
+    fn get_proof(&mut self, query: &ProofQuery) -> Result<EIP1186ProofResponse> {
+        let index = self.queried_account(query, query.address)?.clone();
+        let no_data = || anyhow!("No data for {:?}", query);
+        let proof = index.proof.ok_or_else(no_data)?;
+
+        let mut storage_proof = Vec::with_capacity(query.indices.len());
+        for key in &query.indices {
+            let slot_proof = index
+                .storage
+                .get(key)
+                .and_then(|slot| slot.proof.as_ref())
+                .ok_or_else(no_data)?;
+            storage_proof.push(StorageProof {
+                key: U256::from_big_endian(key.as_bytes()),
+                proof: self.get_nodes(&slot_proof.nodes)?,
+                value: slot_proof.value,
+            });
+        }
+
+        Ok(EIP1186ProofResponse {
+            address: query.address,
+            balance: proof.balance,
+            code_hash: proof.code_hash,
+            nonce: proof.nonce,
+            storage_hash: proof.storage_hash,
+            account_proof: self.get_nodes(&proof.nodes)?,
+            storage_proof,
+        })
+    }
+
+    fn get_transaction_count(&mut self, query: &AccountQuery) -> Result<U256> {
+        let index = self.queried_account(query, query.address)?;
+        index
+            .transaction_count
+            .ok_or_else(|| anyhow!("No data for {:?}", query))
+    }
+
+    fn get_balance(&mut self, query: &AccountQuery) -> Result<U256> {
+        let index = self.queried_account(query, query.address)?;
+        index
+            .balance
+            .ok_or_else(|| anyhow!("No data for {:?}", query))
+    }
+
+    fn get_code(&mut self, query: &AccountQuery) -> Result<Bytes> {
+        let index = self.queried_account(query, query.address)?;
+        let code_hash = index
+            .code_hash
+            .ok_or_else(|| anyhow!("No data for {:?}", query))?;
+        self.get("code", &code_hash)
+    }
+
+    fn get_all_codes(&self) -> Iter<AccountQuery, Bytes> {
+        self.code.iter()
+    }
+
+    fn get_storage(&mut self, query: &StorageQuery) -> Result<H256> {
+        let index = self.queried_account(query, query.address)?;
+        index
+            .storage
+            .get(&query.index)
+            .and_then(|slot| slot.value)
+            .ok_or_else(|| anyhow!("No data for {:?}", query))
+    }
+}
+
+impl MutProvider for StoreProvider {
+    fn insert_full_block(&mut self, query: BlockQuery, val: Block<Transaction>) {
+        self.block_mut(&query).full_block = Some(val);
+    }
+
+    fn insert_partial_block(&mut self, query: BlockQuery, val: Block<H256>) {
+        self.block_mut(&query).partial_block = Some(val);
+    }
+
+    fn insert_block_receipts(&mut self, query: BlockQuery, val: Vec<TransactionReceipt>) {
+        self.block_mut(&query).receipts = Some(val);
+    }
+
+    fn insert_proof(&mut self, query: ProofQuery, val: EIP1186ProofResponse) {
+        let account_proof = AccountProof {
+            nonce: val.nonce,
+            balance: val.balance,
+            code_hash: val.code_hash,
+            storage_hash: val.storage_hash,
+            nodes: self.put_nodes(val.account_proof),
+        };
+        let slot_proofs: Vec<_> = val
+            .storage_proof
+            .into_iter()
+            .map(|proof| {
+                let mut key = H256::zero();
+                proof.key.to_big_endian(key.as_bytes_mut());
+                let slot_proof = SlotProof {
+                    value: proof.value,
+                    nodes: self.put_nodes(proof.proof),
+                };
+                (key, slot_proof)
+            })
+            .collect();
+
+        let index = self.account_mut(&query, query.address);
+        index.proof = Some(account_proof);
+        for (key, slot_proof) in slot_proofs {
+            index.storage.entry(key).or_default().proof = Some(slot_proof);
+        }
+    }
+
+    fn insert_transaction_count(&mut self, query: AccountQuery, val: U256) {
+        self.account_mut(&query, query.address).transaction_count = Some(val);
+    }
+
+    fn insert_balance(&mut self, query: AccountQuery, val: U256) {
+        self.account_mut(&query, query.address).balance = Some(val);
+    }
+
+    fn insert_code(&mut self, query: AccountQuery, val: Bytes) {
+        let code_hash = self.put("code", val.clone());
+        self.account_mut(&query, query.address).code_hash = Some(code_hash);
+        self.code.insert(query, val);
+    }
+
+    fn insert_storage(&mut self, query: StorageQuery, val: H256) {
+        let index = self.account_mut(&query, query.address);
+        index.storage.entry(query.index).or_default().value = Some(val);
+    }
+}
+
+/// Returns whether data recorded for the block hash `recorded` can answer a query for the
+/// hash `queried`; unknown hashes match any hash.
+fn same_block(recorded: Option<H256>, queried: Option<H256>) -> bool {
+    recorded.is_none() || queried.is_none() || recorded == queried
+}
+
+/// Reads and deserializes the JSON file, returning `None` if it does not exist.
+fn read_json<T: DeserializeOwned>(path: &Path, gzip: bool) -> Result<Option<T>> {
+    let f = match File::open(path) {
+        Ok(f) => f,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).with_context(|| format!("failed to open {:?}", path)),
+    };
+    let value = if gzip {
+        serde_json::from_reader(GzDecoder::new(f))
+    } else {
+        serde_json::from_reader(io::BufReader::new(f))
+    };
+    let value = value.with_context(|| format!("invalid index {:?}", path))?;
+    Ok(Some(value))
+}
+
+/// Writes the file by renaming a temporary file, so that concurrent readers never see
+/// a partially written file.
+fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let dir = path.parent().context("invalid path")?;
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {:?}", dir))?;
+    let tmp_path = path.with_extension(format!("tmp{}", std::process::id()));
+    let mut f =
+        File::create(&tmp_path).with_context(|| format!("failed to create {:?}", tmp_path))?;
+    f.write_all(content)?;
+    f.sync_all()?;
+    fs::rename(&tmp_path, path).with_context(|| format!("failed to write {:?}", path))?;
+    Ok(())
+}
+
+/// Copies all the data of the [FileProvider] cache file of the block `block_no` into
+/// the store at `store_path`.
+pub fn import_file(store_path: PathBuf, file_path: PathBuf, block_no: u64) -> Result<()> {
+    if !file_path.exists() {
+        bail!("cache file {:?} does not exist", file_path);
+    }
+    let file = FileProvider::new(file_path)?;
+    let mut store = StoreProvider::new(store_path, block_no)?;
+    file.copy_into(&mut store);
+    store.save()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::provider::test_queries::proof_query;
+
+    fn count_files(dir: &Path) -> usize {
+        fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .map(|entry| entry.unwrap().path())
+                    .map(|path| if path.is_dir() { count_files(&path) } else { 1 })
+                    .sum()
+            })
+            .unwrap_or_default()
+    }
+
+    fn proof(address: H160, account_proof: &[&[u8]], keys: &[u64]) -> EIP1186ProofResponse {
+        EIP1186ProofResponse {
+            address,
+            balance: U256::from(1),
+            nonce: U64::from(2),
+            storage_hash: H256::repeat_byte(3),
+            code_hash: H256::repeat_byte(4),
+            account_proof: account_proof
+                .iter()
+                .map(|node| node.to_vec().into())
+                .collect(),
+            storage_proof: keys
+                .iter()
+                .map(|&key| StorageProof {
+                    key: U256::from(key),
+                    proof: vec![vec![key as u8; 40].into()],
+                    value: U256::from(key + 1),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn shared_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let address = H160::from_low_u64_be(1);
+        let code = Bytes::from(vec![0x60; 100]);
+        let shared_node: &[u8] = &[0xaa; 50];
+
+        let mut store = StoreProvider::new(dir.path().to_path_buf(), 2).unwrap();
+        for block_no in [1, 2] {
+            let node: &[u8] = &[block_no as u8; 50];
+            store.insert_proof(
+                proof_query(block_no, 1, &[1, 2]),
+                proof(address, &[shared_node, node], &[1, 2]),
+            );
+            let query = AccountQuery {
+                block_no,
+                block_hash: None,
+                address,
+            };
+            store.insert_code(query.clone(), code.clone());
+            store.insert_balance(query.clone(), U256::from(block_no));
+            store.insert_transaction_count(query, U256::from(block_no));
+            store.insert_storage(
+                StorageQuery {
+                    block_no,
+                    block_hash: None,
+                    address,
+                    index: H256::from_low_u64_be(1),
+                },
+                H256::from_low_u64_be(block_no),
+            );
+        }
+        let block = Block::<H256> {
+            number: Some(U64::from(2)),
+            ..Default::default()
+        };
+        store.insert_partial_block(
+            BlockQuery {
+                block_no: 2,
+                block_hash: None,
+            },
+            block.clone(),
+        );
+        store.save().unwrap();
+
+        // code and nodes are only stored once
+        assert_eq!(count_files(&dir.path().join("code")), 1);
+        assert_eq!(count_files(&dir.path().join("nodes")), 5);
+
+        let mut store = StoreProvider::new(dir.path().to_path_buf(), 2).unwrap();
+        assert_eq!(store.get_all_codes().count(), 2);
+        for block_no in [1, 2] {
+            let node: &[u8] = &[block_no as u8; 50];
+            let query = AccountQuery {
+                block_no,
+                block_hash: None,
+                address,
+            };
+            assert_eq!(
+                store.get_proof(&proof_query(block_no, 1, &[1, 2])).unwrap(),
+                proof(address, &[shared_node, node], &[1, 2])
+            );
+            assert_eq!(
+                store.get_account(&query).unwrap(),
+                (U256::from(block_no), U256::from(block_no), code.clone())
+            );
+        }
+        assert_eq!(
+            store
+                .get_partial_block(&BlockQuery {
+                    block_no: 2,
+                    block_hash: None,
+                })
+                .unwrap(),
+            block
+        );
+
+        // any subset of the proven storage slots can be queried
+        assert_eq!(
+            store.get_proof(&proof_query(1, 1, &[2])).unwrap(),
+            proof(address, &[shared_node, &[1; 50]], &[2])
+        );
+        assert!(store.get_proof(&proof_query(1, 1, &[3])).is_err());
+        assert!(store.get_proof(&proof_query(3, 1, &[])).is_err());
+        assert!(store
+            .get_full_block(&BlockQuery {
+                block_no: 2,
+                block_hash: None,
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn import() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("ethereum").join("2.json.gz");
+        let store_path = dir.path().join("ethereum").join("store");
+        let address = H160::from_low_u64_be(1);
+        let query = proof_query(1, 1, &[1]);
+
+        let mut file = FileProvider::new(file_path.clone()).unwrap();
+        file.insert_proof(query.clone(), proof(address, &[&[0xaa; 50]], &[1]));
+        file.insert_code(
+            AccountQuery {
+                block_no: 1,
+                block_hash: None,
+                address,
+            },
+            Bytes::from(vec![1]),
+        );
+        file.insert_storage(
+            StorageQuery {
+                block_no: 2,
+                block_hash: None,
+                address,
+                index: H256::zero(),
+            },
+            H256::repeat_byte(1),
+        );
+        file.save().unwrap();
+
+        import_file(store_path.clone(), file_path, 2).unwrap();
+        let mut store = StoreProvider::new(store_path, 2).unwrap();
+        assert_eq!(
+            store.get_proof(&query).unwrap(),
+            file.get_proof(&query).unwrap()
+        );
+        assert_eq!(
+            store.get_all_codes().collect::<Vec<_>>(),
+            file.get_all_codes().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            store
+                .get_storage(&StorageQuery {
+                    block_no: 2,
+                    block_hash: None,
+                    address,
+                    index: H256::zero(),
+                })
+                .unwrap(),
+            H256::repeat_byte(1)
+        );
+
+        assert!(import_file(
+            dir.path().join("missing"),
+            dir.path().join("missing.json.gz"),
+            2
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn reorg() {
+        let dir = tempfile::tempdir().unwrap();
+        let address = H160::from_low_u64_be(1);
+        let query = |block_hash: u64| AccountQuery {
+            block_no: 1,
+            block_hash: Some(H256::from_low_u64_be(block_hash)),
+            address,
+        };
+
+        let mut store = StoreProvider::new(dir.path().to_path_buf(), 1).unwrap();
+        store.insert_balance(query(1), U256::from(1));
+        store.insert_transaction_count(query(1), U256::from(1));
+        store.save().unwrap();
+
+        let mut store = StoreProvider::new(dir.path().to_path_buf(), 1).unwrap();
+        assert_eq!(store.get_balance(&query(1)).unwrap(), U256::from(1));
+        assert!(store.get_balance(&query(2)).is_err());
+        let unknown_hash = AccountQuery {
+            block_hash: None,
+            ..query(1)
+        };
+        assert_eq!(store.get_balance(&unknown_hash).unwrap(), U256::from(1));
+
+        // data of another hash replaces all the data of the reorged block
+        store.insert_balance(query(2), U256::from(2));
+        assert_eq!(store.get_balance(&query(2)).unwrap(), U256::from(2));
+        assert!(store.get_balance(&query(1)).is_err());
+        assert!(store.get_transaction_count(&query(2)).is_err());
+    }
+
+    #[test]
+    fn unreadable_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let address = H160::from_low_u64_be(1);
+        let query = AccountQuery {
+            block_no: 1,
+            block_hash: None,
+            address,
+        };
+
+        let mut store = StoreProvider::new(dir.path().to_path_buf(), 1).unwrap();
+        store.insert_balance(query.clone(), U256::from(1));
+        store.save().unwrap();
+
+        // only the modified indexes are written
+        let path = store.account_path(1, &address);
+        fs::remove_file(&path).unwrap();
+        store.save().unwrap();
+        assert!(!path.exists());
+
+        // the indexes of the opened block are read when the store is opened
+        fs::write(&path, b"invalid").unwrap();
+        assert!(StoreProvider::new(dir.path().to_path_buf(), 1).is_err());
+
+        // an unreadable index of another block is replaced
+        let mut store = StoreProvider::new(dir.path().to_path_buf(), 3).unwrap();
+        assert!(store.get_balance(&query).is_err());
+        store.insert_balance(query.clone(), U256::from(2));
+        store.save().unwrap();
+        let mut store = StoreProvider::new(dir.path().to_path_buf(), 1).unwrap();
+        assert_eq!(store.get_balance(&query).unwrap(), U256::from(2));
+    }
+}
@@ -0,0 +1,50 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Queries shared by the tests of the providers.
+
+use ethers_core::types::{H160, H256};
+
+use super::{AccountQuery, ProofQuery};
+
+/// Returns the address with the given number.
+pub fn address(number: u64) -> H160 {
+    H160::from_low_u64_be(number)
+}
+
+/// Returns the storage slot with the given number.
+pub fn slot(number: u64) -> H256 {
+    H256::from_low_u64_be(number)
+}
+
+/// Returns the query for the account with the given [address] number at the given block,
+/// without a block hash.
+pub fn account_query(block_no: u64, account: u64) -> AccountQuery {
+    AccountQuery {
+        block_no,
+        block_hash: None,
+        address: address(account),
+    }
+}
+
+/// Returns the query for the proof of the given [slot] numbers of the account with the
+/// given [address] number at the given block, without a block hash.
+pub fn proof_query(block_no: u64, account: u64, slots: &[u64]) -> ProofQuery {
+    ProofQuery {
+        block_no,
+        block_hash: None,
+        address: address(account),
+        indices: slots.iter().copied().map(slot).collect(),
+    }
+}
@@ -18,22 +18,32 @@ use std::collections::BTreeSet;
 
 use ethers_core::types::{EIP1186ProofResponse, H160, H256};
 use revm::{
-    Database,
-    DatabaseCommit, primitives::{Account, AccountInfo, Bytecode},
+    primitives::{Account, AccountInfo, Bytecode},
+    Database, DatabaseCommit,
+};
+use zeth_primitives::{
+    block::Header,
+    ethers::{from_ethers_bytes, from_ethers_u256},
+    keccak::KECCAK_EMPTY,
+    Address, Bytes, B256, U256,
 };
-
-use zeth_primitives::{Address, B256, block::Header, Bytes, ethers::{from_ethers_bytes, from_ethers_u256}, U256};
-use zeth_primitives::keccak::KECCAK_EMPTY;
 
 use crate::{
+    consts::{HISTORY_SERVE_WINDOW, HISTORY_STORAGE_ADDRESS},
     host::provider::{AccountQuery, BlockQuery, ProofQuery, Provider, StorageQuery},
     mem_db::{DbError, MemDb},
+    HashMap,
 };
-use crate::HashMap;
 
 pub struct ProviderDb {
     pub provider: Box<dyn Provider>,
     pub block_no: u64,
+    /// Hash of the block `block_no`, if known.
+    pub block_hash: Option<H256>,
+    /// Hash of the block `block_no + 1`, if known.
+    pub next_block_hash: Option<H256>,
+    /// Whether the hashes of the ancestors can be read from the history storage contract.
+    pub history_storage: bool,
     pub initial_db: MemDb,
     pub latest_db: MemDb,
     pub aux_db: MemDb,
@@ -44,9 +54,9 @@ impl ProviderDb {
         let mut aux_db: MemDb = Default::default();
         #[cfg(feature = "revm-rwasm")]
         for (query, code) in provider.get_all_codes() {
-            let mut account_info = AccountInfo{
+            let mut account_info = AccountInfo {
                 code: Some(Bytecode::LegacyRaw(Bytes::copy_from_slice(code.0.as_ref()))),
-                .. Default::default()
+                ..Default::default()
             };
             aux_db.insert_contract(&mut account_info);
             // aux_db.insert_account_info(Address::new(query.address.0), account_info);
@@ -55,6 +65,9 @@ impl ProviderDb {
         let mut provider_db = ProviderDb {
             provider,
             block_no,
+            block_hash: None,
+            next_block_hash: None,
+            history_storage: false,
             initial_db: Default::default(),
             latest_db: Default::default(),
             aux_db,
@@ -63,6 +76,14 @@ impl ProviderDb {
         provider_db
     }
 
+    /// Sets the hashes of the block `block_no` and its child, so that all queries refer
+    /// to these exact blocks.
+    pub fn with_block_hashes(mut self, block_hash: H256, next_block_hash: H256) -> Self {
+        self.block_hash = Some(block_hash);
+        self.next_block_hash = Some(next_block_hash);
+        self
+    }
+
     pub fn save_provider(&self) -> anyhow::Result<()> {
         self.provider.save()
     }
@@ -78,33 +99,39 @@ impl ProviderDb {
     fn get_proofs(
         &mut self,
         block_no: u64,
+        block_hash: Option<H256>,
         storage_keys: HashMap<Address, Vec<U256>>,
     ) -> Result<HashMap<Address, EIP1186ProofResponse>, anyhow::Error> {
-        let mut out = HashMap::new();
-
-        for (address, indices) in storage_keys {
-            let proof = {
-                let address: H160 = address.into_array().into();
+        // create one query per account, so that all proofs can be fetched at once
+        let (addresses, queries): (Vec<_>, Vec<_>) = storage_keys
+            .into_iter()
+            .map(|(address, indices)| {
                 let indices: BTreeSet<H256> = indices
                     .into_iter()
                     .map(|x| x.to_be_bytes().into())
                     .collect();
-                self.provider.get_proof(&ProofQuery {
+                let query = ProofQuery {
                     block_no,
-                    address,
+                    block_hash,
+                    address: H160::from(address.into_array()),
                     indices,
-                })?
-            };
-            out.insert(address, proof);
-        }
+                };
+                (address, query)
+            })
+            .unzip();
+        let proofs = self.provider.get_proofs(&queries)?;
 
-        Ok(out)
+        Ok(addresses.into_iter().zip(proofs).collect())
     }
 
     pub fn get_initial_proofs(
         &mut self,
     ) -> Result<HashMap<Address, EIP1186ProofResponse>, anyhow::Error> {
-        self.get_proofs(self.block_no, self.initial_db.storage_keys())
+        self.get_proofs(
+            self.block_no,
+            self.block_hash,
+            self.initial_db.storage_keys(),
+        )
     }
 
     pub fn get_latest_proofs(
@@ -121,21 +148,51 @@ impl ProviderDb {
             }
         }
 
-        self.get_proofs(self.block_no + 1, storage_keys)
+        self.get_proofs(self.block_no + 1, self.next_block_hash, storage_keys)
     }
 
+    /// Returns the headers from the parent of `block_no` back to the oldest block whose
+    /// hash was accessed.
+    ///
+    /// With [ProviderDb::history_storage], the hashes are read from the history storage
+    /// contract instead and only the blocks missing from the contract need headers. As
+    /// this accesses the storage, it must be called before
+    /// [ProviderDb::get_initial_proofs].
     pub fn get_ancestor_headers(&mut self) -> Result<Vec<Header>, anyhow::Error> {
-        let earliest_block = self
+        let mut block_hashes: Vec<_> = self
             .initial_db
             .block_hashes
-            .keys()
+            .iter()
+            .filter(|(block_no, _)| **block_no < self.block_no)
+            .map(|(block_no, block_hash)| (*block_no, *block_hash))
+            .collect();
+        if self.history_storage {
+            // the account must be loaded before its storage
+            self.basic(HISTORY_STORAGE_ADDRESS)?;
+            let mut missing = Vec::new();
+            for (block_no, block_hash) in block_hashes {
+                let slot = U256::from(block_no % HISTORY_SERVE_WINDOW);
+                let stored = self.storage(HISTORY_STORAGE_ADDRESS, slot)?;
+                if B256::from(stored.to_be_bytes::<32>()) != block_hash {
+                    missing.push((block_no, block_hash));
+                }
+            }
+            block_hashes = missing;
+        }
+
+        let earliest_block = block_hashes
+            .iter()
+            .map(|(block_no, _)| *block_no)
             .min()
-            .unwrap_or(&self.block_no);
-        let headers = (*earliest_block..self.block_no)
+            .unwrap_or(self.block_no);
+        let headers = (earliest_block..self.block_no)
             .rev()
             .map(|block_no| {
                 self.provider
-                    .get_partial_block(&BlockQuery { block_no })
+                    .get_partial_block(&BlockQuery {
+                        block_no,
+                        block_hash: None,
+                    })
                     .expect("Failed to retrieve ancestor block")
                     .try_into()
                     .expect("Failed to convert ethers block to zeth block")
@@ -163,11 +220,10 @@ impl Database for ProviderDb {
         let account_info = {
             let query = AccountQuery {
                 block_no: self.block_no,
+                block_hash: self.block_hash,
                 address: address.into_array().into(),
             };
-            let nonce = self.provider.get_transaction_count(&query)?;
-            let balance = self.provider.get_balance(&query)?;
-            let code = self.provider.get_code(&query)?;
+            let (nonce, balance, code) = self.provider.get_account(&query)?;
             let bytecode = Bytecode::new_raw(from_ethers_bytes(code));
 
             AccountInfo::new(
@@ -187,7 +243,7 @@ impl Database for ProviderDb {
         // not needed because we already load code with basic info
         // unreachable!()
         if code_hash == KECCAK_EMPTY {
-            return Ok(Bytecode::new())
+            return Ok(Bytecode::new());
         }
         // match self.latest_db.code_by_hash(code_hash) {
         //     Ok(code) => return Ok(code),
@@ -227,6 +283,7 @@ impl Database for ProviderDb {
 
             let storage = self.provider.get_storage(&StorageQuery {
                 block_no: self.block_no,
+                block_hash: self.block_hash,
                 address: address.into_array().into(),
                 index,
             })?;
@@ -248,7 +305,10 @@ impl Database for ProviderDb {
         let block_no = u64::try_from(number).unwrap();
         let block_hash = self
             .provider
-            .get_partial_block(&BlockQuery { block_no })?
+            .get_partial_block(&BlockQuery {
+                block_no,
+                block_hash: None,
+            })?
             .hash
             .unwrap()
             .0
@@ -0,0 +1,104 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Preflight of a range of consecutive blocks.
+
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{ensure, Context, Result};
+use ethers_core::types::{Bytes, Transaction as EthersTransaction, H256};
+use log::info;
+use zeth_primitives::transactions::TxEssence;
+
+use crate::{
+    builder::BlockBuilderStrategy,
+    consts::ChainSpec,
+    host::{
+        preflight::{Data, Preflight},
+        provider::range_provider::{RangeProvider, RollingState},
+        ProviderFactory,
+    },
+};
+
+pub trait RangePreflight<E: TxEssence> {
+    /// Runs the preflight of `block_count` consecutive blocks starting at `block_no`
+    /// and passes the data of each block to `on_block`, in order.
+    ///
+    /// The state after a block, the code and the recent block hashes are taken from the
+    /// previous blocks, so only the accounts and slots that were not accessed by the
+    /// previous block are queried from the provider.
+    fn preflight_range(
+        chain_spec: &ChainSpec,
+        providers: &ProviderFactory,
+        block_no: u64,
+        block_count: u64,
+        on_block: impl FnMut(Data<E>) -> Result<()>,
+    ) -> Result<()>;
+}
+
+/// Implements the [RangePreflight] trait for all compatible [BlockBuilderStrategy]s.
+impl<N: BlockBuilderStrategy> RangePreflight<N::TxEssence> for N
+where
+    N::TxEssence: TryFrom<EthersTransaction>,
+    <N::TxEssence as TryFrom<EthersTransaction>>::Error: Debug,
+{
+    fn preflight_range(
+        chain_spec: &ChainSpec,
+        providers: &ProviderFactory,
+        block_no: u64,
+        block_count: u64,
+        mut on_block: impl FnMut(Data<N::TxEssence>) -> Result<()>,
+    ) -> Result<()> {
+        ensure!(block_count > 0, "the block range is empty");
+
+        let state = Arc::new(Mutex::new(RollingState::default()));
+        for block_no in block_no..block_no + block_count {
+            info!("Running preflight of block {block_no} ...");
+
+            let provider = RangeProvider::new(providers.create_provider(block_no)?, state.clone());
+            let data = N::preflight_with_provider(chain_spec, Box::new(provider), block_no)?;
+
+            let header = data.header.as_ref().context("missing block header")?;
+            let code = data
+                .db
+                .accounts
+                .values()
+                .chain(data.changed_accounts.values())
+                .filter_map(|account| account.info.code.as_ref())
+                .filter(|code| !code.is_empty())
+                .map(|code| Bytes::from(code.bytecode().to_vec()));
+            let mut state = state.lock().unwrap();
+            state.advance(
+                block_no,
+                H256(header.hash().0),
+                data.proofs
+                    .values()
+                    .map(|proof| (proof.address, proof.clone())),
+                code,
+            );
+            info!(
+                "{} queries of block {block_no} were answered by the previous blocks",
+                state.take_reused()
+            );
+            drop(state);
+
+            on_block(data)?;
+        }
+
+        Ok(())
+    }
+}
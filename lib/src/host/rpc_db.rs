@@ -24,7 +24,7 @@ use zeth_primitives::{
 use crate::{
     host::{
         cache_file_path,
-        provider::{new_provider, BlockQuery},
+        provider::{new_provider, BlockQuery, CachePath, RpcConfig},
     },
     optimism::{
         batcher_db::{BatcherDb, BlockInput, MemDb},
@@ -33,23 +33,23 @@ use crate::{
     },
 };
 
-fn eth_cache_path(cache: &Option<PathBuf>, block_no: u64) -> Option<PathBuf> {
+fn eth_cache_path(cache: &Option<PathBuf>, block_no: u64) -> Option<CachePath> {
     cache
         .as_ref()
-        .map(|dir| cache_file_path(dir, "ethereum", block_no, "json.gz"))
+        .map(|dir| cache_file_path(dir, "ethereum", block_no, "json.gz").into())
 }
 
-fn op_cache_path(cache: &Option<PathBuf>, block_no: u64) -> Option<PathBuf> {
+fn op_cache_path(cache: &Option<PathBuf>, block_no: u64) -> Option<CachePath> {
     cache
         .as_ref()
-        .map(|dir| cache_file_path(dir, "optimism", block_no, "json.gz"))
+        .map(|dir| cache_file_path(dir, "optimism", block_no, "json.gz").into())
 }
 
 pub struct RpcDb {
     deposit_contract: Address,
     system_config_contract: Address,
-    eth_rpc_url: Option<String>,
-    op_rpc_url: Option<String>,
+    eth_rpc_config: Option<RpcConfig>,
+    op_rpc_config: Option<RpcConfig>,
     cache: Option<PathBuf>,
     mem_db: MemDb,
 }
@@ -57,15 +57,15 @@ pub struct RpcDb {
 impl RpcDb {
     pub fn new(
         config: &ChainConfig,
-        eth_rpc_url: Option<String>,
-        op_rpc_url: Option<String>,
+        eth_rpc_config: Option<RpcConfig>,
+        op_rpc_config: Option<RpcConfig>,
         cache: Option<PathBuf>,
     ) -> Self {
         RpcDb {
             deposit_contract: config.deposit_contract,
             system_config_contract: config.system_config_contract,
-            eth_rpc_url,
-            op_rpc_url,
+            eth_rpc_config,
+            op_rpc_config,
             cache,
             mem_db: MemDb::new(),
         }
@@ -87,11 +87,14 @@ impl BatcherDb for RpcDb {
     ) -> anyhow::Result<BlockInput<OptimismTxEssence>> {
         let mut provider = new_provider(
             op_cache_path(&self.cache, block_no),
-            self.op_rpc_url.clone(),
+            self.op_rpc_config.clone(),
         )
         .context("failed to create provider")?;
         let block = {
-            let ethers_block = provider.get_full_block(&BlockQuery { block_no })?;
+            let ethers_block = provider.get_full_block(&BlockQuery {
+                block_no,
+                block_hash: None,
+            })?;
             BlockInput {
                 block_header: ethers_block.clone().try_into().unwrap(),
                 transactions: ethers_block
@@ -110,10 +113,13 @@ impl BatcherDb for RpcDb {
     fn get_op_block_header(&mut self, block_no: u64) -> anyhow::Result<Header> {
         let mut provider = new_provider(
             op_cache_path(&self.cache, block_no),
-            self.op_rpc_url.clone(),
+            self.op_rpc_config.clone(),
         )?;
         let header: Header = provider
-            .get_partial_block(&BlockQuery { block_no })?
+            .get_partial_block(&BlockQuery {
+                block_no,
+                block_hash: None,
+            })?
             .try_into()?;
         self.mem_db.op_block_header.insert(block_no, header.clone());
         provider.save()?;
@@ -124,10 +130,13 @@ impl BatcherDb for RpcDb {
         &mut self,
         block_no: u64,
     ) -> anyhow::Result<&BlockInput<EthereumTxEssence>> {
-        let query = BlockQuery { block_no };
+        let query = BlockQuery {
+            block_no,
+            block_hash: None,
+        };
         let mut provider = new_provider(
             eth_cache_path(&self.cache, block_no),
-            self.eth_rpc_url.clone(),
+            self.eth_rpc_config.clone(),
         )?;
         let block = {
             let ethers_block = provider.get_full_block(&query)?;
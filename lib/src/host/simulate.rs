@@ -0,0 +1,299 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::marker::PhantomData;
+
+use anyhow::{bail, Context, Result};
+use log::info;
+use revm::{Database, DatabaseCommit};
+use serde::{Deserialize, Serialize};
+use zeth_primitives::{
+    block::Header,
+    keccak::keccak,
+    receipt::Receipt,
+    transactions::{Transaction, TxEssence},
+    trie::{to_nibs, MptNode, MptNodeData},
+    Address, Bytes, U256,
+};
+
+use crate::{
+    builder::{BlockBuilder, BlockBuilderStrategy, HeaderPrepStrategy},
+    consts::ChainSpec,
+    host::{
+        preflight::Data,
+        provider::{new_provider, BlockQuery, CachePath, RpcConfig},
+        provider_db::ProviderDb,
+    },
+    input::{BlockBuildInput, StateInput},
+    mem_db::{AccountState, MemDb},
+};
+
+/// Seconds between the parent block and the simulated block, unless overridden.
+const BLOCK_TIME: u64 = 12;
+
+/// Overrides of the simulated block, analogous to the `blockOverrides` of
+/// `eth_simulateV1`. Fields that are not set are derived from the parent block.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockOverrides {
+    pub fee_recipient: Option<Address>,
+    pub time: Option<u64>,
+    pub base_fee_per_gas: Option<U256>,
+}
+
+/// The outcome of a simulated block.
+#[derive(Debug, Clone)]
+pub struct Simulation<E: TxEssence> {
+    /// Header of the simulated block, containing the post-state root.
+    pub header: Header,
+    pub receipts: Vec<Receipt>,
+    /// The input to build, and prove, the simulated block. A block with an overridden
+    /// base fee cannot be proven, as the guest always derives the base fee.
+    pub input: BlockBuildInput<E>,
+}
+
+/// Prepares the header like `H`, but keeps the base fee of a header that was set before
+/// preparing it. It is never used to build proven blocks, so that a proof always shows
+/// that the base fee follows EIP-1559.
+pub struct SimulationHeaderPrepStrategy<H>(PhantomData<H>);
+
+impl<H: HeaderPrepStrategy> HeaderPrepStrategy for SimulationHeaderPrepStrategy<H> {
+    fn prepare_header<D, E, M>(
+        mut block_builder: BlockBuilder<D, E, M>,
+    ) -> Result<BlockBuilder<D, E, M>>
+    where
+        D: Database + DatabaseCommit,
+        <D as Database>::Error: core::fmt::Debug,
+        E: TxEssence,
+    {
+        let base_fee_per_gas = block_builder
+            .header
+            .take()
+            .and_then(|header| header.base_fee_per_gas);
+        let mut block_builder = H::prepare_header(block_builder)?;
+        if let Some(base_fee_per_gas) = base_fee_per_gas {
+            let header = block_builder
+                .header
+                .as_mut()
+                .context("Header not initialized")?;
+            header.base_fee_per_gas = Some(base_fee_per_gas);
+        }
+        Ok(block_builder)
+    }
+}
+
+/// Sets the base fee that [SimulationHeaderPrepStrategy] puts into the header.
+fn with_base_fee<D, E: TxEssence, M>(
+    mut block_builder: BlockBuilder<D, E, M>,
+    base_fee_per_gas: Option<U256>,
+) -> BlockBuilder<D, E, M> {
+    block_builder.header = base_fee_per_gas.map(|base_fee_per_gas| Header {
+        base_fee_per_gas: Some(base_fee_per_gas),
+        ..Default::default()
+    });
+    block_builder
+}
+
+pub trait Simulate<E: TxEssence> {
+    /// Executes the signed transactions in a new block on top of the state of the block
+    /// `parent_block_no` from the RPC provider.
+    ///
+    /// No block contains the simulated post-state, so there are no proofs of the nodes
+    /// that replace a deleted key when a deletion restructures a trie. Simulating such a
+    /// block returns an error before it is built.
+    fn simulate(
+        chain_spec: &ChainSpec,
+        cache_path: Option<CachePath>,
+        rpc_config: Option<RpcConfig>,
+        parent_block_no: u64,
+        overrides: &BlockOverrides,
+        transactions: Vec<Transaction<E>>,
+    ) -> Result<Simulation<E>>;
+}
+
+/// Returns an error if a deletion of the block merges a branch with its only remaining
+/// child, while that child is not part of the parent proofs.
+///
+/// Preflight takes these children from the proofs of the block after, but no block
+/// contains the simulated post-state. Insertions of the block are not taken into account,
+/// so a block is also rejected if they keep such a branch intact.
+fn ensure_resolvable_deletions<E: TxEssence>(input: &BlockBuildInput<E>, db: &MemDb) -> Result<()> {
+    let mut state_trie = input.parent_state_trie.clone();
+    for (address, account) in &db.accounts {
+        match account.state {
+            AccountState::Deleted => delete(&mut state_trie, &keccak(address))
+                .with_context(|| format!("failed to delete account {}", address))?,
+            AccountState::Touched => {
+                let Some((storage_trie, _)) = input.parent_storage.get(address) else {
+                    continue;
+                };
+                let mut storage_trie = storage_trie.clone();
+                for (slot, value) in &account.storage {
+                    if *value == U256::ZERO {
+                        delete(&mut storage_trie, &keccak(slot.to_be_bytes::<32>())).with_context(
+                            || format!("failed to delete slot {} of {}", slot, address),
+                        )?;
+                    }
+                }
+            }
+            // cleared storage starts from the empty trie
+            AccountState::StorageCleared | AccountState::None => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes the key from the trie, unless this merges a branch with an unresolved node.
+fn delete(trie: &mut MptNode, key: &[u8]) -> Result<()> {
+    if orphans_digest(trie, &to_nibs(key)) {
+        bail!("simulating a block whose deletions restructure a trie is not supported");
+    }
+    trie.delete(key)?;
+    Ok(())
+}
+
+/// Returns whether deleting the key leaves a branch with a single child that is only
+/// known by its digest. As the type of the child is unknown, so is the merged node.
+fn orphans_digest(node: &MptNode, key_nibs: &[u8]) -> bool {
+    match node.as_data() {
+        MptNodeData::Branch(children) => {
+            let Some((index, tail)) = key_nibs.split_first() else {
+                return false;
+            };
+            let Some(child) = &children[*index as usize] else {
+                return false;
+            };
+            if matches!(child.as_data(), MptNodeData::Leaf(..)) && child.nibs() == tail {
+                let mut siblings = children
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, sibling)| sibling.as_ref().filter(|_| i != *index as usize));
+                return match (siblings.next(), siblings.next()) {
+                    (Some(sibling), None) => sibling.is_digest(),
+                    _ => false,
+                };
+            }
+            orphans_digest(child, tail)
+        }
+        MptNodeData::Extension(_, child) => match key_nibs.strip_prefix(node.nibs().as_slice()) {
+            Some(tail) => orphans_digest(child, tail),
+            None => false,
+        },
+        MptNodeData::Null | MptNodeData::Leaf(..) | MptNodeData::Digest(_) => false,
+    }
+}
+
+/// Implements the [Simulate] trait for all [BlockBuilderStrategy]s.
+impl<N: BlockBuilderStrategy> Simulate<N::TxEssence> for N {
+    fn simulate(
+        chain_spec: &ChainSpec,
+        cache_path: Option<CachePath>,
+        rpc_config: Option<RpcConfig>,
+        parent_block_no: u64,
+        overrides: &BlockOverrides,
+        transactions: Vec<Transaction<N::TxEssence>>,
+    ) -> Result<Simulation<N::TxEssence>> {
+        let mut provider = new_provider(cache_path, rpc_config)?;
+
+        // Fetch the parent block
+        let parent_block = provider.get_partial_block(&BlockQuery {
+            block_no: parent_block_no,
+            block_hash: None,
+        })?;
+        let parent_hash = parent_block.hash.context("parent block has no hash")?;
+        let parent_header: Header = parent_block.try_into().context("invalid parent block")?;
+
+        let input = BlockBuildInput {
+            state_input: StateInput {
+                parent_header: parent_header.clone(),
+                beneficiary: overrides.fee_recipient.unwrap_or(parent_header.beneficiary),
+                gas_limit: parent_header.gas_limit,
+                timestamp: match overrides.time {
+                    Some(time) => U256::from(time),
+                    None => parent_header.timestamp + U256::from(BLOCK_TIME),
+                },
+                extra_data: Bytes::new(),
+                mix_hash: parent_header.mix_hash,
+                transactions: transactions.clone(),
+                withdrawals: vec![],
+            },
+            parent_state_trie: Default::default(),
+            parent_storage: Default::default(),
+            contracts: Default::default(),
+            ancestor_headers: Default::default(),
+        };
+
+        // Run the transactions against the state of the parent block
+        let mut provider_db = ProviderDb::new(provider, parent_header.number);
+        provider_db.block_hash = Some(parent_hash);
+        provider_db.history_storage = chain_spec
+            .history_storage_active(parent_header.number + 1, &input.state_input.timestamp);
+        let builder = BlockBuilder::new(chain_spec, input)
+            .with_db(provider_db)
+            .with_receipts();
+        let mut builder = with_base_fee(builder, overrides.base_fee_per_gas)
+            .prepare_header::<SimulationHeaderPrepStrategy<N::HeaderPrepStrategy>>()
+            .context("invalid block overrides")?
+            .execute_transactions::<N::TxExecStrategy>()
+            .context("failed to execute the transactions")?;
+        let receipts = builder.take_receipts().unwrap_or_default();
+        let header = builder.header.clone();
+        let mut provider_db = builder.take_db().unwrap();
+        let ancestor_headers = provider_db.get_ancestor_headers()?;
+
+        info!("Gathering inclusion proofs ...");
+
+        // No block contains the simulated post-state, so the parent proofs replace the
+        // proofs after the block.
+        let parent_proofs = provider_db.get_initial_proofs()?;
+        provider_db.save_provider()?;
+
+        let data = Data {
+            db: provider_db.get_initial_db().clone(),
+            // the changes are only needed to verify a block
+            changed_accounts: Default::default(),
+            parent_header,
+            proofs: parent_proofs.clone(),
+            parent_proofs,
+            header,
+            transactions,
+            withdrawals: vec![],
+            ancestor_headers,
+            receipts: receipts.clone(),
+            rpc_receipts: None,
+        };
+        let input = BlockBuildInput::try_from(data)?;
+
+        ensure_resolvable_deletions(&input, provider_db.get_latest_db())?;
+
+        // Build the block from the input alone, as it would be proven apart from the
+        // base fee
+        let builder = BlockBuilder::<MemDb, _, MptNode>::new(chain_spec, input.clone())
+            .initialize_database::<N::DbInitStrategy>()?;
+        let (header, _) = with_base_fee(builder, overrides.base_fee_per_gas)
+            .prepare_header::<SimulationHeaderPrepStrategy<N::HeaderPrepStrategy>>()
+            .context("the simulated block is invalid")?
+            .execute_transactions::<N::TxExecStrategy>()
+            .context("the simulated block is invalid")?
+            .finalize::<N::BlockFinalizeStrategy>()
+            .context("Error while building block")?;
+
+        Ok(Simulation {
+            header,
+            receipts,
+            input,
+        })
+    }
+}
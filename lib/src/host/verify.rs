@@ -12,21 +12,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::{fmt::Debug, iter};
+
+use alloy_rlp::Decodable;
 use anyhow::{bail, Context, Result};
-use ethers_core::types::EIP1186ProofResponse;
-use crate::HashMap;
+use ethers_core::types::{EIP1186ProofResponse, StorageProof};
 use log::error;
+use serde::{Deserialize, Serialize};
 use zeth_primitives::{
     block::Header,
+    ethers::{from_ethers_h256, from_ethers_u256},
     keccak::keccak,
-    transactions::TxEssence,
-    trie::{Error as TrieError, MptNode, StateAccount},
+    receipt::Receipt,
+    transactions::{Transaction, TxEssence},
+    trie::{Error as TrieError, MptDiff, MptNode, StateAccount},
     Address, B256, U256,
 };
 
 use super::{mpt, preflight};
+use crate::{mem_db::AccountState, HashMap};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum VerifyError {
     BalanceMismatch {
         rpc_value: U256,
@@ -51,23 +57,146 @@ pub enum VerifyError {
         rpc_value: B256,
         our_value: B256,
     },
+    /// The value of a storage slot differs from the RPC's view of the storage trie. The
+    /// index of the slot is only known, if it was accessed during the execution.
+    StorageSlotMismatch {
+        key: B256,
+        index: Option<U256>,
+        rpc_value: Option<U256>,
+        our_value: Option<U256>,
+    },
+    /// The storage tries differ below the given nibble path, which is not resolved.
+    UnresolvedStorage {
+        path: Vec<u8>,
+    },
+    /// The storage tries could not be compared to locate the differing slots.
+    StorageTrieError {
+        message: String,
+    },
     /// The account is not in the trie, but the RPC response proves that it is included.
     MissingAccount,
     /// The account cannot be resolved in the trie.
     UnresolvedAccount,
 }
 
+/// Whether each field of the built header and its hash match the expected header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaderMatches {
+    pub parent_hash: bool,
+    pub ommers_hash: bool,
+    pub beneficiary: bool,
+    pub state_root: bool,
+    pub transactions_root: bool,
+    pub receipts_root: bool,
+    pub logs_bloom: bool,
+    pub difficulty: bool,
+    pub number: bool,
+    pub gas_limit: bool,
+    pub gas_used: bool,
+    pub timestamp: bool,
+    pub extra_data: bool,
+    pub mix_hash: bool,
+    pub nonce: bool,
+    pub base_fee_per_gas: bool,
+    pub withdrawals_root: bool,
+    pub blob_gas_used: bool,
+    pub excess_blob_gas: bool,
+    pub parent_beacon_block_root: bool,
+    pub requests_hash: bool,
+    pub block_hash: bool,
+}
+
+/// The verification errors found for a single account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountErrors {
+    pub address: Address,
+    pub errors: Vec<VerifyError>,
+}
+
+/// The first transaction whose locally built receipt differs from the RPC's receipt, with
+/// a flag for each compared field telling whether it matches.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiptDivergence {
+    pub tx_index: usize,
+    pub tx_hash: B256,
+    pub status: bool,
+    pub cumulative_gas_used: bool,
+    pub logs: bool,
+    pub logs_bloom: bool,
+    /// Gas used by the transaction locally minus the gas used according to the RPC.
+    pub gas_delta: i64,
+}
+
+/// The result of comparing the locally built receipts with the RPC's receipts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiptComparison {
+    /// Number of locally built receipts.
+    pub receipts: usize,
+    /// Number of receipts according to the RPC.
+    pub expected_receipts: usize,
+    /// Number of receipts that differ in at least one field or have no counterpart.
+    pub mismatches: usize,
+    pub first_divergence: Option<ReceiptDivergence>,
+}
+
+/// The result of verifying a built block against the RPC's view of it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerificationReport {
+    pub block_number: u64,
+    pub block_hash: B256,
+    pub expected_block_hash: B256,
+    pub header: HeaderMatches,
+    /// Number of accounts whose state after the block was checked.
+    pub accounts_checked: usize,
+    /// The accounts with at least one error, sorted by address.
+    pub accounts: Vec<AccountErrors>,
+    pub correct_accounts_percent: f64,
+    /// The receipt comparison, if the RPC provided the receipts of the block.
+    pub receipts: Option<ReceiptComparison>,
+    /// The overall verdict: the block is valid iff its hash matches the expected hash.
+    pub valid: bool,
+}
+
+impl VerificationReport {
+    /// Returns an error if the verdict of the report is negative.
+    pub fn ensure_valid(&self) -> Result<()> {
+        if !self.valid {
+            bail!(
+                "Invalid block hash {} (expected {})",
+                self.block_hash,
+                self.expected_block_hash
+            );
+        }
+        Ok(())
+    }
+}
+
 /// Verify the block header and state trie.
 pub trait Verifier {
-    fn verify_block(&self, header: &Header, state: &MptNode) -> Result<()>;
+    fn verify_block(&self, header: &Header, state: &MptNode) -> Result<VerificationReport>;
 }
 
 /// Verify using the preflight data.
 impl<E: TxEssence> Verifier for preflight::Data<E> {
-    fn verify_block(&self, header: &Header, state: &MptNode) -> Result<()> {
-        let errors =
+    fn verify_block(&self, header: &Header, state: &MptNode) -> Result<VerificationReport> {
+        let mut errors =
             verify_state_trie(state, &self.proofs).context("failed to verify state trie")?;
 
+        // locate the differing slots for each account with a mismatching storage root
+        for (address, address_errors) in errors.iter_mut() {
+            if address_errors
+                .iter()
+                .any(|err| matches!(err, VerifyError::StorageRootMismatch { .. }))
+            {
+                match verify_storage_trie(self, address) {
+                    Ok(storage_errors) => address_errors.extend(storage_errors),
+                    Err(err) => address_errors.push(VerifyError::StorageTrieError {
+                        message: format!("{err:#}"),
+                    }),
+                }
+            }
+        }
+
         for (address, address_errors) in &errors {
             error!(
                 "Verify found {:?} error(s) for address {:?}",
@@ -81,66 +210,237 @@ impl<E: TxEssence> Verifier for preflight::Data<E> {
 
         let accounts_len = self.proofs.len();
         let errors_len = errors.len();
+        let correct_accounts_percent = if accounts_len > 0 {
+            100.0 * (accounts_len - errors_len) as f64 / accounts_len as f64
+        } else {
+            100.0
+        };
         if errors_len > 0 {
             error!(
                 "Verify found {:?} account(s) with error(s) ({}% correct)",
-                errors_len,
-                (100.0 * (accounts_len - errors_len) as f64 / accounts_len as f64)
+                errors_len, correct_accounts_percent
             );
         }
 
-        verify_header(header, self.header.as_ref().unwrap())
+        let receipts = match &self.rpc_receipts {
+            Some(rpc_receipts) => Some(
+                compare_receipts(&self.transactions, &self.receipts, rpc_receipts)
+                    .context("failed to compare receipts")?,
+            ),
+            None => None,
+        };
+
+        let exp_header = self.header.as_ref().context("missing expected header")?;
+        let header_matches = verify_header(header, exp_header);
+
+        let mut accounts: Vec<_> = errors
+            .into_iter()
+            .map(|(address, errors)| AccountErrors { address, errors })
+            .collect();
+        accounts.sort_unstable_by_key(|account| account.address);
+
+        Ok(VerificationReport {
+            block_number: header.number,
+            block_hash: header.hash(),
+            expected_block_hash: exp_header.hash(),
+            header: header_matches,
+            accounts_checked: accounts_len,
+            accounts,
+            correct_accounts_percent,
+            receipts,
+            valid: header_matches.block_hash,
+        })
     }
 }
 
-fn verify_header(header: &Header, exp_header: &Header) -> Result<()> {
-    if header.state_root != exp_header.state_root {
-        error!(
-            "State root mismatch {} (expected {})",
-            header.state_root, exp_header.state_root
-        );
-    }
+fn verify_header(header: &Header, exp_header: &Header) -> HeaderMatches {
+    // destructure the header, so that no field can be left unchecked
+    let Header {
+        parent_hash,
+        ommers_hash,
+        beneficiary,
+        state_root,
+        transactions_root,
+        receipts_root,
+        logs_bloom,
+        difficulty,
+        number,
+        gas_limit,
+        gas_used,
+        timestamp,
+        extra_data,
+        mix_hash,
+        nonce,
+        base_fee_per_gas,
+        withdrawals_root,
+        blob_gas_used,
+        excess_blob_gas,
+        parent_beacon_block_root,
+        requests_hash,
+    } = header;
+    let matches = HeaderMatches {
+        parent_hash: field_matches("Parent hash", parent_hash, &exp_header.parent_hash),
+        ommers_hash: field_matches("Ommers hash", ommers_hash, &exp_header.ommers_hash),
+        beneficiary: field_matches("Beneficiary", beneficiary, &exp_header.beneficiary),
+        state_root: field_matches("State root", state_root, &exp_header.state_root),
+        transactions_root: field_matches(
+            "Transactions root",
+            transactions_root,
+            &exp_header.transactions_root,
+        ),
+        receipts_root: field_matches("Receipts root", receipts_root, &exp_header.receipts_root),
+        logs_bloom: field_matches("Logs bloom", logs_bloom, &exp_header.logs_bloom),
+        difficulty: field_matches("Difficulty", difficulty, &exp_header.difficulty),
+        number: field_matches("Number", number, &exp_header.number),
+        gas_limit: field_matches("Gas limit", gas_limit, &exp_header.gas_limit),
+        gas_used: field_matches("Gas used", gas_used, &exp_header.gas_used),
+        timestamp: field_matches("Timestamp", timestamp, &exp_header.timestamp),
+        extra_data: field_matches("Extra data", extra_data, &exp_header.extra_data),
+        mix_hash: field_matches("Mix hash", mix_hash, &exp_header.mix_hash),
+        nonce: field_matches("Nonce", nonce, &exp_header.nonce),
+        base_fee_per_gas: field_matches("Base fee", base_fee_per_gas, &exp_header.base_fee_per_gas),
+        withdrawals_root: field_matches(
+            "Withdrawals root",
+            withdrawals_root,
+            &exp_header.withdrawals_root,
+        ),
+        blob_gas_used: field_matches("Blob gas used", blob_gas_used, &exp_header.blob_gas_used),
+        excess_blob_gas: field_matches(
+            "Excess blob gas",
+            excess_blob_gas,
+            &exp_header.excess_blob_gas,
+        ),
+        parent_beacon_block_root: field_matches(
+            "Parent beacon block root",
+            parent_beacon_block_root,
+            &exp_header.parent_beacon_block_root,
+        ),
+        requests_hash: field_matches("Requests hash", requests_hash, &exp_header.requests_hash),
+        block_hash: header.hash() == exp_header.hash(),
+    };
 
-    if header.transactions_root != exp_header.transactions_root {
+    if !matches.block_hash {
         error!(
-            "Transactions root mismatch {} (expected {})",
-            header.transactions_root, exp_header.transactions_root
+            "Final block hash mismatch {} (expected {})",
+            header.hash(),
+            exp_header.hash(),
         );
     }
 
-    if header.receipts_root != exp_header.receipts_root {
-        error!(
-            "Receipts root mismatch {} (expected {})",
-            header.receipts_root, exp_header.receipts_root
-        );
-    }
+    matches
+}
 
-    if header.base_fee_per_gas != exp_header.base_fee_per_gas {
-        error!(
-            "Base fee mismatch {} (expected {})",
-            header.base_fee_per_gas, exp_header.base_fee_per_gas
-        );
+/// Returns whether the header field matches its expected value, logging it otherwise.
+fn field_matches<T: PartialEq + Debug>(name: &str, value: &T, expected: &T) -> bool {
+    let matches = value == expected;
+    if !matches {
+        error!("{} mismatch {:?} (expected {:?})", name, value, expected);
     }
+    matches
+}
 
-    if header.withdrawals_root != exp_header.withdrawals_root {
-        error!(
-            "Withdrawals root mismatch {:?} (expected {:?})",
-            header.withdrawals_root, exp_header.withdrawals_root
-        );
+fn compare_receipts<E: TxEssence>(
+    transactions: &[Transaction<E>],
+    receipts: &[Receipt],
+    rpc_receipts: &[Receipt],
+) -> Result<ReceiptComparison> {
+    let tx_hash = |tx_index: usize| -> Result<B256> {
+        Ok(transactions
+            .get(tx_index)
+            .context("missing transaction")?
+            .hash())
+    };
+
+    let mut mismatches = 0;
+    let mut first_divergence = None;
+    let (mut our_cumulative, mut rpc_cumulative) = (0, 0);
+    for (tx_index, (ours, rpc)) in receipts.iter().zip(rpc_receipts).enumerate() {
+        let our_gas = gas_used(ours, &mut our_cumulative)?;
+        let rpc_gas = gas_used(rpc, &mut rpc_cumulative)?;
+
+        let (ours, rpc) = (&ours.payload, &rpc.payload);
+        let divergence = ReceiptDivergence {
+            tx_index,
+            tx_hash: tx_hash(tx_index)?,
+            status: ours.success == rpc.success,
+            cumulative_gas_used: ours.cumulative_gas_used == rpc.cumulative_gas_used,
+            logs: ours.logs == rpc.logs,
+            logs_bloom: ours.logs_bloom == rpc.logs_bloom,
+            gas_delta: our_gas as i64 - rpc_gas as i64,
+        };
+        if divergence.status
+            && divergence.cumulative_gas_used
+            && divergence.logs
+            && divergence.logs_bloom
+        {
+            continue;
+        }
+
+        mismatches += 1;
+        if first_divergence.is_none() {
+            error!(
+                "Execution diverged at transaction {} ({}): {:?}",
+                tx_index, divergence.tx_hash, divergence
+            );
+            first_divergence = Some(divergence);
+        }
     }
 
-    let found_hash = header.hash();
-    let expected_hash = exp_header.hash();
-    if found_hash.as_slice() != expected_hash.as_slice() {
+    // every receipt without a counterpart is a mismatch in all fields
+    if receipts.len() != rpc_receipts.len() {
         error!(
-            "Final block hash mismatch {} (expected {})",
-            found_hash, expected_hash,
+            "Receipt count mismatch {} (expected {})",
+            receipts.len(),
+            rpc_receipts.len()
         );
-
-        bail!("Invalid block hash");
+        let tx_index = receipts.len().min(rpc_receipts.len());
+        mismatches += receipts.len().abs_diff(rpc_receipts.len());
+        if first_divergence.is_none() {
+            let our_gas = match receipts.get(tx_index) {
+                Some(receipt) => gas_used(receipt, &mut our_cumulative)?,
+                None => 0,
+            };
+            let rpc_gas = match rpc_receipts.get(tx_index) {
+                Some(receipt) => gas_used(receipt, &mut rpc_cumulative)?,
+                None => 0,
+            };
+            first_divergence = Some(ReceiptDivergence {
+                tx_index,
+                tx_hash: tx_hash(tx_index)?,
+                status: false,
+                cumulative_gas_used: false,
+                logs: false,
+                logs_bloom: false,
+                gas_delta: our_gas as i64 - rpc_gas as i64,
+            });
+        }
+    }
+    if mismatches > 0 {
+        error!("Verify found {} mismatching receipt(s)", mismatches);
     }
 
-    Ok(())
+    Ok(ReceiptComparison {
+        receipts: receipts.len(),
+        expected_receipts: rpc_receipts.len(),
+        mismatches,
+        first_divergence,
+    })
+}
+
+/// Returns the gas used by the transaction of the receipt, given the cumulative gas used
+/// by all previous transactions, which is then updated.
+fn gas_used(receipt: &Receipt, cumulative: &mut u64) -> Result<u64> {
+    let total: u64 = receipt
+        .payload
+        .cumulative_gas_used
+        .try_into()
+        .ok()
+        .context("invalid cumulative gas")?;
+    let gas = total
+        .checked_sub(*cumulative)
+        .context("decreasing cumulative gas")?;
+    *cumulative = total;
+    Ok(gas)
 }
 
 fn verify_state_trie(
@@ -235,7 +535,223 @@ fn verify_state_trie(
     Ok(errors)
 }
 
+/// Compares our storage trie of the given account after the block with the RPC's view.
+fn verify_storage_trie<E: TxEssence>(
+    data: &preflight::Data<E>,
+    address: &Address,
+) -> Result<Vec<VerifyError>> {
+    let parent_proof = data
+        .parent_proofs
+        .get(address)
+        .context("missing parent proof")?;
+    let proof = data.proofs.get(address).context("missing proof")?;
+
+    // reconstruct the sparse storage trie before the block and apply our changes
+    let (_, mut storage) = preflight::proofs_to_tries(
+        data.parent_header.state_root,
+        iter::once((*address, parent_proof.clone())).collect(),
+        iter::once((*address, proof.clone())).collect(),
+    )?;
+    let (mut our_trie, _) = storage.remove(address).context("missing storage trie")?;
+    let mut slots: HashMap<B256, U256> = HashMap::new();
+    if let Some(account) = data.changed_accounts.get(address) {
+        if matches!(
+            account.state,
+            AccountState::Deleted | AccountState::StorageCleared
+        ) {
+            our_trie.clear();
+        }
+        if account.state != AccountState::Deleted {
+            let mut updates: Vec<_> = account
+                .storage
+                .iter()
+                .map(|(index, value)| {
+                    let key = keccak(index.to_be_bytes::<32>());
+                    slots.insert(key.into(), *index);
+                    (
+                        key,
+                        (*value != U256::ZERO).then(|| alloy_rlp::encode(value)),
+                    )
+                })
+                .collect();
+            updates.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            our_trie.batch_update(updates)?;
+        }
+    }
+
+    let rpc_trie =
+        storage_trie_from_proof(from_ethers_h256(proof.storage_hash), &proof.storage_proof)
+            .context("invalid storage_proof")?;
+    for storage_proof in &proof.storage_proof {
+        let index = from_ethers_u256(storage_proof.key);
+        slots.insert(keccak(index.to_be_bytes::<32>()).into(), index);
+    }
+
+    let decode = |value: Option<Vec<u8>>| -> Result<Option<U256>> {
+        Ok(value.map(|v| U256::decode(&mut v.as_slice())).transpose()?)
+    };
+    our_trie
+        .diff(&rpc_trie)
+        .into_iter()
+        .map(|diff| match diff {
+            MptDiff::Value { key, old, new } => {
+                let key = B256::from_slice(&key);
+                Ok(VerifyError::StorageSlotMismatch {
+                    key,
+                    index: slots.get(&key).copied(),
+                    rpc_value: decode(new)?,
+                    our_value: decode(old)?,
+                })
+            }
+            MptDiff::Unresolved { path, .. } => Ok(VerifyError::UnresolvedStorage { path }),
+        })
+        .collect()
+}
+
+/// Creates the sparse storage trie containing all the nodes of the given proofs.
+fn storage_trie_from_proof(storage_root: B256, proofs: &[StorageProof]) -> Result<MptNode> {
+    let mut nodes = HashMap::new();
+    for storage_proof in proofs {
+        for node in mpt::parse_proof(&storage_proof.proof)? {
+            nodes.insert(node.reference(), node);
+        }
+    }
+    let trie = mpt::resolve_nodes(&storage_root.into(), &nodes);
+    if trie.hash() != storage_root {
+        bail!("storage root mismatch");
+    }
+
+    Ok(trie)
+}
+
 fn account_deleted(key: &[u8], proof: &[impl AsRef<[u8]>]) -> Result<bool> {
     let proof_nodes = mpt::parse_proof(proof).context("invalid encoding")?;
     mpt::is_not_included(key, &proof_nodes)
 }
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::{
+        transactions::{
+            ethereum::{EthereumTxEssence, TxEssenceLegacy},
+            signature::TxSignature,
+        },
+        Bloom,
+    };
+
+    use super::*;
+
+    /// Returns the names of the header fields that do not match.
+    fn mismatches(matches: HeaderMatches) -> Vec<String> {
+        let serde_json::Value::Object(flags) = serde_json::to_value(matches).unwrap() else {
+            panic!("not an object")
+        };
+        let mut names: Vec<_> = flags
+            .into_iter()
+            .filter(|(_, flag)| flag == &serde_json::Value::Bool(false))
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn header_mismatch() {
+        let expected = Header::default();
+        assert!(mismatches(verify_header(&expected, &expected)).is_empty());
+
+        let header = Header {
+            receipts_root: B256::repeat_byte(0x01),
+            ..expected.clone()
+        };
+        let matches = verify_header(&header, &expected);
+        assert_eq!(mismatches(matches), ["block_hash", "receipts_root"]);
+
+        // fields outside of the roots are compared as well
+        let header = Header {
+            gas_used: U256::from(21_000),
+            logs_bloom: Bloom::repeat_byte(0xff),
+            ..expected.clone()
+        };
+        let matches = verify_header(&header, &expected);
+        assert!(!matches.gas_used && !matches.logs_bloom);
+        assert_eq!(
+            mismatches(matches),
+            ["block_hash", "gas_used", "logs_bloom"]
+        );
+    }
+
+    #[test]
+    fn receipt_divergence() {
+        let receipt = |cumulative_gas_used: u64| {
+            Receipt::new(2, true, U256::from(cumulative_gas_used), Vec::new())
+        };
+        let transactions = vec![
+            Transaction {
+                essence: EthereumTxEssence::Legacy(TxEssenceLegacy::default()),
+                signature: TxSignature::default(),
+            };
+            3
+        ];
+
+        let rpc_receipts = vec![receipt(21_000), receipt(42_000), receipt(63_000)];
+        let comparison = compare_receipts(&transactions, &rpc_receipts, &rpc_receipts).unwrap();
+        assert_eq!(comparison.mismatches, 0);
+        assert_eq!(comparison.first_divergence, None);
+
+        // the second transaction uses 100 more gas, which shifts all following receipts
+        let receipts = vec![receipt(21_000), receipt(42_100), receipt(63_100)];
+        let comparison = compare_receipts(&transactions, &receipts, &rpc_receipts).unwrap();
+        assert_eq!(comparison.mismatches, 2);
+        let divergence = comparison.first_divergence.unwrap();
+        assert_eq!(divergence.tx_index, 1);
+        assert!(divergence.status && divergence.logs && divergence.logs_bloom);
+        assert!(!divergence.cumulative_gas_used);
+        assert_eq!(divergence.gas_delta, 100);
+
+        // a missing receipt diverges at the first index without a counterpart
+        let comparison =
+            compare_receipts(&transactions, &rpc_receipts[..2], &rpc_receipts).unwrap();
+        assert_eq!((comparison.receipts, comparison.expected_receipts), (2, 3));
+        assert_eq!(comparison.mismatches, 1);
+        let divergence = comparison.first_divergence.unwrap();
+        assert_eq!(divergence.tx_index, 2);
+        assert_eq!(divergence.tx_hash, transactions[2].hash());
+        assert!(!divergence.status && !divergence.cumulative_gas_used);
+        assert_eq!(divergence.gas_delta, -21_000);
+
+        // an earlier divergence takes precedence
+        let comparison = compare_receipts(&transactions, &receipts[..2], &rpc_receipts).unwrap();
+        assert_eq!(comparison.mismatches, 2);
+        assert_eq!(comparison.first_divergence.unwrap().tx_index, 1);
+    }
+
+    #[test]
+    fn report_json() {
+        let report = VerificationReport {
+            block_number: 1,
+            block_hash: B256::repeat_byte(0x01),
+            expected_block_hash: B256::repeat_byte(0x02),
+            header: verify_header(&Header::default(), &Header::default()),
+            accounts_checked: 2,
+            accounts: vec![AccountErrors {
+                address: Address::repeat_byte(0x03),
+                errors: vec![
+                    VerifyError::NonceMismatch {
+                        rpc_value: 1,
+                        our_value: 2,
+                    },
+                    VerifyError::MissingAccount,
+                ],
+            }],
+            correct_accounts_percent: 50.0,
+            receipts: None,
+            valid: false,
+        };
+        assert!(report.ensure_valid().is_err());
+
+        let json = serde_json::to_string(&report).unwrap();
+        let decoded: VerificationReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, report);
+    }
+}
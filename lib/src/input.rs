@@ -28,17 +28,22 @@ use zeth_primitives::{
 /// Represents the state of an account's storage.
 /// The storage trie together with the used storage slots allow us to reconstruct all the
 /// required values.
-pub type StorageEntry = (MptNode, Vec<U256>);
+pub type StorageEntry<M = MptNode> = (M, Vec<U256>);
 
 /// External block input.
+///
+/// The tries are represented by [MptNode] by default, but any [Trie] implementation can
+/// be used.
+///
+/// [Trie]: zeth_primitives::trie::Trie
 #[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize, Serialize)]
-pub struct BlockBuildInput<E: TxEssence> {
+pub struct BlockBuildInput<E: TxEssence, M = MptNode> {
     /// Block and transaction data to execute
     pub state_input: StateInput<E>,
     /// State trie of the parent block.
-    pub parent_state_trie: MptNode,
+    pub parent_state_trie: M,
     /// Maps each address with its storage trie and the used storage slots.
-    pub parent_storage: HashMap<Address, StorageEntry>,
+    pub parent_storage: HashMap<Address, StorageEntry<M>>,
     /// The code of all unique contracts.
     pub contracts: Vec<Bytes>,
     /// List of at most 256 previous block headers
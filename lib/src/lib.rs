@@ -18,11 +18,13 @@ extern crate core;
 pub mod host;
 
 pub mod builder;
+pub mod compact;
 pub mod consts;
 pub mod input;
 pub mod mem_db;
 // pub mod optimism;
 pub mod output;
+pub mod stf;
 
 mod utils;
 
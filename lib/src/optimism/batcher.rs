@@ -156,7 +156,7 @@ impl Batcher {
                 number: eth_block.block_header.number,
                 hash: eth_block_hash,
                 timestamp: timestamp.try_into().unwrap(),
-                base_fee_per_gas: eth_block.block_header.base_fee_per_gas,
+                base_fee_per_gas: eth_block.block_header.base_fee_per_gas.unwrap_or_default(),
                 deposits: deposits::extract_transactions(&config, eth_block)?,
             },
         );
@@ -206,7 +206,7 @@ impl Batcher {
             number: eth_block.block_header.number,
             hash: eth_block_hash,
             timestamp: eth_block.block_header.timestamp.try_into().unwrap(),
-            base_fee_per_gas: eth_block.block_header.base_fee_per_gas,
+            base_fee_per_gas: eth_block.block_header.base_fee_per_gas.unwrap_or_default(),
             deposits: deposits::extract_transactions(&self.config, eth_block)?,
         })?;
 
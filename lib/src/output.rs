@@ -17,11 +17,11 @@ use zeth_primitives::{block::Header, trie::MptNode, B256};
 
 /// Output of block execution
 #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
-pub enum BlockBuildOutput {
+pub enum BlockBuildOutput<M = MptNode> {
     SUCCESS {
         hash: B256,
         head: Header,
-        state: MptNode,
+        state: M,
         state_input_hash: B256,
     },
     FAILURE {
@@ -29,7 +29,7 @@ pub enum BlockBuildOutput {
     },
 }
 
-impl BlockBuildOutput {
+impl<M> BlockBuildOutput<M> {
     /// Returns true iff of type [`BlockBuildOutput::SUCCESS`]
     pub fn success(&self) -> bool {
         match self {
@@ -59,9 +59,11 @@ impl BlockBuildOutput {
             } => None,
         }
     }
+}
 
+impl<M: From<B256>> BlockBuildOutput<M> {
     /// Replaces the `state` [`MptNode`] with its root hash, returning the original state.
-    pub fn replace_state_with_hash(&mut self) -> Option<MptNode> {
+    pub fn replace_state_with_hash(&mut self) -> Option<M> {
         if let BlockBuildOutput::SUCCESS {
             head: new_block_head,
             state: new_block_state,
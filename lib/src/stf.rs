@@ -0,0 +1,295 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The interface of the `fluent-stf` guest.
+
+use anyhow::{bail, ensure, Result};
+use serde::{Deserialize, Serialize};
+use zeth_primitives::{block::Header, B256, U256};
+
+use crate::{consts::ChainSpec, output::BlockBuildOutput};
+
+/// Version of the [StfInputHeader] format.
+pub const STF_INPUT_VERSION: u32 = 2;
+
+/// Version of the encoding of the [StfOutput].
+pub const STF_OUTPUT_VERSION: u8 = 2;
+
+/// Size of the encoded [StfOutput] in bytes.
+pub const STF_OUTPUT_SIZE: usize = 9 * 32;
+
+/// The [BlockBuilderStrategy](crate::builder::BlockBuilderStrategy) used by the guest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum StfStrategy {
+    /// The [EthereumStrategy](crate::builder::EthereumStrategy).
+    Ethereum = 0,
+}
+
+/// The envelope preceding the [CompactBlockBuildInput](crate::compact::CompactBlockBuildInput)
+/// in the input of the `fluent-stf` guest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StfInputHeader {
+    /// Must be [STF_INPUT_VERSION].
+    pub version: u32,
+    pub strategy: StfStrategy,
+    /// The rules used to build the block, committed as [ChainSpec::hash] in the output.
+    pub chain_spec: ChainSpec,
+}
+
+impl StfInputHeader {
+    pub fn new(strategy: StfStrategy, chain_spec: ChainSpec) -> Self {
+        StfInputHeader {
+            version: STF_INPUT_VERSION,
+            strategy,
+            chain_spec,
+        }
+    }
+}
+
+/// The reason why the guest did not build a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum StfFailure {
+    /// The input could not be decoded.
+    InvalidInput = 1,
+    /// The input does not contain a valid witness of the parent state, this does not
+    /// indicate a faulty block.
+    InvalidWitness = 2,
+    /// The block is faulty, i.e. its header or one of its transactions is invalid.
+    InvalidBlock = 3,
+}
+
+impl TryFrom<u8> for StfFailure {
+    type Error = anyhow::Error;
+
+    fn try_from(code: u8) -> Result<Self> {
+        match code {
+            1 => Ok(StfFailure::InvalidInput),
+            2 => Ok(StfFailure::InvalidWitness),
+            3 => Ok(StfFailure::InvalidBlock),
+            _ => bail!("invalid failure reason {code}"),
+        }
+    }
+}
+
+/// The output written by the `fluent-stf` guest.
+///
+/// It is encoded like the Solidity tuple
+/// `(uint8 version, bool success, uint8 failure, uint64 blockNumber, bytes32 blockHash,
+/// bytes32 parentHash, bytes32 stateRoot, bytes32 stateInputHash, bytes32 chainSpecHash)`,
+/// so that it can be decoded by the verifier contract with `abi.decode`. Fields that are
+/// not known because of a failure are zero.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StfOutput {
+    /// The reason of the failure, or `None` if the block was built.
+    pub failure: Option<StfFailure>,
+    pub block_number: u64,
+    pub block_hash: B256,
+    pub parent_hash: B256,
+    pub state_root: B256,
+    pub state_input_hash: B256,
+    /// The [ChainSpec::hash] of the chain spec in the [StfInputHeader].
+    pub chain_spec_hash: B256,
+}
+
+impl StfOutput {
+    /// Returns the output for the result of building a block on top of `parent_header`.
+    pub fn new<M>(parent_header: &Header, output: &BlockBuildOutput<M>) -> Self {
+        match output {
+            BlockBuildOutput::SUCCESS {
+                hash,
+                head,
+                state_input_hash,
+                ..
+            } => StfOutput {
+                failure: None,
+                block_number: head.number,
+                block_hash: *hash,
+                parent_hash: head.parent_hash,
+                state_root: head.state_root,
+                state_input_hash: *state_input_hash,
+                ..Default::default()
+            },
+            BlockBuildOutput::FAILURE { state_input_hash } => StfOutput {
+                state_input_hash: *state_input_hash,
+                ..StfOutput::failed(StfFailure::InvalidBlock, Some(parent_header))
+            },
+        }
+    }
+
+    /// Returns the output for a failure, with the block number and the parent hash if
+    /// the parent is known.
+    pub fn failed(failure: StfFailure, parent_header: Option<&Header>) -> Self {
+        StfOutput {
+            failure: Some(failure),
+            block_number: parent_header.map_or(0, |header| header.number + 1),
+            parent_hash: parent_header.map_or(B256::ZERO, |header| header.hash()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the output committing to the given chain spec.
+    pub fn with_chain_spec(self, chain_spec: &ChainSpec) -> Self {
+        StfOutput {
+            chain_spec_hash: chain_spec.hash(),
+            ..self
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    /// Encodes the output as [STF_OUTPUT_SIZE] bytes.
+    pub fn encode(&self) -> Vec<u8> {
+        let words: [B256; 9] = [
+            U256::from(STF_OUTPUT_VERSION).into(),
+            U256::from(self.success() as u8).into(),
+            U256::from(self.failure.map_or(0, |failure| failure as u8)).into(),
+            U256::from(self.block_number).into(),
+            self.block_hash,
+            self.parent_hash,
+            self.state_root,
+            self.state_input_hash,
+            self.chain_spec_hash,
+        ];
+        words.concat()
+    }
+
+    /// Decodes an output encoded by [StfOutput::encode].
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() == STF_OUTPUT_SIZE,
+            "invalid output size {}, expected {}",
+            bytes.len(),
+            STF_OUTPUT_SIZE
+        );
+        let words: Vec<B256> = bytes.chunks_exact(32).map(B256::from_slice).collect();
+        let small = |index: usize, max: u64| {
+            let value = U256::from_be_bytes(words[index].0);
+            ensure!(value <= U256::from(max), "invalid value in word {index}");
+            Ok(value.to::<u64>())
+        };
+
+        let version = small(0, u8::MAX.into())?;
+        ensure!(
+            version == STF_OUTPUT_VERSION as u64,
+            "unsupported output version {version}, expected {STF_OUTPUT_VERSION}"
+        );
+        let success = small(1, 1)? == 1;
+        let failure = match small(2, u8::MAX.into())? {
+            0 => None,
+            code => Some(StfFailure::try_from(code as u8)?),
+        };
+        ensure!(
+            success == failure.is_none(),
+            "success flag does not match the failure reason"
+        );
+
+        Ok(StfOutput {
+            failure,
+            block_number: small(3, u64::MAX)?,
+            block_hash: words[4],
+            parent_hash: words[5],
+            state_root: words[6],
+            state_input_hash: words[7],
+            chain_spec_hash: words[8],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::ETH_MAINNET_CHAIN_SPEC;
+
+    fn header() -> Header {
+        Header {
+            number: 41,
+            state_root: B256::repeat_byte(0x5e),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn encoding() {
+        let output = StfOutput::new(
+            &header(),
+            &BlockBuildOutput::SUCCESS {
+                hash: B256::repeat_byte(0x01),
+                head: Header {
+                    number: 42,
+                    parent_hash: header().hash(),
+                    state_root: B256::repeat_byte(0x02),
+                    ..Default::default()
+                },
+                state: (),
+                state_input_hash: B256::repeat_byte(0x03),
+            },
+        )
+        .with_chain_spec(&ETH_MAINNET_CHAIN_SPEC);
+        let bytes = output.encode();
+        assert_eq!(bytes.len(), STF_OUTPUT_SIZE);
+        // the version and the block number are right-aligned in their words
+        assert_eq!(bytes[31], STF_OUTPUT_VERSION);
+        assert_eq!(bytes[96..128], U256::from(42).to_be_bytes::<32>());
+        assert_eq!(bytes[256..], ETH_MAINNET_CHAIN_SPEC.hash().0);
+        assert_eq!(StfOutput::decode(&bytes).unwrap(), output);
+
+        let output = StfOutput::new::<()>(
+            &header(),
+            &BlockBuildOutput::FAILURE {
+                state_input_hash: B256::repeat_byte(0x03),
+            },
+        );
+        assert_eq!(output.failure, Some(StfFailure::InvalidBlock));
+        assert_eq!(output.block_number, 42);
+        assert_eq!(output.parent_hash, header().hash());
+        assert_eq!(StfOutput::decode(&output.encode()).unwrap(), output);
+
+        let output = StfOutput::failed(StfFailure::InvalidInput, None);
+        assert_eq!(output.block_number, 0);
+        assert_eq!(StfOutput::decode(&output.encode()).unwrap(), output);
+    }
+
+    #[test]
+    fn input_header() {
+        let header = StfInputHeader::new(StfStrategy::Ethereum, ETH_MAINNET_CHAIN_SPEC.clone());
+        let bytes = bincode::serialize(&header).unwrap();
+        let decoded: StfInputHeader = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded.version, STF_INPUT_VERSION);
+        assert_eq!(decoded.strategy, StfStrategy::Ethereum);
+        assert_eq!(decoded.chain_spec.hash(), ETH_MAINNET_CHAIN_SPEC.hash());
+    }
+
+    #[test]
+    fn invalid_encoding() {
+        let bytes = StfOutput::failed(StfFailure::InvalidWitness, Some(&header())).encode();
+        assert!(StfOutput::decode(&bytes[..STF_OUTPUT_SIZE - 1]).is_err());
+
+        let mut version = bytes.clone();
+        version[31] = STF_OUTPUT_VERSION + 1;
+        assert!(StfOutput::decode(&version).is_err());
+
+        // a failure reason requires the success flag to be unset
+        let mut success = bytes.clone();
+        success[63] = 1;
+        assert!(StfOutput::decode(&success).is_err());
+
+        let mut failure = bytes;
+        failure[95] = 4;
+        assert!(StfOutput::decode(&failure).is_err());
+    }
+}
@@ -0,0 +1,501 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Convert from Alloy RPC types.
+
+use alloy_primitives::{Address, B256, U256, U64};
+use alloy_rpc_types::{
+    AccessList as AlloyAccessList, AccessListItem as AlloyAccessListItem,
+    AnyTransactionReceipt as AlloyReceipt, Block as AlloyBlock, EIP1186AccountProofResponse,
+    Header as AlloyHeader, Transaction as AlloyTransaction, Withdrawal as AlloyWithdrawal,
+};
+use anyhow::{bail, Context};
+
+use crate::{
+    access_list::{AccessList, AccessListItem},
+    block::Header,
+    receipt::{Log, Receipt, ReceiptPayload, OPTIMISM_DEPOSIT_NONCE_VERSION},
+    transactions::{
+        ethereum::{
+            EthereumTxEssence, TransactionKind, TxEssenceEip1559, TxEssenceEip2930, TxEssenceLegacy,
+        },
+        signature::TxSignature,
+        Transaction, TxEssence,
+    },
+    trie::StateAccount,
+    withdrawal::Withdrawal,
+};
+
+/// Conversion from `AlloyAccessListItem` to the local [AccessListItem].
+impl From<AlloyAccessListItem> for AccessListItem {
+    fn from(item: AlloyAccessListItem) -> Self {
+        AccessListItem {
+            address: item.address,
+            storage_keys: item.storage_keys,
+        }
+    }
+}
+
+/// Conversion from `AlloyAccessList` to the local [AccessList].
+impl From<AlloyAccessList> for AccessList {
+    fn from(list: AlloyAccessList) -> Self {
+        AccessList(list.0.into_iter().map(|item| item.into()).collect())
+    }
+}
+
+/// Convert an optional `Address` to the local [TransactionKind].
+impl From<Option<Address>> for TransactionKind {
+    fn from(addr: Option<Address>) -> Self {
+        match addr {
+            Some(address) => TransactionKind::Call(address),
+            None => TransactionKind::Create,
+        }
+    }
+}
+
+/// Conversion from `AlloyHeader` to the local [Header].
+/// This conversion may fail if certain expected fields are missing.
+///
+/// The `requestsHash` of Prague headers is unknown to `AlloyHeader`, so it is only set
+/// when converting the whole block.
+impl TryFrom<AlloyHeader> for Header {
+    type Error = anyhow::Error;
+
+    fn try_from(header: AlloyHeader) -> Result<Self, Self::Error> {
+        Ok(Header {
+            parent_hash: header.parent_hash,
+            ommers_hash: header.uncles_hash,
+            beneficiary: header.miner,
+            state_root: header.state_root,
+            transactions_root: header.transactions_root,
+            receipts_root: header.receipts_root,
+            logs_bloom: header.logs_bloom,
+            difficulty: header.difficulty,
+            number: header.number.context("number missing")?,
+            gas_limit: U256::from(header.gas_limit),
+            gas_used: U256::from(header.gas_used),
+            timestamp: U256::from(header.timestamp),
+            extra_data: header.extra_data,
+            mix_hash: header.mix_hash.context("mix_hash missing")?,
+            nonce: header.nonce.context("nonce missing")?,
+            base_fee_per_gas: header.base_fee_per_gas.map(U256::from),
+            withdrawals_root: header.withdrawals_root,
+            blob_gas_used: header.blob_gas_used.map(u64::try_from).transpose()?,
+            excess_blob_gas: header.excess_blob_gas.map(u64::try_from).transpose()?,
+            parent_beacon_block_root: header.parent_beacon_block_root,
+            requests_hash: None,
+        })
+    }
+}
+
+/// Conversion from `AlloyBlock` to the local [Header].
+/// This conversion may fail if certain expected fields are missing.
+///
+/// The `requestsHash` of Prague headers is read from the unknown fields of the block.
+impl<T> TryFrom<AlloyBlock<T>> for Header {
+    type Error = anyhow::Error;
+
+    fn try_from(block: AlloyBlock<T>) -> Result<Self, Self::Error> {
+        let requests_hash = block
+            .other
+            .get_deserialized::<B256>("requestsHash")
+            .transpose()
+            .context("invalid requestsHash")?;
+        let header: Header = block.header.try_into()?;
+
+        Ok(Header {
+            requests_hash,
+            ..header
+        })
+    }
+}
+
+/// Conversion from `AlloyTransaction` to the local [Transaction].
+/// This conversion may fail if certain expected fields are missing.
+impl<E: TxEssence + TryFrom<AlloyTransaction, Error = anyhow::Error>> TryFrom<AlloyTransaction>
+    for Transaction<E>
+{
+    type Error = anyhow::Error;
+
+    fn try_from(value: AlloyTransaction) -> Result<Self, Self::Error> {
+        let signature = value.signature.context("signature missing")?;
+        let signature = TxSignature {
+            v: signature.v.try_into().ok().context("invalid v")?,
+            r: signature.r,
+            s: signature.s,
+        };
+        let essence = value.try_into()?;
+
+        Ok(Transaction { essence, signature })
+    }
+}
+
+/// Conversion from `AlloyTransaction` to the local [EthereumTxEssence].
+/// This conversion may fail if certain expected fields are missing.
+impl TryFrom<AlloyTransaction> for EthereumTxEssence {
+    type Error = anyhow::Error;
+
+    fn try_from(tx: AlloyTransaction) -> Result<Self, Self::Error> {
+        let essence = match tx.transaction_type {
+            None | Some(0) => EthereumTxEssence::Legacy(TxEssenceLegacy {
+                chain_id: tx.chain_id,
+                nonce: tx.nonce,
+                gas_price: U256::from(tx.gas_price.context("gas_price missing")?),
+                gas_limit: U256::from(tx.gas),
+                to: tx.to.into(),
+                value: tx.value,
+                data: tx.input,
+            }),
+            Some(1) => EthereumTxEssence::Eip2930(TxEssenceEip2930 {
+                chain_id: tx.chain_id.context("chain_id missing")?,
+                nonce: tx.nonce,
+                gas_price: U256::from(tx.gas_price.context("gas_price missing")?),
+                gas_limit: U256::from(tx.gas),
+                to: tx.to.into(),
+                value: tx.value,
+                access_list: tx.access_list.context("access_list missing")?.into(),
+                data: tx.input,
+            }),
+            Some(2) => EthereumTxEssence::Eip1559(TxEssenceEip1559 {
+                chain_id: tx.chain_id.context("chain_id missing")?,
+                nonce: tx.nonce,
+                max_priority_fee_per_gas: U256::from(
+                    tx.max_priority_fee_per_gas
+                        .context("max_priority_fee_per_gas missing")?,
+                ),
+                max_fee_per_gas: U256::from(tx.max_fee_per_gas.context("max_fee_per_gas missing")?),
+                gas_limit: U256::from(tx.gas),
+                to: tx.to.into(),
+                value: tx.value,
+                access_list: tx.access_list.context("access_list missing")?.into(),
+                data: tx.input,
+            }),
+            Some(tx_type) => bail!("unsupported transaction type: {}", tx_type),
+        };
+        Ok(essence)
+    }
+}
+
+/// Conversion from `AlloyWithdrawal` to the local [Withdrawal].
+impl From<AlloyWithdrawal> for Withdrawal {
+    fn from(withdrawal: AlloyWithdrawal) -> Self {
+        Withdrawal {
+            index: withdrawal.index,
+            validator_index: withdrawal.validator_index,
+            address: withdrawal.address,
+            amount: withdrawal.amount,
+        }
+    }
+}
+
+/// Conversion from `AlloyReceipt` to the local [Receipt].
+///
+/// The receipt is taken with its unknown fields, so that the deposit nonce and its
+/// version of Optimism deposit receipts are preserved.
+impl TryFrom<AlloyReceipt> for Receipt {
+    type Error = anyhow::Error;
+
+    fn try_from(receipt: AlloyReceipt) -> Result<Self, Self::Error> {
+        let deposit_nonce = receipt
+            .other
+            .get_deserialized::<U64>("depositNonce")
+            .transpose()
+            .context("invalid depositNonce")?
+            .map(|nonce| nonce.to::<u64>());
+        let deposit_nonce_version = match receipt
+            .other
+            .get_deserialized::<U64>("depositReceiptVersion")
+            .transpose()
+            .context("invalid depositReceiptVersion")?
+        {
+            Some(version) => Some(
+                version
+                    .try_into()
+                    .context("invalid depositReceiptVersion")?,
+            ),
+            None => deposit_nonce.map(|_| OPTIMISM_DEPOSIT_NONCE_VERSION),
+        };
+
+        let envelope = receipt.inner.inner;
+        Ok(Receipt {
+            tx_type: envelope.r#type,
+            payload: ReceiptPayload {
+                success: envelope.status(),
+                cumulative_gas_used: U256::from(envelope.cumulative_gas_used()),
+                logs_bloom: envelope.bloom(),
+                logs: envelope
+                    .logs()
+                    .iter()
+                    .map(|log| Log {
+                        address: log.inner.address,
+                        topics: log.inner.data.topics().to_vec(),
+                        data: log.inner.data.data.clone(),
+                    })
+                    .collect(),
+                deposit_nonce,
+                deposit_nonce_version,
+            },
+        })
+    }
+}
+
+/// Conversion from `EIP1186AccountProofResponse` to the local [StateAccount].
+impl From<EIP1186AccountProofResponse> for StateAccount {
+    fn from(response: EIP1186AccountProofResponse) -> Self {
+        StateAccount {
+            nonce: response.nonce,
+            balance: response.balance,
+            storage_root: response.storage_hash,
+            code_hash: response.code_hash,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{address, b256, bytes};
+    use serde_json::json;
+
+    use super::*;
+    use crate::transactions::EthereumTransaction;
+
+    #[test]
+    fn block_header() {
+        // mainnet block 18090206
+        let block: AlloyBlock = serde_json::from_value(json!({
+            "hash": "0x0e370e334fe75e14371a22557f0f0671ff3d5fc8618e6135cf133ff2acdf12bc",
+            "parentHash": "0xd26535a618b36d40c36460836c0ee771d740dd03d733480c61b1307efbed67eb",
+            "sha3Uncles": "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347",
+            "miner": "0xeee27662c2b8eba3cd936a23f039f3189633e4c8",
+            "stateRoot": "0x604113fabbd508ed7773a92f8cf297100834f0a88c6a65cd3230ab8d347cb213",
+            "transactionsRoot": "0x2233057ba778494343a535de60268c314410075fc42fa17b68122cbcbf6593dd",
+            "receiptsRoot": "0xeab9d0c42ecb845ebbcd1c965755e341b93ce10f03faffbe61c015291062ce10",
+            "logsBloom": "0xb967e4b673a36a08c63068e6fe056b2a3da9282126e13a2e4c8d552ea7638c00c00001f961176a5144b013295c1519bee6af2434c8232aec7c7875ccb37c650040b97618cffd3eb9fa4ee7cfeea99a3c6fa152c63b644bbccd744e56e2e73040da0560ca57a685e14680993c090abe5964bc1cc9b91805c09e8bc97ec10a5d022f379ad25321e98c46d731e9a334a426cc20890dadaa067d5fef9c621dd40fb88e31774c5fe1e0c66336c8e4d7185ec8ecf346f26e6f094a67d0ec3b149a4a50869c02be8021eed32d060114cc7b93c12072ba40c66dbc18df25022629bf60329c10a493056670a9865e84f1f807a80984a696981bb829542ba1bee7b5910f13",
+            "number": "0x11408de",
+            "gasUsed": "0xbb24ce",
+            "gasLimit": "0x1c9c380",
+            "extraData": "0x546974616e2028746974616e6275696c6465722e78797a29",
+            "timestamp": "0x64facce7",
+            "difficulty": "0x0",
+            "totalDifficulty": "0xc70d815d562d3cfa955",
+            "uncles": [],
+            "size": "0xd078",
+            "mixHash": "0xeeec157604a40f5fa0401616be341a09148db95c155ec8d6e84a07c4fe023396",
+            "nonce": "0x0000000000000000",
+            "baseFeePerGas": "0x29d45eec1",
+            "withdrawalsRoot": "0x23e58c2863ab315ec90b2a489a59e76407b0bce5e9954b1b48da4f49ba401aa7",
+            "transactions": []
+        }))
+        .unwrap();
+        let expected_hash = block.header.hash.unwrap();
+
+        let header = Header::try_from(block).unwrap();
+        assert_eq!(header.base_fee_per_gas, Some(U256::from(0x29d45eec1_u64)));
+        assert_eq!(header.blob_gas_used, None);
+        assert_eq!(header.hash(), expected_hash);
+    }
+
+    #[test]
+    fn legacy_transaction() {
+        // first transaction of mainnet block 18090206
+        let tx: AlloyTransaction = serde_json::from_value(json!({
+            "hash": "0x6832bc9ea82e79441cc3d41d7bc3c6ce2f4485b55ddd3f8ec67e04c152cf8079",
+            "nonce": "0x5ca",
+            "blockHash": "0x0e370e334fe75e14371a22557f0f0671ff3d5fc8618e6135cf133ff2acdf12bc",
+            "blockNumber": "0x11408de",
+            "transactionIndex": "0x0",
+            "from": "0xb24c8ef593563e1fff3ba6e8bb3a8b7042beff1f",
+            "to": "0x599bb2682a16b24b0f8e27cc549f66a9981bc6fd",
+            "value": "0x0",
+            "gasPrice": "0x156a443f07",
+            "gas": "0xdd31",
+            "input": "0x095ea7b30000000000000000000000007a250d5630b4cf539739df2c5dacb4c659f2488dffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+            "v": "0x25",
+            "r": "0xb8d2d7accff9e088c8d53745e503d9420920958fbe543feef647636677f6e8d",
+            "s": "0x16c0546903cf2123108429bf94aa64d0248da4494ed9cd0db559bbfa06513b90",
+            "type": "0x0",
+            "chainId": "0x1"
+        }))
+        .unwrap();
+        let expected_hash = tx.hash;
+        let expected_from = tx.from;
+
+        let tx = EthereumTransaction::try_from(tx).unwrap();
+        assert!(matches!(tx.essence, EthereumTxEssence::Legacy(_)));
+        assert_eq!(tx.hash(), expected_hash);
+        assert_eq!(tx.recover_from().unwrap(), expected_from);
+    }
+
+    #[test]
+    fn eip1559_transaction() {
+        // second transaction of mainnet block 18090206
+        let tx: AlloyTransaction = serde_json::from_value(json!({
+            "hash": "0x16adb9d8dd4a6940532794b58fc0c431e16d3030785acf803a39267c45679e6d",
+            "nonce": "0xcd",
+            "blockHash": "0x0e370e334fe75e14371a22557f0f0671ff3d5fc8618e6135cf133ff2acdf12bc",
+            "blockNumber": "0x11408de",
+            "transactionIndex": "0x1",
+            "from": "0xa37e605cbf06865f8fda0f7f72e397f320568511",
+            "to": "0xdb5889e35e379ef0498aae126fc2cce1fbd23216",
+            "value": "0xb1a2bc2ec50000",
+            "gasPrice": "0x61b57c4c1",
+            "gas": "0x4ad48",
+            "input": "0x0162e2d000000000000000000000000000000000000000000000000000000000000000e000000000000000000000000000000000000000000000000000000000000001600000000000000000000000007a250d5630b4cf539739df2c5dacb4c659f2488d0000000000000000000000000000000000000000000000000000000000000032000000000000000000000000000000000000000000000000000000000000000100000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000064facce700000000000000000000000000000000000000000000000000000000000000030000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000002000000000000000000000000c02aaa39b223fe8d0a0e5c4f27ead9083c756cc20000000000000000000000008144683a7f5ab4a8f01c0377387ce4de46ca57e0",
+            "v": "0x0",
+            "r": "0x7fddd3f909907ae8e756cdaea9a752c20404451e809fd64d8e8289a8720c88fe",
+            "s": "0x46965ce65869f4611f0bfec3cecb387fecab2a5eb3d37f6ef6ff14df4233c2e6",
+            "type": "0x2",
+            "accessList": [],
+            "maxPriorityFeePerGas": "0x37e11d600",
+            "maxFeePerGas": "0x61b57c4c1",
+            "chainId": "0x1"
+        }))
+        .unwrap();
+        let expected_hash = tx.hash;
+        let expected_from = tx.from;
+
+        let tx = EthereumTransaction::try_from(tx).unwrap();
+        assert!(matches!(tx.essence, EthereumTxEssence::Eip1559(_)));
+        assert_eq!(tx.hash(), expected_hash);
+        assert_eq!(tx.recover_from().unwrap(), expected_from);
+    }
+
+    #[test]
+    fn receipt() {
+        // receipt of the first transaction of mainnet block 18090206
+        let receipt: AlloyReceipt = serde_json::from_value(json!({
+            "transactionHash": "0x6832bc9ea82e79441cc3d41d7bc3c6ce2f4485b55ddd3f8ec67e04c152cf8079",
+            "transactionIndex": "0x0",
+            "blockHash": "0x0e370e334fe75e14371a22557f0f0671ff3d5fc8618e6135cf133ff2acdf12bc",
+            "blockNumber": "0x11408de",
+            "from": "0xb24c8ef593563e1fff3ba6e8bb3a8b7042beff1f",
+            "to": "0x599bb2682a16b24b0f8e27cc549f66a9981bc6fd",
+            "cumulativeGasUsed": "0xb854",
+            "gasUsed": "0xb854",
+            "contractAddress": null,
+            "logs": [
+                {
+                    "address": "0x599bb2682a16b24b0f8e27cc549f66a9981bc6fd",
+                    "topics": [
+                        "0x8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925",
+                        "0x000000000000000000000000b24c8ef593563e1fff3ba6e8bb3a8b7042beff1f",
+                        "0x0000000000000000000000007a250d5630b4cf539739df2c5dacb4c659f2488d"
+                    ],
+                    "data": "0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                    "blockHash": "0x0e370e334fe75e14371a22557f0f0671ff3d5fc8618e6135cf133ff2acdf12bc",
+                    "blockNumber": "0x11408de",
+                    "transactionHash": "0x6832bc9ea82e79441cc3d41d7bc3c6ce2f4485b55ddd3f8ec67e04c152cf8079",
+                    "transactionIndex": "0x0",
+                    "logIndex": "0x0",
+                    "removed": false
+                }
+            ],
+            "status": "0x1",
+            "logsBloom": "0x00000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000000040000000000200000000000000000000000000000000000002000000000000000000000000000000000000080000000000000000000000000000000000000000000000000000000000000000000000000004000000000000000000100000000000200000000000000020000080000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000020000010000000000000000000000000000000000000000000000000000000000000",
+            "type": "0x0",
+            "effectiveGasPrice": "0x156a443f07"
+        }))
+        .unwrap();
+
+        let expected = Receipt::new(
+            0,
+            true,
+            U256::from(0xb854),
+            vec![Log {
+                address: address!("599bb2682a16b24b0f8e27cc549f66a9981bc6fd"),
+                topics: vec![
+                    b256!("8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925"),
+                    b256!("000000000000000000000000b24c8ef593563e1fff3ba6e8bb3a8b7042beff1f"),
+                    b256!("0000000000000000000000007a250d5630b4cf539739df2c5dacb4c659f2488d"),
+                ],
+                data: bytes!("ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff"),
+            }],
+        );
+        assert_eq!(Receipt::try_from(receipt).unwrap(), expected);
+    }
+
+    #[test]
+    fn deposit_receipt() {
+        // receipt of an Optimism deposit after the Canyon update
+        let receipt: AlloyReceipt = serde_json::from_value(json!({
+            "transactionHash": "0x3ab9bd1d7ac5e4b3eeaf3d7f4d5b53b8b8dd8ae1d2bf7c2cc10a98fc2a5e4b6f",
+            "transactionIndex": "0x0",
+            "blockHash": "0xc2930ec5efb31e42b744851e2f816629968c0eae5740ddeedf7873988bac311f",
+            "blockNumber": "0x68228c3",
+            "from": "0xdeaddeaddeaddeaddeaddeaddeaddeaddead0001",
+            "to": "0x4200000000000000000000000000000000000015",
+            "cumulativeGasUsed": "0xb741",
+            "gasUsed": "0xb741",
+            "contractAddress": null,
+            "logs": [],
+            "status": "0x1",
+            "logsBloom": format!("0x{}", "00".repeat(256)),
+            "type": "0x7e",
+            "effectiveGasPrice": "0x0",
+            "depositNonce": "0x3d3bbf",
+            "depositReceiptVersion": "0x1"
+        }))
+        .unwrap();
+
+        let receipt = Receipt::try_from(receipt).unwrap();
+        assert_eq!(
+            receipt,
+            Receipt::new(0x7e, true, U256::from(46913), vec![]).with_deposit_nonce(4012991)
+        );
+    }
+
+    #[test]
+    fn withdrawal() {
+        let withdrawal: AlloyWithdrawal = serde_json::from_value(json!({
+            "index": "0x101a116",
+            "validatorIndex": "0x9dcea",
+            "address": "0xb9d7934878b5fb9610b3fe8a5e441e8fad7e293f",
+            "amount": "0xf111e7"
+        }))
+        .unwrap();
+
+        assert_eq!(
+            Withdrawal::from(withdrawal),
+            Withdrawal {
+                index: 0x101a116,
+                validator_index: 0x9dcea,
+                address: address!("b9d7934878b5fb9610b3fe8a5e441e8fad7e293f"),
+                amount: 0xf111e7,
+            }
+        );
+    }
+
+    #[test]
+    fn account_proof() {
+        // proof of 0x..01 in the parent of Optimism block 109194691, with only its leaf
+        let proof: EIP1186AccountProofResponse = serde_json::from_value(json!({
+            "address": "0x0000000000000000000000000000000000000001",
+            "balance": "0x44c7d142da002",
+            "codeHash": "0xc5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+            "nonce": "0x0",
+            "storageHash": "0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421",
+            "accountProof": [
+                "0xf86e9e208056310c82aa4c01a7e12a10f8111a0560e72b700555479031b86c357db84df84b8087044c7d142da002a056e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421a0c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+            ],
+            "storageProof": []
+        }))
+        .unwrap();
+        let leaf = proof.account_proof[0].clone();
+
+        // the converted account must encode to the value stored in the leaf
+        let account = StateAccount::from(proof);
+        let encoded = alloy_rlp::encode(&account);
+        assert!(leaf.ends_with(&encoded));
+    }
+}
@@ -12,18 +12,33 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
 use alloy_primitives::{b256, Address, BlockHash, BlockNumber, Bloom, Bytes, B256, B64, U256};
-use alloy_rlp_derive::RlpEncodable;
+use alloy_rlp::{Decodable, Encodable};
 use serde::{Deserialize, Serialize};
 
-use crate::{keccak::keccak, r#const::EMPTY_ROOT};
+use crate::{
+    keccak::keccak,
+    r#const::EMPTY_ROOT,
+    transactions::{Transaction, TxEssence},
+    withdrawal::Withdrawal,
+    RlpBytes,
+};
 
 /// Keccak-256 hash of the RLP of an empty list.
 pub const EMPTY_LIST_HASH: B256 =
     b256!("1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347");
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, RlpEncodable)]
-#[rlp(trailing)]
+/// Represents a block header.
+///
+/// The fields added by later network upgrades are optional. When decoding from RLP, they are
+/// read as long as the list contains more values, and unknown additional trailing fields are
+/// rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Header {
     /// Hash of the parent block's header.
     pub parent_hash: BlockHash,
@@ -55,12 +70,27 @@ pub struct Header {
     pub mix_hash: B256,
     /// Unused 64-bit hash, always zero.
     pub nonce: B64,
-    /// Base fee paid by all transactions in the block.
-    pub base_fee_per_gas: U256,
+    /// Base fee paid by all transactions in the block. Present after the London update.
+    #[serde(default)]
+    pub base_fee_per_gas: Option<U256>,
     /// Root hash of the trie containing all withdrawals in the block. Present after the
     /// Shanghai update.
     #[serde(default)]
     pub withdrawals_root: Option<B256>,
+    /// Total amount of blob gas used by the transactions in this block. Present after the
+    /// Cancun update.
+    #[serde(default)]
+    pub blob_gas_used: Option<u64>,
+    /// Running total of blob gas consumed in excess of the target. Present after the Cancun
+    /// update.
+    #[serde(default)]
+    pub excess_blob_gas: Option<u64>,
+    /// Root of the parent beacon block. Present after the Cancun update.
+    #[serde(default)]
+    pub parent_beacon_block_root: Option<B256>,
+    /// Hash of the execution layer requests. Present after the Prague update.
+    #[serde(default)]
+    pub requests_hash: Option<B256>,
 }
 
 impl Default for Header {
@@ -82,8 +112,12 @@ impl Default for Header {
             extra_data: Bytes::new(),
             mix_hash: B256::ZERO,
             nonce: B64::ZERO,
-            base_fee_per_gas: U256::ZERO,
+            base_fee_per_gas: Some(U256::ZERO),
             withdrawals_root: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            parent_beacon_block_root: None,
+            requests_hash: None,
         }
     }
 }
@@ -93,6 +127,245 @@ impl Header {
     pub fn hash(&self) -> BlockHash {
         keccak(alloy_rlp::encode(self)).into()
     }
+
+    /// Returns the fields in the order of their RLP encoding. The optional fields are
+    /// included up to the first one that is not set.
+    fn fields(&self) -> Vec<&dyn Encodable> {
+        let mut fields: Vec<&dyn Encodable> = vec![
+            &self.parent_hash,
+            &self.ommers_hash,
+            &self.beneficiary,
+            &self.state_root,
+            &self.transactions_root,
+            &self.receipts_root,
+            &self.logs_bloom,
+            &self.difficulty,
+            &self.number,
+            &self.gas_limit,
+            &self.gas_used,
+            &self.timestamp,
+            &self.extra_data,
+            &self.mix_hash,
+            &self.nonce,
+        ];
+        let optional: [Option<&dyn Encodable>; 6] = [
+            self.base_fee_per_gas.as_ref().map(|v| v as &dyn Encodable),
+            self.withdrawals_root.as_ref().map(|v| v as &dyn Encodable),
+            self.blob_gas_used.as_ref().map(|v| v as &dyn Encodable),
+            self.excess_blob_gas.as_ref().map(|v| v as &dyn Encodable),
+            self.parent_beacon_block_root
+                .as_ref()
+                .map(|v| v as &dyn Encodable),
+            self.requests_hash.as_ref().map(|v| v as &dyn Encodable),
+        ];
+        fields.extend(optional.into_iter().map_while(|field| field));
+        fields
+    }
+
+    fn payload_length(&self) -> usize {
+        self.fields().iter().map(|field| field.length()).sum()
+    }
+}
+
+impl Encodable for Header {
+    fn encode(&self, out: &mut dyn alloy_rlp::BufMut) {
+        let fields = self.fields();
+        alloy_rlp::Header {
+            list: true,
+            payload_length: fields.iter().map(|field| field.length()).sum(),
+        }
+        .encode(out);
+        for field in fields {
+            field.encode(out);
+        }
+    }
+
+    fn length(&self) -> usize {
+        let payload_length = self.payload_length();
+        payload_length + alloy_rlp::length_of_length(payload_length)
+    }
+}
+
+impl Decodable for Header {
+    fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
+        let mut payload = alloy_rlp::Header::decode_bytes(buf, true)?;
+        let payload = &mut payload;
+
+        let header = Header {
+            parent_hash: Decodable::decode(payload)?,
+            ommers_hash: Decodable::decode(payload)?,
+            beneficiary: Decodable::decode(payload)?,
+            state_root: Decodable::decode(payload)?,
+            transactions_root: Decodable::decode(payload)?,
+            receipts_root: Decodable::decode(payload)?,
+            logs_bloom: Decodable::decode(payload)?,
+            difficulty: Decodable::decode(payload)?,
+            number: Decodable::decode(payload)?,
+            gas_limit: Decodable::decode(payload)?,
+            gas_used: Decodable::decode(payload)?,
+            timestamp: Decodable::decode(payload)?,
+            extra_data: Decodable::decode(payload)?,
+            mix_hash: Decodable::decode(payload)?,
+            nonce: Decodable::decode(payload)?,
+            base_fee_per_gas: decode_optional(payload)?,
+            withdrawals_root: decode_optional(payload)?,
+            blob_gas_used: decode_optional(payload)?,
+            excess_blob_gas: decode_optional(payload)?,
+            parent_beacon_block_root: decode_optional(payload)?,
+            requests_hash: decode_optional(payload)?,
+        };
+        if !payload.is_empty() {
+            return Err(alloy_rlp::Error::Custom(
+                "Unexpected trailing header fields",
+            ));
+        }
+
+        Ok(header)
+    }
+}
+
+/// Decodes an optional trailing field, which is present if the list contains more values.
+fn decode_optional<T: Decodable>(payload: &mut &[u8]) -> alloy_rlp::Result<Option<T>> {
+    if payload.is_empty() {
+        Ok(None)
+    } else {
+        T::decode(payload).map(Some)
+    }
+}
+
+/// Represents a complete block consisting of the header and the body.
+///
+/// The RLP encoding matches the one used by the devp2p protocol and `geth export`, i.e.
+/// `[header, [transactions], [ommers]]` with the list of withdrawals appended after the
+/// Shanghai update. In contrast to [Transaction], typed transactions are wrapped in an RLP
+/// string.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Block<E: TxEssence> {
+    /// The header of the block.
+    pub header: Header,
+    /// The transactions of the block in their order of execution.
+    pub transactions: Vec<Transaction<E>>,
+    /// The headers of the ommer blocks, always empty after the Paris update.
+    pub ommers: Vec<Header>,
+    /// The withdrawals of the block. Present after the Shanghai update.
+    #[serde(default)]
+    pub withdrawals: Option<Vec<Withdrawal>>,
+}
+
+impl<E: TxEssence> Block<E> {
+    /// Computes the hash of the block, i.e. the hash of its header.
+    pub fn hash(&self) -> BlockHash {
+        self.header.hash()
+    }
+
+    fn transactions_payload_length(&self) -> usize {
+        self.transactions.iter().map(tx_length).sum()
+    }
+
+    fn payload_length(&self) -> usize {
+        let transactions_payload_length = self.transactions_payload_length();
+        let mut length = self.header.length()
+            + transactions_payload_length
+            + alloy_rlp::length_of_length(transactions_payload_length)
+            + self.ommers.length();
+        if let Some(withdrawals) = &self.withdrawals {
+            length += withdrawals.length();
+        }
+        length
+    }
+}
+
+impl<E: TxEssence> Encodable for Block<E> {
+    fn encode(&self, out: &mut dyn alloy_rlp::BufMut) {
+        alloy_rlp::Header {
+            list: true,
+            payload_length: self.payload_length(),
+        }
+        .encode(out);
+        self.header.encode(out);
+        alloy_rlp::Header {
+            list: true,
+            payload_length: self.transactions_payload_length(),
+        }
+        .encode(out);
+        for tx in &self.transactions {
+            tx_encode(tx, out);
+        }
+        self.ommers.encode(out);
+        if let Some(withdrawals) = &self.withdrawals {
+            withdrawals.encode(out);
+        }
+    }
+
+    fn length(&self) -> usize {
+        let payload_length = self.payload_length();
+        payload_length + alloy_rlp::length_of_length(payload_length)
+    }
+}
+
+impl<E: TxEssence + Debug> Decodable for Block<E> {
+    fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
+        let mut payload = alloy_rlp::Header::decode_bytes(buf, true)?;
+
+        let header = Header::decode(&mut payload)?;
+        let mut transactions_payload = alloy_rlp::Header::decode_bytes(&mut payload, true)?;
+        let mut transactions = Vec::new();
+        while !transactions_payload.is_empty() {
+            transactions.push(tx_decode(&mut transactions_payload)?);
+        }
+        let ommers = Vec::<Header>::decode(&mut payload)?;
+        let withdrawals = if payload.is_empty() {
+            None
+        } else {
+            Some(Vec::<Withdrawal>::decode(&mut payload)?)
+        };
+        if !payload.is_empty() {
+            return Err(alloy_rlp::Error::Custom("Unexpected trailing block fields"));
+        }
+
+        Ok(Block {
+            header,
+            transactions,
+            ommers,
+            withdrawals,
+        })
+    }
+}
+
+/// Encodes a transaction as part of a block, wrapping typed transactions in a string.
+fn tx_encode<E: TxEssence>(tx: &Transaction<E>, out: &mut dyn alloy_rlp::BufMut) {
+    if tx.essence.tx_type() != 0 {
+        alloy_rlp::Header {
+            list: false,
+            payload_length: tx.length(),
+        }
+        .encode(out);
+    }
+    tx.encode(out);
+}
+
+/// Returns the length of a transaction encoded as part of a block.
+fn tx_length<E: TxEssence>(tx: &Transaction<E>) -> usize {
+    let length = tx.length();
+    if tx.essence.tx_type() != 0 {
+        length + alloy_rlp::length_of_length(length)
+    } else {
+        length
+    }
+}
+
+/// Decodes a transaction that is part of a block.
+fn tx_decode<E: TxEssence + Debug>(buf: &mut &[u8]) -> alloy_rlp::Result<Transaction<E>> {
+    // legacy transactions are encoded as a list
+    if alloy_rlp::Header::decode(&mut &buf[..])?.list {
+        return Transaction::decode(buf);
+    }
+    // typed transactions are wrapped in a string containing the EIP-2718 encoding
+    let typed = alloy_rlp::Header::decode_bytes(buf, false)?;
+    match typed.first() {
+        Some(tx_type) if *tx_type <= 0x7f => Transaction::decode_bytes(typed),
+        _ => Err(alloy_rlp::Error::Custom("Invalid typed transaction")),
+    }
 }
 
 #[cfg(test)]
@@ -100,6 +373,7 @@ mod tests {
     use serde_json::json;
 
     use super::*;
+    use crate::transactions::{ethereum::EthereumTxEssence, EthereumTransaction};
 
     #[test]
     fn paris() {
@@ -126,6 +400,9 @@ mod tests {
 
         // verify that bincode serialization works
         let _: Header = bincode::deserialize(&bincode::serialize(&header).unwrap()).unwrap();
+        // verify the RLP roundtrip
+        let decoded = Header::decode_bytes(alloy_rlp::encode(&header)).unwrap();
+        assert_eq!(decoded, header);
 
         assert_eq!(
             "0x56a9bb0302da44b8c0b3df540781424684c3af04d0b7a38d72842b762076a664",
@@ -159,10 +436,190 @@ mod tests {
 
         // verify that bincode serialization works
         let _: Header = bincode::deserialize(&bincode::serialize(&header).unwrap()).unwrap();
+        // verify the RLP roundtrip
+        let decoded = Header::decode_bytes(alloy_rlp::encode(&header)).unwrap();
+        assert_eq!(decoded, header);
 
         assert_eq!(
             "0xe22c56f211f03baadcc91e4eb9a24344e6848c5df4473988f893b58223f5216c",
             header.hash().to_string()
         )
     }
+
+    #[test]
+    fn frontier() {
+        // the genesis block of the Ethereum mainnet
+        let value = json!({
+            "parent_hash": "0x0000000000000000000000000000000000000000000000000000000000000000",
+            "ommers_hash": "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347",
+            "beneficiary": "0x0000000000000000000000000000000000000000",
+            "state_root": "0xd7f8974fb5ac78d9ac099b9ad5018bedc2ce0a72dad1827a1709da30580f0544",
+            "transactions_root": "0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421",
+            "receipts_root": "0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421",
+            "logs_bloom": format!("0x{}", "0".repeat(512)),
+            "difficulty": "0x400000000",
+            "number": 0,
+            "gas_limit": "0x1388",
+            "gas_used": "0x0",
+            "timestamp": "0x0",
+            "extra_data": "0x11bbe8db4e347b4e8c937c1c8370e4b5ed33adb3db69cbdb7a38e1e50b1b82fa",
+            "mix_hash": "0x0000000000000000000000000000000000000000000000000000000000000000",
+            "nonce": "0x0000000000000042"
+        });
+        let header: Header = serde_json::from_value(value).unwrap();
+        assert_eq!(header.base_fee_per_gas, None);
+
+        // the header is encoded without a base fee
+        let encoded = alloy_rlp::encode(&header);
+        let decoded = Header::decode_bytes(&encoded).unwrap();
+        assert_eq!(decoded, header);
+
+        assert_eq!(
+            "0xd4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3",
+            header.hash().to_string()
+        )
+    }
+
+    #[test]
+    fn cancun() {
+        // the blob gas used and the excess blob gas are zero, encoded as empty strings
+        let header = Header {
+            withdrawals_root: Some(EMPTY_ROOT),
+            blob_gas_used: Some(0),
+            excess_blob_gas: Some(0),
+            parent_beacon_block_root: Some(B256::repeat_byte(0xbe)),
+            ..Default::default()
+        };
+        let encoded = alloy_rlp::encode(&header);
+        assert_eq!(encoded.len(), header.length());
+        let decoded = Header::decode_bytes(&encoded).unwrap();
+        assert_eq!(decoded, header);
+
+        // the Cancun fields change the hash
+        let shanghai = Header {
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            parent_beacon_block_root: None,
+            ..header.clone()
+        };
+        assert_ne!(shanghai.hash(), header.hash());
+
+        // the bincode serialization includes the Cancun fields
+        let _: Header = bincode::deserialize(&bincode::serialize(&header).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn header_unknown_trailing_field() {
+        let header = Header {
+            withdrawals_root: Some(EMPTY_ROOT),
+            blob_gas_used: Some(0),
+            excess_blob_gas: Some(0),
+            parent_beacon_block_root: Some(B256::ZERO),
+            requests_hash: Some(B256::ZERO),
+            ..Default::default()
+        };
+        // append an additional field after the last known one
+        let mut payload = alloy_rlp::encode(&header);
+        let header_length = alloy_rlp::Header::decode(&mut &payload[..])
+            .unwrap()
+            .length();
+        payload.drain(..header_length);
+        0u64.encode(&mut payload);
+        let mut encoded = Vec::new();
+        alloy_rlp::Header {
+            list: true,
+            payload_length: payload.len(),
+        }
+        .encode(&mut encoded);
+        encoded.extend(payload);
+
+        assert!(Header::decode_bytes(encoded).is_err());
+    }
+
+    fn transactions() -> Vec<EthereumTransaction> {
+        let txs = json!([
+            {
+                "essence": {
+                    "Legacy": {
+                        "nonce": 0,
+                        "gas_price": "0x2d79883d2000",
+                        "gas_limit": "0x5208",
+                        "to": { "Call": "0x5df9b87991262f6ba471f09758cde1c0fc1de734" },
+                        "value": "0x7a69",
+                        "data": "0x"
+                    }
+                },
+                "signature": {
+                    "v": 28,
+                    "r": "0x88ff6cf0fefd94db46111149ae4bfc179e9b94721fffd821d38d16464b3f71d0",
+                    "s": "0x45e0aff800961cfce805daef7016b9b675c137a6a41a548f7b60a3484c06a33a"
+                }
+            },
+            {
+                "essence": {
+                    "Eip1559": {
+                        "chain_id": 1,
+                        "nonce": 32,
+                        "max_priority_fee_per_gas": "0x3b9aca00",
+                        "max_fee_per_gas": "0x89d5f3200",
+                        "gas_limit": "0x5b04",
+                        "to": { "Call": "0xa9d1e08c7793af67e9d92fe308d5697fb81d3e43" },
+                        "value": "0x1dd1f234f68cde2",
+                        "data": "0x",
+                        "access_list": []
+                    }
+                },
+                "signature": {
+                    "v": 0,
+                    "r": "0x2bdf47562da5f2a09f09cce70aed35ec9ac62f5377512b6a04cc427e0fda1f4d",
+                    "s": "0x28f9311b515a5f17aa3ad5ea8bafaecfb0958801f01ca11fd593097b5087121b"
+                }
+            }
+        ]);
+        serde_json::from_value(txs).unwrap()
+    }
+
+    #[test]
+    fn block_rlp() {
+        let transactions = transactions();
+        let block = Block::<EthereumTxEssence> {
+            header: Header {
+                withdrawals_root: Some(EMPTY_ROOT),
+                ..Default::default()
+            },
+            transactions: transactions.clone(),
+            ommers: vec![],
+            withdrawals: Some(vec![Withdrawal {
+                index: 1,
+                validator_index: 2,
+                address: Address::with_last_byte(3),
+                amount: 4,
+            }]),
+        };
+        let encoded = alloy_rlp::encode(&block);
+        assert_eq!(encoded.len(), block.length());
+
+        // the legacy transaction is a list, the typed transaction is wrapped in a string
+        let mut payload = alloy_rlp::Header::decode_bytes(&mut &encoded[..], true).unwrap();
+        Header::decode(&mut payload).unwrap();
+        let mut txs = alloy_rlp::Header::decode_bytes(&mut payload, true).unwrap();
+        let legacy = alloy_rlp::encode(&transactions[0]);
+        assert!(txs.starts_with(&legacy));
+        txs = &txs[legacy.len()..];
+        let typed = alloy_rlp::Header::decode_bytes(&mut txs, false).unwrap();
+        assert_eq!(typed, alloy_rlp::encode(&transactions[1]));
+        assert!(txs.is_empty());
+
+        assert_eq!(Block::decode_bytes(&encoded).unwrap(), block);
+
+        // blocks before the Shanghai update have no withdrawals
+        let block = Block {
+            withdrawals: None,
+            ..block
+        };
+        assert_eq!(
+            Block::decode_bytes(alloy_rlp::encode(&block)).unwrap(),
+            block
+        );
+    }
 }
@@ -72,6 +72,24 @@ pub fn from_ethers_bytes(v: EthersBytes) -> Bytes {
     v.0.into()
 }
 
+/// Convert an `Address` type to the `EthersH160` type.
+#[inline]
+pub fn to_ethers_h160(v: Address) -> EthersH160 {
+    v.into_array().into()
+}
+
+/// Convert a `B256` type to the `EthersH256` type.
+#[inline]
+pub fn to_ethers_h256(v: B256) -> EthersH256 {
+    v.0.into()
+}
+
+/// Convert a `Bytes` type to the `EthersBytes` type.
+#[inline]
+pub fn to_ethers_bytes(v: Bytes) -> EthersBytes {
+    v.0.into()
+}
+
 /// Conversion from `EthersAccessListItem` to the local [AccessListItem].
 impl From<EthersAccessListItem> for AccessListItem {
     fn from(item: EthersAccessListItem) -> Self {
@@ -105,6 +123,8 @@ impl From<Option<EthersH160>> for TransactionKind {
 
 /// Conversion from `EthersBlock` to the local [Header].
 /// This conversion may fail if certain expected fields are missing.
+///
+/// The `requestsHash` of Prague headers is read from the unknown fields of the block.
 impl<T> TryFrom<EthersBlock<T>> for Header {
     type Error = anyhow::Error;
 
@@ -127,10 +147,17 @@ impl<T> TryFrom<EthersBlock<T>> for Header {
             extra_data: block.extra_data.0.into(),
             mix_hash: block.mix_hash.context("mix_hash missing")?.0.into(),
             nonce: block.nonce.context("nonce missing")?.0.into(),
-            base_fee_per_gas: from_ethers_u256(
-                block.base_fee_per_gas.context("base_fee_per_gas missing")?,
-            ),
+            base_fee_per_gas: block.base_fee_per_gas.map(from_ethers_u256),
             withdrawals_root: block.withdrawals_root.map(from_ethers_h256),
+            blob_gas_used: block.blob_gas_used.map(|gas| gas.as_u64()),
+            excess_blob_gas: block.excess_blob_gas.map(|gas| gas.as_u64()),
+            parent_beacon_block_root: block.parent_beacon_block_root.map(from_ethers_h256),
+            requests_hash: block
+                .other
+                .get_deserialized::<EthersH256>("requestsHash")
+                .transpose()
+                .context("invalid requestsHash")?
+                .map(from_ethers_h256),
         })
     }
 }
@@ -27,6 +27,8 @@ pub mod transactions;
 pub mod trie;
 pub mod withdrawal;
 
+#[cfg(feature = "alloy")]
+pub mod alloy;
 #[cfg(feature = "ethers")]
 pub mod ethers;
 
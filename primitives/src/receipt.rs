@@ -15,15 +15,17 @@
 use alloc::{boxed::Box, format, vec, vec::Vec};
 
 use alloy_primitives::{Address, Bloom, BloomInput, Bytes, TxNumber, B256, U256};
-use alloy_rlp::Encodable;
-use alloy_rlp_derive::RlpEncodable;
+use alloy_rlp::{Decodable, Encodable};
+use alloy_rlp_derive::{RlpDecodable, RlpEncodable};
 use serde::{Deserialize, Serialize};
 
 /// Version of the deposit nonce field in the receipt.
 pub const OPTIMISM_DEPOSIT_NONCE_VERSION: u32 = 1;
 
 /// Represents an Ethereum log entry.
-#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, RlpEncodable)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, RlpEncodable, RlpDecodable,
+)]
 pub struct Log {
     /// Contract that emitted this log.
     pub address: Address,
@@ -34,7 +36,9 @@ pub struct Log {
 }
 
 /// Payload of a [Receipt].
-#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, RlpEncodable)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, RlpEncodable, RlpDecodable,
+)]
 #[rlp(trailing)]
 pub struct ReceiptPayload {
     /// Indicates whether the transaction was executed successfully.
@@ -90,6 +94,30 @@ impl Encodable for Receipt {
     }
 }
 
+impl Decodable for Receipt {
+    /// Decodes a legacy receipt or an EIP-2718 typed receipt from the `buf` buffer.
+    ///
+    /// The Optimism deposit nonce and its version are decoded, if present.
+    #[inline]
+    fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
+        match buf.first().copied() {
+            // check the EIP-2718 transaction type for typed receipts
+            Some(tx_type) if tx_type <= 0x7f => {
+                *buf = &buf[1..];
+                Ok(Receipt {
+                    tx_type,
+                    payload: ReceiptPayload::decode(buf)?,
+                })
+            }
+            Some(_) => Ok(Receipt {
+                tx_type: 0,
+                payload: ReceiptPayload::decode(buf)?,
+            }),
+            None => Err(alloy_rlp::Error::InputTooShort),
+        }
+    }
+}
+
 impl Receipt {
     /// Constructs a new [Receipt].
     pub fn new(tx_type: u8, success: bool, cumulative_gas_used: U256, logs: Vec<Log>) -> Receipt {
@@ -128,6 +156,7 @@ mod tests {
     use serde_json::json;
 
     use super::*;
+    use crate::RlpBytes;
 
     #[test]
     fn legacy() {
@@ -160,6 +189,7 @@ mod tests {
         receipt.encode(&mut data);
 
         assert_eq!(data, expected);
+        assert_eq!(Receipt::decode_bytes(&data).unwrap(), receipt);
     }
 
     #[test]
@@ -193,6 +223,7 @@ mod tests {
         receipt.encode(&mut data);
 
         assert_eq!(data, expected);
+        assert_eq!(Receipt::decode_bytes(&data).unwrap(), receipt);
     }
 
     #[test]
@@ -226,5 +257,27 @@ mod tests {
         receipt.encode(&mut data);
 
         assert_eq!(data, expected);
+        assert_eq!(Receipt::decode_bytes(&data).unwrap(), receipt);
+    }
+
+    #[test]
+    fn deposit_nonce() {
+        let receipt =
+            Receipt::new(0x7e, true, U256::from(46913), vec![]).with_deposit_nonce(4012991);
+        let data = alloy_rlp::encode(&receipt);
+        assert_eq!(data[0], 0x7e);
+
+        let decoded = Receipt::decode_bytes(&data).unwrap();
+        assert_eq!(decoded, receipt);
+        assert_eq!(decoded.payload.deposit_nonce, Some(4012991));
+        assert_eq!(
+            decoded.payload.deposit_nonce_version,
+            Some(OPTIMISM_DEPOSIT_NONCE_VERSION)
+        );
+
+        // receipts of deposits before the Canyon update do not contain a nonce
+        let receipt = Receipt::new(0x7e, true, U256::from(46913), vec![]);
+        let decoded = Receipt::decode_bytes(alloy_rlp::encode(&receipt)).unwrap();
+        assert_eq!(decoded.payload.deposit_nonce, None);
     }
 }
@@ -0,0 +1,302 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloy_primitives::{Address, Bytes, ChainId, TxNumber, U256};
+use k256::ecdsa::SigningKey;
+
+use super::{
+    ethereum::{
+        verifying_key_address, EthereumTxEssence, TransactionKind, TxEssenceEip1559,
+        TxEssenceEip2930, TxEssenceLegacy,
+    },
+    signature::TxSignature,
+    EthereumTransaction,
+};
+use crate::access_list::AccessList;
+
+/// Builder for the essences of Ethereum transactions.
+///
+/// All fields start with neutral defaults, i.e. a value transfer of zero to the zero
+/// address on mainnet using 21000 gas, so that tests only need to set the relevant
+/// fields. The essence of each transaction type is created by the corresponding `build_*`
+/// method, ignoring the fields that do not apply to that type.
+#[derive(Debug, Clone)]
+pub struct TxBuilder {
+    chain_id: ChainId,
+    nonce: TxNumber,
+    gas_limit: U256,
+    to: TransactionKind,
+    value: U256,
+    data: Bytes,
+    access_list: AccessList,
+    gas_price: U256,
+    max_fee_per_gas: U256,
+    max_priority_fee_per_gas: U256,
+}
+
+impl Default for TxBuilder {
+    fn default() -> Self {
+        TxBuilder {
+            chain_id: 1,
+            nonce: 0,
+            gas_limit: U256::from(21_000),
+            to: TransactionKind::Call(Address::ZERO),
+            value: U256::ZERO,
+            data: Bytes::new(),
+            access_list: AccessList::default(),
+            gas_price: U256::ZERO,
+            max_fee_per_gas: U256::ZERO,
+            max_priority_fee_per_gas: U256::ZERO,
+        }
+    }
+}
+
+impl TxBuilder {
+    /// Creates a new builder with the default values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the chain ID.
+    pub fn chain_id(mut self, chain_id: ChainId) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
+    /// Sets the nonce of the sender.
+    pub fn nonce(mut self, nonce: TxNumber) -> Self {
+        self.nonce = nonce;
+        self
+    }
+
+    /// Sets the gas limit.
+    pub fn gas_limit(mut self, gas_limit: u64) -> Self {
+        self.gas_limit = U256::from(gas_limit);
+        self
+    }
+
+    /// Sets the recipient of a message call.
+    pub fn to(mut self, to: Address) -> Self {
+        self.to = TransactionKind::Call(to);
+        self
+    }
+
+    /// Turns the transaction into a contract creation.
+    pub fn create(mut self) -> Self {
+        self.to = TransactionKind::Create;
+        self
+    }
+
+    /// Sets the amount of Wei to transfer.
+    pub fn value(mut self, value: U256) -> Self {
+        self.value = value;
+        self
+    }
+
+    /// Sets the call data or the init code for contract creations.
+    pub fn data(mut self, data: impl Into<Bytes>) -> Self {
+        self.data = data.into();
+        self
+    }
+
+    /// Sets the access list. This is ignored for Legacy transactions.
+    pub fn access_list(mut self, access_list: AccessList) -> Self {
+        self.access_list = access_list;
+        self
+    }
+
+    /// Sets the gas price of Legacy and EIP-2930 transactions.
+    pub fn gas_price(mut self, gas_price: U256) -> Self {
+        self.gas_price = gas_price;
+        self
+    }
+
+    /// Sets the maximum fee per gas of EIP-1559 transactions.
+    pub fn max_fee_per_gas(mut self, max_fee_per_gas: U256) -> Self {
+        self.max_fee_per_gas = max_fee_per_gas;
+        self
+    }
+
+    /// Sets the maximum priority fee per gas of EIP-1559 transactions.
+    pub fn max_priority_fee_per_gas(mut self, max_priority_fee_per_gas: U256) -> Self {
+        self.max_priority_fee_per_gas = max_priority_fee_per_gas;
+        self
+    }
+
+    /// Builds the essence of an EIP-155 protected Legacy transaction.
+    pub fn build_legacy(self) -> EthereumTxEssence {
+        EthereumTxEssence::Legacy(TxEssenceLegacy {
+            chain_id: Some(self.chain_id),
+            nonce: self.nonce,
+            gas_price: self.gas_price,
+            gas_limit: self.gas_limit,
+            to: self.to,
+            value: self.value,
+            data: self.data,
+        })
+    }
+
+    /// Builds the essence of an EIP-2930 transaction.
+    pub fn build_eip2930(self) -> EthereumTxEssence {
+        EthereumTxEssence::Eip2930(TxEssenceEip2930 {
+            chain_id: self.chain_id,
+            nonce: self.nonce,
+            gas_price: self.gas_price,
+            gas_limit: self.gas_limit,
+            to: self.to,
+            value: self.value,
+            data: self.data,
+            access_list: self.access_list,
+        })
+    }
+
+    /// Builds the essence of an EIP-1559 transaction.
+    pub fn build_eip1559(self) -> EthereumTxEssence {
+        EthereumTxEssence::Eip1559(TxEssenceEip1559 {
+            chain_id: self.chain_id,
+            nonce: self.nonce,
+            max_priority_fee_per_gas: self.max_priority_fee_per_gas,
+            max_fee_per_gas: self.max_fee_per_gas,
+            gas_limit: self.gas_limit,
+            to: self.to,
+            value: self.value,
+            data: self.data,
+            access_list: self.access_list,
+        })
+    }
+}
+
+/// Signs the transaction essence for the given chain.
+///
+/// The chain ID of the essence is replaced by `chain_id`, so Legacy transactions are
+/// always EIP-155 protected. The signature is deterministic as described in RFC 6979, and
+/// `v` is computed according to the transaction type.
+pub fn sign(
+    mut essence: EthereumTxEssence,
+    signing_key: &SigningKey,
+    chain_id: ChainId,
+) -> EthereumTransaction {
+    match &mut essence {
+        EthereumTxEssence::Legacy(tx) => tx.chain_id = Some(chain_id),
+        EthereumTxEssence::Eip2930(tx) => tx.chain_id = chain_id,
+        EthereumTxEssence::Eip1559(tx) => tx.chain_id = chain_id,
+    }
+
+    let (signature, recovery_id) = signing_key
+        .sign_prehash_recoverable(essence.signing_hash().as_slice())
+        .expect("failed to sign transaction");
+    let y_parity = recovery_id.is_y_odd() as u64;
+    let v = match essence {
+        EthereumTxEssence::Legacy(_) => 35 + 2 * chain_id + y_parity,
+        _ => y_parity,
+    };
+    let (r, s) = signature.split_bytes();
+
+    EthereumTransaction {
+        essence,
+        signature: TxSignature {
+            v,
+            r: U256::from_be_slice(&r),
+            s: U256::from_be_slice(&s),
+        },
+    }
+}
+
+/// Returns the address of the account controlled by the given key.
+pub fn signer_address(signing_key: &SigningKey) -> Address {
+    verifying_key_address(signing_key.verifying_key())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::address;
+    use hex_literal::hex;
+
+    use super::*;
+    use crate::{access_list::AccessListItem, transactions::Transaction, RlpBytes};
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_slice(&[0x46; 32]).unwrap()
+    }
+
+    #[test]
+    fn eip155_example() {
+        // example from https://eips.ethereum.org/EIPS/eip-155
+        let essence = TxBuilder::new()
+            .nonce(9)
+            .gas_price(U256::from(20_000_000_000u64))
+            .gas_limit(21000)
+            .to(address!("3535353535353535353535353535353535353535"))
+            .value(U256::from(1_000_000_000_000_000_000u64))
+            .build_legacy();
+        let transaction = sign(essence, &signing_key(), 1);
+
+        assert_eq!(transaction.signature.v, 37);
+        assert_eq!(
+            transaction.signature.r,
+            "18515461264373351373200002665853028612451056578545711640558177340181847433846"
+                .parse::<U256>()
+                .unwrap()
+        );
+        assert_eq!(
+            transaction.signature.s,
+            "46948507304638947509940763649030358759909902576025900602547168820602576006531"
+                .parse::<U256>()
+                .unwrap()
+        );
+        assert_eq!(
+            alloy_rlp::encode(&transaction),
+            hex!("f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83")
+        );
+        assert_eq!(
+            transaction.recover_from().unwrap(),
+            address!("9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F")
+        );
+    }
+
+    #[test]
+    fn sign_all_types() {
+        let key = signing_key();
+        let sender = signer_address(&key);
+        let builder = TxBuilder::new()
+            .nonce(1)
+            .gas_limit(100_000)
+            .to(Address::with_last_byte(1))
+            .value(U256::from(10))
+            .data(vec![0xde, 0xad])
+            .access_list(AccessList(vec![AccessListItem {
+                address: Address::with_last_byte(2),
+                storage_keys: vec![Default::default()],
+            }]))
+            .gas_price(U256::from(10))
+            .max_fee_per_gas(U256::from(10))
+            .max_priority_fee_per_gas(U256::from(1));
+
+        for chain_id in [1, 10, 1337, 20993] {
+            for essence in [
+                builder.clone().build_legacy(),
+                builder.clone().build_eip2930(),
+                builder.clone().create().build_eip1559(),
+            ] {
+                let transaction = sign(essence, &key, chain_id);
+                assert_eq!(transaction.recover_from().unwrap(), sender);
+
+                // the signature must survive the RLP roundtrip
+                let decoded = Transaction::decode_bytes(alloy_rlp::encode(&transaction)).unwrap();
+                assert_eq!(decoded, transaction);
+                assert_eq!(decoded.recover_from().unwrap(), sender);
+            }
+        }
+    }
+}
@@ -549,6 +549,17 @@ impl EthereumTxEssence {
     }
 }
 
+/// Computes the Ethereum address corresponding to the given public key.
+pub(crate) fn verifying_key_address(verifying_key: &K256VerifyingKey) -> Address {
+    let public_key = K256PublicKey::from(verifying_key);
+    let public_key = public_key.to_encoded_point(false);
+    let public_key = public_key.as_bytes();
+    debug_assert_eq!(public_key[0], 0x04);
+    let hash = keccak(&public_key[1..]);
+
+    Address::from_slice(&hash[12..])
+}
+
 /// Converts a given value into a boolean based on its parity.
 fn checked_bool(v: u64) -> Option<bool> {
     match v {
@@ -597,13 +608,7 @@ impl TxEssence for EthereumTxEssence {
         )
         .expect("invalid signature");
 
-        let public_key = K256PublicKey::from(&verify_key);
-        let public_key = public_key.to_encoded_point(false);
-        let public_key = public_key.as_bytes();
-        debug_assert_eq!(public_key[0], 0x04);
-        let hash = keccak(&public_key[1..]);
-
-        Ok(Address::from_slice(&hash[12..]))
+        Ok(verifying_key_address(&verify_key))
     }
     /// Returns the length of the RLP-encoding payload in bytes.
     fn payload_length(&self) -> usize {
@@ -25,6 +25,7 @@ use self::{
 };
 use crate::{keccak::keccak, transactions::ethereum::EthereumTxEssence, U256};
 
+pub mod builder;
 pub mod ethereum;
 pub mod optimism;
 pub mod signature;
@@ -138,7 +139,7 @@ impl<E: TxEssence + Debug> Decodable for Transaction<E> {
     }
 }
 
-impl<E: TxEssence + Debug> Transaction<E> {
+impl<E: TxEssence> Transaction<E> {
     /// Calculates the Keccak hash of the RLP-encoded transaction.
     ///
     /// This hash uniquely identifies the transaction on the Ethereum network.
@@ -156,7 +157,9 @@ impl<E: TxEssence + Debug> Transaction<E> {
     pub fn recover_from(&self) -> anyhow::Result<Address> {
         self.essence.recover_from(&self.signature)
     }
+}
 
+impl<E: TxEssence + Debug> Transaction<E> {
     /// Fully consumes the provided input RLP buffer to decode a Transaction instance
     pub fn decode_strict(buf: &mut &[u8]) -> anyhow::Result<Self> {
         let result = Self::decode(buf).expect("failed to decode tx");
@@ -0,0 +1,878 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+extern crate alloc;
+
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{cell::RefCell, iter, mem};
+
+use alloy_primitives::B256;
+use alloy_rlp::Encodable;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{
+    mpt::{lcp, prefix_nibs},
+    to_encoded_path, to_nibs, Error, MptNode, MptNodeData, MptNodeReference, Trie,
+};
+use crate::{keccak::keccak, r#const::EMPTY_ROOT};
+
+/// Represents the data of a node in an [ArenaTrie].
+///
+/// This corresponds to [MptNodeData], but children are referenced by their index in the
+/// arena of the trie instead of being boxed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArenaNodeData {
+    /// Represents an empty trie node.
+    #[default]
+    Null,
+    /// A node that can have up to 16 children, given by their index.
+    Branch([Option<u32>; 16]),
+    /// A leaf node that contains a key and a value, both represented as byte vectors.
+    Leaf(Vec<u8>, Vec<u8>),
+    /// A node that has exactly one child, given by its index.
+    Extension(Vec<u8>, u32),
+    /// Represents a sub-trie by its hash.
+    Digest(B256),
+}
+
+#[derive(Clone, Debug, Default)]
+struct ArenaNode {
+    data: ArenaNodeData,
+    /// Cache for a previously computed reference of this node.
+    cached_reference: RefCell<Option<MptNodeReference>>,
+}
+
+/// A sparse Merkle Patricia Trie storing all of its nodes in a single arena.
+///
+/// It provides the same functionality as a trie of [MptNode]s, but instead of allocating
+/// each node individually, all nodes are stored in one vector and linked by their index.
+/// This significantly reduces the number of allocations when a large trie is loaded.
+/// Nodes that are removed from the trie are not reclaimed until the trie is cleared or
+/// serialized.
+#[derive(Clone, Debug)]
+pub struct ArenaTrie {
+    nodes: Vec<ArenaNode>,
+    root: u32,
+}
+
+impl Default for ArenaTrie {
+    fn default() -> Self {
+        ArenaTrie {
+            nodes: vec![ArenaNode::default()],
+            root: 0,
+        }
+    }
+}
+
+impl From<B256> for ArenaTrie {
+    fn from(digest: B256) -> Self {
+        match digest {
+            EMPTY_ROOT | B256::ZERO => ArenaTrie::default(),
+            _ => ArenaTrie {
+                nodes: vec![ArenaNodeData::Digest(digest).into()],
+                root: 0,
+            },
+        }
+    }
+}
+
+impl From<ArenaNodeData> for ArenaNode {
+    fn from(data: ArenaNodeData) -> Self {
+        ArenaNode {
+            data,
+            cached_reference: RefCell::new(None),
+        }
+    }
+}
+
+/// Converts a trie of [MptNode]s into an [ArenaTrie].
+impl From<&MptNode> for ArenaTrie {
+    fn from(root: &MptNode) -> Self {
+        fn push(node: &MptNode, nodes: &mut Vec<ArenaNode>) -> u32 {
+            let data = match node.as_data() {
+                MptNodeData::Null => ArenaNodeData::Null,
+                MptNodeData::Branch(children) => {
+                    let mut indices = [None; 16];
+                    for (index, child) in indices.iter_mut().zip(children) {
+                        *index = child.as_ref().map(|child| push(child, nodes));
+                    }
+                    ArenaNodeData::Branch(indices)
+                }
+                MptNodeData::Leaf(prefix, value) => {
+                    ArenaNodeData::Leaf(prefix.clone(), value.clone())
+                }
+                MptNodeData::Extension(prefix, child) => {
+                    ArenaNodeData::Extension(prefix.clone(), push(child, nodes))
+                }
+                MptNodeData::Digest(digest) => ArenaNodeData::Digest(*digest),
+            };
+            nodes.push(data.into());
+            (nodes.len() - 1) as u32
+        }
+
+        let mut nodes = Vec::new();
+        let root = push(root, &mut nodes);
+        ArenaTrie { nodes, root }
+    }
+}
+
+/// Converts an [ArenaTrie] into a trie of [MptNode]s.
+impl From<&ArenaTrie> for MptNode {
+    fn from(trie: &ArenaTrie) -> Self {
+        fn build(trie: &ArenaTrie, index: u32) -> MptNode {
+            let data = match trie.data(index) {
+                ArenaNodeData::Null => MptNodeData::Null,
+                ArenaNodeData::Branch(indices) => {
+                    let mut children: [Option<Box<MptNode>>; 16] = Default::default();
+                    for (child, index) in children.iter_mut().zip(indices) {
+                        *child = index.map(|index| Box::new(build(trie, index)));
+                    }
+                    MptNodeData::Branch(children)
+                }
+                ArenaNodeData::Leaf(prefix, value) => {
+                    MptNodeData::Leaf(prefix.clone(), value.clone())
+                }
+                ArenaNodeData::Extension(prefix, index) => {
+                    MptNodeData::Extension(prefix.clone(), Box::new(build(trie, *index)))
+                }
+                ArenaNodeData::Digest(digest) => MptNodeData::Digest(*digest),
+            };
+            data.into()
+        }
+
+        build(trie, trie.root)
+    }
+}
+
+/// The serialized form of an [ArenaTrie], only containing the reachable nodes.
+#[derive(Serialize, Deserialize)]
+struct ArenaTrieParts {
+    nodes: Vec<ArenaNodeData>,
+    root: u32,
+}
+
+impl Serialize for ArenaTrie {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut nodes = Vec::new();
+        let root = self.compact_into(self.root, &mut nodes);
+        ArenaTrieParts { nodes, root }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ArenaTrie {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let parts = ArenaTrieParts::deserialize(deserializer)?;
+        ArenaTrie::from_parts(parts.nodes, parts.root).map_err(de::Error::custom)
+    }
+}
+
+impl ArenaTrie {
+    /// Creates a trie from its nodes and the index of its root node.
+    ///
+    /// Each node must be stored after its children and must have at most one parent.
+    pub fn from_parts(nodes: Vec<ArenaNodeData>, root: u32) -> Result<Self, Error> {
+        let mut has_parent = vec![false; nodes.len()];
+        for (index, data) in nodes.iter().enumerate() {
+            for child in children(data) {
+                if child as usize >= index || mem::replace(&mut has_parent[child as usize], true) {
+                    return Err(Error::InvalidNodeIndex(child));
+                }
+            }
+        }
+        if root as usize >= nodes.len() || has_parent[root as usize] {
+            return Err(Error::InvalidNodeIndex(root));
+        }
+
+        Ok(ArenaTrie {
+            nodes: nodes.into_iter().map(ArenaNode::from).collect(),
+            root,
+        })
+    }
+
+    /// Clears the trie, releasing all of its nodes.
+    pub fn clear(&mut self) {
+        *self = ArenaTrie::default();
+    }
+
+    /// Computes and returns the 256-bit hash of the trie.
+    pub fn hash(&self) -> B256 {
+        match self.data(self.root) {
+            ArenaNodeData::Null => EMPTY_ROOT,
+            _ => match self.reference(self.root) {
+                MptNodeReference::Digest(digest) => digest,
+                MptNodeReference::Bytes(bytes) => keccak(bytes).into(),
+            },
+        }
+    }
+
+    /// Determines if the trie is empty.
+    pub fn is_empty(&self) -> bool {
+        matches!(self.data(self.root), ArenaNodeData::Null)
+    }
+
+    /// Determines if the root of the trie is a digest.
+    pub fn is_digest(&self) -> bool {
+        matches!(self.data(self.root), ArenaNodeData::Digest(_))
+    }
+
+    /// Returns the number of traversable nodes in the trie.
+    pub fn size(&self) -> usize {
+        self.size_internal(self.root)
+    }
+
+    /// Retrieves the value associated with a given key in the trie.
+    pub fn get(&self, key: &[u8]) -> Result<Option<&[u8]>, Error> {
+        self.get_internal(self.root, &to_nibs(key))
+    }
+
+    /// Retrieves the RLP-decoded value corresponding to the key.
+    pub fn get_rlp<T: alloy_rlp::Decodable>(&self, key: &[u8]) -> Result<Option<T>, Error> {
+        match self.get(key)? {
+            Some(mut bytes) => Ok(Some(T::decode(&mut bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Inserts a key-value pair into the trie.
+    ///
+    /// It returns `true` if the trie was modified.
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) -> Result<bool, Error> {
+        if value.is_empty() {
+            panic!("value must not be empty");
+        }
+        self.insert_internal(self.root, &to_nibs(key), value)
+    }
+
+    /// Inserts an RLP-encoded value into the trie.
+    pub fn insert_rlp(&mut self, key: &[u8], value: impl Encodable) -> Result<bool, Error> {
+        self.insert_internal(self.root, &to_nibs(key), alloy_rlp::encode(value))
+    }
+
+    /// Removes a key from the trie.
+    ///
+    /// It returns `true` if the key was present.
+    pub fn delete(&mut self, key: &[u8]) -> Result<bool, Error> {
+        self.delete_internal(self.root, &to_nibs(key))
+    }
+
+    /// Applies a sequence of updates, sorted by key, where `None` deletes the key.
+    ///
+    /// The keys must be sorted and unique, otherwise [Error::UnsortedKeys] is returned.
+    /// Unlike [MptNode::batch_update], the updates are applied one key at a time: an
+    /// update only clears the cached references on its path and each of these nodes is
+    /// hashed at most once by the next [ArenaTrie::hash]. A merged pass would only save
+    /// the repeated descent from the root, which is cheap with index links.
+    /// It returns `true` if the trie was modified.
+    pub fn batch_update<K: AsRef<[u8]>>(
+        &mut self,
+        sorted_kvs: impl IntoIterator<Item = (K, Option<Vec<u8>>)>,
+    ) -> Result<bool, Error> {
+        let updates: Vec<_> = sorted_kvs.into_iter().collect();
+        if updates
+            .windows(2)
+            .any(|w| w[0].0.as_ref() >= w[1].0.as_ref())
+        {
+            return Err(Error::UnsortedKeys);
+        }
+
+        let mut updated = false;
+        for (key, value) in updates {
+            updated |= match value {
+                Some(value) => self.insert(key.as_ref(), value)?,
+                None => self.delete(key.as_ref())?,
+            };
+        }
+        Ok(updated)
+    }
+
+    fn data(&self, index: u32) -> &ArenaNodeData {
+        &self.nodes[index as usize].data
+    }
+
+    fn push(&mut self, data: ArenaNodeData) -> u32 {
+        self.nodes.push(data.into());
+        (self.nodes.len() - 1) as u32
+    }
+
+    fn invalidate_ref_cache(&mut self, index: u32) {
+        self.nodes[index as usize].cached_reference.get_mut().take();
+    }
+
+    fn size_internal(&self, index: u32) -> usize {
+        match self.data(index) {
+            ArenaNodeData::Null | ArenaNodeData::Digest(_) => 0,
+            ArenaNodeData::Branch(children) => {
+                children
+                    .iter()
+                    .flatten()
+                    .map(|child| self.size_internal(*child))
+                    .sum::<usize>()
+                    + 1
+            }
+            ArenaNodeData::Leaf(_, _) => 1,
+            ArenaNodeData::Extension(_, child) => self.size_internal(*child) + 1,
+        }
+    }
+
+    /// Appends the nodes reachable from `index` to `nodes`, so that each node is stored
+    /// after its children, and returns the new index of the node.
+    fn compact_into(&self, index: u32, nodes: &mut Vec<ArenaNodeData>) -> u32 {
+        let data = match self.data(index) {
+            ArenaNodeData::Branch(children) => {
+                let mut indices = [None; 16];
+                for (new, child) in indices.iter_mut().zip(children) {
+                    *new = child.map(|child| self.compact_into(child, nodes));
+                }
+                ArenaNodeData::Branch(indices)
+            }
+            ArenaNodeData::Extension(prefix, child) => {
+                ArenaNodeData::Extension(prefix.clone(), self.compact_into(*child, nodes))
+            }
+            data => data.clone(),
+        };
+        nodes.push(data);
+        (nodes.len() - 1) as u32
+    }
+
+    fn reference(&self, index: u32) -> MptNodeReference {
+        self.nodes[index as usize]
+            .cached_reference
+            .borrow_mut()
+            .get_or_insert_with(|| self.calc_reference(index))
+            .clone()
+    }
+
+    fn calc_reference(&self, index: u32) -> MptNodeReference {
+        match self.data(index) {
+            ArenaNodeData::Null => MptNodeReference::Bytes(vec![alloy_rlp::EMPTY_STRING_CODE]),
+            ArenaNodeData::Digest(digest) => MptNodeReference::Digest(*digest),
+            _ => {
+                let payload_length = self.payload_length(index);
+                let mut encoded = Vec::with_capacity(
+                    payload_length + alloy_rlp::length_of_length(payload_length),
+                );
+                self.encode(index, payload_length, &mut encoded);
+                if encoded.len() < 32 {
+                    MptNodeReference::Bytes(encoded)
+                } else {
+                    MptNodeReference::Digest(keccak(encoded).into())
+                }
+            }
+        }
+    }
+
+    fn encode(&self, index: u32, payload_length: usize, out: &mut dyn alloy_rlp::BufMut) {
+        let header = alloy_rlp::Header {
+            list: true,
+            payload_length,
+        };
+        match self.data(index) {
+            ArenaNodeData::Branch(children) => {
+                header.encode(out);
+                for child in children {
+                    match child {
+                        Some(child) => self.reference_encode(*child, out),
+                        None => out.put_u8(alloy_rlp::EMPTY_STRING_CODE),
+                    }
+                }
+                // in the MPT reference, branches have values so always add empty value
+                out.put_u8(alloy_rlp::EMPTY_STRING_CODE);
+            }
+            ArenaNodeData::Leaf(prefix, value) => {
+                header.encode(out);
+                prefix.as_slice().encode(out);
+                value.as_slice().encode(out);
+            }
+            ArenaNodeData::Extension(prefix, child) => {
+                header.encode(out);
+                prefix.as_slice().encode(out);
+                self.reference_encode(*child, out);
+            }
+            ArenaNodeData::Null | ArenaNodeData::Digest(_) => unreachable!(),
+        }
+    }
+
+    fn reference_encode(&self, index: u32, out: &mut dyn alloy_rlp::BufMut) {
+        match self.reference(index) {
+            MptNodeReference::Bytes(bytes) => out.put_slice(&bytes),
+            MptNodeReference::Digest(digest) => {
+                out.put_u8(alloy_rlp::EMPTY_STRING_CODE + 32);
+                out.put_slice(digest.as_slice());
+            }
+        }
+    }
+
+    fn reference_length(&self, index: u32) -> usize {
+        match self.reference(index) {
+            MptNodeReference::Bytes(bytes) => bytes.len(),
+            MptNodeReference::Digest(_) => 1 + 32,
+        }
+    }
+
+    fn payload_length(&self, index: u32) -> usize {
+        match self.data(index) {
+            ArenaNodeData::Null => 0,
+            ArenaNodeData::Branch(children) => {
+                1 + children
+                    .iter()
+                    .map(|child| child.map_or(1, |child| self.reference_length(child)))
+                    .sum::<usize>()
+            }
+            ArenaNodeData::Leaf(prefix, value) => {
+                prefix.as_slice().length() + value.as_slice().length()
+            }
+            ArenaNodeData::Extension(prefix, child) => {
+                prefix.as_slice().length() + self.reference_length(*child)
+            }
+            ArenaNodeData::Digest(_) => 32,
+        }
+    }
+
+    fn get_internal(&self, index: u32, key_nibs: &[u8]) -> Result<Option<&[u8]>, Error> {
+        match self.data(index) {
+            ArenaNodeData::Null => Ok(None),
+            ArenaNodeData::Branch(children) => match key_nibs.split_first() {
+                Some((i, tail)) => match children[*i as usize] {
+                    Some(child) => self.get_internal(child, tail),
+                    None => Ok(None),
+                },
+                None => Ok(None),
+            },
+            ArenaNodeData::Leaf(prefix, value) => {
+                if prefix_nibs(prefix) == key_nibs {
+                    Ok(Some(value))
+                } else {
+                    Ok(None)
+                }
+            }
+            ArenaNodeData::Extension(prefix, child) => {
+                match key_nibs.strip_prefix(prefix_nibs(prefix).as_slice()) {
+                    Some(tail) => self.get_internal(*child, tail),
+                    None => Ok(None),
+                }
+            }
+            ArenaNodeData::Digest(digest) => Err(Error::NodeNotResolved(*digest)),
+        }
+    }
+
+    /// Applies `f` to the detached data of the node at `index` and invalidates the
+    /// reference of that node, if it was modified.
+    fn modify(
+        &mut self,
+        index: u32,
+        f: impl FnOnce(&mut Self, &mut ArenaNodeData) -> Result<bool, Error>,
+    ) -> Result<bool, Error> {
+        let mut data = mem::take(&mut self.nodes[index as usize].data);
+        let result = f(self, &mut data);
+        self.nodes[index as usize].data = data;
+        if let Ok(true) = result {
+            self.invalidate_ref_cache(index);
+        }
+        result
+    }
+
+    fn insert_internal(
+        &mut self,
+        index: u32,
+        key_nibs: &[u8],
+        value: Vec<u8>,
+    ) -> Result<bool, Error> {
+        self.modify(index, |trie, data| match data {
+            ArenaNodeData::Null => {
+                *data = ArenaNodeData::Leaf(to_encoded_path(key_nibs, true), value);
+                Ok(true)
+            }
+            ArenaNodeData::Branch(children) => {
+                let Some((i, tail)) = key_nibs.split_first() else {
+                    return Err(Error::ValueInBranch);
+                };
+                match children[*i as usize] {
+                    Some(child) => trie.insert_internal(child, tail, value),
+                    // if the corresponding child is empty, insert a new leaf
+                    None => {
+                        let leaf = ArenaNodeData::Leaf(to_encoded_path(tail, true), value);
+                        children[*i as usize] = Some(trie.push(leaf));
+                        Ok(true)
+                    }
+                }
+            }
+            ArenaNodeData::Leaf(prefix, old_value) => {
+                let self_nibs = prefix_nibs(prefix);
+                let common_len = lcp(&self_nibs, key_nibs);
+                if common_len == self_nibs.len() && common_len == key_nibs.len() {
+                    // if self_nibs == key_nibs, update the value if it is different
+                    if old_value == &value {
+                        return Ok(false);
+                    }
+                    *old_value = value;
+                } else if common_len == self_nibs.len() || common_len == key_nibs.len() {
+                    return Err(Error::ValueInBranch);
+                } else {
+                    let split_point = common_len + 1;
+                    // otherwise, create a branch with two children
+                    let mut children = [None; 16];
+                    children[self_nibs[common_len] as usize] =
+                        Some(trie.push(ArenaNodeData::Leaf(
+                            to_encoded_path(&self_nibs[split_point..], true),
+                            mem::take(old_value),
+                        )));
+                    children[key_nibs[common_len] as usize] = Some(trie.push(ArenaNodeData::Leaf(
+                        to_encoded_path(&key_nibs[split_point..], true),
+                        value,
+                    )));
+
+                    let branch = ArenaNodeData::Branch(children);
+                    *data = if common_len > 0 {
+                        // create parent extension for new branch
+                        ArenaNodeData::Extension(
+                            to_encoded_path(&self_nibs[..common_len], false),
+                            trie.push(branch),
+                        )
+                    } else {
+                        branch
+                    };
+                }
+                Ok(true)
+            }
+            ArenaNodeData::Extension(prefix, existing_child) => {
+                let self_nibs = prefix_nibs(prefix);
+                let common_len = lcp(&self_nibs, key_nibs);
+                if common_len == self_nibs.len() {
+                    // traverse down for update
+                    return trie.insert_internal(*existing_child, &key_nibs[common_len..], value);
+                } else if common_len == key_nibs.len() {
+                    return Err(Error::ValueInBranch);
+                }
+
+                let split_point = common_len + 1;
+                // otherwise, create a branch with two children
+                let mut children = [None; 16];
+                children[self_nibs[common_len] as usize] = if split_point < self_nibs.len() {
+                    Some(trie.push(ArenaNodeData::Extension(
+                        to_encoded_path(&self_nibs[split_point..], false),
+                        *existing_child,
+                    )))
+                } else {
+                    Some(*existing_child)
+                };
+                children[key_nibs[common_len] as usize] = Some(trie.push(ArenaNodeData::Leaf(
+                    to_encoded_path(&key_nibs[split_point..], true),
+                    value,
+                )));
+
+                let branch = ArenaNodeData::Branch(children);
+                *data = if common_len > 0 {
+                    // create parent extension for new branch
+                    ArenaNodeData::Extension(
+                        to_encoded_path(&self_nibs[..common_len], false),
+                        trie.push(branch),
+                    )
+                } else {
+                    branch
+                };
+                Ok(true)
+            }
+            ArenaNodeData::Digest(digest) => Err(Error::NodeNotResolved(*digest)),
+        })
+    }
+
+    fn delete_internal(&mut self, index: u32, key_nibs: &[u8]) -> Result<bool, Error> {
+        self.modify(index, |trie, data| {
+            match data {
+                ArenaNodeData::Null => return Ok(false),
+                ArenaNodeData::Branch(children) => {
+                    let Some((i, tail)) = key_nibs.split_first() else {
+                        return Err(Error::ValueInBranch);
+                    };
+                    let Some(child) = children[*i as usize] else {
+                        return Ok(false);
+                    };
+                    if !trie.delete_internal(child, tail)? {
+                        return Ok(false);
+                    }
+                    // if the node is now empty, remove it
+                    if matches!(trie.data(child), ArenaNodeData::Null) {
+                        children[*i as usize] = None;
+                    }
+
+                    trie.normalize_branch(data);
+                }
+                ArenaNodeData::Leaf(prefix, _) => {
+                    if prefix_nibs(prefix) != key_nibs {
+                        return Ok(false);
+                    }
+                    *data = ArenaNodeData::Null;
+                }
+                ArenaNodeData::Extension(prefix, child) => {
+                    let Some(tail) = key_nibs.strip_prefix(prefix_nibs(prefix).as_slice()) else {
+                        return Ok(false);
+                    };
+                    if !trie.delete_internal(*child, tail)? {
+                        return Ok(false);
+                    }
+
+                    trie.normalize_extension(data);
+                }
+                ArenaNodeData::Digest(digest) => return Err(Error::NodeNotResolved(*digest)),
+            }
+            Ok(true)
+        })
+    }
+
+    /// Restores the invariants of a branch node after its children have been modified.
+    fn normalize_branch(&mut self, data: &mut ArenaNodeData) {
+        let ArenaNodeData::Branch(children) = data else {
+            unreachable!()
+        };
+
+        let mut remaining = children
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| Some((i, (*c)?)));
+        let Some((index, orphan)) = remaining.next() else {
+            *data = ArenaNodeData::Null;
+            return;
+        };
+        // if there is only exactly one node left, we need to convert the branch
+        if remaining.next().is_none() {
+            match &mut self.nodes[orphan as usize].data {
+                // if the orphan is a leaf, prepend the corresponding nib to it
+                ArenaNodeData::Leaf(prefix, orphan_value) => {
+                    let new_nibs: Vec<_> =
+                        iter::once(index as u8).chain(prefix_nibs(prefix)).collect();
+                    *data = ArenaNodeData::Leaf(
+                        to_encoded_path(&new_nibs, true),
+                        mem::take(orphan_value),
+                    );
+                }
+                // if the orphan is an extension, prepend the corresponding nib to it
+                ArenaNodeData::Extension(prefix, orphan_child) => {
+                    let new_nibs: Vec<_> =
+                        iter::once(index as u8).chain(prefix_nibs(prefix)).collect();
+                    *data =
+                        ArenaNodeData::Extension(to_encoded_path(&new_nibs, false), *orphan_child);
+                }
+                // if the orphan is a branch or digest, convert to an extension
+                ArenaNodeData::Branch(_) | ArenaNodeData::Digest(_) => {
+                    *data =
+                        ArenaNodeData::Extension(to_encoded_path(&[index as u8], false), orphan);
+                }
+                ArenaNodeData::Null => unreachable!(),
+            }
+        }
+    }
+
+    /// Restores the invariants of an extension node after its child has been modified.
+    fn normalize_extension(&mut self, data: &mut ArenaNodeData) {
+        let ArenaNodeData::Extension(prefix, child) = data else {
+            unreachable!()
+        };
+
+        let mut self_nibs = prefix_nibs(prefix);
+        match &mut self.nodes[*child as usize].data {
+            // if the child is empty, remove the extension
+            ArenaNodeData::Null => {
+                *data = ArenaNodeData::Null;
+            }
+            // for a leaf, replace the extension with the extended leaf
+            ArenaNodeData::Leaf(prefix, value) => {
+                self_nibs.extend(prefix_nibs(prefix));
+                *data = ArenaNodeData::Leaf(to_encoded_path(&self_nibs, true), mem::take(value));
+            }
+            // for an extension, replace the extension with the extended extension
+            ArenaNodeData::Extension(prefix, node) => {
+                self_nibs.extend(prefix_nibs(prefix));
+                *data = ArenaNodeData::Extension(to_encoded_path(&self_nibs, false), *node);
+            }
+            // for a branch or digest, the extension is still correct
+            ArenaNodeData::Branch(_) | ArenaNodeData::Digest(_) => {}
+        }
+    }
+}
+
+impl Trie for ArenaTrie {
+    fn hash(&self) -> B256 {
+        ArenaTrie::hash(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        ArenaTrie::is_empty(self)
+    }
+
+    fn clear(&mut self) {
+        ArenaTrie::clear(self)
+    }
+
+    fn size(&self) -> usize {
+        ArenaTrie::size(self)
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<&[u8]>, Error> {
+        ArenaTrie::get(self, key)
+    }
+
+    fn insert(&mut self, key: &[u8], value: Vec<u8>) -> Result<bool, Error> {
+        ArenaTrie::insert(self, key, value)
+    }
+
+    fn delete(&mut self, key: &[u8]) -> Result<bool, Error> {
+        ArenaTrie::delete(self, key)
+    }
+
+    fn batch_update<K: AsRef<[u8]>>(
+        &mut self,
+        sorted_kvs: impl IntoIterator<Item = (K, Option<Vec<u8>>)>,
+    ) -> Result<bool, Error> {
+        ArenaTrie::batch_update(self, sorted_kvs)
+    }
+}
+
+/// Returns the indices of all children of the node.
+fn children(data: &ArenaNodeData) -> impl Iterator<Item = u32> + '_ {
+    let (branch, extension) = match data {
+        ArenaNodeData::Branch(children) => (&children[..], None),
+        ArenaNodeData::Extension(_, child) => (&[][..], Some(*child)),
+        _ => (&[][..], None),
+    };
+    branch.iter().flatten().copied().chain(extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_arena_keccak_trie() {
+        const N: usize = 512;
+
+        let mut trie = ArenaTrie::default();
+        let mut reference = MptNode::default();
+        for i in 0..N {
+            let key = keccak(i.to_be_bytes());
+            assert!(trie.insert_rlp(&key, i).unwrap());
+            reference.insert_rlp(&key, i).unwrap();
+            assert_eq!(trie.hash(), reference.hash());
+        }
+        assert_eq!(trie.size(), reference.size());
+
+        for i in 0..N {
+            assert_eq!(trie.get_rlp(&keccak(i.to_be_bytes())).unwrap(), Some(i));
+            assert!(trie.get(&keccak((i + N).to_be_bytes())).unwrap().is_none());
+        }
+        // updating a value with the same value does not modify the trie
+        assert!(!trie
+            .insert_rlp(&keccak(0usize.to_be_bytes()), 0usize)
+            .unwrap());
+
+        for i in 0..N {
+            let key = keccak(i.to_be_bytes());
+            assert!(trie.delete(&key).unwrap());
+            assert!(!trie.delete(&key).unwrap());
+            reference.delete(&key).unwrap();
+            assert_eq!(trie.hash(), reference.hash());
+        }
+        assert!(trie.is_empty());
+    }
+
+    #[test]
+    pub fn test_arena_index_trie() {
+        const N: usize = 512;
+
+        let mut trie = ArenaTrie::default();
+        let mut reference = MptNode::default();
+        for i in 0..N {
+            let key = alloy_rlp::encode(i);
+            trie.insert_rlp(&key, i).unwrap();
+            reference.insert_rlp(&key, i).unwrap();
+            assert_eq!(trie.hash(), reference.hash());
+        }
+        for i in (0..N).rev() {
+            let key = alloy_rlp::encode(i);
+            trie.delete(&key).unwrap();
+            reference.delete(&key).unwrap();
+            assert_eq!(trie.hash(), reference.hash());
+        }
+        assert!(trie.is_empty());
+    }
+
+    #[test]
+    pub fn test_arena_batch_update() {
+        let mut trie = ArenaTrie::default();
+        let mut reference = MptNode::default();
+        let mut keys: Vec<_> = (0..64usize).map(|i| keccak(i.to_be_bytes())).collect();
+        keys.sort();
+        let inserts = keys.iter().map(|key| (key, Some(alloy_rlp::encode(key))));
+        assert!(trie.batch_update(inserts.clone()).unwrap());
+        reference.batch_update(inserts).unwrap();
+        assert_eq!(trie.hash(), reference.hash());
+
+        let deletes = keys.iter().step_by(2).map(|key| (key, None));
+        assert!(trie.batch_update(deletes.clone()).unwrap());
+        reference.batch_update(deletes).unwrap();
+        assert_eq!(trie.hash(), reference.hash());
+
+        let unsorted = [(keys[1], None), (keys[0], None)];
+        assert!(matches!(
+            trie.batch_update(unsorted),
+            Err(Error::UnsortedKeys)
+        ));
+        assert_eq!(trie.hash(), reference.hash());
+    }
+
+    #[test]
+    pub fn test_arena_conversion() {
+        let mut reference = MptNode::default();
+        for i in 0..64usize {
+            reference.insert_rlp(&keccak(i.to_be_bytes()), i).unwrap();
+        }
+        // prune the first child of the root
+        let MptNodeData::Branch(mut children) = reference.as_data().clone() else {
+            panic!("not a branch")
+        };
+        let digest = children[0].as_ref().unwrap().hash();
+        children[0] = Some(Box::new(digest.into()));
+        let reference: MptNode = MptNodeData::Branch(children).into();
+
+        let mut trie = ArenaTrie::from(&reference);
+        assert_eq!(trie.hash(), reference.hash());
+        assert_eq!(trie.size(), reference.size());
+        assert_eq!(MptNode::from(&trie).hash(), reference.hash());
+
+        // modify the trie, so that the arena contains unreachable nodes
+        let key = keccak(63usize.to_be_bytes());
+        trie.delete(&key).unwrap();
+        trie.insert_rlp(&key, 1usize).unwrap();
+        let hash = trie.hash();
+
+        let trie: ArenaTrie = bincode::deserialize(&bincode::serialize(&trie).unwrap()).unwrap();
+        assert_eq!(trie.hash(), hash);
+        assert_eq!(trie.size(), reference.size());
+    }
+
+    #[test]
+    pub fn test_arena_from_parts() {
+        let leaf = ArenaNodeData::Leaf(vec![0x20], vec![0x01]);
+        assert!(ArenaTrie::from_parts(vec![leaf.clone()], 0).is_ok());
+        assert!(ArenaTrie::from_parts(vec![leaf.clone()], 1).is_err());
+        // cycles
+        let extension = ArenaNodeData::Extension(vec![0x11], 0);
+        assert!(ArenaTrie::from_parts(vec![extension], 0).is_err());
+        // shared nodes
+        let mut children = [None; 16];
+        children[0] = Some(0);
+        children[1] = Some(0);
+        let branch = ArenaNodeData::Branch(children);
+        assert!(ArenaTrie::from_parts(vec![leaf, branch], 1).is_err());
+    }
+}
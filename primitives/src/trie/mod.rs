@@ -13,8 +13,74 @@
 // limitations under the License.
 
 pub mod account;
+pub mod arena;
 pub mod mpt;
+pub mod receipts;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
 
 use alloy_primitives::{b256, B256};
 
-pub use self::{account::StateAccount, mpt::*};
+pub use self::{
+    account::StateAccount,
+    arena::{ArenaNodeData, ArenaTrie},
+    mpt::*,
+};
+
+/// Common interface of the sparse Merkle Patricia Trie implementations.
+///
+/// This allows the block builder to be used with either [MptNode] or [ArenaTrie].
+pub trait Trie: Default + Clone + From<B256> {
+    /// Computes and returns the 256-bit hash of the trie.
+    fn hash(&self) -> B256;
+
+    /// Determines if the trie is empty.
+    fn is_empty(&self) -> bool;
+
+    /// Clears the trie, replacing it with an empty trie.
+    fn clear(&mut self);
+
+    /// Returns the number of traversable nodes in the trie.
+    fn size(&self) -> usize;
+
+    /// Retrieves the value associated with a given key in the trie.
+    fn get(&self, key: &[u8]) -> Result<Option<&[u8]>, Error>;
+
+    /// Retrieves the RLP-decoded value corresponding to the key.
+    fn get_rlp<T: alloy_rlp::Decodable>(&self, key: &[u8]) -> Result<Option<T>, Error> {
+        match self.get(key)? {
+            Some(mut bytes) => Ok(Some(T::decode(&mut bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Inserts a key-value pair into the trie. It returns `true` if the trie was
+    /// modified.
+    fn insert(&mut self, key: &[u8], value: Vec<u8>) -> Result<bool, Error>;
+
+    /// Inserts an RLP-encoded value into the trie.
+    fn insert_rlp(&mut self, key: &[u8], value: impl alloy_rlp::Encodable) -> Result<bool, Error> {
+        self.insert(key, alloy_rlp::encode(value))
+    }
+
+    /// Removes a key from the trie. It returns `true` if the key was present.
+    fn delete(&mut self, key: &[u8]) -> Result<bool, Error>;
+
+    /// Applies a sequence of updates, sorted by key, where `None` deletes the key. It
+    /// returns `true` if the trie was modified.
+    fn batch_update<K: AsRef<[u8]>>(
+        &mut self,
+        sorted_kvs: impl IntoIterator<Item = (K, Option<Vec<u8>>)>,
+    ) -> Result<bool, Error> {
+        let mut updated = false;
+        for (key, value) in sorted_kvs {
+            updated |= match value {
+                Some(value) => self.insert(key.as_ref(), value)?,
+                None => self.delete(key.as_ref())?,
+            };
+        }
+        Ok(updated)
+    }
+}
@@ -14,12 +14,13 @@
 
 extern crate alloc;
 
-use alloc::{boxed::Box, format, string, string::String, vec, vec::Vec};
+use alloc::{boxed::Box, collections::BTreeMap, format, string, string::String, vec, vec::Vec};
 use core::{
     cell::RefCell,
     cmp,
     fmt::{Debug, Write},
     iter, mem,
+    ops::Range,
 };
 
 use alloy_primitives::B256;
@@ -28,6 +29,7 @@ use rlp::{Decodable, DecoderError, Prototype, Rlp};
 use serde::{Deserialize, Serialize};
 use thiserror::Error as ThisError;
 
+use super::Trie;
 use crate::{keccak::keccak, r#const::EMPTY_ROOT};
 
 /// Represents the root node of a sparse Merkle Patricia Trie.
@@ -69,6 +71,12 @@ pub enum Error {
     /// errors.
     #[error("RLP error")]
     LegacyRlp(#[from] DecoderError),
+    /// Occurs when a node references an invalid node index in an arena-backed trie.
+    #[error("invalid node index: {0}")]
+    InvalidNodeIndex(u32),
+    /// Occurs when the keys of a batch update are not sorted or not unique.
+    #[error("keys are not sorted and unique")]
+    UnsortedKeys,
 }
 
 impl From<B256> for MptNode {
@@ -119,6 +127,26 @@ pub enum MptNodeReference {
     Digest(B256),
 }
 
+/// Represents a single difference between two tries as returned by [MptNode::diff].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MptDiff {
+    /// The value stored at `key` differs. A value of `None` means that the key is not
+    /// contained in the corresponding trie.
+    Value {
+        key: Vec<u8>,
+        old: Option<Vec<u8>>,
+        new: Option<Vec<u8>>,
+    },
+    /// The sub-tries at the nibble path `path` differ, but the differing keys cannot be
+    /// determined, because the sub-trie is unresolved in at least one of the tries. The
+    /// digest of each unresolved sub-trie is given in `old` and `new` respectively.
+    Unresolved {
+        path: Vec<u8>,
+        old: Option<B256>,
+        new: Option<B256>,
+    },
+}
+
 /// Provides a conversion from [MptNodeData] to [MptNode].
 ///
 /// This implementation allows for conversion from [MptNodeData] to [MptNode],
@@ -471,41 +499,7 @@ impl MptNode {
                     return Err(Error::ValueInBranch);
                 }
 
-                let mut remaining = children.iter_mut().enumerate().filter(|(_, n)| n.is_some());
-                // there will always be at least one remaining node
-                let (index, node) = remaining.next().unwrap();
-                // if there is only exactly one node left, we need to convert the branch
-                if remaining.next().is_none() {
-                    let mut orphan = node.take().unwrap();
-                    match &mut orphan.data {
-                        // if the orphan is a leaf, prepend the corresponding nib to it
-                        MptNodeData::Leaf(prefix, orphan_value) => {
-                            let new_nibs: Vec<_> =
-                                iter::once(index as u8).chain(prefix_nibs(prefix)).collect();
-                            self.data = MptNodeData::Leaf(
-                                to_encoded_path(&new_nibs, true),
-                                mem::take(orphan_value),
-                            );
-                        }
-                        // if the orphan is an extension, prepend the corresponding nib to it
-                        MptNodeData::Extension(prefix, orphan_child) => {
-                            let new_nibs: Vec<_> =
-                                iter::once(index as u8).chain(prefix_nibs(prefix)).collect();
-                            self.data = MptNodeData::Extension(
-                                to_encoded_path(&new_nibs, false),
-                                mem::take(orphan_child),
-                            );
-                        }
-                        // if the orphan is a branch or digest, convert to an extension
-                        MptNodeData::Branch(_) | MptNodeData::Digest(_) => {
-                            self.data = MptNodeData::Extension(
-                                to_encoded_path(&[index as u8], false),
-                                orphan,
-                            );
-                        }
-                        MptNodeData::Null => unreachable!(),
-                    }
-                }
+                self.normalize_branch();
             }
             MptNodeData::Leaf(prefix, _) => {
                 if prefix_nibs(prefix) != key_nibs {
@@ -514,8 +508,7 @@ impl MptNode {
                 self.data = MptNodeData::Null;
             }
             MptNodeData::Extension(prefix, child) => {
-                let mut self_nibs = prefix_nibs(prefix);
-                if let Some(tail) = key_nibs.strip_prefix(self_nibs.as_slice()) {
+                if let Some(tail) = key_nibs.strip_prefix(prefix_nibs(prefix).as_slice()) {
                     if !child.delete_internal(tail)? {
                         return Ok(false);
                     }
@@ -523,30 +516,7 @@ impl MptNode {
                     return Ok(false);
                 }
 
-                // an extension can only point to a branch or a digest; since it's sub trie was
-                // modified, we need to make sure that this property still holds
-                match &mut child.data {
-                    // if the child is empty, remove the extension
-                    MptNodeData::Null => {
-                        self.data = MptNodeData::Null;
-                    }
-                    // for a leaf, replace the extension with the extended leaf
-                    MptNodeData::Leaf(prefix, value) => {
-                        self_nibs.extend(prefix_nibs(prefix));
-                        self.data =
-                            MptNodeData::Leaf(to_encoded_path(&self_nibs, true), mem::take(value));
-                    }
-                    // for an extension, replace the extension with the extended extension
-                    MptNodeData::Extension(prefix, node) => {
-                        self_nibs.extend(prefix_nibs(prefix));
-                        self.data = MptNodeData::Extension(
-                            to_encoded_path(&self_nibs, false),
-                            mem::take(node),
-                        );
-                    }
-                    // for a branch or digest, the extension is still correct
-                    MptNodeData::Branch(_) | MptNodeData::Digest(_) => {}
-                }
+                self.normalize_extension();
             }
             MptNodeData::Digest(digest) => return Err(Error::NodeNotResolved(*digest)),
         };
@@ -691,10 +661,336 @@ impl MptNode {
         Ok(true)
     }
 
+    /// Applies a batch of insertions and deletions to the trie in a single pass.
+    ///
+    /// Each entry consists of a key and either its new value or [None] if the key should
+    /// be removed. The entries must be sorted by key and each key must occur at most
+    /// once, otherwise [Error::UnsortedKeys] is returned.
+    /// In contrast to calling [MptNode::insert] and [MptNode::delete] for every entry,
+    /// each node is visited, restructured and invalidated at most once, so that nodes
+    /// shared by many of the keys are only re-hashed once. It returns `true` if the trie
+    /// was modified. If an error is returned, the trie may be partially updated.
+    pub fn batch_update<K: AsRef<[u8]>>(
+        &mut self,
+        sorted_kvs: impl IntoIterator<Item = (K, Option<Vec<u8>>)>,
+    ) -> Result<bool, Error> {
+        let mut updates: Vec<_> = sorted_kvs
+            .into_iter()
+            .map(|(key, value)| {
+                if matches!(&value, Some(value) if value.is_empty()) {
+                    panic!("value must not be empty");
+                }
+                (to_nibs(key.as_ref()), value)
+            })
+            .collect();
+        if updates.windows(2).any(|w| w[0].0 >= w[1].0) {
+            return Err(Error::UnsortedKeys);
+        }
+
+        if updates.is_empty() {
+            return Ok(false);
+        }
+        self.batch_update_internal(&mut updates, 0)
+    }
+
+    fn batch_update_internal(
+        &mut self,
+        updates: &mut [(Vec<u8>, Option<Vec<u8>>)],
+        depth: usize,
+    ) -> Result<bool, Error> {
+        // a single update does not benefit from batching
+        if let [(key_nibs, value)] = updates {
+            return match value.take() {
+                Some(value) => self.insert_internal(&key_nibs[depth..], value),
+                None => self.delete_internal(&key_nibs[depth..]),
+            };
+        }
+
+        match &mut self.data {
+            MptNodeData::Null | MptNodeData::Leaf(_, _) => {
+                // merge the existing leaf with the updates and rebuild this sub-trie
+                let mut leaf = match &self.data {
+                    MptNodeData::Leaf(prefix, value) => Some((prefix_nibs(prefix), value.clone())),
+                    _ => None,
+                };
+                let mut changed = false;
+                let mut entries = Vec::with_capacity(updates.len() + 1);
+                for (key_nibs, value) in updates.iter_mut() {
+                    let key_nibs = &key_nibs[depth..];
+                    if matches!(&leaf, Some((leaf_nibs, _)) if leaf_nibs == key_nibs) {
+                        let (_, old_value) = leaf.take().unwrap();
+                        match value.take() {
+                            Some(value) => {
+                                changed |= value != old_value;
+                                entries.push((key_nibs.to_vec(), value));
+                            }
+                            None => changed = true,
+                        }
+                    } else if let Some(value) = value.take() {
+                        changed = true;
+                        entries.push((key_nibs.to_vec(), value));
+                    }
+                }
+                if !changed {
+                    return Ok(false);
+                }
+                if let Some(leaf) = leaf {
+                    entries.push(leaf);
+                    entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+                }
+
+                self.data = build_trie(&mut entries, 0)?;
+            }
+            MptNodeData::Branch(children) => {
+                if updates.iter().any(|(key_nibs, _)| key_nibs.len() == depth) {
+                    return Err(Error::ValueInBranch);
+                }
+
+                let mut changed = false;
+                for range in nib_ranges(updates, depth) {
+                    let child = &mut children[updates[range.start].0[depth] as usize];
+                    let group = &mut updates[range];
+                    match child {
+                        Some(node) => {
+                            if node.batch_update_internal(group, depth + 1)? {
+                                changed = true;
+                                if node.is_empty() {
+                                    *child = None;
+                                }
+                            }
+                        }
+                        // if the corresponding child is empty, create a new sub-trie
+                        None => {
+                            let mut node = MptNode::default();
+                            if node.batch_update_internal(group, depth + 1)? && !node.is_empty() {
+                                changed = true;
+                                *child = Some(Box::new(node));
+                            }
+                        }
+                    }
+                }
+                if !changed {
+                    return Ok(false);
+                }
+
+                self.normalize_branch();
+            }
+            MptNodeData::Extension(prefix, child) => {
+                let self_nibs = prefix_nibs(prefix);
+                let diverges = updates.iter().any(|(key_nibs, value)| {
+                    value.is_some() && !key_nibs[depth..].starts_with(&self_nibs)
+                });
+                if diverges {
+                    // expand the extension into an equivalent branch with a single child, so that
+                    // the diverging keys can be added; the branch is normalized again afterwards
+                    let mut children: [Option<Box<MptNode>>; 16] = Default::default();
+                    children[self_nibs[0] as usize] = if self_nibs.len() > 1 {
+                        Some(Box::new(
+                            MptNodeData::Extension(
+                                to_encoded_path(&self_nibs[1..], false),
+                                mem::take(child),
+                            )
+                            .into(),
+                        ))
+                    } else {
+                        Some(mem::take(child))
+                    };
+                    self.data = MptNodeData::Branch(children);
+
+                    self.batch_update_internal(updates, depth)?;
+                    return Ok(true);
+                }
+
+                // deletions of keys not sharing the prefix cannot match, so they can be skipped
+                // as the keys are sorted, all the remaining keys form a contiguous range
+                let start = updates
+                    .iter()
+                    .position(|(key_nibs, _)| key_nibs[depth..].starts_with(&self_nibs));
+                let Some(start) = start else {
+                    return Ok(false);
+                };
+                let len = updates[start..]
+                    .iter()
+                    .take_while(|(key_nibs, _)| key_nibs[depth..].starts_with(&self_nibs))
+                    .count();
+                let group = &mut updates[start..start + len];
+                if !child.batch_update_internal(group, depth + self_nibs.len())? {
+                    return Ok(false);
+                }
+
+                self.normalize_extension();
+            }
+            MptNodeData::Digest(digest) => return Err(Error::NodeNotResolved(*digest)),
+        };
+
+        self.invalidate_ref_cache();
+        Ok(true)
+    }
+
+    /// Restores the invariants of a branch node after its children have been modified.
+    ///
+    /// A branch without children becomes empty and a branch with exactly one child is
+    /// merged with that child.
+    fn normalize_branch(&mut self) {
+        let MptNodeData::Branch(children) = &mut self.data else {
+            unreachable!()
+        };
+
+        let mut remaining = children.iter_mut().enumerate().filter(|(_, n)| n.is_some());
+        let Some((index, node)) = remaining.next() else {
+            self.data = MptNodeData::Null;
+            return;
+        };
+        // if there is only exactly one node left, we need to convert the branch
+        if remaining.next().is_none() {
+            let mut orphan = node.take().unwrap();
+            match &mut orphan.data {
+                // if the orphan is a leaf, prepend the corresponding nib to it
+                MptNodeData::Leaf(prefix, orphan_value) => {
+                    let new_nibs: Vec<_> =
+                        iter::once(index as u8).chain(prefix_nibs(prefix)).collect();
+                    self.data = MptNodeData::Leaf(
+                        to_encoded_path(&new_nibs, true),
+                        mem::take(orphan_value),
+                    );
+                }
+                // if the orphan is an extension, prepend the corresponding nib to it
+                MptNodeData::Extension(prefix, orphan_child) => {
+                    let new_nibs: Vec<_> =
+                        iter::once(index as u8).chain(prefix_nibs(prefix)).collect();
+                    self.data = MptNodeData::Extension(
+                        to_encoded_path(&new_nibs, false),
+                        mem::take(orphan_child),
+                    );
+                }
+                // if the orphan is a branch or digest, convert to an extension
+                MptNodeData::Branch(_) | MptNodeData::Digest(_) => {
+                    self.data =
+                        MptNodeData::Extension(to_encoded_path(&[index as u8], false), orphan);
+                }
+                MptNodeData::Null => unreachable!(),
+            }
+        }
+    }
+
+    /// Restores the invariants of an extension node after its child has been modified.
+    fn normalize_extension(&mut self) {
+        let MptNodeData::Extension(prefix, child) = &mut self.data else {
+            unreachable!()
+        };
+
+        // an extension can only point to a branch or a digest; since it's sub trie was
+        // modified, we need to make sure that this property still holds
+        let mut self_nibs = prefix_nibs(prefix);
+        match &mut child.data {
+            // if the child is empty, remove the extension
+            MptNodeData::Null => {
+                self.data = MptNodeData::Null;
+            }
+            // for a leaf, replace the extension with the extended leaf
+            MptNodeData::Leaf(prefix, value) => {
+                self_nibs.extend(prefix_nibs(prefix));
+                self.data = MptNodeData::Leaf(to_encoded_path(&self_nibs, true), mem::take(value));
+            }
+            // for an extension, replace the extension with the extended extension
+            MptNodeData::Extension(prefix, node) => {
+                self_nibs.extend(prefix_nibs(prefix));
+                self.data =
+                    MptNodeData::Extension(to_encoded_path(&self_nibs, false), mem::take(node));
+            }
+            // for a branch or digest, the extension is still correct
+            MptNodeData::Branch(_) | MptNodeData::Digest(_) => {}
+        }
+    }
+
     fn invalidate_ref_cache(&mut self) {
         self.cached_reference.borrow_mut().take();
     }
 
+    /// Computes the differences between this trie and `other`.
+    ///
+    /// Both tries are traversed in parallel and sub-tries with identical references are
+    /// skipped, so the cost is proportional to the size of the differing parts only. The
+    /// differences are returned in ascending order of their keys. If two sub-tries differ
+    /// and at least one of them is only available as a digest, this is reported as
+    /// [MptDiff::Unresolved] for the path of that sub-trie.
+    pub fn diff(&self, other: &MptNode) -> Vec<MptDiff> {
+        let mut diffs = Vec::new();
+        diff_internal(
+            DiffView::Node(self),
+            DiffView::Node(other),
+            &mut Vec::new(),
+            &mut diffs,
+        );
+        diffs
+    }
+
+    /// Creates a Merkle proof for the given key.
+    ///
+    /// The proof consists of the RLP encodings of all the nodes on the path to the key,
+    /// starting with the root, in the format of the `eth_getProof` RPC method. Nodes that
+    /// are embedded into their parent, because their encoding is shorter than 32 bytes,
+    /// are not listed separately. If the key is not present, an exclusion proof is
+    /// returned. Use [verify_proof] to check the proof against the root hash.
+    pub fn prove(&self, key: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+        let mut proof = Vec::new();
+        self.prove_internal(&to_nibs(key), true, &mut proof)?;
+        Ok(proof)
+    }
+
+    fn prove_internal(
+        &self,
+        key_nibs: &[u8],
+        is_root: bool,
+        proof: &mut Vec<Vec<u8>>,
+    ) -> Result<(), Error> {
+        match &self.data {
+            MptNodeData::Null => return Ok(()),
+            MptNodeData::Digest(digest) => return Err(Error::NodeNotResolved(*digest)),
+            _ => {}
+        }
+        if is_root || matches!(self.reference(), MptNodeReference::Digest(_)) {
+            proof.push(alloy_rlp::encode(self));
+        }
+
+        match &self.data {
+            MptNodeData::Branch(children) => {
+                if let Some((i, tail)) = key_nibs.split_first() {
+                    if let Some(child) = &children[*i as usize] {
+                        child.prove_internal(tail, false, proof)?;
+                    }
+                }
+            }
+            MptNodeData::Extension(prefix, child) => {
+                if let Some(tail) = key_nibs.strip_prefix(prefix_nibs(prefix).as_slice()) {
+                    child.prove_internal(tail, false, proof)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Replaces all digests with the corresponding nodes of `nodes`, keyed by their hash.
+    fn resolve_digests(&mut self, nodes: &BTreeMap<B256, MptNode>) {
+        match &mut self.data {
+            MptNodeData::Digest(digest) => {
+                if let Some(node) = nodes.get(digest) {
+                    // the reference of the node does not change, as the hash is identical
+                    self.data = node.data.clone();
+                    self.resolve_digests(nodes);
+                }
+            }
+            MptNodeData::Branch(children) => {
+                for child in children.iter_mut().flatten() {
+                    child.resolve_digests(nodes);
+                }
+            }
+            MptNodeData::Extension(_, child) => child.resolve_digests(nodes),
+            MptNodeData::Null | MptNodeData::Leaf(_, _) => {}
+        }
+    }
+
     /// Returns the number of traversable nodes in the trie.
     ///
     /// This method provides a count of all the nodes that can be traversed within the
@@ -773,6 +1069,232 @@ impl MptNode {
     }
 }
 
+impl Trie for MptNode {
+    fn hash(&self) -> B256 {
+        MptNode::hash(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        MptNode::is_empty(self)
+    }
+
+    fn clear(&mut self) {
+        MptNode::clear(self)
+    }
+
+    fn size(&self) -> usize {
+        MptNode::size(self)
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<&[u8]>, Error> {
+        MptNode::get(self, key)
+    }
+
+    fn insert(&mut self, key: &[u8], value: Vec<u8>) -> Result<bool, Error> {
+        MptNode::insert(self, key, value)
+    }
+
+    fn delete(&mut self, key: &[u8]) -> Result<bool, Error> {
+        MptNode::delete(self, key)
+    }
+
+    fn batch_update<K: AsRef<[u8]>>(
+        &mut self,
+        sorted_kvs: impl IntoIterator<Item = (K, Option<Vec<u8>>)>,
+    ) -> Result<bool, Error> {
+        MptNode::batch_update(self, sorted_kvs)
+    }
+}
+
+/// A possibly virtual sub-trie, used to align two tries of different shapes in
+/// [MptNode::diff].
+enum DiffView<'a> {
+    Empty,
+    Node(&'a MptNode),
+    /// The remainder of a leaf below the given nibbles.
+    Leaf(Vec<u8>, &'a [u8]),
+    /// The remainder of an extension below the given nibbles.
+    Extension(Vec<u8>, &'a MptNode),
+}
+
+/// The content of a [DiffView] at its own path.
+enum DiffStep<'a> {
+    Value(&'a [u8]),
+    Children(Box<[Option<DiffView<'a>>; 16]>),
+}
+
+impl<'a> DiffView<'a> {
+    fn leaf(nibs: &[u8], value: &'a [u8]) -> Self {
+        DiffView::Leaf(nibs.to_vec(), value)
+    }
+
+    fn extension(nibs: &[u8], child: &'a MptNode) -> Self {
+        if nibs.is_empty() {
+            DiffView::Node(child)
+        } else {
+            DiffView::Extension(nibs.to_vec(), child)
+        }
+    }
+
+    /// Returns whether both views are known to represent the same sub-trie.
+    fn is_same(&self, other: &DiffView) -> bool {
+        match (self, other) {
+            (DiffView::Empty, DiffView::Empty) => true,
+            (DiffView::Node(a), DiffView::Node(b)) => a.reference() == b.reference(),
+            (DiffView::Leaf(a_nibs, a_value), DiffView::Leaf(b_nibs, b_value)) => {
+                a_nibs == b_nibs && a_value == b_value
+            }
+            (DiffView::Extension(a_nibs, a_child), DiffView::Extension(b_nibs, b_child)) => {
+                a_nibs == b_nibs && a_child.reference() == b_child.reference()
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the digest, if the view represents an unresolved sub-trie.
+    fn digest(&self) -> Option<B256> {
+        match self {
+            DiffView::Node(node) => match node.as_data() {
+                MptNodeData::Digest(digest) => Some(*digest),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Expands the view by one nibble. This must not be called for unresolved views.
+    fn step(self) -> DiffStep<'a> {
+        let mut children: Box<[Option<DiffView>; 16]> = Default::default();
+        let (nibs, child) = match self {
+            DiffView::Empty => return DiffStep::Children(children),
+            DiffView::Node(node) => match node.as_data() {
+                MptNodeData::Null => return DiffStep::Children(children),
+                MptNodeData::Branch(nodes) => {
+                    for (child, node) in children.iter_mut().zip(nodes) {
+                        *child = node.as_deref().map(DiffView::Node);
+                    }
+                    return DiffStep::Children(children);
+                }
+                MptNodeData::Leaf(prefix, value) => {
+                    let nibs = prefix_nibs(prefix);
+                    match nibs.split_first() {
+                        None => return DiffStep::Value(value),
+                        Some((i, tail)) => (*i, DiffView::leaf(tail, value)),
+                    }
+                }
+                MptNodeData::Extension(prefix, node) => {
+                    let nibs = prefix_nibs(prefix);
+                    let (i, tail) = nibs.split_first().unwrap();
+                    (*i, DiffView::extension(tail, node))
+                }
+                MptNodeData::Digest(_) => unreachable!(),
+            },
+            DiffView::Leaf(nibs, value) => match nibs.split_first() {
+                None => return DiffStep::Value(value),
+                Some((i, tail)) => (*i, DiffView::leaf(tail, value)),
+            },
+            DiffView::Extension(nibs, node) => {
+                let (i, tail) = nibs.split_first().unwrap();
+                (*i, DiffView::extension(tail, node))
+            }
+        };
+        children[nibs as usize] = Some(child);
+
+        DiffStep::Children(children)
+    }
+}
+
+fn diff_internal(a: DiffView, b: DiffView, path: &mut Vec<u8>, diffs: &mut Vec<MptDiff>) {
+    if a.is_same(&b) {
+        return;
+    }
+    if a.digest().is_some() || b.digest().is_some() {
+        diffs.push(MptDiff::Unresolved {
+            path: path.clone(),
+            old: a.digest(),
+            new: b.digest(),
+        });
+        return;
+    }
+
+    match (a.step(), b.step()) {
+        (DiffStep::Value(old), DiffStep::Value(new)) => {
+            if old != new {
+                diffs.push(MptDiff::Value {
+                    key: from_nibs(path),
+                    old: Some(old.to_vec()),
+                    new: Some(new.to_vec()),
+                });
+            }
+        }
+        (DiffStep::Value(old), DiffStep::Children(children)) => {
+            diffs.push(MptDiff::Value {
+                key: from_nibs(path),
+                old: Some(old.to_vec()),
+                new: None,
+            });
+            diff_children(Default::default(), children, path, diffs);
+        }
+        (DiffStep::Children(children), DiffStep::Value(new)) => {
+            diffs.push(MptDiff::Value {
+                key: from_nibs(path),
+                old: None,
+                new: Some(new.to_vec()),
+            });
+            diff_children(children, Default::default(), path, diffs);
+        }
+        (DiffStep::Children(a_children), DiffStep::Children(b_children)) => {
+            diff_children(a_children, b_children, path, diffs);
+        }
+    }
+}
+
+fn diff_children(
+    a_children: Box<[Option<DiffView>; 16]>,
+    b_children: Box<[Option<DiffView>; 16]>,
+    path: &mut Vec<u8>,
+    diffs: &mut Vec<MptDiff>,
+) {
+    for (i, (a, b)) in (*a_children).into_iter().zip(*b_children).enumerate() {
+        path.push(i as u8);
+        diff_internal(
+            a.unwrap_or(DiffView::Empty),
+            b.unwrap_or(DiffView::Empty),
+            path,
+            diffs,
+        );
+        path.pop();
+    }
+}
+
+/// Converts a vector of nibbles back into bytes. An odd trailing nibble is stored in the
+/// high half of the last byte.
+fn from_nibs(nibs: &[u8]) -> Vec<u8> {
+    nibs.chunks(2)
+        .map(|chunk| (chunk[0] << 4) | chunk.get(1).copied().unwrap_or(0))
+        .collect()
+}
+
+/// Verifies a Merkle proof, as created by [MptNode::prove], against the root hash of the
+/// trie and returns the value of the key.
+///
+/// It returns `None` if the proof shows that the key is not present. If the proof does
+/// not cover the path to the key, [Error::NodeNotResolved] is returned.
+pub fn verify_proof(
+    root: B256,
+    key: &[u8],
+    proof: &[impl AsRef<[u8]>],
+) -> Result<Option<Vec<u8>>, Error> {
+    let nodes = proof
+        .iter()
+        .map(|bytes| Ok((keccak(bytes).into(), MptNode::decode(bytes)?)))
+        .collect::<Result<BTreeMap<_, _>, Error>>()?;
+    let mut trie = MptNode::from(root);
+    trie.resolve_digests(&nodes);
+
+    Ok(trie.get(key)?.map(<[u8]>::to_vec))
+}
+
 /// Converts a byte slice into a vector of nibbles.
 ///
 /// A nibble is 4 bits or half of an 8-bit byte. This function takes each byte from the
@@ -807,8 +1329,64 @@ pub fn to_encoded_path(mut nibs: &[u8], is_leaf: bool) -> Vec<u8> {
         .collect()
 }
 
+/// Builds the data of a new sub-trie from key-value pairs, where the keys are given as
+/// nibbles starting at `depth`. The entries must be sorted and unique.
+fn build_trie(entries: &mut [(Vec<u8>, Vec<u8>)], depth: usize) -> Result<MptNodeData, Error> {
+    match entries {
+        [] => Ok(MptNodeData::Null),
+        [(key_nibs, value)] => Ok(MptNodeData::Leaf(
+            to_encoded_path(&key_nibs[depth..], true),
+            mem::take(value),
+        )),
+        _ => {
+            // as the entries are sorted, the common prefix of all keys is the common prefix of
+            // the first and the last key, and only the first key can be a prefix of another
+            let first = &entries[0].0[depth..];
+            let common_len = lcp(first, &entries[entries.len() - 1].0[depth..]);
+            if common_len == first.len() {
+                return Err(Error::ValueInBranch);
+            }
+            let split_point = depth + common_len;
+
+            let mut children: [Option<Box<MptNode>>; 16] = Default::default();
+            for range in nib_ranges(entries, split_point) {
+                let nib = entries[range.start].0[split_point];
+                let child = build_trie(&mut entries[range], split_point + 1)?;
+                children[nib as usize] = Some(Box::new(child.into()));
+            }
+
+            let branch = MptNodeData::Branch(children);
+            if common_len > 0 {
+                // create parent extension for new branch
+                Ok(MptNodeData::Extension(
+                    to_encoded_path(&entries[0].0[depth..split_point], false),
+                    Box::new(branch.into()),
+                ))
+            } else {
+                Ok(branch)
+            }
+        }
+    }
+}
+
+/// Splits sorted entries into the ranges sharing the same nibble at position `depth`.
+fn nib_ranges<T>(entries: &[(Vec<u8>, T)], depth: usize) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while start < entries.len() {
+        let nib = entries[start].0[depth];
+        let len = entries[start..]
+            .iter()
+            .take_while(|(key_nibs, _)| key_nibs[depth] == nib)
+            .count();
+        ranges.push(start..start + len);
+        start += len;
+    }
+    ranges
+}
+
 /// Returns the length of the common prefix.
-fn lcp(a: &[u8], b: &[u8]) -> usize {
+pub(crate) fn lcp(a: &[u8], b: &[u8]) -> usize {
     for (i, (a, b)) in iter::zip(a, b).enumerate() {
         if a != b {
             return i;
@@ -817,7 +1395,7 @@ fn lcp(a: &[u8], b: &[u8]) -> usize {
     cmp::min(a.len(), b.len())
 }
 
-fn prefix_nibs(prefix: &[u8]) -> Vec<u8> {
+pub(crate) fn prefix_nibs(prefix: &[u8]) -> Vec<u8> {
     let (extension, tail) = prefix.split_first().unwrap();
     // the first bit of the first nibble denotes the parity
     let is_odd = extension & (1 << 4) != 0;
@@ -836,6 +1414,8 @@ fn prefix_nibs(prefix: &[u8]) -> Vec<u8> {
 
 #[cfg(test)]
 mod tests {
+    use alloc::collections::BTreeMap;
+
     use hex_literal::hex;
 
     use super::*;
@@ -1098,4 +1678,261 @@ mod tests {
         }
         assert!(trie.is_empty());
     }
+
+    #[test]
+    pub fn test_proof() {
+        // small index tries contain nodes that are embedded into their parent
+        for n in [1usize, 3, 20, 512] {
+            let mut trie = MptNode::default();
+            for i in 0..n {
+                trie.insert_rlp(&alloy_rlp::encode(i), i).unwrap();
+            }
+            let root = trie.hash();
+
+            for i in 0..n + 1 {
+                let key = alloy_rlp::encode(i);
+                let proof = trie.prove(&key).unwrap();
+                assert_eq!(
+                    verify_proof(root, &key, &proof).unwrap(),
+                    trie.get(&key).unwrap().map(<[u8]>::to_vec)
+                );
+                // the proof is only valid for the correct root
+                assert!(verify_proof(B256::with_last_byte(1), &key, &proof).is_err());
+            }
+        }
+
+        // an incomplete proof cannot be verified
+        let mut trie = MptNode::default();
+        for i in 0..512usize {
+            trie.insert_rlp(&keccak(i.to_be_bytes()), i).unwrap();
+        }
+        let key = keccak(0usize.to_be_bytes());
+        let proof = trie.prove(&key).unwrap();
+        assert!(proof.len() > 1);
+        assert!(verify_proof(trie.hash(), &key, &proof[..proof.len() - 1]).is_err());
+
+        // proofs for the empty trie are empty
+        let proof = MptNode::default().prove(&key).unwrap();
+        assert!(proof.is_empty());
+        assert_eq!(verify_proof(EMPTY_ROOT, &key, &proof).unwrap(), None);
+    }
+
+    #[test]
+    pub fn test_batch_update() {
+        const N: usize = 512;
+
+        let mut trie = MptNode::default();
+        for i in 0..N {
+            trie.insert_rlp(&keccak(i.to_be_bytes()), i).unwrap();
+        }
+        // make sure that the references are cached before the update
+        trie.hash();
+
+        // delete every second key, update every third key, insert new keys and
+        // delete some keys that are not present in the trie
+        let mut updates = Vec::new();
+        for i in 0..(2 * N) {
+            let value = match i {
+                i if i < N && i % 2 == 0 => None,
+                i if i < N && i % 3 == 0 => Some(alloy_rlp::encode(i + 1)),
+                i if i < N => continue,
+                i if i < N + N / 2 => Some(alloy_rlp::encode(i)),
+                _ => None,
+            };
+            updates.push((keccak(i.to_be_bytes()), value));
+        }
+        updates.sort_by_key(|(key, _)| *key);
+
+        let mut reference = trie.clone();
+        for (key, value) in &updates {
+            match value {
+                Some(value) => reference.insert(key, value.clone()).unwrap(),
+                None => reference.delete(key).unwrap(),
+            };
+        }
+
+        assert!(trie.batch_update(updates).unwrap());
+        assert_eq!(trie.hash(), reference.hash());
+        assert_eq!(trie.size(), reference.size());
+
+        // applying the same values again must not modify the trie
+        let updates: Vec<_> = (0..N)
+            .map(|i| keccak(i.to_be_bytes()))
+            .filter_map(|key| trie.get(&key).unwrap().map(|v| (key, Some(v.to_vec()))))
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .collect();
+        assert!(!trie.batch_update(updates).unwrap());
+        assert_eq!(trie.hash(), reference.hash());
+
+        // delete everything
+        let updates: BTreeMap<_, _> = (0..(2 * N))
+            .map(|i| (keccak(i.to_be_bytes()), None))
+            .collect();
+        assert!(trie.batch_update(updates).unwrap());
+        assert!(trie.is_empty());
+    }
+
+    #[test]
+    pub fn test_batch_update_errors() {
+        // a key ending in a branch
+        let mut trie = MptNode::default();
+        let updates = [
+            (vec![0x12], Some(b"a".to_vec())),
+            (vec![0x12, 0x34], Some(b"b".to_vec())),
+        ];
+        assert!(matches!(
+            trie.batch_update(updates),
+            Err(Error::ValueInBranch)
+        ));
+
+        // unsorted or duplicate keys
+        let mut trie = MptNode::default();
+        let updates = [(vec![0x34], None), (vec![0x12], Some(b"a".to_vec()))];
+        assert!(matches!(
+            trie.batch_update(updates),
+            Err(Error::UnsortedKeys)
+        ));
+        let updates = [(vec![0x12], None), (vec![0x12], Some(b"a".to_vec()))];
+        assert!(matches!(
+            trie.batch_update(updates),
+            Err(Error::UnsortedKeys)
+        ));
+        assert!(trie.is_empty());
+
+        // updates below an unresolved node
+        let mut trie = MptNode::default();
+        for i in 0..16u8 {
+            trie.insert(&[i << 4], vec![i + 1]).unwrap();
+        }
+        let mut trie: MptNode = MptNodeData::Digest(trie.hash()).into();
+        let updates = [(vec![0x00], Some(b"a".to_vec())), (vec![0x10], None)];
+        assert!(matches!(
+            trie.batch_update(updates),
+            Err(Error::NodeNotResolved(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_diff() {
+        const N: usize = 512;
+
+        let mut old = MptNode::default();
+        for i in 0..N {
+            old.insert_rlp(&keccak(i.to_be_bytes()), i).unwrap();
+        }
+        assert!(old.diff(&old.clone()).is_empty());
+
+        // update, delete and insert some keys
+        let mut new = old.clone();
+        let mut expected = BTreeMap::new();
+        for i in (0..N).step_by(7) {
+            let key = keccak(i.to_be_bytes()).to_vec();
+            if i % 2 == 0 {
+                new.insert_rlp(&key, i + 1).unwrap();
+                expected.insert(
+                    key,
+                    (Some(alloy_rlp::encode(i)), Some(alloy_rlp::encode(i + 1))),
+                );
+            } else {
+                new.delete(&key).unwrap();
+                expected.insert(key, (Some(alloy_rlp::encode(i)), None));
+            }
+        }
+        for i in N..(N + 8) {
+            let key = keccak(i.to_be_bytes()).to_vec();
+            new.insert_rlp(&key, i).unwrap();
+            expected.insert(key, (None, Some(alloy_rlp::encode(i))));
+        }
+
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(key, (old, new))| MptDiff::Value { key, old, new })
+            .collect();
+        assert_eq!(old.diff(&new), expected);
+
+        // the reverse diff swaps old and new values
+        let reversed: Vec<_> = expected
+            .iter()
+            .cloned()
+            .map(|diff| match diff {
+                MptDiff::Value { key, old, new } => MptDiff::Value {
+                    key,
+                    old: new,
+                    new: old,
+                },
+                diff => diff,
+            })
+            .collect();
+        assert_eq!(new.diff(&old), reversed);
+
+        // tries of different shapes
+        let mut leaf = MptNode::default();
+        leaf.insert(&[0x12, 0x34], b"a".to_vec()).unwrap();
+        let mut branch = leaf.clone();
+        branch.insert(&[0x12, 0x56], b"b".to_vec()).unwrap();
+        assert_eq!(
+            leaf.diff(&branch),
+            vec![MptDiff::Value {
+                key: vec![0x12, 0x56],
+                old: None,
+                new: Some(b"b".to_vec())
+            }]
+        );
+        assert_eq!(
+            leaf.diff(&MptNode::default()),
+            vec![MptDiff::Value {
+                key: vec![0x12, 0x34],
+                old: Some(b"a".to_vec()),
+                new: None
+            }]
+        );
+    }
+
+    #[test]
+    pub fn test_diff_unresolved() {
+        let mut old = MptNode::default();
+        for i in 0..16u8 {
+            old.insert(&[i << 4, 0x00], vec![1]).unwrap();
+            old.insert(&[i << 4, 0x01], vec![2]).unwrap();
+        }
+        let mut new = old.clone();
+        new.insert(&[0x10, 0x00], vec![3]).unwrap();
+        new.insert(&[0x30, 0x00], vec![3]).unwrap();
+
+        // replace the first two children of the old trie by their digests
+        let MptNodeData::Branch(children) = &mut old.data else {
+            panic!("not a branch")
+        };
+        let digest = children[1].as_ref().unwrap().hash();
+        children[1] = Some(Box::new(MptNodeData::Digest(digest).into()));
+        children[2] = Some(Box::new(
+            MptNodeData::Digest(children[2].as_ref().unwrap().hash()).into(),
+        ));
+        old.invalidate_ref_cache();
+        // identical unresolved sub-tries are skipped as well
+        new.invalidate_ref_cache();
+        let MptNodeData::Branch(children) = &mut new.data else {
+            panic!("not a branch")
+        };
+        children[2] = Some(Box::new(
+            MptNodeData::Digest(children[2].as_ref().unwrap().hash()).into(),
+        ));
+
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                MptDiff::Unresolved {
+                    path: vec![1],
+                    old: Some(digest),
+                    new: None
+                },
+                MptDiff::Value {
+                    key: vec![0x30, 0x00],
+                    old: Some(vec![1]),
+                    new: Some(vec![3])
+                }
+            ]
+        );
+    }
 }
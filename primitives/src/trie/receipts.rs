@@ -0,0 +1,186 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use alloy_primitives::{Bytes, B256};
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{mpt::verify_proof, Error, MptNode};
+use crate::{
+    receipt::{Log, Receipt},
+    RlpBytes,
+};
+
+/// Represents errors that can occur when creating or verifying a [LogProof].
+#[derive(Debug, ThisError)]
+pub enum LogProofError {
+    /// The block does not contain a receipt for the given transaction index.
+    #[error("no receipt for transaction {0}")]
+    ReceiptNotFound(u64),
+    /// The receipt does not contain a log with the given index.
+    #[error("no log {1} in the receipt of transaction {0}")]
+    LogNotFound(u64, u64),
+    /// The proof is not valid for the given receipts root.
+    #[error("invalid receipt proof")]
+    InvalidProof(#[from] Error),
+    /// The proven receipt could not be decoded.
+    #[error("invalid receipt")]
+    InvalidReceipt(#[from] alloy_rlp::Error),
+}
+
+/// Builds the trie of the given receipts of a block, where each receipt is keyed by the
+/// RLP encoding of its transaction index.
+///
+/// The hash of the returned trie corresponds to the `receipts_root` of the block header.
+pub fn receipt_trie<'a>(receipts: impl IntoIterator<Item = &'a Receipt>) -> MptNode {
+    let mut trie = MptNode::default();
+    for (tx_index, receipt) in receipts.into_iter().enumerate() {
+        trie.insert_rlp(&alloy_rlp::encode(tx_index), receipt)
+            .expect("failed to insert receipt");
+    }
+    trie
+}
+
+/// A proof that a transaction of a block emitted a certain log.
+///
+/// The proof contains the Merkle proof of the full receipt in the receipt trie, so it can
+/// be checked against the `receipts_root` of the corresponding block header.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogProof {
+    /// Index of the transaction in the block.
+    pub tx_index: u64,
+    /// Index of the log in the receipt of the transaction.
+    pub log_index: u64,
+    /// RLP-encoded nodes on the path to the receipt, starting with the root.
+    pub receipt_proof: Vec<Bytes>,
+}
+
+impl LogProof {
+    /// Creates the proof that the transaction with index `tx_index` emitted the log with
+    /// index `log_index` from the receipts of a block.
+    pub fn new(receipts: &[Receipt], tx_index: u64, log_index: u64) -> Result<Self, LogProofError> {
+        let receipt = receipts
+            .get(tx_index as usize)
+            .ok_or(LogProofError::ReceiptNotFound(tx_index))?;
+        if receipt.payload.logs.len() as u64 <= log_index {
+            return Err(LogProofError::LogNotFound(tx_index, log_index));
+        }
+
+        let trie = receipt_trie(receipts);
+        let receipt_proof = trie
+            .prove(&alloy_rlp::encode(tx_index))?
+            .into_iter()
+            .map(Bytes::from)
+            .collect();
+
+        Ok(LogProof {
+            tx_index,
+            log_index,
+            receipt_proof,
+        })
+    }
+
+    /// Verifies the proof against the receipts root of a block header and returns the
+    /// proven log together with the receipt that contains it.
+    pub fn verify(&self, receipts_root: B256) -> Result<(Log, Receipt), LogProofError> {
+        let encoded = verify_proof(
+            receipts_root,
+            &alloy_rlp::encode(self.tx_index),
+            &self.receipt_proof,
+        )?
+        .ok_or(LogProofError::ReceiptNotFound(self.tx_index))?;
+        let receipt = Receipt::decode_bytes(encoded)?;
+        let log = receipt
+            .payload
+            .logs
+            .get(self.log_index as usize)
+            .cloned()
+            .ok_or(LogProofError::LogNotFound(self.tx_index, self.log_index))?;
+
+        Ok((log, receipt))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{Address, U256};
+
+    use super::*;
+
+    fn receipts(n: u64) -> Vec<Receipt> {
+        (0..n)
+            .map(|i| {
+                let logs = (0..i % 3)
+                    .map(|j| Log {
+                        address: Address::with_last_byte(j as u8),
+                        topics: vec![B256::with_last_byte(i as u8)],
+                        data: Bytes::from(vec![j as u8; 40]),
+                    })
+                    .collect();
+                Receipt::new((i % 3) as u8, true, U256::from(21000 * (i + 1)), logs)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn log_proof() {
+        for n in [2, 3, 200] {
+            let receipts = receipts(n);
+            let receipts_root = receipt_trie(&receipts).hash();
+
+            for (tx_index, receipt) in receipts.iter().enumerate() {
+                for (log_index, log) in receipt.payload.logs.iter().enumerate() {
+                    let proof =
+                        LogProof::new(&receipts, tx_index as u64, log_index as u64).unwrap();
+                    let (proven_log, proven_receipt) = proof.verify(receipts_root).unwrap();
+                    assert_eq!(&proven_log, log);
+                    assert_eq!(&proven_receipt, receipt);
+
+                    // the proof must not be valid for a different block
+                    assert!(proof.verify(B256::ZERO).is_err());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn log_proof_invalid() {
+        let receipts = receipts(10);
+        let receipts_root = receipt_trie(&receipts).hash();
+
+        assert!(matches!(
+            LogProof::new(&receipts, 0, 0),
+            Err(LogProofError::LogNotFound(0, 0))
+        ));
+        assert!(matches!(
+            LogProof::new(&receipts, 10, 0),
+            Err(LogProofError::ReceiptNotFound(10))
+        ));
+
+        // the proof must not be valid for a different log or transaction
+        let mut proof = LogProof::new(&receipts, 2, 1).unwrap();
+        proof.log_index = 2;
+        assert!(matches!(
+            proof.verify(receipts_root),
+            Err(LogProofError::LogNotFound(2, 2))
+        ));
+        proof.log_index = 0;
+        proof.tx_index = 5;
+        assert!(proof.verify(receipts_root).is_err());
+    }
+}
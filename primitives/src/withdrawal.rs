@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use alloy_primitives::Address;
-use alloy_rlp_derive::{RlpEncodable, RlpMaxEncodedLen};
+use alloy_rlp_derive::{RlpDecodable, RlpEncodable, RlpMaxEncodedLen};
 use serde::{Deserialize, Serialize};
 
 /// Represents a validator's withdrawal from the Ethereum consensus layer.
@@ -24,7 +24,16 @@ use serde::{Deserialize, Serialize};
 /// specific identifiers and target details to ensure the accurate and secure transfer of
 /// ether.
 #[derive(
-    Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, RlpEncodable, RlpMaxEncodedLen,
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    RlpEncodable,
+    RlpDecodable,
+    RlpMaxEncodedLen,
 )]
 pub struct Withdrawal {
     /// A unique, monotonically increasing identifier assigned by the consensus layer to
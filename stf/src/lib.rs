@@ -6,28 +6,64 @@ use fluentbase_sdk::{LowLevelSDK, SharedAPI};
 use risc0_zkvm::serde::Deserializer;
 use zeth_lib::{
     builder::{BlockBuilderStrategy, EthereumStrategy},
-    consts::FLUENT_DEVNET_CHAIN_SPEC,
+    compact::CompactBlockBuildInput,
+    consts::ChainSpec,
     input::BlockBuildInput,
+    stf::{StfFailure, StfInputHeader, StfOutput, StfStrategy, STF_INPUT_VERSION},
 };
-use zeth_primitives::{private::serde::Deserialize, transactions::ethereum::EthereumTxEssence};
+use zeth_primitives::private::serde::{de::DeserializeOwned, Deserialize};
 
 use crate::word_reader::FluentWordReader;
 
 mod word_reader;
 
+/// The trie representation used to build the block. The arena-backed trie requires
+/// considerably fewer allocations, the `mpt-trie` feature allows comparing it against
+/// the nested [zeth_primitives::trie::MptNode].
+#[cfg(not(feature = "mpt-trie"))]
+type Trie = zeth_primitives::trie::ArenaTrie;
+#[cfg(feature = "mpt-trie")]
+type Trie = zeth_primitives::trie::MptNode;
+
 #[no_mangle]
 pub extern "C" fn main() {
-    let mut word_reader = FluentWordReader::default();
-    let input: BlockBuildInput<EthereumTxEssence> =
-        BlockBuildInput::deserialize(&mut Deserializer::new(&mut word_reader))
-            .expect("failed to deserialize input");
-    let block_build_output = EthereumStrategy::build_from(&FLUENT_DEVNET_CHAIN_SPEC, input)
-        .expect("failed to build block");
-    assert!(block_build_output.success());
-    let result_hash = block_build_output.hash().unwrap_or_default();
-    // let output =
-    //     BlockBuilder::<MemDb, EthereumTxEssence>::new(&FLUENT_DEVNET_CHAIN_SPEC, input)
-    //         .finalize::<MemDbBlockFinalizeStrategy>().expect("failed to build block");
-    // let result_hash = output.0.hash();
-    LowLevelSDK::write(result_hash.as_ptr(), result_hash.len() as u32);
+    let output = build_block(&mut FluentWordReader::default()).encode();
+    LowLevelSDK::write(output.as_ptr(), output.len() as u32);
+}
+
+/// Builds the block of the input with the chain spec and strategy of its header,
+/// reporting any failure in the output instead of panicking.
+fn build_block(word_reader: &mut FluentWordReader) -> StfOutput {
+    let Ok(header) = StfInputHeader::deserialize(&mut Deserializer::new(&mut *word_reader)) else {
+        return StfOutput::failed(StfFailure::InvalidInput, None);
+    };
+    if header.version != STF_INPUT_VERSION {
+        return StfOutput::failed(StfFailure::InvalidInput, None);
+    }
+    let output = match header.strategy {
+        StfStrategy::Ethereum => build_with::<EthereumStrategy>(&header.chain_spec, word_reader),
+    };
+    output.with_chain_spec(&header.chain_spec)
+}
+
+fn build_with<N: BlockBuilderStrategy>(
+    chain_spec: &ChainSpec,
+    word_reader: &mut FluentWordReader,
+) -> StfOutput
+where
+    N::TxEssence: DeserializeOwned,
+{
+    let Ok(input) =
+        CompactBlockBuildInput::<N::TxEssence>::deserialize(&mut Deserializer::new(word_reader))
+    else {
+        return StfOutput::failed(StfFailure::InvalidInput, None);
+    };
+    let parent_header = input.state_input.parent_header.clone();
+    let Ok(input) = BlockBuildInput::<_, Trie>::try_from(input) else {
+        return StfOutput::failed(StfFailure::InvalidInput, Some(&parent_header));
+    };
+    match N::build_from(chain_spec, input) {
+        Ok(output) => StfOutput::new(&parent_header, &output),
+        Err(_) => StfOutput::failed(StfFailure::InvalidWitness, Some(&parent_header)),
+    }
 }
@@ -18,12 +18,10 @@ impl WordRead for FluentWordReader {
     fn read_padded_bytes(&mut self, bytes: &mut [u8]) -> risc0_zkvm::serde::Result<()> {
         LowLevelSDK::read(bytes.as_mut_ptr(), bytes.len() as u32, self.0);
         self.0 += bytes.len() as u32;
+        // skip the padding to the next word
         let unaligned = bytes.len() % WORD_SIZE;
         if unaligned != 0 {
-            let pad_bytes = WORD_SIZE - unaligned;
-            let mut padding = [0u8; WORD_SIZE];
-            LowLevelSDK::write(padding.as_ptr(), pad_bytes as u32);
-            self.0 += pad_bytes as u32;
+            self.0 += (WORD_SIZE - unaligned) as u32;
         }
         Ok(())
     }
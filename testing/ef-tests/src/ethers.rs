@@ -43,7 +43,7 @@ impl Provider for TestProvider {
             bail!("block {} not found", query.block_no);
         }
 
-        Ok(Block::<H256> {
+        let mut block = Block::<H256> {
             parent_hash: self.header.parent_hash.0.into(),
             uncles_hash: self.header.ommers_hash.0.into(),
             author: Some(self.header.beneficiary.into_array().into()),
@@ -59,11 +59,25 @@ impl Provider for TestProvider {
             extra_data: self.header.extra_data.0.clone().into(),
             mix_hash: Some(self.header.mix_hash.0.into()),
             nonce: Some(self.header.nonce.0.into()),
-            base_fee_per_gas: Some(self.header.base_fee_per_gas.to_be_bytes().into()),
+            base_fee_per_gas: self
+                .header
+                .base_fee_per_gas
+                .map(|base_fee| base_fee.to_be_bytes().into()),
             withdrawals_root: self.header.withdrawals_root.map(|r| r.0.into()),
+            blob_gas_used: self.header.blob_gas_used.map(U256::from),
+            excess_blob_gas: self.header.excess_blob_gas.map(U256::from),
+            parent_beacon_block_root: self.header.parent_beacon_block_root.map(|r| r.0.into()),
             hash: Some(self.header.hash().0.into()),
             ..Default::default()
-        })
+        };
+        if let Some(requests_hash) = self.header.requests_hash {
+            block.other.insert(
+                "requestsHash".to_string(),
+                serde_json::to_value(requests_hash)?,
+            );
+        }
+
+        Ok(block)
     }
 
     fn get_block_receipts(
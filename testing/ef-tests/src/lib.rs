@@ -166,8 +166,9 @@ impl From<TestHeader> for Header {
             extra_data: header.extra_data,
             mix_hash: header.mix_hash,
             nonce: header.nonce,
-            base_fee_per_gas: header.base_fee_per_gas.unwrap(),
+            base_fee_per_gas: header.base_fee_per_gas,
             withdrawals_root: header.withdrawals_root,
+            ..Default::default()
         }
     }
 }